@@ -0,0 +1,62 @@
+use ckb_core::{
+    block::BlockBuilder,
+    transaction::{CellOutPoint, CellOutput, TransactionBuilder},
+};
+use ckb_db::{DBConfig, RocksDB};
+use ckb_store::{ChainKVStore, ChainStore, StoreBatch, COLUMNS};
+use criterion::{criterion_group, criterion_main, Criterion};
+
+fn bench(c: &mut Criterion) {
+    let tmp_dir = tempfile::Builder::new().tempdir().unwrap();
+    let config = DBConfig {
+        path: tmp_dir.as_ref().to_path_buf(),
+        ..Default::default()
+    };
+
+    // the cells spent by a transaction of 200 inputs
+    let out_points = {
+        let db = RocksDB::open(&config, COLUMNS);
+        let store = ChainKVStore::new(db);
+
+        let tx = TransactionBuilder::default()
+            .outputs(vec![CellOutput::default(); 200])
+            .build();
+        let out_points = (0..200)
+            .map(|index| CellOutPoint {
+                tx_hash: tx.hash().to_owned(),
+                index,
+            })
+            .collect::<Vec<_>>();
+        let block = BlockBuilder::default().transaction(tx).build();
+
+        let mut batch = store.new_batch().unwrap();
+        batch.insert_block(&block).unwrap();
+        batch.attach_block(&block).unwrap();
+        batch.commit().unwrap();
+        out_points
+    };
+
+    let db = RocksDB::open(&config, COLUMNS);
+    let store = ChainKVStore::new(db);
+    let benched_out_points = out_points.clone();
+    c.bench_function("fetch_200_cell_metas_one_by_one", move |b| {
+        b.iter(|| {
+            for out_point in &benched_out_points {
+                let _ = store
+                    .get_cell_meta(&out_point.tx_hash, out_point.index)
+                    .unwrap();
+            }
+        })
+    });
+
+    let db = RocksDB::open(&config, COLUMNS);
+    let store = ChainKVStore::new(db);
+    c.bench_function("fetch_200_cell_metas_in_batch", move |b| {
+        b.iter(|| {
+            let _ = store.get_cell_metas(&out_points);
+        })
+    });
+}
+
+criterion_group!(benches, bench);
+criterion_main!(benches);
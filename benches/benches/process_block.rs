@@ -1,5 +1,5 @@
 use ckb_chain::chain::{ChainController, ChainService};
-use ckb_chain_spec::consensus::{Consensus, ProposalWindow};
+use ckb_chain_spec::consensus::{Consensus, MaturityMetric, ProposalWindow};
 use ckb_core::block::{Block, BlockBuilder};
 use ckb_core::header::HeaderBuilder;
 use ckb_core::script::Script;
@@ -7,7 +7,7 @@ use ckb_core::transaction::{
     CellInput, CellOutput, OutPoint, ProposalShortId, Transaction, TransactionBuilder,
 };
 use ckb_core::{capacity_bytes, Bytes, Capacity};
-use ckb_db::{CacheDB, DBConfig, RocksDB};
+use ckb_db::{CacheDB, DBConfig, PersistentDB};
 use ckb_notify::NotifyService;
 use ckb_shared::shared::{Shared, SharedBuilder};
 use ckb_store::ChainKVStore;
@@ -147,7 +147,7 @@ fn new_chain(
     txs_size: usize,
 ) -> (
     ChainController,
-    Shared<ChainKVStore<CacheDB<RocksDB>>>,
+    Shared<ChainKVStore<CacheDB<PersistentDB>>>,
     TempDir,
     H256,
     H256,
@@ -189,13 +189,13 @@ fn new_chain(
 
     let mut consensus = Consensus::default().set_genesis_block(genesis_block);
     consensus.tx_proposal_window = ProposalWindow(1, 10);
-    consensus.cellbase_maturity = 0;
+    consensus.cellbase_maturity = MaturityMetric::Blocks(0);
 
     let db_dir = tempdir().unwrap();
-    let shared = SharedBuilder::<CacheDB<RocksDB>>::default()
+    let shared = SharedBuilder::<CacheDB<PersistentDB>>::default()
         .db(&DBConfig {
             path: db_dir.path().to_owned(),
-            options: None,
+            ..Default::default()
         })
         .consensus(consensus)
         .build()
@@ -288,5 +288,6 @@ fn create_transaction(
             vec![],
         ))
         .dep(OutPoint::new_cell(system_cell_hash.to_owned(), 0))
+        .witness(vec![])
         .build()
 }
@@ -12,7 +12,7 @@ use ckb_shared::cell_set::CellSetDiff;
 use ckb_shared::chain_state::ChainState;
 use ckb_shared::error::SharedError;
 use ckb_shared::shared::Shared;
-use ckb_store::{ChainStore, StoreBatch};
+use ckb_store::{ChainStore, Prunable, StoreBatch};
 use ckb_traits::ChainProvider;
 use ckb_verification::{BlockVerifier, ContextualBlockVerifier, Verifier};
 use crossbeam_channel::{self, select, Receiver, Sender};
@@ -263,7 +263,7 @@ impl<CS: ChainStore + 'static> ChainService<CS> {
                 block.header().number(), block.header().hash(),
                 &cannon_total_difficulty - &current_total_difficulty
             );
-            self.find_fork(&mut fork, chain_state.tip_number(), &block, ext);
+            self.find_fork(&mut fork, chain_state.tip_number(), &block, ext)?;
             self.update_index(&mut batch, &fork.detached_blocks, &fork.attached_blocks)?;
             // MUST update index before reconcile_main_chain
             cell_set_diff = self.reconcile_main_chain(
@@ -353,13 +353,22 @@ impl<CS: ChainStore + 'static> ChainService<CS> {
         Ok(())
     }
 
+    // The blocks to attach or detach, the reorgs reaching the pruned blocks are refused
+    fn fork_block(&self, hash: &H256) -> Result<Option<Block>, FailureError> {
+        match self.shared.store().read_block(hash) {
+            Prunable::Found(block) => Ok(Some(block)),
+            Prunable::Pruned => Err(SharedError::PrunedBlock(hash.to_owned()).into()),
+            Prunable::NotFound => Ok(None),
+        }
+    }
+
     fn alignment_fork(
         &self,
         fork: &mut ForkChanges,
         index: &mut GlobalIndex,
         new_tip_number: BlockNumber,
         current_tip_number: BlockNumber,
-    ) {
+    ) -> Result<(), FailureError> {
         if new_tip_number <= current_tip_number {
             for bn in new_tip_number..=current_tip_number {
                 let hash = self
@@ -367,8 +376,7 @@ impl<CS: ChainStore + 'static> ChainService<CS> {
                     .block_hash(bn)
                     .expect("block hash stored before alignment_fork");
                 let old_block = self
-                    .shared
-                    .block(&hash)
+                    .fork_block(&hash)?
                     .expect("block data stored before alignment_fork");
                 fork.detached_blocks.push(old_block);
             }
@@ -386,16 +394,20 @@ impl<CS: ChainStore + 'static> ChainService<CS> {
                     }
                 }
                 let new_block = self
-                    .shared
-                    .block(&index.hash)
+                    .fork_block(&index.hash)?
                     .expect("block data stored before alignment_fork");
                 index.forward(new_block.header().parent_hash().to_owned());
                 fork.attached_blocks.push(new_block);
             }
         }
+        Ok(())
     }
 
-    fn find_fork_until_latest_common(&self, fork: &mut ForkChanges, index: &mut GlobalIndex) {
+    fn find_fork_until_latest_common(
+        &self,
+        fork: &mut ForkChanges,
+        index: &mut GlobalIndex,
+    ) -> Result<(), FailureError> {
         loop {
             if index.number == 0 {
                 break;
@@ -408,8 +420,7 @@ impl<CS: ChainStore + 'static> ChainService<CS> {
                 break;
             }
             let detached_blocks = self
-                .shared
-                .block(&detached_hash)
+                .fork_block(&detached_hash)?
                 .expect("detached block stored before find_fork_until_latest_common");
             fork.detached_blocks.push(detached_blocks);
 
@@ -426,12 +437,12 @@ impl<CS: ChainStore + 'static> ChainService<CS> {
             }
 
             let attached_block = self
-                .shared
-                .block(&index.hash)
+                .fork_block(&index.hash)?
                 .expect("attached block stored before find_fork_until_latest_common");
             index.forward(attached_block.header().parent_hash().to_owned());
             fork.attached_blocks.push(attached_block);
         }
+        Ok(())
     }
 
     pub(crate) fn find_fork(
@@ -440,7 +451,7 @@ impl<CS: ChainStore + 'static> ChainService<CS> {
         current_tip_number: BlockNumber,
         new_tip_block: &Block,
         new_tip_ext: BlockExt,
-    ) {
+    ) -> Result<(), FailureError> {
         let new_tip_number = new_tip_block.header().number();
         fork.dirty_exts.push(new_tip_ext);
 
@@ -458,10 +469,10 @@ impl<CS: ChainStore + 'static> ChainService<CS> {
         // then detached_blocks.extend(chain[new_tip_number .. =current_tip_number])
         // if new_tip_number > current_tip_number
         // then attached_blocks.extend(forks[current_tip_number + 1 .. =new_tip_number])
-        self.alignment_fork(fork, &mut index, new_tip_number, current_tip_number);
+        self.alignment_fork(fork, &mut index, new_tip_number, current_tip_number)?;
 
         // find latest common ancestor
-        self.find_fork_until_latest_common(fork, &mut index);
+        self.find_fork_until_latest_common(fork, &mut index)
     }
 
     // we found new best_block total_difficulty > old_chain.total_difficulty
@@ -489,6 +500,7 @@ impl<CS: ChainStore + 'static> ChainService<CS> {
         for b in detached_blocks_iter {
             cell_set_diff.push_old(b);
             block_headers_provider.push_detached(b);
+            self.shared.dep_cell_data_cache().invalidate_block(b);
         }
 
         for b in attached_blocks_iter.take(unverified_len) {
@@ -544,6 +556,7 @@ impl<CS: ChainStore + 'static> ChainService<CS> {
                                 &fork.attached_blocks,
                                 b,
                                 txs_verify_cache,
+                                self.shared.dep_cell_data_cache(),
                             ) {
                                 Ok(_) => {
                                     cell_set_diff.push_new(b);
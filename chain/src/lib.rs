@@ -8,6 +8,7 @@
 //!   implement `ChainProvider`
 
 pub mod chain;
+pub mod prune;
 
 #[cfg(test)]
 mod tests;
@@ -0,0 +1,126 @@
+use ckb_core::service::SIGNAL_CHANNEL_SIZE;
+use ckb_core::BlockNumber;
+use ckb_shared::shared::Shared;
+use ckb_store::{prune_blocks, ChainStore};
+use ckb_traits::ChainProvider;
+use crossbeam_channel::{self, select};
+use failure::Error as FailureError;
+use log::{error, info};
+use serde_derive::{Deserialize, Serialize};
+use std::time::Duration;
+use std::{cmp, thread};
+use stop_handler::{SignalSender, StopHandler};
+
+const DEFAULT_PRUNE_INTERVAL: u64 = 60;
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PruneConfig {
+    /// The number of the latest blocks whose bodies are kept, the others are pruned. Pruning is
+    /// off when it is not set.
+    pub retention: Option<BlockNumber>,
+    /// Seconds between two rounds of pruning
+    #[serde(default = "default_prune_interval")]
+    pub interval: u64,
+}
+
+impl Default for PruneConfig {
+    fn default() -> Self {
+        PruneConfig {
+            retention: None,
+            interval: DEFAULT_PRUNE_INTERVAL,
+        }
+    }
+}
+
+fn default_prune_interval() -> u64 {
+    DEFAULT_PRUNE_INTERVAL
+}
+
+pub struct PruneController {
+    stop: StopHandler<()>,
+}
+
+impl Drop for PruneController {
+    fn drop(&mut self) {
+        self.stop.try_send();
+    }
+}
+
+/// Prunes the block bodies buried deeper than the retention in the background. A reorg reaching
+/// the pruned blocks is refused by the chain, so the retention is the deepest reorg accepted.
+pub struct PruneService<CS> {
+    shared: Shared<CS>,
+    retention: BlockNumber,
+    interval: Duration,
+}
+
+impl<CS: ChainStore + 'static> PruneService<CS> {
+    /// Returns `None` when pruning is off
+    pub fn new(shared: Shared<CS>, config: &PruneConfig) -> Option<PruneService<CS>> {
+        config.retention.map(|retention| {
+            // the verification of new blocks reads the uncles and the proposals of the blocks
+            // within these windows, which are never pruned
+            let consensus = shared.consensus();
+            let retention = cmp::max(
+                retention,
+                cmp::max(
+                    consensus.max_uncles_age() as BlockNumber,
+                    consensus.tx_proposal_window().start(),
+                ),
+            );
+            PruneService {
+                shared,
+                retention,
+                interval: Duration::from_secs(config.interval),
+            }
+        })
+    }
+
+    pub fn start<S: ToString>(self, thread_name: Option<S>) -> PruneController {
+        let (signal_sender, signal_receiver) =
+            crossbeam_channel::bounded::<()>(SIGNAL_CHANNEL_SIZE);
+        let ticker = crossbeam_channel::tick(self.interval);
+
+        let mut thread_builder = thread::Builder::new();
+        if let Some(name) = thread_name {
+            thread_builder = thread_builder.name(name.to_string());
+        }
+        let thread = thread_builder
+            .spawn(move || loop {
+                select! {
+                    recv(signal_receiver) -> _ => {
+                        break;
+                    },
+                    recv(ticker) -> _ => match self.prune() {
+                        Ok(0) => {},
+                        Ok(pruned) => {
+                            info!(target: "chain", "pruned the bodies of {} blocks", pruned);
+                        },
+                        Err(err) => {
+                            error!(target: "chain", "failed to prune blocks: {:?}", err);
+                        },
+                    }
+                }
+            })
+            .expect("Start PruneService failed");
+        let stop = StopHandler::new(SignalSender::Crossbeam(signal_sender), thread);
+
+        PruneController { stop }
+    }
+
+    /// Prunes the blocks up to the retention below the tip, returns the number of blocks pruned
+    pub fn prune(&self) -> Result<u64, FailureError> {
+        let store = self.shared.store();
+        let mut pruned = 0;
+        loop {
+            // the chain state is locked while pruning a block, for no reorg to read it meanwhile
+            let chain_state = self.shared.lock_chain_state();
+            let number = store.get_pruned_number().map_or(0, |number| number + 1);
+            if number + self.retention > chain_state.tip_number() {
+                break;
+            }
+            pruned += prune_blocks(store.as_ref(), number)?;
+        }
+        Ok(pruned)
+    }
+}
@@ -76,7 +76,9 @@ fn test_find_fork_case1() {
 
     let mut fork = ForkChanges::default();
 
-    chain_service.find_fork(&mut fork, tip_number, &new_block, ext);
+    chain_service
+        .find_fork(&mut fork, tip_number, &new_block, ext)
+        .unwrap();
 
     let detached_blocks: HashSet<Block> = HashSet::from_iter(fork1.into_iter());
     let attached_blocks: HashSet<Block> = HashSet::from_iter(fork2.into_iter());
@@ -160,7 +162,9 @@ fn test_find_fork_case2() {
 
     let mut fork = ForkChanges::default();
 
-    chain_service.find_fork(&mut fork, tip_number, &new_block, ext);
+    chain_service
+        .find_fork(&mut fork, tip_number, &new_block, ext)
+        .unwrap();
 
     let detached_blocks: HashSet<Block> = HashSet::from_iter(fork1[1..].iter().cloned());
     let attached_blocks: HashSet<Block> = HashSet::from_iter(fork2.into_iter());
@@ -236,7 +240,9 @@ fn test_find_fork_case3() {
     };
     let mut fork = ForkChanges::default();
 
-    chain_service.find_fork(&mut fork, tip_number, &new_block, ext);
+    chain_service
+        .find_fork(&mut fork, tip_number, &new_block, ext)
+        .unwrap();
 
     let detached_blocks: HashSet<Block> = HashSet::from_iter(fork1.into_iter());
     let attached_blocks: HashSet<Block> = HashSet::from_iter(fork2.into_iter());
@@ -313,7 +319,9 @@ fn test_find_fork_case4() {
 
     let mut fork = ForkChanges::default();
 
-    chain_service.find_fork(&mut fork, tip_number, &new_block, ext);
+    chain_service
+        .find_fork(&mut fork, tip_number, &new_block, ext)
+        .unwrap();
 
     let detached_blocks: HashSet<Block> = HashSet::from_iter(fork1.into_iter());
     let attached_blocks: HashSet<Block> = HashSet::from_iter(fork2.into_iter());
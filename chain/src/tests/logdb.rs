@@ -0,0 +1,51 @@
+use crate::chain::ChainService;
+use crate::tests::util::{always_success_consensus, gen_block};
+use ckb_db::{CacheDB, DBBackend, DBConfig, LogDB, PersistentDB};
+use ckb_notify::NotifyService;
+use ckb_shared::shared::SharedBuilder;
+use ckb_store::{ChainKVStore, ChainStore, COLUMNS};
+use ckb_traits::ChainProvider;
+use numext_fixed_uint::U256;
+use std::sync::Arc;
+use tempfile;
+
+#[test]
+fn test_sync_into_logdb() {
+    let tmp_dir = tempfile::Builder::new()
+        .prefix("test_sync_into_logdb")
+        .tempdir()
+        .unwrap();
+    let config = DBConfig {
+        path: tmp_dir.as_ref().to_path_buf(),
+        backend: DBBackend::LogDB,
+        ..Default::default()
+    };
+    let shared = SharedBuilder::<CacheDB<PersistentDB>>::new()
+        .consensus(always_success_consensus())
+        .db(&config)
+        .build()
+        .unwrap();
+    let notify = NotifyService::default().start::<&str>(None);
+    let chain_controller = ChainService::new(shared.clone(), notify).start::<&str>(None);
+
+    let mut parent = shared.block_header(&shared.block_hash(0).unwrap()).unwrap();
+    let mut blocks = Vec::new();
+    for _ in 0..10 {
+        let difficulty = parent.difficulty().to_owned() + U256::from(100u64);
+        let block = gen_block(&parent, difficulty, vec![], vec![], vec![]);
+        chain_controller
+            .process_block(Arc::new(block.clone()), false)
+            .expect("process block ok");
+        parent = block.header().to_owned();
+        blocks.push(block);
+    }
+    assert_eq!(shared.lock_chain_state().tip_number(), 10);
+    drop(chain_controller);
+
+    // the blocks are read back from the log
+    let store = ChainKVStore::new(LogDB::open(&config, COLUMNS).unwrap());
+    assert_eq!(store.get_tip_header(), Some(parent));
+    for block in &blocks {
+        assert_eq!(store.get_block(block.header().hash()).as_ref(), Some(block));
+    }
+}
@@ -1,4 +1,7 @@
 mod basic;
 mod delay_verify;
 mod find_fork;
+#[cfg(feature = "logdb")]
+mod logdb;
+mod prune;
 mod util;
@@ -0,0 +1,106 @@
+use crate::prune::{PruneConfig, PruneService};
+use crate::tests::util::{gen_block, start_chain};
+use ckb_core::block::Block;
+use ckb_core::header::Header;
+use ckb_shared::error::SharedError;
+use ckb_store::ChainStore;
+use ckb_traits::ChainProvider;
+use numext_fixed_uint::U256;
+use std::sync::Arc;
+
+fn gen_chain(parent: &Header, len: usize, difficulty_step: u64) -> Vec<Block> {
+    let mut parent = parent.to_owned();
+    let mut blocks = Vec::new();
+    for _ in 0..len {
+        let difficulty = parent.difficulty().to_owned() + U256::from(difficulty_step);
+        let block = gen_block(&parent, difficulty, vec![], vec![], vec![]);
+        parent = block.header().to_owned();
+        blocks.push(block);
+    }
+    blocks
+}
+
+#[test]
+fn test_prune_with_retention() {
+    let (chain_controller, shared) = start_chain(None);
+    let genesis = shared.block_header(&shared.block_hash(0).unwrap()).unwrap();
+    let chain = gen_chain(&genesis, 30, 100);
+    for block in &chain {
+        chain_controller
+            .process_block(Arc::new(block.clone()), false)
+            .expect("process block ok");
+    }
+
+    let config = PruneConfig {
+        retention: Some(12),
+        ..Default::default()
+    };
+    let prune_service = PruneService::new(shared.clone(), &config).unwrap();
+    assert_eq!(prune_service.prune().unwrap(), 19);
+    assert_eq!(shared.store().get_pruned_number(), Some(18));
+    assert_eq!(prune_service.prune().unwrap(), 0);
+
+    let pruned_hash = chain[17].header().hash();
+    assert!(shared.store().read_block(pruned_hash).is_pruned());
+    assert!(shared.block_header(pruned_hash).is_some());
+    assert_eq!(
+        shared.block(chain[18].header().hash()),
+        Some(chain[18].to_owned())
+    );
+
+    // the chain goes on over the pruned blocks
+    let next = gen_chain(chain[29].header(), 1, 100);
+    chain_controller
+        .process_block(Arc::new(next[0].clone()), false)
+        .expect("process block ok");
+    assert_eq!(prune_service.prune().unwrap(), 1);
+}
+
+#[test]
+fn test_refuse_reorg_beyond_pruned() {
+    let (chain_controller, shared) = start_chain(None);
+    let genesis = shared.block_header(&shared.block_hash(0).unwrap()).unwrap();
+    let chain = gen_chain(&genesis, 30, 100);
+    for block in &chain {
+        chain_controller
+            .process_block(Arc::new(block.clone()), false)
+            .expect("process block ok");
+    }
+    let config = PruneConfig {
+        retention: Some(12),
+        ..Default::default()
+    };
+    PruneService::new(shared.clone(), &config)
+        .unwrap()
+        .prune()
+        .unwrap();
+    let tip_hash = chain[29].header().hash().to_owned();
+
+    // a fork from block 10 outgrows the chain, but reorganizing it detaches the pruned blocks
+    let fork = gen_chain(chain[9].header(), 30, 200);
+    let mut refused = None;
+    for block in &fork {
+        if let Err(err) = chain_controller.process_block(Arc::new(block.clone()), false) {
+            refused = Some(err);
+            break;
+        }
+    }
+    let err = refused.expect("deep reorg refused");
+    assert!(match err.downcast_ref::<SharedError>() {
+        Some(SharedError::PrunedBlock(_)) => true,
+        _ => false,
+    });
+    assert_eq!(shared.lock_chain_state().tip_hash(), &tip_hash);
+
+    // a fork within the retention is still reorganized
+    let fork = gen_chain(chain[24].header(), 6, 200);
+    for block in &fork {
+        chain_controller
+            .process_block(Arc::new(block.clone()), false)
+            .expect("process block ok");
+    }
+    assert_eq!(
+        shared.lock_chain_state().tip_hash(),
+        fork[5].header().hash()
+    );
+}
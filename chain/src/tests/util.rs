@@ -1,5 +1,5 @@
 use crate::chain::{ChainController, ChainService};
-use ckb_chain_spec::consensus::Consensus;
+use ckb_chain_spec::consensus::{Consensus, MaturityMetric};
 use ckb_core::block::Block;
 use ckb_core::block::BlockBuilder;
 use ckb_core::header::{Header, HeaderBuilder};
@@ -29,19 +29,22 @@ fn create_always_success_out_point() -> OutPoint {
     OutPoint::new_cell(create_always_success_tx().hash().to_owned(), 0)
 }
 
+// The genesis block holds the always success cell, which can be spent at once
+pub(crate) fn always_success_consensus() -> Consensus {
+    let genesis_block = BlockBuilder::default()
+        .transaction(create_always_success_tx())
+        .build();
+    Consensus::default()
+        .set_cellbase_maturity(MaturityMetric::Blocks(0))
+        .set_genesis_block(genesis_block)
+}
+
 pub(crate) fn start_chain(
     consensus: Option<Consensus>,
 ) -> (ChainController, Shared<ChainKVStore<MemoryKeyValueDB>>) {
     let builder = SharedBuilder::<MemoryKeyValueDB>::new();
     let shared = builder
-        .consensus(consensus.unwrap_or_else(|| {
-            let genesis_block = BlockBuilder::default()
-                .transaction(create_always_success_tx())
-                .build();
-            Consensus::default()
-                .set_cellbase_maturity(0)
-                .set_genesis_block(genesis_block)
-        }))
+        .consensus(consensus.unwrap_or_else(always_success_consensus))
         .build()
         .unwrap();
 
@@ -113,5 +116,6 @@ pub(crate) fn create_transaction_with_out_point(
         ))
         .input(CellInput::new(out_point, 0, vec![]))
         .dep(always_success_out_point)
+        .witness(vec![])
         .build()
 }
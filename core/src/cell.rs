@@ -305,6 +305,15 @@ pub struct ResolvedTransaction<'a> {
 
 pub trait CellProvider {
     fn cell(&self, out_point: &OutPoint) -> CellStatus;
+
+    /// The statuses of the cells, in the order of `out_points`. The providers backed by a store
+    /// override it to look up the cells in one batch.
+    fn cells(&self, out_points: &[&OutPoint]) -> Vec<CellStatus> {
+        out_points
+            .iter()
+            .map(|out_point| self.cell(out_point))
+            .collect()
+    }
 }
 
 pub struct OverlayCellProvider<'a> {
@@ -330,6 +339,23 @@ impl<'a> CellProvider for OverlayCellProvider<'a> {
             CellStatus::Unspecified => CellStatus::Unspecified,
         }
     }
+
+    fn cells(&self, out_points: &[&OutPoint]) -> Vec<CellStatus> {
+        let mut statuses = self.overlay.cells(out_points);
+        let (indexes, unknown): (Vec<_>, Vec<_>) = statuses
+            .iter()
+            .zip(out_points)
+            .enumerate()
+            .filter(|(_, (status, _))| **status == CellStatus::Unknown)
+            .map(|(i, (_, out_point))| (i, *out_point))
+            .unzip();
+        if !unknown.is_empty() {
+            for (i, status) in indexes.into_iter().zip(self.cell_provider.cells(&unknown)) {
+                statuses[i] = status;
+            }
+        }
+        statuses
+    }
 }
 
 pub struct BlockCellProvider<'a> {
@@ -527,57 +553,76 @@ pub fn resolve_transaction<'a, CP: CellProvider, HP: HeaderProvider>(
     );
 
     // skip resolve input of cellbase
-    if !transaction.is_cellbase() {
-        for out_point in transaction.input_pts_iter() {
-            if out_point.is_issuing_dao() {
-                resolved_inputs.push(ResolvedOutPoint::issuing_dao());
-                continue;
+    let input_pts = if transaction.is_cellbase() {
+        Vec::new()
+    } else {
+        transaction.input_pts_iter().collect::<Vec<_>>()
+    };
+    // The cells of the inputs not seen yet and of the deps are looked up in one batch
+    let looked_up_inputs = input_pts
+        .iter()
+        .map(|out_point| !out_point.is_issuing_dao() && !seen_inputs.contains(*out_point))
+        .collect::<Vec<_>>();
+    let lookups = input_pts
+        .iter()
+        .zip(&looked_up_inputs)
+        .filter(|(_, looked_up)| **looked_up)
+        .map(|(out_point, _)| *out_point)
+        .chain(transaction.deps_iter())
+        .collect::<Vec<_>>();
+    let mut cell_statuses = cell_provider.cells(&lookups).into_iter();
+
+    for (out_point, looked_up) in input_pts.into_iter().zip(looked_up_inputs) {
+        if out_point.is_issuing_dao() {
+            resolved_inputs.push(ResolvedOutPoint::issuing_dao());
+            continue;
+        }
+
+        let cell_status = if looked_up {
+            cell_statuses.next()
+        } else {
+            None
+        };
+        let (cell_status, header_status) = match cell_status {
+            Some(cell_status) if seen_inputs.insert(out_point.to_owned()) => {
+                (cell_status, header_provider.header(out_point))
             }
+            _ => (CellStatus::Dead, HeaderStatus::Unknown),
+        };
 
-            let (cell_status, header_status) = if seen_inputs.insert(out_point.to_owned()) {
-                (
-                    cell_provider.cell(out_point),
-                    header_provider.header(out_point),
-                )
-            } else {
-                (CellStatus::Dead, HeaderStatus::Unknown)
-            };
-
-            match (cell_status, header_status) {
-                (CellStatus::Dead, _) => {
-                    return Err(UnresolvableError::Dead(out_point.to_owned()));
-                }
-                (CellStatus::Unknown, _) => {
-                    unknown_out_points.push(out_point.to_owned());
-                }
-                // Input cell must exist
-                (CellStatus::Unspecified, _) => {
-                    return Err(UnresolvableError::UnspecifiedInputCell(
-                        out_point.to_owned(),
-                    ));
-                }
-                (_, HeaderStatus::Unknown) => {
-                    // TODO: should we change transaction pool so transactions
-                    // with unknown header can be included as orphans, waiting
-                    // for the correct block header to enable it?
-                    return Err(UnresolvableError::InvalidHeader(out_point.to_owned()));
-                }
-                (_, HeaderStatus::InclusionFaliure) => {
-                    return Err(UnresolvableError::InvalidHeader(out_point.to_owned()));
-                }
+        match (cell_status, header_status) {
+            (CellStatus::Dead, _) => {
+                return Err(UnresolvableError::Dead(out_point.to_owned()));
+            }
+            (CellStatus::Unknown, _) => {
+                unknown_out_points.push(out_point.to_owned());
+            }
+            // Input cell must exist
+            (CellStatus::Unspecified, _) => {
+                return Err(UnresolvableError::UnspecifiedInputCell(
+                    out_point.to_owned(),
+                ));
+            }
+            (_, HeaderStatus::Unknown) => {
+                // TODO: should we change transaction pool so transactions
+                // with unknown header can be included as orphans, waiting
+                // for the correct block header to enable it?
+                return Err(UnresolvableError::InvalidHeader(out_point.to_owned()));
+            }
+            (_, HeaderStatus::InclusionFaliure) => {
+                return Err(UnresolvableError::InvalidHeader(out_point.to_owned()));
+            }
 
-                (CellStatus::Live(cell_meta), HeaderStatus::Live(header)) => {
-                    resolved_inputs.push(ResolvedOutPoint::cell_and_header(*cell_meta, *header));
-                }
-                (CellStatus::Live(cell_meta), HeaderStatus::Unspecified) => {
-                    resolved_inputs.push(ResolvedOutPoint::cell_only(*cell_meta));
-                }
+            (CellStatus::Live(cell_meta), HeaderStatus::Live(header)) => {
+                resolved_inputs.push(ResolvedOutPoint::cell_and_header(*cell_meta, *header));
+            }
+            (CellStatus::Live(cell_meta), HeaderStatus::Unspecified) => {
+                resolved_inputs.push(ResolvedOutPoint::cell_only(*cell_meta));
             }
         }
     }
 
-    for out_point in transaction.deps_iter() {
-        let cell_status = cell_provider.cell(out_point);
+    for (out_point, cell_status) in transaction.deps_iter().zip(cell_statuses) {
         let header_status = header_provider.header(out_point);
 
         match (cell_status, header_status) {
@@ -1069,4 +1114,62 @@ mod tests {
             );
         }
     }
+
+    #[test]
+    fn resolve_transaction_should_reject_duplicated_inputs() {
+        let mut cell_provider = CellMemoryDb::default();
+        let header_provider = HeaderMemoryDb::default();
+
+        let out_point = OutPoint::new_cell(h256!("0x2"), 3);
+        cell_provider.cells.insert(
+            out_point.cell.clone().unwrap(),
+            Some(generate_dummy_cell_meta()),
+        );
+
+        let tx = TransactionBuilder::default()
+            .input(CellInput::new(out_point.clone(), 0, vec![]))
+            .input(CellInput::new(out_point.clone(), 0, vec![]))
+            .build();
+        let result = resolve_transaction(
+            &tx,
+            &mut FnvHashSet::default(),
+            &cell_provider,
+            &header_provider,
+        );
+        assert_eq!(result.err(), Some(UnresolvableError::Dead(out_point)));
+    }
+
+    #[test]
+    fn overlay_cell_provider_cells_in_order() {
+        let mut overlay = CellMemoryDb::default();
+        let mut cell_provider = CellMemoryDb::default();
+        let out_points = (0..4)
+            .map(|index| OutPoint::new_cell(h256!("0x2"), index))
+            .collect::<Vec<_>>();
+        overlay
+            .cells
+            .insert(out_points[0].cell.clone().unwrap(), None);
+        cell_provider.cells.insert(
+            out_points[0].cell.clone().unwrap(),
+            Some(generate_dummy_cell_meta()),
+        );
+        cell_provider.cells.insert(
+            out_points[2].cell.clone().unwrap(),
+            Some(generate_dummy_cell_meta()),
+        );
+
+        let provider = OverlayCellProvider::new(&overlay, &cell_provider);
+        let lookups = vec![
+            &out_points[2],
+            &out_points[0],
+            &out_points[1],
+            &out_points[2],
+        ];
+        let expected = lookups
+            .iter()
+            .map(|out_point| provider.cell(out_point))
+            .collect::<Vec<_>>();
+        assert_eq!(expected[1], CellStatus::Dead);
+        assert_eq!(provider.cells(&lookups), expected);
+    }
 }
@@ -34,6 +34,15 @@ pub struct TransactionAddress {
     pub length: usize,
 }
 
+/// Where a committed transaction is in the main chain
+#[derive(Clone, Serialize, Deserialize, Eq, PartialEq, Debug)]
+pub struct TransactionInfo {
+    pub block_hash: H256,
+    pub block_number: BlockNumber,
+    // Index of the transaction in the block
+    pub index: usize,
+}
+
 #[derive(Clone, Serialize, Deserialize, Eq, PartialEq, Debug)]
 pub struct EpochExt {
     pub(crate) number: EpochNumber,
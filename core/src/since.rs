@@ -0,0 +1,252 @@
+//! The `since` field of a transaction input, see RFC 0017
+//!
+//! https://github.com/nervosnetwork/rfcs/blob/master/rfcs/0017-tx-valid-since/0017-tx-valid-since.md
+
+use crate::{BlockNumber, EpochNumber};
+use std::error::Error as StdError;
+use std::fmt;
+
+pub const LOCK_TYPE_FLAG: u64 = 1 << 63;
+pub const METRIC_TYPE_FLAG_MASK: u64 = 0x6000_0000_0000_0000;
+pub const VALUE_MASK: u64 = 0x00ff_ffff_ffff_ffff;
+pub const REMAIN_FLAGS_BITS: u64 = 0x1f00_0000_0000_0000;
+
+const BLOCK_NUMBER_FLAG: u64 = 0x0000_0000_0000_0000;
+const EPOCH_NUMBER_FLAG: u64 = 0x2000_0000_0000_0000;
+const TIMESTAMP_FLAG: u64 = 0x4000_0000_0000_0000;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum SinceMetric {
+    BlockNumber(BlockNumber),
+    EpochNumber(EpochNumber),
+    /// Timestamp in seconds
+    Timestamp(u64),
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum SinceError {
+    /// The reserved flag bits are not zero
+    RemainFlags,
+    /// The metric flag bits are `0b11`, which does not represent any metric
+    InvalidMetric,
+    /// The value does not fit in 56 bits
+    ValueOverflow,
+}
+
+impl StdError for SinceError {}
+
+impl fmt::Display for SinceError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(&self, f)
+    }
+}
+
+/// A valid since value, the value 0 means no restriction.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Default)]
+pub struct Since(u64);
+
+impl Since {
+    /// Parse a raw since value, rejects values with the reserved flags set or an unknown metric.
+    pub fn from_raw(raw: u64) -> Result<Self, SinceError> {
+        if raw & REMAIN_FLAGS_BITS != 0 {
+            return Err(SinceError::RemainFlags);
+        }
+        if raw & METRIC_TYPE_FLAG_MASK == METRIC_TYPE_FLAG_MASK {
+            return Err(SinceError::InvalidMetric);
+        }
+        Ok(Since(raw))
+    }
+
+    /// Rejects the values which do not fit in 56 bits.
+    fn build(relative: bool, metric_flag: u64, value: u64) -> Result<Self, SinceError> {
+        if value & !VALUE_MASK != 0 {
+            return Err(SinceError::ValueOverflow);
+        }
+        let lock_type = if relative { LOCK_TYPE_FLAG } else { 0 };
+        Ok(Since(lock_type | metric_flag | value))
+    }
+
+    pub fn new_absolute_block(number: BlockNumber) -> Result<Self, SinceError> {
+        Self::build(false, BLOCK_NUMBER_FLAG, number)
+    }
+
+    pub fn new_relative_block(number: BlockNumber) -> Result<Self, SinceError> {
+        Self::build(true, BLOCK_NUMBER_FLAG, number)
+    }
+
+    pub fn new_absolute_epoch(number: EpochNumber) -> Result<Self, SinceError> {
+        Self::build(false, EPOCH_NUMBER_FLAG, number)
+    }
+
+    pub fn new_relative_epoch(number: EpochNumber) -> Result<Self, SinceError> {
+        Self::build(true, EPOCH_NUMBER_FLAG, number)
+    }
+
+    /// `unix_secs` is the median time of the past blocks, in seconds
+    pub fn new_absolute_timestamp(unix_secs: u64) -> Result<Self, SinceError> {
+        Self::build(false, TIMESTAMP_FLAG, unix_secs)
+    }
+
+    /// `secs` is the duration in seconds since the median time of the blocks before the cell
+    /// is committed
+    pub fn new_relative_timestamp(secs: u64) -> Result<Self, SinceError> {
+        Self::build(true, TIMESTAMP_FLAG, secs)
+    }
+
+    pub fn as_u64(self) -> u64 {
+        self.0
+    }
+
+    /// Whether the since is 0, which means no restriction
+    pub fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+
+    pub fn is_absolute(self) -> bool {
+        self.0 & LOCK_TYPE_FLAG == 0
+    }
+
+    #[inline]
+    pub fn is_relative(self) -> bool {
+        !self.is_absolute()
+    }
+
+    pub fn metric(self) -> SinceMetric {
+        let value = self.0 & VALUE_MASK;
+        match self.0 & METRIC_TYPE_FLAG_MASK {
+            BLOCK_NUMBER_FLAG => SinceMetric::BlockNumber(value),
+            EPOCH_NUMBER_FLAG => SinceMetric::EpochNumber(value),
+            TIMESTAMP_FLAG => SinceMetric::Timestamp(value),
+            _ => unreachable!("since metric is checked in from_raw"),
+        }
+    }
+}
+
+impl From<Since> for u64 {
+    fn from(since: Since) -> u64 {
+        since.0
+    }
+}
+
+impl fmt::Display for Since {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let lock_type = if self.is_absolute() {
+            "absolute"
+        } else {
+            "relative"
+        };
+        match self.metric() {
+            SinceMetric::BlockNumber(number) => write!(f, "{} block number {}", lock_type, number),
+            SinceMetric::EpochNumber(number) => write!(f, "{} epoch number {}", lock_type, number),
+            SinceMetric::Timestamp(secs) => write!(f, "{} timestamp {}s", lock_type, secs),
+        }
+    }
+}
+
+impl fmt::Debug for Since {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Since({:#018x}: {})", self.0, self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Since, SinceError, SinceMetric};
+
+    #[test]
+    fn test_round_trip() {
+        let cases = vec![
+            (
+                Since::new_absolute_block(10).unwrap(),
+                false,
+                SinceMetric::BlockNumber(10),
+            ),
+            (
+                Since::new_relative_block(10).unwrap(),
+                true,
+                SinceMetric::BlockNumber(10),
+            ),
+            (
+                Since::new_absolute_epoch(3).unwrap(),
+                false,
+                SinceMetric::EpochNumber(3),
+            ),
+            (
+                Since::new_relative_epoch(3).unwrap(),
+                true,
+                SinceMetric::EpochNumber(3),
+            ),
+            (
+                Since::new_absolute_timestamp(1_557_844_037).unwrap(),
+                false,
+                SinceMetric::Timestamp(1_557_844_037),
+            ),
+            (
+                Since::new_relative_timestamp(1024).unwrap(),
+                true,
+                SinceMetric::Timestamp(1024),
+            ),
+        ];
+        for (since, relative, metric) in cases {
+            let parsed = Since::from_raw(since.as_u64()).expect("valid since");
+            assert_eq!(parsed, since);
+            assert_eq!(parsed.is_relative(), relative);
+            assert_eq!(parsed.is_absolute(), !relative);
+            assert_eq!(parsed.metric(), metric);
+        }
+    }
+
+    #[test]
+    fn test_raw_values() {
+        assert_eq!(
+            Since::new_absolute_block(10).unwrap().as_u64(),
+            0x0000_0000_0000_000a
+        );
+        assert_eq!(
+            Since::new_relative_epoch(1).unwrap().as_u64(),
+            0xa000_0000_0000_0001
+        );
+        assert_eq!(
+            Since::new_relative_timestamp(2).unwrap().as_u64(),
+            0xc000_0000_0000_0002
+        );
+        assert!(Since::from_raw(0).expect("valid since").is_empty());
+    }
+
+    #[test]
+    fn test_invalid_flags() {
+        assert_eq!(
+            Since::from_raw(0x1000_0000_0000_0000),
+            Err(SinceError::RemainFlags)
+        );
+        assert_eq!(
+            Since::from_raw(0x6000_0000_0000_0001),
+            Err(SinceError::InvalidMetric)
+        );
+        assert_eq!(
+            Since::from_raw(0xe000_0000_0000_0001),
+            Err(SinceError::InvalidMetric)
+        );
+    }
+
+    #[test]
+    fn test_display() {
+        assert_eq!(
+            Since::new_relative_timestamp(1024).unwrap().to_string(),
+            "relative timestamp 1024s"
+        );
+        assert_eq!(
+            format!("{:?}", Since::new_absolute_block(10).unwrap()),
+            "Since(0x000000000000000a: absolute block number 10)"
+        );
+    }
+
+    #[test]
+    fn test_value_overflow() {
+        assert_eq!(
+            Since::new_absolute_block(1 << 56),
+            Err(SinceError::ValueOverflow)
+        );
+        assert!(Since::new_relative_timestamp((1 << 56) - 1).is_ok());
+    }
+}
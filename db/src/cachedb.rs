@@ -1,12 +1,37 @@
-use crate::{Col, DbBatch, KeyValueDB, Result};
+use crate::{Col, DBSnapshot, DbBatch, IterableKeyValueDB, KeyValueDB, Result};
 use ckb_util::Mutex;
 use fnv::FnvHashMap;
 use lru_cache::LruCache;
-use std::ops::Range;
+use serde_derive::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::ops::{Bound, Range};
 use std::sync::Arc;
 
 type CacheTable = FnvHashMap<Col, Mutex<LruCache<Vec<u8>, Vec<u8>>>>;
-pub type CacheCols = (u32, usize);
+
+/// How a column is cached. A column not enabled bypasses the cache on both reads and writes,
+/// and so does a column without a policy.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CachePolicy {
+    pub enabled: bool,
+    pub capacity: usize,
+}
+
+impl CachePolicy {
+    pub fn enabled(capacity: usize) -> Self {
+        CachePolicy {
+            enabled: true,
+            capacity,
+        }
+    }
+
+    pub fn disabled() -> Self {
+        CachePolicy {
+            enabled: false,
+            capacity: 0,
+        }
+    }
+}
 
 enum BatchOperation {
     Insert {
@@ -63,6 +88,11 @@ impl<T: DbBatch> DbBatch for CacheDBBatch<T> {
         Ok(())
     }
 
+    // the cache only holds committed values, the inner batch layers the staged ones over them
+    fn get(&self, col: Col, key: &[u8]) -> Result<Option<Vec<u8>>> {
+        self.inner.get(col, key)
+    }
+
     fn commit(self) -> Result<()> {
         self.inner.commit()?;
         for op in self.operations {
@@ -97,10 +127,10 @@ impl<T> CacheDB<T>
 where
     T: KeyValueDB,
 {
-    pub fn new(db: T, cols: &[CacheCols]) -> Self {
-        let mut table = FnvHashMap::with_capacity_and_hasher(cols.len(), Default::default());
-        for (idx, capacity) in cols {
-            table.insert(*idx, Mutex::new(LruCache::new(*capacity)));
+    pub fn new(db: T, policies: &HashMap<Col, CachePolicy>) -> Self {
+        let mut table = FnvHashMap::with_capacity_and_hasher(policies.len(), Default::default());
+        for (col, policy) in policies.iter().filter(|(_, policy)| policy.enabled) {
+            table.insert(*col, Mutex::new(LruCache::new(policy.capacity)));
         }
         CacheDB {
             db,
@@ -136,7 +166,101 @@ where
         self.db.partial_read(col, key, range)
     }
 
+    fn read_multi(&self, col: Col, keys: &[&[u8]]) -> Result<Vec<Option<Vec<u8>>>> {
+        let cache = match self.cache.get(&col) {
+            Some(cache) => cache,
+            None => return self.db.read_multi(col, keys),
+        };
+        let mut values = {
+            let mut cache_guard = cache.lock();
+            keys.iter()
+                .map(|key| cache_guard.get_refresh(*key).cloned())
+                .collect::<Vec<_>>()
+        };
+        // the keys missing in the cache are read in one batch
+        let (indexes, missing): (Vec<_>, Vec<_>) = keys
+            .iter()
+            .zip(values.iter())
+            .enumerate()
+            .filter(|(_, (_, value))| value.is_none())
+            .map(|(i, (key, _))| (i, *key))
+            .unzip();
+        if !missing.is_empty() {
+            for (i, value) in indexes.into_iter().zip(self.db.read_multi(col, &missing)?) {
+                values[i] = value;
+            }
+        }
+        Ok(values)
+    }
+
     fn batch(&self) -> Result<Self::Batch> {
         Ok(CacheDBBatch::new(self.db.batch()?, Arc::clone(&self.cache)))
     }
 }
+
+impl<T> IterableKeyValueDB for CacheDB<T>
+where
+    T: IterableKeyValueDB,
+{
+    // the cache only holds what is in the db already
+    fn seek<'a>(
+        &'a self,
+        col: Col,
+        from: Bound<&[u8]>,
+    ) -> Result<Box<Iterator<Item = Result<(Vec<u8>, Vec<u8>)>> + 'a>> {
+        self.db.seek(col, from)
+    }
+
+    fn snapshot<'a>(&'a self) -> Result<Box<DBSnapshot + 'a>> {
+        self.db.snapshot()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{conformance, MemoryKeyValueDB};
+
+    #[test]
+    fn conformance_suite() {
+        let mut policies = HashMap::new();
+        policies.insert(0, CachePolicy::enabled(16));
+        policies.insert(2, CachePolicy::enabled(16));
+        let db = CacheDB::new(
+            MemoryKeyValueDB::open(conformance::COLUMNS as usize),
+            &policies,
+        );
+        conformance::check(&db);
+    }
+
+    #[test]
+    fn bypass_disabled_column() {
+        let mut policies = HashMap::new();
+        policies.insert(0, CachePolicy::enabled(16));
+        policies.insert(1, CachePolicy::disabled());
+        let db = CacheDB::new(MemoryKeyValueDB::open(3), &policies);
+        let mut batch = db.batch().unwrap();
+        batch.insert(0, &[0], &[0, 0]).unwrap();
+        batch.insert(1, &[1], &[1, 1]).unwrap();
+        batch.insert(2, &[2], &[2, 2]).unwrap();
+        batch.commit().unwrap();
+
+        assert!(db.cache.get(&0).unwrap().lock().contains_key(&[0u8][..]));
+        assert!(db.cache.get(&1).is_none());
+        assert!(db.cache.get(&2).is_none());
+
+        // the reads of the bypassed columns hit the inner db
+        let mut batch = db.db.batch().unwrap();
+        batch.insert(0, &[0], &[0, 0, 0]).unwrap();
+        batch.insert(1, &[1], &[1, 1, 1]).unwrap();
+        batch.commit().unwrap();
+        assert_eq!(db.read(0, &[0]).unwrap(), Some(vec![0, 0]));
+        assert_eq!(db.read(1, &[1]).unwrap(), Some(vec![1, 1, 1]));
+        assert_eq!(db.partial_read(1, &[1], &(1..3)).unwrap(), Some(vec![1, 1]));
+        assert_eq!(
+            db.read_multi(1, &[&[1], &[2]]).unwrap(),
+            vec![Some(vec![1, 1, 1]), None]
+        );
+        assert_eq!(db.read(2, &[2]).unwrap(), Some(vec![2, 2]));
+    }
+}
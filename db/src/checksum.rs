@@ -0,0 +1,123 @@
+//! The checksums of the values in the critical columns, see `DBConfig::checksum_columns`.
+//!
+//! A checksummed value is framed as the magic, the version of the framing, the checksum of the
+//! key and the value (8 bytes) and the value. Which columns are framed is recorded in the
+//! database, a column is framed or unframed as a whole by a one-time migration when it is added
+//! to or removed from the checksummed columns, so the format of a value is never guessed from its
+//! bytes.
+//!
+//! RocksDB verifies the checksums of its blocks on read already (`DBConfig::verify_checksums`,
+//! on by default), but only of the blocks in the table files. The values garbled in memory or
+//! written under the wrong key pass it, and LogDB has no such check at all. The checksums here
+//! cover the key and the value end to end, whatever the backend.
+
+use crate::{Col, Error, Result};
+use fnv::FnvHasher;
+use std::borrow::Cow;
+use std::hash::Hasher;
+
+const MAGIC: &[u8] = b"\xc4\x6b\x73";
+const VERSION: u8 = 1;
+const HEADER_SIZE: usize = 12;
+
+#[derive(Clone, Debug, Default)]
+pub(crate) struct Checksums {
+    columns: Vec<Col>,
+}
+
+impl Checksums {
+    pub(crate) fn new(columns: &[Col]) -> Self {
+        Checksums {
+            columns: columns.to_vec(),
+        }
+    }
+
+    pub(crate) fn enabled(&self, col: Col) -> bool {
+        self.columns.contains(&col)
+    }
+
+    /// The value to store
+    pub(crate) fn seal<'a>(&self, col: Col, key: &[u8], value: &'a [u8]) -> Cow<'a, [u8]> {
+        if !self.enabled(col) {
+            return Cow::Borrowed(value);
+        }
+        let mut framed = Vec::with_capacity(HEADER_SIZE + value.len());
+        framed.extend_from_slice(MAGIC);
+        framed.push(VERSION);
+        framed.extend_from_slice(&checksum(key, value).to_le_bytes());
+        framed.extend_from_slice(value);
+        Cow::Owned(framed)
+    }
+
+    /// The value stored, verified and stripped of its frame
+    pub(crate) fn open(&self, col: Col, key: &[u8], stored: Vec<u8>) -> Result<Vec<u8>> {
+        if !self.enabled(col) {
+            return Ok(stored);
+        }
+        let corruption = || {
+            Error::Corruption(format!(
+                "the value of key {:?} in column {} fails its checksum",
+                key, col
+            ))
+        };
+        if stored.len() < HEADER_SIZE
+            || !stored.starts_with(MAGIC)
+            || stored[MAGIC.len()] != VERSION
+        {
+            return Err(corruption());
+        }
+        let mut sum = [0; 8];
+        sum.copy_from_slice(&stored[MAGIC.len() + 1..HEADER_SIZE]);
+        if checksum(key, &stored[HEADER_SIZE..]) != u64::from_le_bytes(sum) {
+            return Err(corruption());
+        }
+        Ok(stored[HEADER_SIZE..].to_vec())
+    }
+
+    pub(crate) fn open_option(
+        &self,
+        col: Col,
+        key: &[u8],
+        stored: Option<Vec<u8>>,
+    ) -> Result<Option<Vec<u8>>> {
+        stored.map(|stored| self.open(col, key, stored)).transpose()
+    }
+}
+
+fn checksum(key: &[u8], value: &[u8]) -> u64 {
+    let mut hasher = FnvHasher::default();
+    hasher.write(&(key.len() as u32).to_le_bytes());
+    hasher.write(key);
+    hasher.write(value);
+    hasher.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn seal_and_open() {
+        let checksums = Checksums::new(&[1]);
+        assert_eq!(
+            checksums.seal(0, &[0], &[1, 2]),
+            Cow::Borrowed(&[1u8, 2][..])
+        );
+
+        let sealed = checksums.seal(1, &[0], &[1, 2]).into_owned();
+        assert_eq!(sealed.len(), HEADER_SIZE + 2);
+        assert_eq!(checksums.open(1, &[0], sealed.clone()), Ok(vec![1, 2]));
+        // the checksum covers the key
+        assert!(checksums.open(1, &[1], sealed.clone()).is_err());
+        // the values of a checksummed column are all framed
+        assert!(checksums.open(1, &[0], vec![1, 2]).is_err());
+        assert_eq!(checksums.open(0, &[0], vec![1, 2]), Ok(vec![1, 2]));
+
+        let mut truncated = sealed.clone();
+        truncated.pop();
+        assert!(checksums.open(1, &[0], truncated).is_err());
+        let mut unknown_version = sealed;
+        unknown_version[MAGIC.len()] = VERSION + 1;
+        assert!(checksums.open(1, &[0], unknown_version).is_err());
+    }
+}
@@ -1,3 +1,5 @@
+use crate::{CachePolicy, Col, Error, Result};
+use log::warn;
 use serde_derive::Deserialize;
 use std::collections::HashMap;
 use std::path::PathBuf;
@@ -6,5 +8,321 @@ use std::path::PathBuf;
 pub struct DBConfig {
     #[serde(default)]
     pub path: PathBuf,
+    #[serde(default)]
+    pub backend: DBBackend,
+    /// The supported options by their names, see `DBOptions`. The other names are still passed
+    /// to RocksDB as raw options, which is deprecated in favor of `raw_options`.
     pub options: Option<HashMap<String, String>>,
+    /// Other mutable column family options passed to RocksDB as they are, for power users
+    pub raw_options: Option<HashMap<String, String>>,
+    /// The cache policies by columns, overriding the defaults of the store. It is not read from
+    /// the config file, but derived from the cache policies by column names of the store config.
+    #[serde(skip)]
+    pub cache: HashMap<Col, CachePolicy>,
+    /// The columns whose values are written with a checksum, which is verified on read.
+    /// RocksDB verifies the checksums of its blocks on every read already, these catch what is
+    /// garbled outside of them. A column added to or removed from an existing database is
+    /// migrated once on open. LogDB checks its whole log on open and ignores them.
+    #[serde(default)]
+    pub checksum_columns: Vec<Col>,
+    /// Whether RocksDB verifies the checksums of the blocks it reads from its table files, on
+    /// all the reads and iterations. RocksDB verifies them unless it is set to false, LogDB
+    /// ignores it.
+    #[serde(default)]
+    pub verify_checksums: Option<bool>,
+}
+
+/// The persistent backends of the database
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DBBackend {
+    RocksDB,
+    /// The pure Rust log-structured backend, built with the feature `logdb`
+    LogDB,
+}
+
+impl Default for DBBackend {
+    #[cfg(feature = "rocksdb")]
+    fn default() -> Self {
+        DBBackend::RocksDB
+    }
+
+    // The only persistent backend of a build without RocksDB
+    #[cfg(not(feature = "rocksdb"))]
+    fn default() -> Self {
+        DBBackend::LogDB
+    }
+}
+
+/// The typed options of the database
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct DBOptions {
+    /// The number of files opened at most, -1 for no limit
+    pub max_open_files: Option<i32>,
+    /// The number of background threads for flushes and compactions
+    pub parallelism: Option<i32>,
+    pub write_buffer_size: Option<usize>,
+    pub max_write_buffer_number: Option<i32>,
+    pub block_size: Option<usize>,
+    pub block_cache: Option<bool>,
+    pub block_cache_size: Option<usize>,
+}
+
+const OPTIONS: &[&str] = &[
+    "max_open_files",
+    "parallelism",
+    "write_buffer_size",
+    "max_write_buffer_number",
+    "block_size",
+    "block_cache",
+    "block_cache_size",
+];
+
+impl DBConfig {
+    /// Checks the typed options and returns them
+    pub fn db_options(&self) -> Result<DBOptions> {
+        let mut db_options = DBOptions::default();
+        if let Some(options) = self.options.as_ref() {
+            for (key, value) in options {
+                if OPTIONS.contains(&key.as_str()) {
+                    db_options.set(key, value)?;
+                }
+            }
+        }
+        db_options.check()?;
+        Ok(db_options)
+    }
+
+    /// The raw RocksDB options in the order of their names: `raw_options` along with the
+    /// untyped names under `options`, which are still accepted with a deprecation warning.
+    /// They are checked by RocksDB on open.
+    pub fn raw_options(&self) -> Result<Vec<(&str, &str)>> {
+        let mut raw_options: Vec<(&str, &str)> = self
+            .raw_options
+            .iter()
+            .flatten()
+            .map(|(key, value)| (key.as_str(), value.as_str()))
+            .collect();
+        if let Some(options) = self.options.as_ref() {
+            for (key, value) in options {
+                if OPTIONS.contains(&key.as_str()) {
+                    continue;
+                }
+                if self
+                    .raw_options
+                    .as_ref()
+                    .map_or(false, |raw_options| raw_options.contains_key(key))
+                {
+                    return Err(Error::DBError(format!(
+                        "database option {} is set in both options and raw_options",
+                        key
+                    )));
+                }
+                warn!(
+                    "Passing the raw RocksDB option {} in [db] options is deprecated, \
+                     move it into [db] raw_options",
+                    key
+                );
+                raw_options.push((key.as_str(), value.as_str()));
+            }
+        }
+        raw_options.sort();
+        Ok(raw_options)
+    }
+}
+
+/// The typed option whose name is close to `key`, which is likely misspelled
+#[cfg(any(feature = "rocksdb", test))]
+pub(crate) fn similar_option(key: &str) -> Option<&'static str> {
+    OPTIONS
+        .iter()
+        .cloned()
+        .min_by_key(|name| edit_distance(key, name))
+        .filter(|name| edit_distance(key, name) <= 2)
+}
+
+impl DBOptions {
+    fn set(&mut self, key: &str, value: &str) -> Result<()> {
+        match key {
+            "max_open_files" => self.max_open_files = Some(parse(key, value)?),
+            "parallelism" => self.parallelism = Some(parse(key, value)?),
+            "write_buffer_size" => self.write_buffer_size = Some(parse(key, value)?),
+            "max_write_buffer_number" => self.max_write_buffer_number = Some(parse(key, value)?),
+            "block_size" => self.block_size = Some(parse(key, value)?),
+            "block_cache" => self.block_cache = Some(parse(key, value)?),
+            "block_cache_size" => self.block_cache_size = Some(parse(key, value)?),
+            _ => unreachable!("only the typed options are set"),
+        }
+        Ok(())
+    }
+
+    fn check(&self) -> Result<()> {
+        let out_of_range = |key: &str, reason: &str| {
+            Err(Error::DBError(format!(
+                "invalid database option {}: {}",
+                key, reason
+            )))
+        };
+        if let Some(max_open_files) = self.max_open_files {
+            if max_open_files == 0 || max_open_files < -1 {
+                return out_of_range("max_open_files", "must be positive or -1 for no limit");
+            }
+        }
+        if self
+            .parallelism
+            .map_or(false, |parallelism| parallelism < 1)
+        {
+            return out_of_range("parallelism", "must be positive");
+        }
+        if self.write_buffer_size == Some(0) {
+            return out_of_range("write_buffer_size", "must be positive");
+        }
+        if self
+            .max_write_buffer_number
+            .map_or(false, |number| number < 1)
+        {
+            return out_of_range("max_write_buffer_number", "must be positive");
+        }
+        if self.block_size == Some(0) {
+            return out_of_range("block_size", "must be positive");
+        }
+        if self.block_cache != Some(false) && self.block_cache_size == Some(0) {
+            return out_of_range(
+                "block_cache_size",
+                "must be positive while the block cache is enabled",
+            );
+        }
+        Ok(())
+    }
+}
+
+fn parse<T: std::str::FromStr>(key: &str, value: &str) -> Result<T> {
+    value.parse().map_err(|_| {
+        Error::DBError(format!(
+            "invalid value {} of database option {}",
+            value, key
+        ))
+    })
+}
+
+#[cfg(any(feature = "rocksdb", test))]
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let above = row[j + 1];
+            row[j + 1] = if ca == *cb {
+                diagonal
+            } else {
+                1 + diagonal.min(above).min(row[j])
+            };
+            diagonal = above;
+        }
+    }
+    row[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(options: &[(&str, &str)], raw_options: &[(&str, &str)]) -> DBConfig {
+        let to_map = |options: &[(&str, &str)]| {
+            Some(
+                options
+                    .iter()
+                    .map(|(key, value)| (key.to_string(), value.to_string()))
+                    .collect(),
+            )
+        };
+        DBConfig {
+            options: to_map(options),
+            raw_options: to_map(raw_options),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn legacy_raw_options() {
+        let legacy = config(
+            &[
+                ("max_open_files", "-1"),
+                ("disable_auto_compactions", "true"),
+            ],
+            &[("compression", "kLZ4Compression")],
+        );
+        assert_eq!(
+            legacy.db_options(),
+            Ok(DBOptions {
+                max_open_files: Some(-1),
+                ..Default::default()
+            })
+        );
+        assert_eq!(
+            legacy.raw_options(),
+            Ok(vec![
+                ("compression", "kLZ4Compression"),
+                ("disable_auto_compactions", "true"),
+            ])
+        );
+        assert_eq!(
+            config(
+                &[("disable_auto_compactions", "true")],
+                &[("disable_auto_compactions", "false")]
+            )
+            .raw_options(),
+            Err(Error::DBError(
+                "database option disable_auto_compactions is set in both options and raw_options"
+                    .to_owned()
+            ))
+        );
+    }
+
+    #[test]
+    fn misspelled_option() {
+        assert_eq!(similar_option("max_open_file"), Some("max_open_files"));
+        assert_eq!(similar_option("disable_auto_compactions"), None);
+    }
+
+    #[test]
+    fn bad_value() {
+        assert_eq!(
+            config(&[("block_cache_size", "0")], &[]).db_options(),
+            Err(Error::DBError(
+                "invalid database option block_cache_size: must be positive while the block \
+                 cache is enabled"
+                    .to_owned()
+            ))
+        );
+        assert!(
+            config(&[("block_cache", "false"), ("block_cache_size", "0")], &[])
+                .db_options()
+                .is_ok()
+        );
+        assert!(config(&[("parallelism", "many")], &[])
+            .db_options()
+            .is_err());
+    }
+
+    #[test]
+    fn typed_and_raw_options() {
+        let config = config(
+            &[("max_open_files", "-1"), ("block_cache_size", "1048576")],
+            &[
+                ("disable_auto_compactions", "true"),
+                ("compression", "kLZ4Compression"),
+            ],
+        );
+        assert_eq!(
+            config.db_options(),
+            Ok(DBOptions {
+                max_open_files: Some(-1),
+                block_cache_size: Some(1_048_576),
+                ..Default::default()
+            })
+        );
+    }
 }
@@ -0,0 +1,262 @@
+//! The behaviors every backend must have, checked against each of them by their tests
+
+use crate::{DbBatch, IterableKeyValueDB, KeyValueDB, Result};
+use std::ops::Bound;
+
+pub(crate) const COLUMNS: u32 = 3;
+
+pub(crate) fn check<T: IterableKeyValueDB>(db: &T) {
+    column_isolation(db);
+    iteration_order(db);
+    seek_bounds(db);
+    paging(db);
+    partial_read_bounds(db);
+    batch_atomicity(db);
+    batch_read_your_writes(db);
+    snapshot_isolation(db);
+}
+
+fn column_isolation<T: KeyValueDB>(db: &T) {
+    let mut batch = db.batch().unwrap();
+    batch.insert(0, &[9], &[0]).unwrap();
+    batch.insert(1, &[9], &[1]).unwrap();
+    batch.commit().unwrap();
+    assert_eq!(db.read(0, &[9]).unwrap(), Some(vec![0]));
+    assert_eq!(db.read(1, &[9]).unwrap(), Some(vec![1]));
+    assert_eq!(db.read(2, &[9]).unwrap(), None);
+
+    let mut batch = db.batch().unwrap();
+    batch.delete(0, &[9]).unwrap();
+    batch.commit().unwrap();
+    assert_eq!(db.read(0, &[9]).unwrap(), None);
+    assert_eq!(db.read(1, &[9]).unwrap(), Some(vec![1]));
+
+    assert!(db.read(COLUMNS, &[9]).is_err());
+}
+
+fn iteration_order<T: IterableKeyValueDB>(db: &T) {
+    let mut batch = db.batch().unwrap();
+    for key in &[vec![3], vec![1, 0], vec![2], vec![1], vec![0, 255]] {
+        batch.insert(2, key, key).unwrap();
+    }
+    batch.commit().unwrap();
+    fn keys<'a>(iter: Box<Iterator<Item = Result<(Vec<u8>, Vec<u8>)>> + 'a>) -> Vec<Vec<u8>> {
+        iter.map(|item| item.unwrap().0).collect()
+    }
+    assert_eq!(
+        keys(db.iter(2).unwrap()),
+        vec![vec![0, 255], vec![1], vec![1, 0], vec![2], vec![3]]
+    );
+    assert_eq!(
+        keys(db.iter_from(2, &[1, 0]).unwrap()),
+        vec![vec![1, 0], vec![2], vec![3]]
+    );
+    assert!(keys(db.iter_from(2, &[4]).unwrap()).is_empty());
+    assert_eq!(keys(db.iter(0).unwrap()).len(), 0);
+}
+
+fn seek_bounds<T: IterableKeyValueDB>(db: &T) {
+    let mut batch = db.batch().unwrap();
+    for key in &[vec![20], vec![22], vec![24]] {
+        batch.insert(1, key, key).unwrap();
+    }
+    batch.commit().unwrap();
+    let keys = |from: Bound<&[u8]>| -> Vec<Vec<u8>> {
+        db.seek(1, from)
+            .unwrap()
+            .map(|item| item.unwrap().0)
+            .filter(|key| key[0] >= 20)
+            .collect()
+    };
+    assert_eq!(keys(Bound::Unbounded), vec![vec![20], vec![22], vec![24]]);
+    assert_eq!(keys(Bound::Included(&[22][..])), vec![vec![22], vec![24]]);
+    assert_eq!(keys(Bound::Excluded(&[22][..])), vec![vec![24]]);
+    assert_eq!(keys(Bound::Excluded(&[24][..])), Vec::<Vec<u8>>::new());
+    // a missing key is the same excluded or included
+    assert_eq!(
+        keys(Bound::Excluded(&[21][..])),
+        keys(Bound::Included(&[21][..]))
+    );
+    assert_eq!(keys(Bound::Excluded(&[21][..])), vec![vec![22], vec![24]]);
+    assert_eq!(keys(Bound::Excluded(&[22, 0][..])), vec![vec![24]]);
+
+    let mut batch = db.batch().unwrap();
+    for key in &[vec![20], vec![22], vec![24]] {
+        batch.delete(1, key).unwrap();
+    }
+    batch.commit().unwrap();
+}
+
+fn paging<T: IterableKeyValueDB>(db: &T) {
+    // after the keys of the other checks
+    let expected = (0..1000u32)
+        .map(|i| {
+            let key = [&[200u8][..], &i.to_be_bytes()[..]].concat();
+            (key, i.to_le_bytes().to_vec())
+        })
+        .collect::<Vec<_>>();
+    let mut batch = db.batch().unwrap();
+    for (key, value) in &expected {
+        batch.insert(0, key, value).unwrap();
+    }
+    batch.commit().unwrap();
+
+    let mut pairs = Vec::new();
+    let mut page = db.page(0, Bound::Included(&[200][..]), 7).unwrap();
+    loop {
+        pairs.extend(page.items.iter().cloned());
+        if !page.has_more {
+            break;
+        }
+        assert_eq!(page.items.len(), 7);
+        page = db.page(0, page.next_bound().unwrap(), 7).unwrap();
+    }
+    // the last page is partial, 1000 = 142 * 7 + 6
+    assert_eq!(page.items.len(), 6);
+    assert_eq!(pairs, expected);
+
+    // a full last page still knows it is the last one
+    let last = db
+        .page(0, Bound::Excluded(&expected[992].0[..]), 7)
+        .unwrap();
+    assert_eq!(last.items.len(), 7);
+    assert!(!last.has_more);
+    assert!(db
+        .page(0, Bound::Excluded(&expected[999].0[..]), 7)
+        .unwrap()
+        .items
+        .is_empty());
+}
+
+fn partial_read_bounds<T: KeyValueDB>(db: &T) {
+    let mut batch = db.batch().unwrap();
+    batch.insert(0, &[7], &[0, 1, 2, 3, 4]).unwrap();
+    batch.commit().unwrap();
+    assert_eq!(db.partial_read(0, &[7], &(1..3)).unwrap(), Some(vec![1, 2]));
+    assert_eq!(
+        db.partial_read(0, &[7], &(0..5)).unwrap(),
+        Some(vec![0, 1, 2, 3, 4])
+    );
+    assert_eq!(db.partial_read(0, &[7], &(5..5)).unwrap(), Some(vec![]));
+    assert_eq!(db.partial_read(0, &[7], &(3..6)).unwrap(), None);
+    assert_eq!(db.partial_read(0, &[8], &(0..1)).unwrap(), None);
+}
+
+fn batch_atomicity<T: KeyValueDB>(db: &T) {
+    let mut batch = db.batch().unwrap();
+    batch.insert(1, &[5], &[5]).unwrap();
+    batch.insert(1, &[6], &[6]).unwrap();
+    drop(batch);
+    assert_eq!(db.read(1, &[5]).unwrap(), None);
+
+    let mut batch = db.batch().unwrap();
+    batch.insert(1, &[5], &[5]).unwrap();
+    batch.insert(1, &[6], &[6]).unwrap();
+    assert_eq!(db.read(1, &[5]).unwrap(), None);
+    batch.commit().unwrap();
+    assert_eq!(
+        db.read_multi(1, &[&[5], &[6], &[4]]).unwrap(),
+        vec![Some(vec![5]), Some(vec![6]), None]
+    );
+
+    // the operations of a batch are applied in order
+    let mut batch = db.batch().unwrap();
+    batch.insert(1, &[4], &[4]).unwrap();
+    batch.delete(1, &[4]).unwrap();
+    batch.delete(1, &[5]).unwrap();
+    batch.insert(1, &[5], &[5, 5]).unwrap();
+    batch.commit().unwrap();
+    assert_eq!(db.read(1, &[4]).unwrap(), None);
+    assert_eq!(db.read(1, &[5]).unwrap(), Some(vec![5, 5]));
+}
+
+fn batch_read_your_writes<T: KeyValueDB>(db: &T) {
+    let mut batch = db.batch().unwrap();
+    batch.insert(2, &[1], &[1]).unwrap();
+    batch.insert(2, &[2], &[2]).unwrap();
+    batch.commit().unwrap();
+
+    let mut batch = db.batch().unwrap();
+    // insert then delete
+    batch.insert(2, &[10], &[10]).unwrap();
+    batch.delete(2, &[10]).unwrap();
+    // insert, delete, insert
+    batch.insert(2, &[11], &[11]).unwrap();
+    batch.delete(2, &[11]).unwrap();
+    batch.insert(2, &[11], &[11, 11]).unwrap();
+    // over the values in the database
+    batch.insert(2, &[1], &[1, 1]).unwrap();
+    batch.delete(2, &[2]).unwrap();
+    batch.insert(0, &[12], &[12]).unwrap();
+
+    assert_eq!(batch.get(2, &[10]).unwrap(), None);
+    assert_eq!(batch.get(2, &[11]).unwrap(), Some(vec![11, 11]));
+    assert_eq!(batch.get(2, &[1]).unwrap(), Some(vec![1, 1]));
+    assert_eq!(batch.get(2, &[2]).unwrap(), None);
+    assert_eq!(batch.get(2, &[12]).unwrap(), None);
+    assert_eq!(batch.get(0, &[12]).unwrap(), Some(vec![12]));
+    assert!(batch.get(COLUMNS, &[1]).is_err());
+    // nothing is visible outside of the batch before it is committed
+    assert_eq!(db.read(2, &[1]).unwrap(), Some(vec![1]));
+    assert_eq!(db.read(2, &[11]).unwrap(), None);
+    // the untouched keys fall through to the database
+    assert_eq!(batch.get(2, &[3]).unwrap(), None);
+    batch.commit().unwrap();
+    assert_eq!(db.read(2, &[1]).unwrap(), Some(vec![1, 1]));
+    assert_eq!(db.read(2, &[11]).unwrap(), Some(vec![11, 11]));
+
+    let mut batch = db.batch().unwrap();
+    assert_eq!(batch.get(2, &[1]).unwrap(), Some(vec![1, 1]));
+    assert_eq!(batch.get(2, &[2]).unwrap(), None);
+    assert_eq!(batch.get(2, &[11]).unwrap(), Some(vec![11, 11]));
+    batch.delete(2, &[1]).unwrap();
+    batch.delete(2, &[11]).unwrap();
+    batch.delete(0, &[12]).unwrap();
+    batch.commit().unwrap();
+}
+
+fn snapshot_isolation<T: IterableKeyValueDB>(db: &T) {
+    let mut batch = db.batch().unwrap();
+    batch.insert(0, &[7], &[7]).unwrap();
+    batch.insert(1, &[7], &[7]).unwrap();
+    batch.commit().unwrap();
+
+    let snapshot = db.snapshot().unwrap();
+    let mut batch = db.batch().unwrap();
+    batch.delete(0, &[7]).unwrap();
+    batch.insert(0, &[8], &[8]).unwrap();
+    batch.insert(1, &[7], &[7, 7]).unwrap();
+    batch.commit().unwrap();
+    // the writes committed after the snapshot is taken are not seen in any column
+    assert_eq!(
+        snapshot
+            .iter(0)
+            .unwrap()
+            .collect::<Result<Vec<_>>>()
+            .unwrap(),
+        vec![(vec![7], vec![7])]
+    );
+    assert_eq!(
+        snapshot
+            .iter(1)
+            .unwrap()
+            .collect::<Result<Vec<_>>>()
+            .unwrap(),
+        vec![(vec![7], vec![7])]
+    );
+    assert_eq!(
+        snapshot
+            .seek(0, Bound::Excluded(&[7]))
+            .unwrap()
+            .collect::<Result<Vec<_>>>()
+            .unwrap(),
+        vec![]
+    );
+    assert!(snapshot.iter(COLUMNS).is_err());
+    assert_eq!(db.iter(0).unwrap().count(), 1);
+
+    let mut batch = db.batch().unwrap();
+    batch.delete(0, &[8]).unwrap();
+    batch.delete(1, &[7]).unwrap();
+    batch.commit().unwrap();
+}
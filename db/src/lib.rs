@@ -2,21 +2,48 @@
 //!
 //! This Library contains the `KeyValueDB` traits
 //! which provides key-value store interface
+//!
+//! The persistent backends are built with the features `rocksdb` (the default) and `logdb`.
+//! The crates of the node depend on this one without the default features, the top-level crate
+//! picks the backends, so the node builds without a C++ toolchain when RocksDB is left out.
 
 use failure::Fail;
-use std::ops::Range;
+use std::ops::{Bound, Range};
 use std::result;
+use std::sync::Arc;
 
 pub mod cachedb;
+mod checksum;
 pub mod config;
+#[cfg(feature = "logdb")]
+pub mod logdb;
 pub mod memorydb;
+pub mod persistentdb;
+pub mod readonlydb;
+#[cfg(feature = "rocksdb")]
 pub mod rocksdb;
 
-pub use crate::cachedb::CacheDB;
-pub use crate::config::DBConfig;
+#[cfg(test)]
+mod conformance;
+
+pub use crate::cachedb::{CacheDB, CachePolicy};
+pub use crate::config::{DBBackend, DBConfig, DBOptions};
+#[cfg(feature = "logdb")]
+pub use crate::logdb::LogDB;
 pub use crate::memorydb::MemoryKeyValueDB;
+pub use crate::persistentdb::PersistentDB;
+pub use crate::readonlydb::ReadOnlyDB;
+#[cfg(feature = "rocksdb")]
 pub use crate::rocksdb::RocksDB;
 
+// If any data format in database was changed, we have to update this constant manually.
+//      - If the data can be migrated at startup automatically: update "x.y.z1" to "x.y.z2".
+//      - If the data can be migrated manually: update "x.y1.z" to "x.y2.0".
+//      - If the data can not be migrated: update "x1.y.z" to "x2.0.0".
+#[cfg(feature = "rocksdb")]
+pub(crate) const VERSION_KEY: &str = "db-version";
+pub(crate) const VERSION_VALUE: &str = "0.12.0";
+
 pub type Col = u32;
 pub type Result<T> = result::Result<T, Error>;
 
@@ -24,17 +51,140 @@ pub type Result<T> = result::Result<T, Error>;
 pub enum Error {
     #[fail(display = "DBError {}", _0)]
     DBError(String),
+    /// A stored value fails its checksum
+    #[fail(display = "Corruption {}", _0)]
+    Corruption(String),
 }
 
 pub trait KeyValueDB: Sync + Send {
     type Batch: DbBatch;
     fn read(&self, col: Col, key: &[u8]) -> Result<Option<Vec<u8>>>;
     fn partial_read(&self, col: Col, key: &[u8], range: &Range<usize>) -> Result<Option<Vec<u8>>>;
+    /// Reads the values of the keys in the column, in the order of `keys`
+    fn read_multi(&self, col: Col, keys: &[&[u8]]) -> Result<Vec<Option<Vec<u8>>>> {
+        keys.iter().map(|key| self.read(col, key)).collect()
+    }
     fn batch(&self) -> Result<Self::Batch>;
 }
 
+// A database shared between a writer and the `ReadOnlyDB` views of it
+impl<T: KeyValueDB> KeyValueDB for Arc<T> {
+    type Batch = T::Batch;
+    fn read(&self, col: Col, key: &[u8]) -> Result<Option<Vec<u8>>> {
+        (**self).read(col, key)
+    }
+    fn partial_read(&self, col: Col, key: &[u8], range: &Range<usize>) -> Result<Option<Vec<u8>>> {
+        (**self).partial_read(col, key, range)
+    }
+    fn read_multi(&self, col: Col, keys: &[&[u8]]) -> Result<Vec<Option<Vec<u8>>>> {
+        (**self).read_multi(col, keys)
+    }
+    fn batch(&self) -> Result<Self::Batch> {
+        (**self).batch()
+    }
+}
+
+/// A key-value store whose columns can be walked through
+pub trait IterableKeyValueDB: KeyValueDB {
+    /// Iterates over the key-value pairs of the column, in the order of the keys
+    fn iter<'a>(
+        &'a self,
+        col: Col,
+    ) -> Result<Box<Iterator<Item = Result<(Vec<u8>, Vec<u8>)>> + 'a>> {
+        self.seek(col, Bound::Unbounded)
+    }
+    /// Iterates over the key-value pairs of the column from the key `from` on, in the order of
+    /// the keys
+    fn iter_from<'a>(
+        &'a self,
+        col: Col,
+        from: &[u8],
+    ) -> Result<Box<Iterator<Item = Result<(Vec<u8>, Vec<u8>)>> + 'a>> {
+        self.seek(col, Bound::Included(from))
+    }
+    /// Iterates over the key-value pairs of the column after the lower bound `from`, in the
+    /// order of the keys. The bound key needs not exist, excluding a missing key is the same as
+    /// including it. A pair that can't be read, such as a value failing its checksum, is yielded
+    /// as an error.
+    fn seek<'a>(
+        &'a self,
+        col: Col,
+        from: Bound<&[u8]>,
+    ) -> Result<Box<Iterator<Item = Result<(Vec<u8>, Vec<u8>)>> + 'a>>;
+    /// Reads at most `limit` key-value pairs of the column after the lower bound `from`, the
+    /// next page starts after the last key of this one
+    fn page(&self, col: Col, from: Bound<&[u8]>, limit: usize) -> Result<Page> {
+        let mut iter = self.seek(col, from)?;
+        let items = iter.by_ref().take(limit).collect::<Result<_>>()?;
+        Ok(Page {
+            items,
+            has_more: iter.next().transpose()?.is_some(),
+        })
+    }
+    /// Takes a consistent view of all the columns, to read several of them as of the same time
+    fn snapshot<'a>(&'a self) -> Result<Box<DBSnapshot + 'a>>;
+}
+
+impl<T: IterableKeyValueDB> IterableKeyValueDB for Arc<T> {
+    fn seek<'a>(
+        &'a self,
+        col: Col,
+        from: Bound<&[u8]>,
+    ) -> Result<Box<Iterator<Item = Result<(Vec<u8>, Vec<u8>)>> + 'a>> {
+        (**self).seek(col, from)
+    }
+    fn snapshot<'a>(&'a self) -> Result<Box<DBSnapshot + 'a>> {
+        (**self).snapshot()
+    }
+}
+
+/// A view of the columns of a database as of the time it is taken, the writes committed
+/// afterwards are not seen through it. See `IterableKeyValueDB::snapshot`.
+pub trait DBSnapshot {
+    /// Same as `IterableKeyValueDB::seek`, over the view
+    fn seek<'a>(
+        &'a self,
+        col: Col,
+        from: Bound<&[u8]>,
+    ) -> Result<Box<Iterator<Item = Result<(Vec<u8>, Vec<u8>)>> + 'a>>;
+    /// Iterates over the key-value pairs of the column, in the order of the keys
+    fn iter<'a>(
+        &'a self,
+        col: Col,
+    ) -> Result<Box<Iterator<Item = Result<(Vec<u8>, Vec<u8>)>> + 'a>> {
+        self.seek(col, Bound::Unbounded)
+    }
+}
+
+/// A page of the key-value pairs of a column, see `IterableKeyValueDB::page`
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Page {
+    pub items: Vec<(Vec<u8>, Vec<u8>)>,
+    /// Whether the column has more pairs after the page, or the page stops at the last one
+    pub has_more: bool,
+}
+
+impl Page {
+    /// The lower bound of the next page
+    pub fn next_bound(&self) -> Option<Bound<&[u8]>> {
+        self.items.last().map(|(key, _)| Bound::Excluded(&key[..]))
+    }
+}
+
+// Whether the key is after the lower bound
+pub(crate) fn is_after(key: &[u8], from: Bound<&[u8]>) -> bool {
+    match from {
+        Bound::Included(from) => key >= from,
+        Bound::Excluded(from) => key > from,
+        Bound::Unbounded => true,
+    }
+}
+
 pub trait DbBatch {
     fn insert(&mut self, col: Col, key: &[u8], value: &[u8]) -> Result<()>;
     fn delete(&mut self, col: Col, key: &[u8]) -> Result<()>;
+    /// Reads the value of the key as if the batch were committed: the last insert or delete of
+    /// the key staged in the batch, or else the value in the database
+    fn get(&self, col: Col, key: &[u8]) -> Result<Option<Vec<u8>>>;
     fn commit(self) -> Result<()>;
 }
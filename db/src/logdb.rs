@@ -0,0 +1,857 @@
+//! A log-structured persistent backend in pure Rust, for the platforms where RocksDB is hard to
+//! build. The keys are kept in memory as sorted maps pointing at their values in the log file,
+//! the values stay on disk. Each committed batch is appended to the log as one frame which is
+//! replayed on open, so a batch is either applied entirely or not at all.
+//!
+//! A frame is the length of its payload (8 bytes), its kind (1 byte), the checksum of the kind
+//! and the payload (8 bytes), the checksum of these header fields (8 bytes) and the payload. The
+//! operations of `FRAME_PART` frames are applied with the next `FRAME_COMMIT` frame, which lets
+//! the compaction write the live data as frames of a bounded size. Only the frames at the very
+//! end of the log can be torn by a crash, they are dropped on open, a bad frame anywhere else is
+//! reported as a corruption. A frame is torn only if its header is intact, so a garbled length
+//! can't pass an early frame off as the torn end of the log.
+
+use crate::{
+    Col, DBConfig, DBSnapshot, DbBatch, Error, IterableKeyValueDB, KeyValueDB, Result,
+    VERSION_VALUE,
+};
+use ckb_util::{Mutex, RwLock};
+use fnv::FnvHasher;
+use log::{info, warn};
+use std::collections::{BTreeMap, HashMap};
+use std::fs::{self, File, OpenOptions};
+use std::hash::Hasher;
+use std::io::{self, BufReader, Read, Write};
+use std::iter;
+use std::ops::{Bound, Range};
+use std::path::Path;
+use std::sync::Arc;
+
+const LOG_FILE: &str = "data.log";
+const COMPACT_FILE: &str = "data.log.compact";
+const VERSION_FILE: &str = "VERSION";
+const FRAME_HEADER_SIZE: u64 = 25;
+// The header fields covered by the header checksum, the length, the kind and the payload checksum
+const FRAME_HEADER_FIELDS_SIZE: usize = 17;
+const FRAME_PART: u8 = 0;
+const FRAME_COMMIT: u8 = 1;
+const OP_INSERT: u8 = 0;
+const OP_DELETE: u8 = 1;
+// The operation header is the op (1 byte), the column (4 bytes) and the length of the key (4 bytes)
+const OP_HEADER_SIZE: u64 = 9;
+// The log is compacted on open when it is larger than this many times of the live data
+const COMPACT_RATIO: u64 = 2;
+// The payload size a compaction frame is closed at
+const COMPACT_FRAME_SIZE: usize = 4 * 1024 * 1024;
+// The number of keys copied out of a table at a time while it is iterated
+const SEEK_CHUNK_SIZE: usize = 256;
+
+// Where a value is in the log
+#[derive(Clone, Copy, Debug)]
+struct Pointer {
+    offset: u64,
+    len: u32,
+}
+
+type Table = BTreeMap<Vec<u8>, Pointer>;
+
+struct Inner {
+    tables: RwLock<Vec<Table>>,
+    file: File,
+    // the end of the log, the writers append to it one by one
+    end: Mutex<u64>,
+}
+
+impl Inner {
+    fn read_value(&self, pointer: Pointer) -> Result<Vec<u8>> {
+        let mut value = vec![0; pointer.len as usize];
+        read_at(&self.file, &mut value, pointer.offset).map_err(io_error)?;
+        Ok(value)
+    }
+
+    fn get(&self, col: Col, key: &[u8]) -> Result<Option<Vec<u8>>> {
+        let pointer = column(&self.tables.read(), col)?.get(key).cloned();
+        pointer.map(|pointer| self.read_value(pointer)).transpose()
+    }
+}
+
+pub struct LogDB {
+    inner: Arc<Inner>,
+}
+
+impl LogDB {
+    pub fn open(config: &DBConfig, columns: u32) -> Result<Self> {
+        Self::open_with_version(&config.path, columns, VERSION_VALUE)
+    }
+
+    pub(crate) fn open_with_version(path: &Path, columns: u32, version: &str) -> Result<Self> {
+        fs::create_dir_all(path).map_err(io_error)?;
+        let version_path = path.join(VERSION_FILE);
+        if version_path.exists() {
+            let stored = fs::read_to_string(&version_path).map_err(io_error)?;
+            if stored != version {
+                return Err(Error::DBError(format!(
+                    "the database version is not matched, require {} but it's {}",
+                    version, stored
+                )));
+            }
+        } else {
+            info!("Initialize a new database");
+            fs::write(&version_path, version).map_err(io_error)?;
+        }
+
+        let log_path = path.join(LOG_FILE);
+        let mut file = OpenOptions::new()
+            .read(true)
+            .append(true)
+            .create(true)
+            .open(&log_path)
+            .map_err(io_error)?;
+        let mut tables = vec![Table::new(); columns as usize];
+        let end = replay(&file, &mut tables)?;
+
+        let live: u64 = tables
+            .iter()
+            .flat_map(|table| table.iter())
+            .map(|(key, pointer)| OP_HEADER_SIZE + 4 + key.len() as u64 + u64::from(pointer.len))
+            .sum();
+        let end = if end > COMPACT_RATIO * (live + FRAME_HEADER_SIZE) {
+            let (compacted, end) = compact(path, &file, &mut tables, COMPACT_FRAME_SIZE)?;
+            file = compacted;
+            end
+        } else {
+            end
+        };
+
+        Ok(LogDB {
+            inner: Arc::new(Inner {
+                tables: RwLock::new(tables),
+                file,
+                end: Mutex::new(end),
+            }),
+        })
+    }
+}
+
+// Applies the committed frames of the log to the tables, drops the frames torn at the end of the
+// log and returns the end of the last committed one
+fn replay(file: &File, tables: &mut [Table]) -> Result<u64> {
+    let size = file.metadata().map_err(io_error)?.len();
+    let mut reader = BufReader::new(file);
+    let mut offset = 0;
+    let mut committed = 0;
+    let mut pending = Vec::new();
+    while offset < size {
+        let (kind, payload) = match read_frame(&mut reader, offset, size)? {
+            Some(frame) => frame,
+            None => break,
+        };
+        let payload_offset = offset + FRAME_HEADER_SIZE;
+        decode_entries(&payload, payload_offset, &mut pending).ok_or_else(|| corruption(offset))?;
+        offset = payload_offset + payload.len() as u64;
+        if kind == FRAME_COMMIT {
+            for entry in pending.drain(..) {
+                entry.apply(tables);
+            }
+            committed = offset;
+        }
+    }
+    if committed < size {
+        warn!(
+            "Dropping the {} bytes torn at the end of the database log",
+            size - committed
+        );
+        file.set_len(committed).map_err(io_error)?;
+        file.sync_all().map_err(io_error)?;
+    }
+    Ok(committed)
+}
+
+// Rewrites the live data as frames of about `frame_size` bytes followed by a commit frame,
+// replacing the log, and returns the new log and its end
+fn compact(
+    path: &Path,
+    file: &File,
+    tables: &mut [Table],
+    frame_size: usize,
+) -> Result<(File, u64)> {
+    info!("Compacting the database log");
+    let compact_path = path.join(COMPACT_FILE);
+    let mut pointers = Vec::new();
+    let end = {
+        let mut compacted = File::create(&compact_path).map_err(io_error)?;
+        let mut end = 0;
+        let mut encoder = Encoder::default();
+        for (col, table) in tables.iter().enumerate() {
+            for (key, pointer) in table.iter() {
+                let mut value = vec![0; pointer.len as usize];
+                read_at(file, &mut value, pointer.offset).map_err(io_error)?;
+                let value_offset = encoder.insert(col as Col, key, &value);
+                pointers.push(end + FRAME_HEADER_SIZE + value_offset);
+                if encoder.payload.len() >= frame_size {
+                    let frame = encoder.finish(FRAME_PART);
+                    compacted.write_all(&frame).map_err(io_error)?;
+                    end += frame.len() as u64;
+                }
+            }
+        }
+        let frame = encoder.finish(FRAME_COMMIT);
+        compacted.write_all(&frame).map_err(io_error)?;
+        end += frame.len() as u64;
+        compacted.sync_all().map_err(io_error)?;
+        end
+    };
+    let log_path = path.join(LOG_FILE);
+    fs::rename(&compact_path, &log_path).map_err(io_error)?;
+    for (pointer, offset) in tables
+        .iter_mut()
+        .flat_map(|table| table.values_mut())
+        .zip(pointers)
+    {
+        pointer.offset = offset;
+    }
+    let file = OpenOptions::new()
+        .read(true)
+        .append(true)
+        .open(&log_path)
+        .map_err(io_error)?;
+    Ok((file, end))
+}
+
+fn io_error(err: io::Error) -> Error {
+    Error::DBError(format!("database io error: {}", err))
+}
+
+fn corruption(offset: u64) -> Error {
+    Error::Corruption(format!(
+        "the frame at offset {} of the database log is broken",
+        offset
+    ))
+}
+
+#[cfg(unix)]
+fn read_at(file: &File, buf: &mut [u8], offset: u64) -> io::Result<()> {
+    use std::os::unix::fs::FileExt;
+    file.read_exact_at(buf, offset)
+}
+
+#[cfg(windows)]
+fn read_at(file: &File, mut buf: &mut [u8], mut offset: u64) -> io::Result<()> {
+    use std::os::windows::fs::FileExt;
+    while !buf.is_empty() {
+        match file.seek_read(buf, offset) {
+            Ok(0) => return Err(io::ErrorKind::UnexpectedEof.into()),
+            Ok(n) => {
+                let rest = buf;
+                buf = &mut rest[n..];
+                offset += n as u64;
+            }
+            Err(ref err) if err.kind() == io::ErrorKind::Interrupted => {}
+            Err(err) => return Err(err),
+        }
+    }
+    Ok(())
+}
+
+fn column<'a>(tables: &'a [Table], col: Col) -> Result<&'a Table> {
+    tables
+        .get(col as usize)
+        .ok_or_else(|| Error::DBError(format!("column {} not found", col)))
+}
+
+impl KeyValueDB for LogDB {
+    type Batch = LogDbBatch;
+
+    fn read(&self, col: Col, key: &[u8]) -> Result<Option<Vec<u8>>> {
+        self.inner.get(col, key)
+    }
+
+    fn partial_read(&self, col: Col, key: &[u8], range: &Range<usize>) -> Result<Option<Vec<u8>>> {
+        let pointer = match column(&self.inner.tables.read(), col)?.get(key) {
+            Some(pointer) => *pointer,
+            None => return Ok(None),
+        };
+        if range.start > range.end || range.end > pointer.len as usize {
+            return Ok(None);
+        }
+        let mut slice = vec![0; range.end - range.start];
+        read_at(
+            &self.inner.file,
+            &mut slice,
+            pointer.offset + range.start as u64,
+        )
+        .map_err(io_error)?;
+        Ok(Some(slice))
+    }
+
+    fn batch(&self) -> Result<Self::Batch> {
+        Ok(LogDbBatch {
+            operations: Vec::new(),
+            staged: HashMap::new(),
+            columns: self.inner.tables.read().len(),
+            inner: Arc::clone(&self.inner),
+        })
+    }
+}
+
+// The keys of the table after `from` and their pointers, `SEEK_CHUNK_SIZE` of them at most
+fn table_chunk(table: &Table, from: Bound<&[u8]>) -> Vec<(Vec<u8>, Pointer)> {
+    table
+        .range::<[u8], _>((from, Bound::Unbounded))
+        .take(SEEK_CHUNK_SIZE)
+        .map(|(key, pointer)| (key.to_owned(), *pointer))
+        .collect()
+}
+
+// Iterates over the pairs after `from`, the keys are copied a chunk at a time by `read_chunk`
+// and the values are read from the log as they are reached
+fn seek_table<'a, F>(
+    inner: &'a Inner,
+    from: Bound<&[u8]>,
+    read_chunk: F,
+) -> Box<Iterator<Item = Result<(Vec<u8>, Vec<u8>)>> + 'a>
+where
+    F: Fn(Bound<&[u8]>) -> Vec<(Vec<u8>, Pointer)> + 'a,
+{
+    let first = read_chunk(from);
+    let chunks = iter::successors(Some(first), move |chunk| {
+        if chunk.len() < SEEK_CHUNK_SIZE {
+            return None;
+        }
+        let (last, _) = chunk.last()?;
+        Some(read_chunk(Bound::Excluded(&last[..])))
+    });
+    Box::new(
+        chunks
+            .flatten()
+            .map(move |(key, pointer)| inner.read_value(pointer).map(|value| (key, value))),
+    )
+}
+
+impl IterableKeyValueDB for LogDB {
+    fn seek<'a>(
+        &'a self,
+        col: Col,
+        from: Bound<&[u8]>,
+    ) -> Result<Box<Iterator<Item = Result<(Vec<u8>, Vec<u8>)>> + 'a>> {
+        column(&self.inner.tables.read(), col)?;
+        // the lock is only held while a chunk of keys is copied, the writes committed in the
+        // meantime may be seen by the later chunks
+        let inner = &self.inner;
+        Ok(seek_table(inner, from, move |from| {
+            table_chunk(&inner.tables.read()[col as usize], from)
+        }))
+    }
+
+    // the log is only appended to while the database is open, the values pointed at by a copy
+    // of the tables stay where they are
+    fn snapshot<'a>(&'a self) -> Result<Box<DBSnapshot + 'a>> {
+        Ok(Box::new(LogSnapshot {
+            inner: &self.inner,
+            tables: self.inner.tables.read().clone(),
+        }))
+    }
+}
+
+struct LogSnapshot<'a> {
+    inner: &'a Inner,
+    tables: Vec<Table>,
+}
+
+impl<'a> DBSnapshot for LogSnapshot<'a> {
+    fn seek<'b>(
+        &'b self,
+        col: Col,
+        from: Bound<&[u8]>,
+    ) -> Result<Box<Iterator<Item = Result<(Vec<u8>, Vec<u8>)>> + 'b>> {
+        let table = column(&self.tables, col)?;
+        Ok(seek_table(self.inner, from, move |from| {
+            table_chunk(table, from)
+        }))
+    }
+}
+
+enum Operation {
+    Insert {
+        col: Col,
+        key: Vec<u8>,
+        value: Vec<u8>,
+    },
+    Delete {
+        col: Col,
+        key: Vec<u8>,
+    },
+}
+
+// An operation read back from the log
+struct Entry {
+    col: Col,
+    key: Vec<u8>,
+    value: Option<Pointer>,
+}
+
+impl Entry {
+    fn apply(self, tables: &mut [Table]) {
+        if let Some(table) = tables.get_mut(self.col as usize) {
+            match self.value {
+                Some(pointer) => {
+                    table.insert(self.key, pointer);
+                }
+                None => {
+                    table.remove(&self.key);
+                }
+            }
+        }
+    }
+}
+
+fn checksum(kind: u8, payload: &[u8]) -> u64 {
+    let mut hasher = FnvHasher::default();
+    hasher.write_u8(kind);
+    hasher.write(payload);
+    hasher.finish()
+}
+
+fn header_checksum(fields: &[u8]) -> u64 {
+    let mut hasher = FnvHasher::default();
+    hasher.write(fields);
+    hasher.finish()
+}
+
+#[derive(Default)]
+struct Encoder {
+    payload: Vec<u8>,
+}
+
+impl Encoder {
+    // Returns the offset of the value in the payload
+    fn insert(&mut self, col: Col, key: &[u8], value: &[u8]) -> u64 {
+        self.payload.push(OP_INSERT);
+        self.payload.extend_from_slice(&col.to_le_bytes());
+        self.put_bytes(key);
+        self.put_bytes(value) - value.len() as u64
+    }
+
+    fn delete(&mut self, col: Col, key: &[u8]) {
+        self.payload.push(OP_DELETE);
+        self.payload.extend_from_slice(&col.to_le_bytes());
+        self.put_bytes(key);
+    }
+
+    // Returns the offset of the end of the bytes in the payload
+    fn put_bytes(&mut self, bytes: &[u8]) -> u64 {
+        self.payload
+            .extend_from_slice(&(bytes.len() as u32).to_le_bytes());
+        self.payload.extend_from_slice(bytes);
+        self.payload.len() as u64
+    }
+
+    // Takes the encoded operations as a frame of the kind
+    fn finish(&mut self, kind: u8) -> Vec<u8> {
+        let payload = std::mem::replace(&mut self.payload, Vec::new());
+        let mut frame = Vec::with_capacity(FRAME_HEADER_SIZE as usize + payload.len());
+        frame.extend_from_slice(&(payload.len() as u64).to_le_bytes());
+        frame.push(kind);
+        frame.extend_from_slice(&checksum(kind, &payload).to_le_bytes());
+        let header_sum = header_checksum(&frame);
+        frame.extend_from_slice(&header_sum.to_le_bytes());
+        frame.extend_from_slice(&payload);
+        frame
+    }
+}
+
+// Reads the kind and the payload of the frame at `offset`, `None` when the frame is torn at the
+// end of the log of `size` bytes. A torn frame is a partial header or an intact header followed
+// by a short or bad payload, a bad header is a corruption wherever it is.
+fn read_frame<R: Read>(reader: &mut R, offset: u64, size: u64) -> Result<Option<(u8, Vec<u8>)>> {
+    if size - offset < FRAME_HEADER_SIZE {
+        return Ok(None);
+    }
+    let mut header = [0; FRAME_HEADER_SIZE as usize];
+    reader.read_exact(&mut header).map_err(io_error)?;
+    let mut header_sum = [0; 8];
+    header_sum.copy_from_slice(&header[FRAME_HEADER_FIELDS_SIZE..]);
+    if header_checksum(&header[..FRAME_HEADER_FIELDS_SIZE]) != u64::from_le_bytes(header_sum) {
+        return Err(corruption(offset));
+    }
+    let mut len = [0; 8];
+    len.copy_from_slice(&header[..8]);
+    let len = u64::from_le_bytes(len);
+    let kind = header[8];
+    let mut sum = [0; 8];
+    sum.copy_from_slice(&header[9..FRAME_HEADER_FIELDS_SIZE]);
+    if len > size - offset - FRAME_HEADER_SIZE {
+        return Ok(None);
+    }
+    let mut payload = vec![0; len as usize];
+    reader.read_exact(&mut payload).map_err(io_error)?;
+    if checksum(kind, &payload) != u64::from_le_bytes(sum) {
+        if offset + FRAME_HEADER_SIZE + len == size {
+            return Ok(None);
+        }
+        return Err(corruption(offset));
+    }
+    if kind != FRAME_PART && kind != FRAME_COMMIT {
+        return Err(corruption(offset));
+    }
+    Ok(Some((kind, payload)))
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    offset: u64,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Option<&'a [u8]> {
+        if self.data.len() < len {
+            return None;
+        }
+        let (taken, rest) = self.data.split_at(len);
+        self.data = rest;
+        self.offset += len as u64;
+        Some(taken)
+    }
+
+    fn u32(&mut self) -> Option<u32> {
+        let mut bytes = [0; 4];
+        bytes.copy_from_slice(self.take(4)?);
+        Some(u32::from_le_bytes(bytes))
+    }
+}
+
+// Decodes the operations of the payload at `offset` of the log
+fn decode_entries(payload: &[u8], offset: u64, entries: &mut Vec<Entry>) -> Option<()> {
+    let mut reader = Reader {
+        data: payload,
+        offset,
+    };
+    while !reader.data.is_empty() {
+        let op = reader.take(1)?[0];
+        let col = reader.u32()?;
+        let key_len = reader.u32()? as usize;
+        let key = reader.take(key_len)?.to_vec();
+        let value = match op {
+            OP_INSERT => {
+                let len = reader.u32()?;
+                let pointer = Pointer {
+                    offset: reader.offset,
+                    len,
+                };
+                reader.take(len as usize)?;
+                Some(pointer)
+            }
+            OP_DELETE => None,
+            _ => return None,
+        };
+        entries.push(Entry { col, key, value });
+    }
+    Some(())
+}
+
+pub struct LogDbBatch {
+    operations: Vec<Operation>,
+    // The position of the last operation staged on each key
+    staged: HashMap<(Col, Vec<u8>), usize>,
+    columns: usize,
+    inner: Arc<Inner>,
+}
+
+impl LogDbBatch {
+    fn check_column(&self, col: Col) -> Result<()> {
+        if (col as usize) < self.columns {
+            Ok(())
+        } else {
+            Err(Error::DBError(format!("column {} not found", col)))
+        }
+    }
+}
+
+impl DbBatch for LogDbBatch {
+    fn insert(&mut self, col: Col, key: &[u8], value: &[u8]) -> Result<()> {
+        self.check_column(col)?;
+        if key.len() > u32::max_value() as usize || value.len() > u32::max_value() as usize {
+            return Err(Error::DBError(format!(
+                "the key or the value is too large to store in column {}",
+                col
+            )));
+        }
+        self.staged
+            .insert((col, key.to_vec()), self.operations.len());
+        self.operations.push(Operation::Insert {
+            col,
+            key: key.to_vec(),
+            value: value.to_vec(),
+        });
+        Ok(())
+    }
+
+    fn delete(&mut self, col: Col, key: &[u8]) -> Result<()> {
+        self.check_column(col)?;
+        self.staged
+            .insert((col, key.to_vec()), self.operations.len());
+        self.operations.push(Operation::Delete {
+            col,
+            key: key.to_vec(),
+        });
+        Ok(())
+    }
+
+    fn get(&self, col: Col, key: &[u8]) -> Result<Option<Vec<u8>>> {
+        self.check_column(col)?;
+        match self.staged.get(&(col, key.to_vec())) {
+            Some(&position) => match &self.operations[position] {
+                Operation::Insert { value, .. } => Ok(Some(value.to_owned())),
+                Operation::Delete { .. } => Ok(None),
+            },
+            None => self.inner.get(col, key),
+        }
+    }
+
+    fn commit(self) -> Result<()> {
+        if self.operations.is_empty() {
+            return Ok(());
+        }
+        let mut encoder = Encoder::default();
+        let value_offsets = self
+            .operations
+            .iter()
+            .map(|operation| match operation {
+                Operation::Insert { col, key, value } => Some(encoder.insert(*col, key, value)),
+                Operation::Delete { col, key } => {
+                    encoder.delete(*col, key);
+                    None
+                }
+            })
+            .collect::<Vec<_>>();
+        let frame = encoder.finish(FRAME_COMMIT);
+
+        // the log is locked until the batch is applied, for the tables to follow the log order
+        let mut end = self.inner.end.lock();
+        let written = (&self.inner.file)
+            .write_all(&frame)
+            .and_then(|_| self.inner.file.sync_data());
+        if let Err(err) = written {
+            // cut the partly written frame, for the next one not to follow a broken frame
+            let _ = self.inner.file.set_len(*end);
+            return Err(io_error(err));
+        }
+        let payload_offset = *end + FRAME_HEADER_SIZE;
+        *end += frame.len() as u64;
+        let mut tables = self.inner.tables.write();
+        for (operation, value_offset) in self.operations.into_iter().zip(value_offsets) {
+            let entry = match operation {
+                Operation::Insert { col, key, value } => Entry {
+                    col,
+                    key,
+                    value: value_offset.map(|value_offset| Pointer {
+                        offset: payload_offset + value_offset,
+                        len: value.len() as u32,
+                    }),
+                },
+                Operation::Delete { col, key } => Entry {
+                    col,
+                    key,
+                    value: None,
+                },
+            };
+            entry.apply(&mut tables);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::conformance;
+    use tempfile;
+
+    fn setup_db(prefix: &str, columns: u32) -> (LogDB, tempfile::TempDir) {
+        let tmp_dir = tempfile::Builder::new().prefix(prefix).tempdir().unwrap();
+        let config = DBConfig {
+            path: tmp_dir.as_ref().to_path_buf(),
+            ..Default::default()
+        };
+        (LogDB::open(&config, columns).unwrap(), tmp_dir)
+    }
+
+    fn log_len(tmp_dir: &tempfile::TempDir) -> u64 {
+        fs::metadata(tmp_dir.path().join(LOG_FILE)).unwrap().len()
+    }
+
+    #[test]
+    fn conformance_suite() {
+        let (db, _tmp_dir) = setup_db("logdb_conformance", conformance::COLUMNS);
+        conformance::check(&db);
+    }
+
+    #[test]
+    fn reopen() {
+        let (db, tmp_dir) = setup_db("logdb_reopen", 2);
+        let mut batch = db.batch().unwrap();
+        batch.insert(0, &[0], &[0, 0]).unwrap();
+        batch.insert(1, &[1], &[1, 1]).unwrap();
+        batch.commit().unwrap();
+        let mut batch = db.batch().unwrap();
+        batch.delete(0, &[0]).unwrap();
+        batch.commit().unwrap();
+        drop(db);
+
+        let db = LogDB::open_with_version(tmp_dir.path(), 2, VERSION_VALUE).unwrap();
+        assert_eq!(db.read(0, &[0]).unwrap(), None);
+        assert_eq!(db.read(1, &[1]).unwrap(), Some(vec![1, 1]));
+        drop(db);
+        assert!(LogDB::open_with_version(tmp_dir.path(), 2, "0.1.0").is_err());
+    }
+
+    #[test]
+    fn drop_torn_batch() {
+        let (db, tmp_dir) = setup_db("logdb_drop_torn_batch", 1);
+        let mut batch = db.batch().unwrap();
+        batch.insert(0, &[0], &[0]).unwrap();
+        batch.commit().unwrap();
+        let mut batch = db.batch().unwrap();
+        batch.insert(0, &[1], &[1]).unwrap();
+        batch.insert(0, &[2], &[2]).unwrap();
+        batch.commit().unwrap();
+        drop(db);
+
+        // a crash in the middle of writing the second batch
+        let log_path = tmp_dir.path().join(LOG_FILE);
+        let len = log_len(&tmp_dir);
+        let file = OpenOptions::new().write(true).open(&log_path).unwrap();
+        file.set_len(len - 3).unwrap();
+        drop(file);
+
+        let db = LogDB::open_with_version(tmp_dir.path(), 1, VERSION_VALUE).unwrap();
+        assert_eq!(db.read(0, &[0]).unwrap(), Some(vec![0]));
+        assert_eq!(db.read(0, &[1]).unwrap(), None);
+        assert_eq!(db.read(0, &[2]).unwrap(), None);
+
+        // the log goes on after the dropped frame
+        let mut batch = db.batch().unwrap();
+        batch.insert(0, &[3], &[3]).unwrap();
+        batch.commit().unwrap();
+        drop(db);
+        let db = LogDB::open_with_version(tmp_dir.path(), 1, VERSION_VALUE).unwrap();
+        assert_eq!(db.read(0, &[3]).unwrap(), Some(vec![3]));
+    }
+
+    #[test]
+    fn report_corruption_before_the_end() {
+        let (db, tmp_dir) = setup_db("logdb_report_corruption", 1);
+        for i in 0..3u8 {
+            let mut batch = db.batch().unwrap();
+            batch.insert(0, &[i], &[i; 8]).unwrap();
+            batch.commit().unwrap();
+        }
+        drop(db);
+
+        // garble a value of the first frame
+        let log_path = tmp_dir.path().join(LOG_FILE);
+        let len = log_len(&tmp_dir);
+        let mut data = fs::read(&log_path).unwrap();
+        data[FRAME_HEADER_SIZE as usize + 16] ^= 0xff;
+        fs::write(&log_path, &data).unwrap();
+
+        match LogDB::open_with_version(tmp_dir.path(), 1, VERSION_VALUE) {
+            Err(Error::Corruption(_)) => {}
+            _ => panic!("the corruption should be reported"),
+        }
+        // nothing is dropped
+        assert_eq!(log_len(&tmp_dir), len);
+    }
+
+    #[test]
+    fn report_garbled_length() {
+        let (db, tmp_dir) = setup_db("logdb_report_garbled_length", 1);
+        for i in 0..3u8 {
+            let mut batch = db.batch().unwrap();
+            batch.insert(0, &[i], &[i; 8]).unwrap();
+            batch.commit().unwrap();
+        }
+        drop(db);
+
+        // the length of the first frame points past the end of the log
+        let log_path = tmp_dir.path().join(LOG_FILE);
+        let len = log_len(&tmp_dir);
+        let mut data = fs::read(&log_path).unwrap();
+        data[7] ^= 0x80;
+        fs::write(&log_path, &data).unwrap();
+
+        match LogDB::open_with_version(tmp_dir.path(), 1, VERSION_VALUE) {
+            Err(Error::Corruption(_)) => {}
+            _ => panic!("the corruption should be reported"),
+        }
+        // the later frames are not dropped as torn
+        assert_eq!(log_len(&tmp_dir), len);
+    }
+
+    #[test]
+    fn drop_uncommitted_parts() {
+        let (db, tmp_dir) = setup_db("logdb_drop_uncommitted_parts", 1);
+        let mut batch = db.batch().unwrap();
+        batch.insert(0, &[0], &[0]).unwrap();
+        batch.commit().unwrap();
+        drop(db);
+        let len = log_len(&tmp_dir);
+
+        // a crash in the middle of writing the frames of a compaction
+        let mut encoder = Encoder::default();
+        encoder.insert(0, &[1], &[1]);
+        let mut file = OpenOptions::new()
+            .append(true)
+            .open(tmp_dir.path().join(LOG_FILE))
+            .unwrap();
+        file.write_all(&encoder.finish(FRAME_PART)).unwrap();
+        drop(file);
+
+        let db = LogDB::open_with_version(tmp_dir.path(), 1, VERSION_VALUE).unwrap();
+        assert_eq!(db.read(0, &[0]).unwrap(), Some(vec![0]));
+        assert_eq!(db.read(0, &[1]).unwrap(), None);
+        assert_eq!(log_len(&tmp_dir), len);
+    }
+
+    #[test]
+    fn compact_on_open() {
+        let (db, tmp_dir) = setup_db("logdb_compact_on_open", 1);
+        for i in 0..10u8 {
+            let mut batch = db.batch().unwrap();
+            batch.insert(0, &[0], &[i; 64]).unwrap();
+            batch.commit().unwrap();
+        }
+        drop(db);
+        let len = log_len(&tmp_dir);
+
+        let db = LogDB::open_with_version(tmp_dir.path(), 1, VERSION_VALUE).unwrap();
+        assert!(log_len(&tmp_dir) < len);
+        assert_eq!(db.read(0, &[0]).unwrap(), Some(vec![9; 64]));
+    }
+
+    #[test]
+    fn compact_into_bounded_frames() {
+        let (db, tmp_dir) = setup_db("logdb_compact_into_bounded_frames", 2);
+        let mut batch = db.batch().unwrap();
+        for i in 0..20u8 {
+            batch.insert(u32::from(i % 2), &[i], &[i; 16]).unwrap();
+        }
+        batch.commit().unwrap();
+        drop(db);
+
+        let file = File::open(tmp_dir.path().join(LOG_FILE)).unwrap();
+        let mut tables = vec![Table::new(); 2];
+        replay(&file, &mut tables).unwrap();
+        let (_, end) = compact(tmp_dir.path(), &file, &mut tables, 64).unwrap();
+        assert_eq!(log_len(&tmp_dir), end);
+        // an insert takes 30 bytes, a frame is closed at the third one: 6 part frames of 3
+        // values and the commit frame of the last 2
+        assert_eq!(end, 7 * FRAME_HEADER_SIZE + 20 * 30);
+
+        let db = LogDB::open_with_version(tmp_dir.path(), 2, VERSION_VALUE).unwrap();
+        for i in 0..20u8 {
+            assert_eq!(db.read(u32::from(i % 2), &[i]).unwrap(), Some(vec![i; 16]));
+        }
+        assert_eq!(db.iter(1).unwrap().count(), 10);
+    }
+}
@@ -1,8 +1,10 @@
 // for unit test
-use crate::{Col, DbBatch, Error, KeyValueDB, Result};
+use crate::checksum::Checksums;
+use crate::{is_after, Col, DBSnapshot, DbBatch, Error, IterableKeyValueDB, KeyValueDB, Result};
 use ckb_util::RwLock;
 use fnv::FnvHashMap;
-use std::ops::Range;
+use std::collections::HashMap;
+use std::ops::{Bound, Range};
 use std::sync::Arc;
 
 pub type MemoryKey = Vec<u8>;
@@ -12,6 +14,7 @@ pub type MemoryTable = FnvHashMap<Col, FnvHashMap<MemoryKey, MemoryValue>>;
 #[derive(Default, Debug)]
 pub struct MemoryKeyValueDB {
     db: Arc<RwLock<MemoryTable>>,
+    checksums: Checksums,
 }
 
 impl MemoryKeyValueDB {
@@ -22,6 +25,15 @@ impl MemoryKeyValueDB {
         }
         MemoryKeyValueDB {
             db: Arc::new(RwLock::new(table)),
+            checksums: Checksums::default(),
+        }
+    }
+
+    /// Same as `open`, with the values of `checksum_columns` checksummed
+    pub fn open_with_checksums(cols: usize, checksum_columns: &[Col]) -> MemoryKeyValueDB {
+        MemoryKeyValueDB {
+            checksums: Checksums::new(checksum_columns),
+            ..Self::open(cols)
         }
     }
 }
@@ -34,11 +46,16 @@ impl KeyValueDB for MemoryKeyValueDB {
 
         match db.get(&col) {
             None => Err(Error::DBError(format!("column {} not found ", col))),
-            Some(map) => Ok(map.get(key).cloned()),
+            Some(map) => self.checksums.open_option(col, key, map.get(key).cloned()),
         }
     }
 
     fn partial_read(&self, col: Col, key: &[u8], range: &Range<usize>) -> Result<Option<Vec<u8>>> {
+        if self.checksums.enabled(col) {
+            return self.read(col, key).map(|v| {
+                v.and_then(|data| data.get(range.start..range.end).map(|slice| slice.to_vec()))
+            });
+        }
         let db = self.db.read();
 
         match db.get(&col) {
@@ -50,17 +67,79 @@ impl KeyValueDB for MemoryKeyValueDB {
         }
     }
 
+    fn read_multi(&self, col: Col, keys: &[&[u8]]) -> Result<Vec<Option<MemoryValue>>> {
+        let db = self.db.read();
+
+        match db.get(&col) {
+            None => Err(Error::DBError(format!("column {} not found ", col))),
+            Some(map) => keys
+                .iter()
+                .map(|key| self.checksums.open_option(col, key, map.get(*key).cloned()))
+                .collect(),
+        }
+    }
+
     fn batch(&self) -> Result<Self::Batch> {
         Ok(Self::Batch {
             operations: Vec::new(),
+            staged: HashMap::new(),
             db: Arc::clone(&self.db),
+            checksums: self.checksums.clone(),
         })
     }
 }
 
+impl IterableKeyValueDB for MemoryKeyValueDB {
+    fn seek<'a>(
+        &'a self,
+        col: Col,
+        from: Bound<&[u8]>,
+    ) -> Result<Box<Iterator<Item = Result<(Vec<u8>, Vec<u8>)>> + 'a>> {
+        let db = self.db.read();
+
+        match db.get(&col) {
+            None => Err(Error::DBError(format!("column {} not found ", col))),
+            Some(map) => {
+                // a sorted snapshot, the lock is not held while iterating
+                let mut pairs = map
+                    .iter()
+                    .filter(|(key, _)| is_after(key, from))
+                    .map(|(key, value)| (key.to_owned(), value.to_owned()))
+                    .collect::<Vec<_>>();
+                pairs.sort();
+                let checksums = &self.checksums;
+                Ok(Box::new(pairs.into_iter().map(move |(key, value)| {
+                    checksums.open(col, &key, value).map(|value| (key, value))
+                })))
+            }
+        }
+    }
+
+    // a copy of the tables
+    fn snapshot<'a>(&'a self) -> Result<Box<DBSnapshot + 'a>> {
+        Ok(Box::new(MemoryKeyValueDB {
+            db: Arc::new(RwLock::new(self.db.read().clone())),
+            checksums: self.checksums.clone(),
+        }))
+    }
+}
+
+impl DBSnapshot for MemoryKeyValueDB {
+    fn seek<'a>(
+        &'a self,
+        col: Col,
+        from: Bound<&[u8]>,
+    ) -> Result<Box<Iterator<Item = Result<(Vec<u8>, Vec<u8>)>> + 'a>> {
+        IterableKeyValueDB::seek(self, col, from)
+    }
+}
+
 pub struct MemoryDbBatch {
     operations: Vec<BatchOperation>,
+    // The position of the last operation staged on each key
+    staged: HashMap<(Col, Vec<u8>), usize>,
     db: Arc<RwLock<MemoryTable>>,
+    checksums: Checksums,
 }
 
 enum BatchOperation {
@@ -77,6 +156,8 @@ enum BatchOperation {
 
 impl DbBatch for MemoryDbBatch {
     fn insert(&mut self, col: Col, key: &[u8], value: &[u8]) -> Result<()> {
+        self.staged
+            .insert((col, key.to_vec()), self.operations.len());
         self.operations.push(BatchOperation::Insert {
             col,
             key: key.to_vec(),
@@ -86,6 +167,8 @@ impl DbBatch for MemoryDbBatch {
     }
 
     fn delete(&mut self, col: Col, key: &[u8]) -> Result<()> {
+        self.staged
+            .insert((col, key.to_vec()), self.operations.len());
         self.operations.push(BatchOperation::Delete {
             col,
             key: key.to_vec(),
@@ -93,11 +176,26 @@ impl DbBatch for MemoryDbBatch {
         Ok(())
     }
 
+    fn get(&self, col: Col, key: &[u8]) -> Result<Option<Vec<u8>>> {
+        if let Some(&position) = self.staged.get(&(col, key.to_vec())) {
+            return match &self.operations[position] {
+                BatchOperation::Insert { value, .. } => Ok(Some(value.to_owned())),
+                BatchOperation::Delete { .. } => Ok(None),
+            };
+        }
+        match self.db.read().get(&col) {
+            None => Err(Error::DBError(format!("column {} not found ", col))),
+            Some(map) => self.checksums.open_option(col, key, map.get(key).cloned()),
+        }
+    }
+
     fn commit(self) -> Result<()> {
         let mut db = self.db.write();
+        let checksums = &self.checksums;
         self.operations.into_iter().for_each(|op| match op {
             BatchOperation::Insert { col, key, value } => {
                 if let Some(map) = db.get_mut(&col) {
+                    let value = checksums.seal(col, &key, &value).into_owned();
                     map.insert(key, value);
                 }
             }
@@ -114,6 +212,17 @@ impl DbBatch for MemoryDbBatch {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::conformance;
+
+    #[test]
+    fn conformance_suite() {
+        let db = MemoryKeyValueDB::open(conformance::COLUMNS as usize);
+        conformance::check(&db);
+        conformance::check(&MemoryKeyValueDB::open_with_checksums(
+            conformance::COLUMNS as usize,
+            &[0, 1, 2],
+        ));
+    }
 
     #[test]
     fn write_and_read() {
@@ -153,4 +262,94 @@ mod tests {
             db.partial_read(0, &[0, 0], &(1..4)).unwrap()
         );
     }
+
+    #[test]
+    fn write_and_read_multi() {
+        let db = MemoryKeyValueDB::open(2);
+        let mut batch = db.batch().unwrap();
+        batch.insert(0, &[0, 0], &[0, 0, 0]).unwrap();
+        batch.insert(0, &[1, 1], &[1, 1, 1]).unwrap();
+        batch.commit().unwrap();
+
+        assert_eq!(
+            vec![
+                Some(vec![1, 1, 1]),
+                None,
+                Some(vec![0, 0, 0]),
+                Some(vec![1, 1, 1])
+            ],
+            db.read_multi(0, &[&[1, 1], &[2, 2], &[0, 0], &[1, 1]])
+                .unwrap()
+        );
+        assert!(db.read_multi(2, &[&[0, 0]]).is_err());
+    }
+
+    #[test]
+    fn write_and_iter() {
+        let db = MemoryKeyValueDB::open(2);
+        let mut batch = db.batch().unwrap();
+        batch.insert(0, &[1, 1], &[1, 1, 1]).unwrap();
+        batch.insert(0, &[0, 0], &[0, 0, 0]).unwrap();
+        batch.insert(1, &[2, 2], &[2, 2, 2]).unwrap();
+        batch.commit().unwrap();
+
+        assert_eq!(
+            vec![(vec![0, 0], vec![0, 0, 0]), (vec![1, 1], vec![1, 1, 1])],
+            db.iter(0).unwrap().collect::<Result<Vec<_>>>().unwrap()
+        );
+        assert_eq!(
+            vec![(vec![1, 1], vec![1, 1, 1])],
+            db.iter_from(0, &[0, 1])
+                .unwrap()
+                .collect::<Result<Vec<_>>>()
+                .unwrap()
+        );
+        assert!(db.iter(2).is_err());
+    }
+
+    #[test]
+    fn detect_corruption() {
+        let db = MemoryKeyValueDB::open_with_checksums(2, &[1]);
+        let mut batch = db.batch().unwrap();
+        batch.insert(0, &[1], &[1, 2, 3]).unwrap();
+        batch.insert(1, &[0], &[0, 0]).unwrap();
+        batch.insert(1, &[1], &[1, 2, 3]).unwrap();
+        batch.commit().unwrap();
+        assert_eq!(db.read(1, &[0]).unwrap(), Some(vec![0, 0]));
+        assert_eq!(db.read(1, &[1]).unwrap(), Some(vec![1, 2, 3]));
+        assert_eq!(db.partial_read(1, &[1], &(1..3)).unwrap(), Some(vec![2, 3]));
+
+        // flip a byte of the stored values
+        for col in 0..2 {
+            let mut table = db.db.write();
+            let stored = table.get_mut(&col).unwrap().get_mut(&vec![1]).unwrap();
+            let last = stored.len() - 1;
+            stored[last] ^= 1;
+        }
+        assert_eq!(db.read(0, &[1]).unwrap(), Some(vec![1, 2, 2]));
+        match db.read(1, &[1]) {
+            Err(Error::Corruption(_)) => {}
+            result => panic!("unexpected {:?}", result),
+        }
+        assert!(db.partial_read(1, &[1], &(0..1)).is_err());
+        assert!(db.read_multi(1, &[&[0], &[1]]).is_err());
+        assert!(db.batch().unwrap().get(1, &[1]).is_err());
+        // the iteration yields the corruption instead of the value
+        let items = db.iter(1).unwrap().collect::<Vec<_>>();
+        assert_eq!(items[0], Ok((vec![0], vec![0, 0])));
+        match items[1] {
+            Err(Error::Corruption(_)) => {}
+            ref item => panic!("unexpected {:?}", item),
+        }
+        assert!(db.page(1, Bound::Unbounded, 1).is_err());
+        assert!(db.page(1, Bound::Unbounded, 2).is_err());
+
+        // the columns are framed as a whole, an unframed value is a corruption too
+        db.db
+            .write()
+            .get_mut(&1)
+            .unwrap()
+            .insert(vec![2], vec![2, 2]);
+        assert!(db.read(1, &[2]).is_err());
+    }
 }
@@ -0,0 +1,126 @@
+//! The persistent database of the backend selected by `DBConfig::backend`. When no backend is
+//! built in, it can't be opened.
+
+#[cfg(feature = "logdb")]
+use crate::logdb::{LogDB, LogDbBatch};
+#[cfg(feature = "rocksdb")]
+use crate::rocksdb::{RocksDB, RocksdbBatch};
+use crate::{
+    Col, DBBackend, DBConfig, DBSnapshot, DbBatch, Error, IterableKeyValueDB, KeyValueDB, Result,
+};
+use std::ops::{Bound, Range};
+
+pub enum PersistentDB {
+    #[cfg(feature = "rocksdb")]
+    RocksDB(RocksDB),
+    #[cfg(feature = "logdb")]
+    LogDB(LogDB),
+}
+
+pub enum PersistentDbBatch {
+    #[cfg(feature = "rocksdb")]
+    RocksDB(RocksdbBatch),
+    #[cfg(feature = "logdb")]
+    LogDB(LogDbBatch),
+}
+
+impl PersistentDB {
+    pub fn open_backend(config: &DBConfig, columns: u32) -> Result<Self> {
+        match config.backend {
+            #[cfg(feature = "rocksdb")]
+            DBBackend::RocksDB => Ok(PersistentDB::RocksDB(RocksDB::open(config, columns))),
+            #[cfg(feature = "logdb")]
+            DBBackend::LogDB => LogDB::open(config, columns).map(PersistentDB::LogDB),
+            #[allow(unreachable_patterns)]
+            backend => Err(Error::DBError(format!(
+                "the database backend {:?} is not built in",
+                backend
+            ))),
+        }
+    }
+
+    // TODO Change `panic(...)` to `Result<...>`
+    pub fn open(config: &DBConfig, columns: u32) -> Self {
+        Self::open_backend(config, columns).unwrap_or_else(|err| panic!("{}", err))
+    }
+}
+
+// Matches on the dereferenced value, which has no variant when no backend is built in
+macro_rules! dispatch {
+    ($self:expr, $db:ident => $body:expr) => {
+        match *$self {
+            #[cfg(feature = "rocksdb")]
+            PersistentDB::RocksDB(ref $db) => $body,
+            #[cfg(feature = "logdb")]
+            PersistentDB::LogDB(ref $db) => $body,
+        }
+    };
+}
+
+impl KeyValueDB for PersistentDB {
+    type Batch = PersistentDbBatch;
+
+    fn read(&self, col: Col, key: &[u8]) -> Result<Option<Vec<u8>>> {
+        dispatch!(self, db => db.read(col, key))
+    }
+
+    fn partial_read(&self, col: Col, key: &[u8], range: &Range<usize>) -> Result<Option<Vec<u8>>> {
+        dispatch!(self, db => db.partial_read(col, key, range))
+    }
+
+    fn read_multi(&self, col: Col, keys: &[&[u8]]) -> Result<Vec<Option<Vec<u8>>>> {
+        dispatch!(self, db => db.read_multi(col, keys))
+    }
+
+    fn batch(&self) -> Result<Self::Batch> {
+        match *self {
+            #[cfg(feature = "rocksdb")]
+            PersistentDB::RocksDB(ref db) => db.batch().map(PersistentDbBatch::RocksDB),
+            #[cfg(feature = "logdb")]
+            PersistentDB::LogDB(ref db) => db.batch().map(PersistentDbBatch::LogDB),
+        }
+    }
+}
+
+impl IterableKeyValueDB for PersistentDB {
+    fn seek<'a>(
+        &'a self,
+        col: Col,
+        from: Bound<&[u8]>,
+    ) -> Result<Box<Iterator<Item = Result<(Vec<u8>, Vec<u8>)>> + 'a>> {
+        dispatch!(self, db => db.seek(col, from))
+    }
+
+    fn snapshot<'a>(&'a self) -> Result<Box<DBSnapshot + 'a>> {
+        dispatch!(self, db => db.snapshot())
+    }
+}
+
+macro_rules! dispatch_batch {
+    ($self:expr, $batch:pat => $body:expr) => {
+        match $self {
+            #[cfg(feature = "rocksdb")]
+            PersistentDbBatch::RocksDB($batch) => $body,
+            #[cfg(feature = "logdb")]
+            PersistentDbBatch::LogDB($batch) => $body,
+        }
+    };
+}
+
+impl DbBatch for PersistentDbBatch {
+    fn insert(&mut self, col: Col, key: &[u8], value: &[u8]) -> Result<()> {
+        dispatch_batch!(*self, ref mut batch => batch.insert(col, key, value))
+    }
+
+    fn delete(&mut self, col: Col, key: &[u8]) -> Result<()> {
+        dispatch_batch!(*self, ref mut batch => batch.delete(col, key))
+    }
+
+    fn get(&self, col: Col, key: &[u8]) -> Result<Option<Vec<u8>>> {
+        dispatch_batch!(*self, ref batch => batch.get(col, key))
+    }
+
+    fn commit(self) -> Result<()> {
+        dispatch_batch!(self, batch => batch.commit())
+    }
+}
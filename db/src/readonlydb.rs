@@ -0,0 +1,133 @@
+//! A read-only view of a database, for the components which must never write to it.
+//!
+//! There is no way to write through it, a batch can't even be created:
+//!
+//! ```compile_fail
+//! use ckb_db::{KeyValueDB, MemoryKeyValueDB, ReadOnlyDB};
+//! use std::sync::Arc;
+//!
+//! let db = ReadOnlyDB::new(Arc::new(MemoryKeyValueDB::open(1)));
+//! let batch = db.batch();
+//! ```
+//!
+//! Nor can the inner database be taken out of it:
+//!
+//! ```compile_fail
+//! use ckb_db::{MemoryKeyValueDB, ReadOnlyDB};
+//! use std::sync::Arc;
+//!
+//! let db = ReadOnlyDB::new(Arc::new(MemoryKeyValueDB::open(1)));
+//! let inner = db.inner;
+//! ```
+
+use crate::{Col, DBSnapshot, IterableKeyValueDB, KeyValueDB, Page, Result};
+use std::ops::{Bound, Range};
+use std::sync::Arc;
+
+pub struct ReadOnlyDB<T> {
+    inner: Arc<T>,
+}
+
+impl<T> Clone for ReadOnlyDB<T> {
+    fn clone(&self) -> Self {
+        ReadOnlyDB {
+            inner: Arc::clone(&self.inner),
+        }
+    }
+}
+
+impl<T> From<Arc<T>> for ReadOnlyDB<T> {
+    fn from(inner: Arc<T>) -> Self {
+        ReadOnlyDB { inner }
+    }
+}
+
+impl<T: KeyValueDB> ReadOnlyDB<T> {
+    pub fn new(inner: Arc<T>) -> Self {
+        ReadOnlyDB { inner }
+    }
+
+    pub fn read(&self, col: Col, key: &[u8]) -> Result<Option<Vec<u8>>> {
+        self.inner.read(col, key)
+    }
+
+    pub fn partial_read(
+        &self,
+        col: Col,
+        key: &[u8],
+        range: &Range<usize>,
+    ) -> Result<Option<Vec<u8>>> {
+        self.inner.partial_read(col, key, range)
+    }
+
+    pub fn read_multi(&self, col: Col, keys: &[&[u8]]) -> Result<Vec<Option<Vec<u8>>>> {
+        self.inner.read_multi(col, keys)
+    }
+}
+
+impl<T: IterableKeyValueDB> ReadOnlyDB<T> {
+    pub fn iter<'a>(
+        &'a self,
+        col: Col,
+    ) -> Result<Box<Iterator<Item = Result<(Vec<u8>, Vec<u8>)>> + 'a>> {
+        self.inner.iter(col)
+    }
+
+    pub fn iter_from<'a>(
+        &'a self,
+        col: Col,
+        from: &[u8],
+    ) -> Result<Box<Iterator<Item = Result<(Vec<u8>, Vec<u8>)>> + 'a>> {
+        self.inner.iter_from(col, from)
+    }
+
+    pub fn snapshot<'a>(&'a self) -> Result<Box<DBSnapshot + 'a>> {
+        self.inner.snapshot()
+    }
+
+    pub fn seek<'a>(
+        &'a self,
+        col: Col,
+        from: Bound<&[u8]>,
+    ) -> Result<Box<Iterator<Item = Result<(Vec<u8>, Vec<u8>)>> + 'a>> {
+        self.inner.seek(col, from)
+    }
+
+    pub fn page(&self, col: Col, from: Bound<&[u8]>, limit: usize) -> Result<Page> {
+        self.inner.page(col, from, limit)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{DbBatch, MemoryKeyValueDB};
+
+    #[test]
+    fn read_through() {
+        let db = Arc::new(MemoryKeyValueDB::open(2));
+        let read_only = ReadOnlyDB::new(Arc::clone(&db));
+        let mut batch = db.batch().unwrap();
+        batch.insert(0, &[1], &[1, 2, 3]).unwrap();
+        batch.insert(0, &[0], &[0]).unwrap();
+        batch.commit().unwrap();
+
+        // the writes of the inner database are seen at once
+        assert_eq!(read_only.read(0, &[1]).unwrap(), db.read(0, &[1]).unwrap());
+        assert_eq!(read_only.read(1, &[1]).unwrap(), None);
+        assert_eq!(
+            read_only.partial_read(0, &[1], &(1..3)).unwrap(),
+            Some(vec![2, 3])
+        );
+        assert_eq!(
+            read_only.read_multi(0, &[&[0], &[2]]).unwrap(),
+            vec![Some(vec![0]), None]
+        );
+        assert_eq!(
+            read_only.iter(0).unwrap().collect::<Vec<_>>(),
+            db.iter(0).unwrap().collect::<Vec<_>>()
+        );
+        assert_eq!(read_only.clone().iter_from(0, &[1]).unwrap().count(), 1);
+        assert!(read_only.read(2, &[1]).is_err());
+    }
+}
@@ -1,18 +1,32 @@
-use crate::{Col, DBConfig, DbBatch, Error, KeyValueDB, Result};
+use crate::checksum::Checksums;
+use crate::config::similar_option;
+use crate::{
+    Col, DBConfig, DBOptions, DBSnapshot, DbBatch, Error, IterableKeyValueDB, KeyValueDB, Result,
+    VERSION_KEY, VERSION_VALUE,
+};
 use log::{info, warn};
-use rocksdb::{ColumnFamily, Error as RdbError, Options, WriteBatch, DB};
-use std::ops::Range;
+use rocksdb::{
+    BlockBasedOptions, ColumnFamily, ColumnFamilyDescriptor, DBIterator, Direction,
+    Error as RdbError, IteratorMode, Options, ReadOptions, Snapshot, WriteBatch, DB,
+};
+use std::collections::HashMap;
+use std::ops::{Bound, Range};
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
+use std::{env, fs, process};
 
-// If any data format in database was changed, we have to update this constant manually.
-//      - If the data can be migrated at startup automatically: update "x.y.z1" to "x.y.z2".
-//      - If the data can be migrated manually: update "x.y1.z" to "x.y2.0".
-//      - If the data can not be migrated: update "x1.y.z" to "x2.0.0".
-pub(crate) const VERSION_KEY: &str = "db-version";
-pub(crate) const VERSION_VALUE: &str = "0.12.0";
+// The columns whose values are framed with checksums, see `checksum.rs`
+const CHECKSUM_COLUMNS_KEY: &str = "checksum-columns";
+// The column being framed or unframed and the last key done, to resume an interrupted migration
+const CHECKSUM_MIGRATION_KEY: &str = "checksum-migration";
+const CHECKSUM_MIGRATION_BATCH_SIZE: usize = 1024;
+// Tells apart the scratch databases the raw options are checked on
+static SCRATCH_DB_COUNT: AtomicUsize = AtomicUsize::new(0);
 
 pub struct RocksDB {
     inner: Arc<DB>,
+    checksums: Checksums,
+    verify_checksums: bool,
 }
 
 impl RocksDB {
@@ -22,23 +36,35 @@ impl RocksDB {
         ver_key: &str,
         ver_val: &str,
     ) -> Result<Self> {
+        // all the options are checked before the data directory is touched
+        let db_options = config.db_options()?;
+        let raw_options = config.raw_options()?;
+        check_raw_options(&raw_options)?;
         let mut opts = Options::default();
         opts.create_if_missing(false);
         opts.create_missing_column_families(true);
+        if let Some(max_open_files) = db_options.max_open_files {
+            opts.set_max_open_files(max_open_files);
+        }
+        if let Some(parallelism) = db_options.parallelism {
+            opts.increase_parallelism(parallelism);
+        }
+        let cf_descriptors = || {
+            (0..columns)
+                .map(|c| ColumnFamilyDescriptor::new(c.to_string(), cf_options(&db_options)))
+                .collect::<Vec<_>>()
+        };
 
-        let cfnames: Vec<_> = (0..columns).map(|c| c.to_string()).collect();
-        let cf_options: Vec<&str> = cfnames.iter().map(|n| n as &str).collect();
-
-        let db = DB::open_cf(&opts, &config.path, &cf_options).or_else(|err| {
+        let db = DB::open_cf_descriptors(&opts, &config.path, cf_descriptors()).or_else(|err| {
             let err_str = err.as_ref();
             if err_str.starts_with("Invalid argument:")
                 && err_str.ends_with("does not exist (create_if_missing is false)")
             {
                 info!("Initialize a new database");
                 opts.create_if_missing(true);
-                let db = DB::open_cf(&opts, &config.path, &cf_options).map_err(|err| {
-                    Error::DBError(format!("failed to open a new created database: {}", err))
-                })?;
+                let db = DB::open_cf_descriptors(&opts, &config.path, cf_descriptors()).map_err(
+                    |err| Error::DBError(format!("failed to open a new created database: {}", err)),
+                )?;
                 db.put(ver_key, ver_val).map_err(|err| {
                     Error::DBError(format!("failed to initiate the database: {}", err))
                 })?;
@@ -52,7 +78,7 @@ impl RocksDB {
                     Error::DBError(format!("failed to repair the database: {}", err))
                 })?;
                 warn!("Opening the repaired rocksdb ...");
-                DB::open_cf(&opts, &config.path, &cf_options).map_err(|err| {
+                DB::open_cf_descriptors(&opts, &config.path, cf_descriptors()).map_err(|err| {
                     Error::DBError(format!("failed to open the repaired database: {}", err))
                 })
             } else {
@@ -63,13 +89,9 @@ impl RocksDB {
             }
         })?;
 
-        if let Some(db_opt) = config.options.as_ref() {
-            let rocksdb_options: Vec<(&str, &str)> = db_opt
-                .iter()
-                .map(|(k, v)| (k.as_str(), v.as_str()))
-                .collect();
-            db.set_options(&rocksdb_options)
-                .map(|_| Error::DBError("failed to set database option".to_owned()))?;
+        if !raw_options.is_empty() {
+            db.set_options(&raw_options)
+                .map_err(|err| Error::DBError(format!("failed to set database option: {}", err)))?;
         }
 
         let version_bytes = db
@@ -103,8 +125,12 @@ impl RocksDB {
             })?;
         }
 
+        migrate_checksums(&db, &config.checksum_columns)?;
+
         Ok(RocksDB {
             inner: Arc::new(db),
+            checksums: Checksums::new(&config.checksum_columns),
+            verify_checksums: config.verify_checksums.unwrap_or(true),
         })
     }
 
@@ -115,6 +141,200 @@ impl RocksDB {
     }
 }
 
+// Sets the raw options one by one on a scratch database, so an invalid one is reported by name
+// before the data directory is opened. RocksDB tells best which options it takes.
+fn check_raw_options(raw_options: &[(&str, &str)]) -> Result<()> {
+    if raw_options.is_empty() {
+        return Ok(());
+    }
+    let path = env::temp_dir().join(format!(
+        "ckb-db-options-{}-{}",
+        process::id(),
+        SCRATCH_DB_COUNT.fetch_add(1, Ordering::SeqCst)
+    ));
+    let mut opts = Options::default();
+    opts.create_if_missing(true);
+    let result = DB::open(&opts, &path)
+        .map_err(|err| {
+            Error::DBError(format!(
+                "failed to open a scratch database to check the options: {}",
+                err
+            ))
+        })
+        .and_then(|db| {
+            raw_options.iter().try_for_each(|&(key, value)| {
+                db.set_options(&[(key, value)]).map_err(|err| {
+                    let hint = similar_option(key)
+                        .map(|name| format!(", did you mean {}?", name))
+                        .unwrap_or_default();
+                    Error::DBError(format!(
+                        "invalid raw database option {} = {}: {}{}",
+                        key, value, err, hint
+                    ))
+                })
+            })
+        });
+    if let Err(err) = fs::remove_dir_all(&path) {
+        warn!("Failed to remove the scratch database {:?}: {}", path, err);
+    }
+    result
+}
+
+fn cf_options(db_options: &DBOptions) -> Options {
+    let mut opts = Options::default();
+    if let Some(write_buffer_size) = db_options.write_buffer_size {
+        opts.set_write_buffer_size(write_buffer_size);
+    }
+    if let Some(max_write_buffer_number) = db_options.max_write_buffer_number {
+        opts.set_max_write_buffer_number(max_write_buffer_number);
+    }
+    let mut block_opts = BlockBasedOptions::default();
+    if let Some(block_size) = db_options.block_size {
+        block_opts.set_block_size(block_size);
+    }
+    if db_options.block_cache == Some(false) {
+        block_opts.disable_cache();
+    } else if let Some(block_cache_size) = db_options.block_cache_size {
+        block_opts.set_lru_cache(block_cache_size);
+    }
+    opts.set_block_based_table_factory(&block_opts);
+    opts
+}
+
+// Frames the values of the columns newly checksummed, and unframes the ones no longer
+// checksummed, so the framed columns recorded in the database are the configured ones
+fn migrate_checksums(db: &DB, columns: &[Col]) -> Result<()> {
+    let mut framed = match db.get(CHECKSUM_COLUMNS_KEY.as_bytes())? {
+        Some(bytes) => decode_columns(&bytes)?,
+        None => Vec::new(),
+    };
+    if let Some(migration) = db.get(CHECKSUM_MIGRATION_KEY.as_bytes())? {
+        let (col, seal, after) = decode_migration(&migration)?;
+        warn!(
+            "Resuming the interrupted checksum migration of column {}",
+            col
+        );
+        migrate_column(db, &mut framed, col, seal, Some(&after))?;
+    }
+    for col in columns {
+        if !framed.contains(col) {
+            migrate_column(db, &mut framed, *col, true, None)?;
+        }
+    }
+    for col in framed.clone() {
+        if !columns.contains(&col) {
+            migrate_column(db, &mut framed, col, false, None)?;
+        }
+    }
+    Ok(())
+}
+
+// Rewrites the values of the column after the key `after` framed, or unframed, in batches each
+// recording its last key, then records the column as framed, or not
+fn migrate_column(
+    db: &DB,
+    framed: &mut Vec<Col>,
+    col: Col,
+    seal: bool,
+    after: Option<&[u8]>,
+) -> Result<()> {
+    info!(
+        "Migrating the values of column {} {} checksums",
+        col,
+        if seal { "to" } else { "from" }
+    );
+    let cf = cf_handle(db, col)?;
+    let checksums = Checksums::new(&[col]);
+    let mode = match after {
+        Some(key) => IteratorMode::From(key, Direction::Forward),
+        None => IteratorMode::Start,
+    };
+    // the iterator reads a snapshot, not the values rewritten
+    let mut iter = db
+        .iterator_cf(cf, mode)?
+        .skip_while(move |(key, _)| after.map_or(false, |after| key[..] == after[..]))
+        .peekable();
+    while iter.peek().is_some() {
+        let mut wb = WriteBatch::default();
+        let mut last = None;
+        for (key, value) in iter.by_ref().take(CHECKSUM_MIGRATION_BATCH_SIZE) {
+            if seal {
+                wb.put_cf(cf, &key, &checksums.seal(col, &key, &value))?;
+            } else {
+                wb.put_cf(cf, &key, &checksums.open(col, &key, value.into_vec())?)?;
+            }
+            last = Some(key);
+        }
+        if let Some(last) = last {
+            wb.put(
+                CHECKSUM_MIGRATION_KEY.as_bytes(),
+                &encode_migration(col, seal, &last),
+            )?;
+        }
+        db.write(wb)?;
+    }
+    if seal {
+        if !framed.contains(&col) {
+            framed.push(col);
+        }
+    } else {
+        framed.retain(|framed_col| *framed_col != col);
+    }
+    let mut wb = WriteBatch::default();
+    wb.delete(CHECKSUM_MIGRATION_KEY.as_bytes())?;
+    wb.put(CHECKSUM_COLUMNS_KEY.as_bytes(), &encode_columns(framed))?;
+    db.write(wb)?;
+    Ok(())
+}
+
+fn encode_columns(columns: &[Col]) -> Vec<u8> {
+    columns
+        .iter()
+        .flat_map(|col| col.to_le_bytes().to_vec())
+        .collect()
+}
+
+fn decode_columns(bytes: &[u8]) -> Result<Vec<Col>> {
+    if bytes.len() % 4 != 0 {
+        return Err(Error::Corruption(
+            "the checksummed columns of the database are malformed".to_owned(),
+        ));
+    }
+    Ok(bytes
+        .chunks(4)
+        .map(|chunk| {
+            let mut col = [0; 4];
+            col.copy_from_slice(chunk);
+            Col::from_le_bytes(col)
+        })
+        .collect())
+}
+
+fn encode_migration(col: Col, seal: bool, last: &[u8]) -> Vec<u8> {
+    let mut bytes = col.to_le_bytes().to_vec();
+    bytes.push(u8::from(seal));
+    bytes.extend_from_slice(last);
+    bytes
+}
+
+fn decode_migration(bytes: &[u8]) -> Result<(Col, bool, Vec<u8>)> {
+    if bytes.len() < 5 {
+        return Err(Error::Corruption(
+            "the checksum migration of the database is malformed".to_owned(),
+        ));
+    }
+    let mut col = [0; 4];
+    col.copy_from_slice(&bytes[..4]);
+    Ok((Col::from_le_bytes(col), bytes[4] != 0, bytes[5..].to_vec()))
+}
+
+// The options of the reads and the iterators, see `DBConfig::verify_checksums`
+fn read_options(verify_checksums: bool) -> ReadOptions {
+    let mut opts = ReadOptions::default();
+    opts.set_verify_checksums(verify_checksums);
+    opts
+}
+
 fn cf_handle(db: &DB, col: Col) -> Result<ColumnFamily> {
     db.cf_handle(&col.to_string())
         .ok_or_else(|| Error::DBError(format!("column {} not found", col)))
@@ -125,46 +345,175 @@ impl KeyValueDB for RocksDB {
 
     fn read(&self, col: Col, key: &[u8]) -> Result<Option<Vec<u8>>> {
         let cf = cf_handle(&self.inner, col)?;
-        self.inner
-            .get_cf(cf, &key)
-            .map(|v| v.map(|vi| vi.to_vec()))
-            .map_err(Into::into)
+        let value = self
+            .inner
+            .get_cf_opt(cf, &key, &read_options(self.verify_checksums))?
+            .map(|vi| vi.to_vec());
+        self.checksums.open_option(col, key, value)
     }
 
     fn partial_read(&self, col: Col, key: &[u8], range: &Range<usize>) -> Result<Option<Vec<u8>>> {
+        if self.checksums.enabled(col) {
+            // the checksum covers the whole value
+            return self.read(col, key).map(|v| {
+                v.and_then(|vi| vi.get(range.start..range.end).map(|slice| slice.to_vec()))
+            });
+        }
         let cf = cf_handle(&self.inner, col)?;
         self.inner
-            .get_pinned_cf(cf, &key)
+            .get_pinned_cf_opt(cf, &key, &read_options(self.verify_checksums))
             .map(|v| v.and_then(|vi| vi.get(range.start..range.end).map(|slice| slice.to_vec())))
             .map_err(Into::into)
     }
 
+    fn read_multi(&self, col: Col, keys: &[&[u8]]) -> Result<Vec<Option<Vec<u8>>>> {
+        // no multi-get in this rocksdb binding, at least the column family is looked up once
+        let cf = cf_handle(&self.inner, col)?;
+        let opts = read_options(self.verify_checksums);
+        keys.iter()
+            .map(|key| {
+                let value = self
+                    .inner
+                    .get_pinned_cf_opt(cf, key, &opts)?
+                    .map(|vi| vi.to_vec());
+                self.checksums.open_option(col, key, value)
+            })
+            .collect()
+    }
+
     fn batch(&self) -> Result<Self::Batch> {
         Ok(Self::Batch {
             db: Arc::clone(&self.inner),
+            checksums: self.checksums.clone(),
             wb: WriteBatch::default(),
+            staged: HashMap::new(),
+            verify_checksums: self.verify_checksums,
         })
     }
 }
 
+fn iterator_mode(from: Bound<&[u8]>) -> IteratorMode {
+    match from {
+        Bound::Included(key) | Bound::Excluded(key) => IteratorMode::From(key, Direction::Forward),
+        Bound::Unbounded => IteratorMode::Start,
+    }
+}
+
+// Skips the excluded bound and opens the checksums of the values
+fn seek_items<'a>(
+    iter: DBIterator,
+    col: Col,
+    from: Bound<&[u8]>,
+    checksums: &'a Checksums,
+) -> Box<Iterator<Item = Result<(Vec<u8>, Vec<u8>)>> + 'a> {
+    // only the first key can be the excluded one
+    let excluded = match from {
+        Bound::Excluded(key) => Some(key.to_vec()),
+        _ => None,
+    };
+    Box::new(
+        iter.skip_while(move |(key, _)| {
+            excluded
+                .as_ref()
+                .map_or(false, |excluded| key[..] == excluded[..])
+        })
+        .map(move |(key, value)| {
+            checksums
+                .open(col, &key, value.into_vec())
+                .map(|value| (key.into_vec(), value))
+        }),
+    )
+}
+
+impl IterableKeyValueDB for RocksDB {
+    fn seek<'a>(
+        &'a self,
+        col: Col,
+        from: Bound<&[u8]>,
+    ) -> Result<Box<Iterator<Item = Result<(Vec<u8>, Vec<u8>)>> + 'a>> {
+        let cf = cf_handle(&self.inner, col)?;
+        let iter = self.inner.iterator_cf_opt(
+            cf,
+            &read_options(self.verify_checksums),
+            iterator_mode(from),
+        )?;
+        Ok(seek_items(iter, col, from, &self.checksums))
+    }
+
+    fn snapshot<'a>(&'a self) -> Result<Box<DBSnapshot + 'a>> {
+        Ok(Box::new(RocksDBSnapshot {
+            db: &self.inner,
+            snapshot: self.inner.snapshot(),
+            checksums: &self.checksums,
+            verify_checksums: self.verify_checksums,
+        }))
+    }
+}
+
+struct RocksDBSnapshot<'a> {
+    db: &'a DB,
+    snapshot: Snapshot<'a>,
+    checksums: &'a Checksums,
+    verify_checksums: bool,
+}
+
+impl<'a> DBSnapshot for RocksDBSnapshot<'a> {
+    fn seek<'b>(
+        &'b self,
+        col: Col,
+        from: Bound<&[u8]>,
+    ) -> Result<Box<Iterator<Item = Result<(Vec<u8>, Vec<u8>)>> + 'b>> {
+        let cf = cf_handle(self.db, col)?;
+        let iter = self.snapshot.iterator_cf_opt(
+            cf,
+            read_options(self.verify_checksums),
+            iterator_mode(from),
+        )?;
+        Ok(seek_items(iter, col, from, self.checksums))
+    }
+}
+
 pub struct RocksdbBatch {
     db: Arc<DB>,
+    checksums: Checksums,
     wb: WriteBatch,
+    // The last value staged for each key, `None` for a delete. The binding has no
+    // `WriteBatchWithIndex` to read a `WriteBatch` back.
+    staged: HashMap<(Col, Vec<u8>), Option<Vec<u8>>>,
+    verify_checksums: bool,
 }
 
 impl DbBatch for RocksdbBatch {
     fn insert(&mut self, col: Col, key: &[u8], value: &[u8]) -> Result<()> {
         let cf = cf_handle(&self.db, col)?;
-        self.wb.put_cf(cf, key, value)?;
+        self.wb
+            .put_cf(cf, key, &self.checksums.seal(col, key, value))?;
+        self.staged
+            .insert((col, key.to_vec()), Some(value.to_vec()));
         Ok(())
     }
 
     fn delete(&mut self, col: Col, key: &[u8]) -> Result<()> {
         let cf = cf_handle(&self.db, col)?;
         self.wb.delete_cf(cf, &key)?;
+        self.staged.insert((col, key.to_vec()), None);
         Ok(())
     }
 
+    fn get(&self, col: Col, key: &[u8]) -> Result<Option<Vec<u8>>> {
+        let cf = cf_handle(&self.db, col)?;
+        match self.staged.get(&(col, key.to_vec())) {
+            Some(value) => Ok(value.to_owned()),
+            None => {
+                let value = self
+                    .db
+                    .get_pinned_cf_opt(cf, &key, &read_options(self.verify_checksums))?
+                    .map(|vi| vi.to_vec());
+                self.checksums.open_option(col, key, value)
+            }
+        }
+    }
+
     fn commit(self) -> Result<()> {
         self.db.write(self.wb)?;
         Ok(())
@@ -180,6 +529,7 @@ impl From<RdbError> for Error {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::conformance;
     use std::collections::HashMap;
     use tempfile;
 
@@ -215,10 +565,75 @@ mod tests {
                 opts.insert("disable_auto_compactions".to_owned(), "true".to_owned());
                 opts
             }),
+            ..Default::default()
+        };
+        RocksDB::open(&config, 2); // no panic
+    }
+
+    #[test]
+    fn test_set_raw_rocksdb_options() {
+        let tmp_dir = tempfile::Builder::new()
+            .prefix("test_set_raw_rocksdb_options")
+            .tempdir()
+            .unwrap();
+        let config = DBConfig {
+            path: tmp_dir.as_ref().to_path_buf(),
+            options: Some({
+                let mut opts = HashMap::new();
+                opts.insert("max_open_files".to_owned(), "64".to_owned());
+                opts
+            }),
+            raw_options: Some({
+                let mut opts = HashMap::new();
+                opts.insert("disable_auto_compactions".to_owned(), "true".to_owned());
+                opts.insert(
+                    "level0_file_num_compaction_trigger".to_owned(),
+                    "many".to_owned(),
+                );
+                opts
+            }),
+            ..Default::default()
+        };
+        assert!(RocksDB::open_with_check(&config, 2, VERSION_KEY, VERSION_VALUE).is_err());
+        // nothing is created for the invalid options
+        assert!(!tmp_dir.as_ref().join("CURRENT").exists());
+
+        let config = DBConfig {
+            raw_options: Some({
+                let mut opts = HashMap::new();
+                opts.insert("disable_auto_compactions".to_owned(), "true".to_owned());
+                opts.insert(
+                    "level0_file_num_compaction_trigger".to_owned(),
+                    "8".to_owned(),
+                );
+                opts
+            }),
+            ..config
         };
         RocksDB::open(&config, 2); // no panic
     }
 
+    #[test]
+    fn misspelled_option() {
+        let tmp_dir = tempfile::Builder::new()
+            .prefix("misspelled_option")
+            .tempdir()
+            .unwrap();
+        let config = DBConfig {
+            path: tmp_dir.as_ref().to_path_buf(),
+            options: Some({
+                let mut opts = HashMap::new();
+                opts.insert("max_open_file".to_owned(), "100".to_owned());
+                opts
+            }),
+            ..Default::default()
+        };
+        match RocksDB::open_with_check(&config, 2, VERSION_KEY, VERSION_VALUE) {
+            Err(Error::DBError(err)) => assert!(err.ends_with("did you mean max_open_files?")),
+            _ => panic!("the misspelled option should be rejected"),
+        }
+    }
+
     #[test]
     #[should_panic]
     fn test_panic_on_invalid_rocksdb_options() {
@@ -233,10 +648,201 @@ mod tests {
                 opts.insert("letsrock".to_owned(), "true".to_owned());
                 opts
             }),
+            ..Default::default()
         };
         RocksDB::open(&config, 2); // panic
     }
 
+    #[test]
+    fn conformance_suite() {
+        let db = setup_db("conformance", conformance::COLUMNS);
+        conformance::check(&db);
+    }
+
+    #[test]
+    fn skip_verifying_checksums() {
+        let tmp_dir = tempfile::Builder::new()
+            .prefix("skip_verifying_checksums")
+            .tempdir()
+            .unwrap();
+        let config = DBConfig {
+            path: tmp_dir.as_ref().to_path_buf(),
+            verify_checksums: Some(false),
+            ..Default::default()
+        };
+        let db = RocksDB::open(&config, 2);
+        assert!(!db.verify_checksums);
+        let mut batch = db.batch().unwrap();
+        batch.insert(1, &[1], &[1, 2, 3]).unwrap();
+        assert_eq!(batch.get(1, &[2]).unwrap(), None);
+        batch.commit().unwrap();
+        assert_eq!(db.read(1, &[1]).unwrap(), Some(vec![1, 2, 3]));
+        assert_eq!(db.partial_read(1, &[1], &(1..3)).unwrap(), Some(vec![2, 3]));
+        assert_eq!(
+            db.read_multi(1, &[&[1]]).unwrap(),
+            vec![Some(vec![1, 2, 3])]
+        );
+        assert_eq!(db.iter(1).unwrap().count(), 1);
+        assert_eq!(db.snapshot().unwrap().iter(1).unwrap().count(), 1);
+    }
+
+    #[test]
+    fn detect_corruption() {
+        let tmp_dir = tempfile::Builder::new()
+            .prefix("detect_corruption")
+            .tempdir()
+            .unwrap();
+        let config = DBConfig {
+            path: tmp_dir.as_ref().to_path_buf(),
+            ..Default::default()
+        };
+        {
+            let db = RocksDB::open(&config, 2);
+            let mut batch = db.batch().unwrap();
+            batch.insert(1, &[0], &[0, 0]).unwrap();
+            batch.commit().unwrap();
+        }
+
+        let config = DBConfig {
+            checksum_columns: vec![1],
+            ..config
+        };
+        let db = RocksDB::open(&config, 2);
+        // the legacy value is framed on open
+        assert_eq!(db.read(1, &[0]).unwrap(), Some(vec![0, 0]));
+        let mut batch = db.batch().unwrap();
+        batch.insert(1, &[1], &[1, 2, 3]).unwrap();
+        batch.commit().unwrap();
+        assert_eq!(db.read(1, &[1]).unwrap(), Some(vec![1, 2, 3]));
+        assert_eq!(db.partial_read(1, &[1], &(1..3)).unwrap(), Some(vec![2, 3]));
+
+        // flip a byte of the stored value
+        let cf = cf_handle(&db.inner, 1).unwrap();
+        let mut stored = db.inner.get_cf(cf, &[1]).unwrap().unwrap().to_vec();
+        let last = stored.len() - 1;
+        stored[last] ^= 1;
+        db.inner.put_cf(cf, &[1], &stored).unwrap();
+        match db.read(1, &[1]) {
+            Err(Error::Corruption(_)) => {}
+            result => panic!("unexpected {:?}", result),
+        }
+        assert!(db.partial_read(1, &[1], &(0..1)).is_err());
+        assert!(db.read_multi(1, &[&[1]]).is_err());
+        assert_eq!(db.read(1, &[0]).unwrap(), Some(vec![0, 0]));
+        // the iteration yields the corruption instead of the value
+        let items = db.iter(1).unwrap().collect::<Vec<_>>();
+        assert_eq!(items[0], Ok((vec![0], vec![0, 0])));
+        match items[1] {
+            Err(Error::Corruption(_)) => {}
+            ref item => panic!("unexpected {:?}", item),
+        }
+        assert!(db.page(1, Bound::Unbounded, 1).is_err());
+    }
+
+    #[test]
+    fn migrate_checksum_columns() {
+        let tmp_dir = tempfile::Builder::new()
+            .prefix("migrate_checksum_columns")
+            .tempdir()
+            .unwrap();
+        let config = DBConfig {
+            path: tmp_dir.as_ref().to_path_buf(),
+            ..Default::default()
+        };
+        // a legacy value looking like a framed one
+        let mut lookalike = b"\xc4\x6b\x73\x01".to_vec();
+        lookalike.extend_from_slice(&[7; 28]);
+        {
+            let db = RocksDB::open(&config, 2);
+            let mut batch = db.batch().unwrap();
+            batch.insert(1, &[0], &lookalike).unwrap();
+            for i in 1..=CHECKSUM_MIGRATION_BATCH_SIZE as u16 {
+                batch.insert(1, &i.to_be_bytes(), &[1]).unwrap();
+            }
+            batch.commit().unwrap();
+        }
+
+        let checksummed = DBConfig {
+            checksum_columns: vec![1],
+            ..config.clone()
+        };
+        {
+            let db = RocksDB::open(&checksummed, 2);
+            assert_eq!(db.read(1, &[0]).unwrap(), Some(lookalike.clone()));
+            assert_eq!(
+                db.iter(1).unwrap().count(),
+                CHECKSUM_MIGRATION_BATCH_SIZE + 1
+            );
+            assert_eq!(
+                db.inner
+                    .get(CHECKSUM_COLUMNS_KEY.as_bytes())
+                    .unwrap()
+                    .unwrap()
+                    .to_vec(),
+                encode_columns(&[1])
+            );
+            assert!(db
+                .inner
+                .get(CHECKSUM_MIGRATION_KEY.as_bytes())
+                .unwrap()
+                .is_none());
+        }
+        // opened again, nothing to migrate
+        {
+            let db = RocksDB::open(&checksummed, 2);
+            assert_eq!(db.read(1, &[0, 1]).unwrap(), Some(vec![1]));
+        }
+
+        // no longer checksummed, the values are unframed
+        let db = RocksDB::open(&config, 2);
+        assert_eq!(db.read(1, &[0]).unwrap(), Some(lookalike));
+        assert_eq!(db.read(1, &[0, 1]).unwrap(), Some(vec![1]));
+        assert_eq!(
+            db.inner
+                .get(CHECKSUM_COLUMNS_KEY.as_bytes())
+                .unwrap()
+                .unwrap()
+                .to_vec(),
+            Vec::<u8>::new()
+        );
+    }
+
+    #[test]
+    fn resume_checksum_migration() {
+        let tmp_dir = tempfile::Builder::new()
+            .prefix("resume_checksum_migration")
+            .tempdir()
+            .unwrap();
+        let config = DBConfig {
+            path: tmp_dir.as_ref().to_path_buf(),
+            ..Default::default()
+        };
+        {
+            let db = RocksDB::open(&config, 2);
+            let checksums = Checksums::new(&[1]);
+            let cf = cf_handle(&db.inner, 1).unwrap();
+            // interrupted after framing the first value
+            db.inner
+                .put_cf(cf, &[0], &checksums.seal(1, &[0], &[0]))
+                .unwrap();
+            db.inner.put_cf(cf, &[1], &[1]).unwrap();
+            db.inner
+                .put(
+                    CHECKSUM_MIGRATION_KEY.as_bytes(),
+                    &encode_migration(1, true, &[0]),
+                )
+                .unwrap();
+        }
+
+        let config = DBConfig {
+            checksum_columns: vec![1],
+            ..config
+        };
+        let db = RocksDB::open(&config, 2);
+        assert_eq!(db.read(1, &[0]).unwrap(), Some(vec![0]));
+        assert_eq!(db.read(1, &[1]).unwrap(), Some(vec![1]));
+    }
+
     #[test]
     fn write_and_read() {
         let db = setup_db("write_and_read", 2);
@@ -278,6 +884,51 @@ mod tests {
         );
     }
 
+    #[test]
+    fn write_and_read_multi() {
+        let db = setup_db("write_and_read_multi", 2);
+
+        let mut batch = db.batch().unwrap();
+        batch.insert(0, &[0, 0], &[0, 0, 0]).unwrap();
+        batch.insert(0, &[1, 1], &[1, 1, 1]).unwrap();
+        batch.commit().unwrap();
+
+        assert_eq!(
+            vec![
+                Some(vec![1, 1, 1]),
+                None,
+                Some(vec![0, 0, 0]),
+                Some(vec![1, 1, 1])
+            ],
+            db.read_multi(0, &[&[1, 1], &[2, 2], &[0, 0], &[1, 1]])
+                .unwrap()
+        );
+        assert_eq!(vec![None], db.read_multi(1, &[&[0, 0]]).unwrap());
+    }
+
+    #[test]
+    fn write_and_iter() {
+        let db = setup_db("write_and_iter", 2);
+
+        let mut batch = db.batch().unwrap();
+        batch.insert(1, &[1, 1], &[1, 1, 1]).unwrap();
+        batch.insert(1, &[0, 0], &[0, 0, 0]).unwrap();
+        batch.insert(0, &[2, 2], &[2, 2, 2]).unwrap();
+        batch.commit().unwrap();
+
+        assert_eq!(
+            vec![(vec![0, 0], vec![0, 0, 0]), (vec![1, 1], vec![1, 1, 1])],
+            db.iter(1).unwrap().collect::<Result<Vec<_>>>().unwrap()
+        );
+        assert_eq!(
+            vec![(vec![1, 1], vec![1, 1, 1])],
+            db.iter_from(1, &[0, 1])
+                .unwrap()
+                .collect::<Result<Vec<_>>>()
+                .unwrap()
+        );
+    }
+
     #[test]
     #[should_panic]
     fn test_version_is_not_matched() {
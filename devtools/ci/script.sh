@@ -82,6 +82,7 @@ if [ "$RUN_TEST" = true ]; then
   fi
   if [ "$TEST" = true ]; then
     fold test make test
+    fold test-logdb make test-logdb
   fi
 
   git diff --exit-code Cargo.lock
@@ -7,6 +7,14 @@ use crate::Score;
 /// Currently this feature is disabled, maybe someday we will add it back or totally remove it.
 #[derive(Debug, Clone, Copy, Eq, PartialEq)]
 pub enum Behaviour {
+    /// Relays a transaction claiming cycles different from the measured ones
+    MismatchedTxCycles,
+    /// Relays a transaction other than the ones asked for
+    MismatchedTx,
+    /// Relays too many transactions never asked for
+    UnsolicitedTx,
+    /// Relays transactions mostly invalid, or too costly to verify for the ones accepted
+    CostlyTxRelay,
     #[cfg(test)]
     TestGood,
     #[cfg(test)]
@@ -17,6 +25,10 @@ impl Behaviour {
     pub fn score(self) -> Score {
         #[allow(unreachable_patterns)]
         match self {
+            Behaviour::MismatchedTxCycles => -10,
+            Behaviour::MismatchedTx => -20,
+            Behaviour::UnsolicitedTx => -10,
+            Behaviour::CostlyTxRelay => -20,
             #[cfg(test)]
             Behaviour::TestGood => 10,
             #[cfg(test)]
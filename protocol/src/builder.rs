@@ -1,19 +1,24 @@
 use crate::protocol_generated::ckb::protocol::{
     Block as FbsBlock, BlockBuilder, BlockProposalBuilder, BlockTransactionsBuilder,
-    Bytes as FbsBytes, BytesBuilder, CellInput as FbsCellInput, CellInputBuilder,
-    CellOutput as FbsCellOutput, CellOutputBuilder, CompactBlock, CompactBlockBuilder,
-    FilteredBlock, FilteredBlockBuilder, GetBlockProposalBuilder, GetBlockTransactionsBuilder,
-    GetBlocks as FbsGetBlocks, GetBlocksBuilder, GetHeaders as FbsGetHeaders, GetHeadersBuilder,
-    GetRelayTransaction as FbsGetRelayTransaction, GetRelayTransactionBuilder, Header as FbsHeader,
-    HeaderBuilder, Headers as FbsHeaders, HeadersBuilder, IndexTransactionBuilder,
-    MerkleProofBuilder, OutPoint as FbsOutPoint, OutPointBuilder,
+    BlocksNotFound as FbsBlocksNotFound, BlocksNotFoundBuilder, Bytes as FbsBytes, BytesBuilder,
+    CellInput as FbsCellInput, CellInputBuilder, CellOutput as FbsCellOutput, CellOutputBuilder,
+    CompactBlock, CompactBlockBuilder, FilteredBlock, FilteredBlockBuilder,
+    GetBlockProposalBuilder, GetBlockTransactionsBuilder, GetBlocks as FbsGetBlocks,
+    GetBlocksBuilder, GetHeaders as FbsGetHeaders, GetHeadersBuilder,
+    GetRelayTransaction as FbsGetRelayTransaction, GetRelayTransactionBuilder,
+    GetRelayTransactions as FbsGetRelayTransactions, GetRelayTransactionsBuilder,
+    Header as FbsHeader, HeaderBuilder, Headers as FbsHeaders, HeadersBuilder,
+    IndexTransactionBuilder, MerkleProofBuilder, OutPoint as FbsOutPoint, OutPointBuilder,
     ProposalShortId as FbsProposalShortId, RelayMessage, RelayMessageBuilder, RelayPayload,
     RelayTransaction as FbsRelayTransaction, RelayTransactionBuilder,
     RelayTransactionHash as FbsRelayTransactionHash, RelayTransactionHashBuilder,
-    Script as FbsScript, ScriptBuilder, SyncMessage, SyncMessageBuilder, SyncPayload,
-    Time as FbsTime, TimeBuilder, TimeMessage, TimeMessageBuilder, Transaction as FbsTransaction,
-    TransactionBuilder, UncleBlock as FbsUncleBlock, UncleBlockBuilder, Witness as FbsWitness,
-    WitnessBuilder, H256 as FbsH256,
+    RelayTransactionHashes as FbsRelayTransactionHashes, RelayTransactionHashesBuilder,
+    RelayTransactionResult as FbsRelayTransactionResult, RelayTransactionResultBuilder,
+    RelayTransactions as FbsRelayTransactions, RelayTransactionsBuilder, Script as FbsScript,
+    ScriptBuilder, SyncMessage, SyncMessageBuilder, SyncPayload, Time as FbsTime, TimeBuilder,
+    TimeMessage, TimeMessageBuilder, Transaction as FbsTransaction, TransactionBuilder,
+    UncleBlock as FbsUncleBlock, UncleBlockBuilder, Witness as FbsWitness, WitnessBuilder,
+    H256 as FbsH256,
 };
 use crate::{short_transaction_id, short_transaction_id_keys};
 use ckb_core::block::Block;
@@ -157,10 +162,58 @@ impl<'a> FbsRelayTransactionHash<'a> {
     pub fn build<'b>(
         fbb: &mut FlatBufferBuilder<'b>,
         tx_hash: &H256,
+        fee_rate: u64,
     ) -> WIPOffset<FbsRelayTransactionHash<'b>> {
         let mut builder = RelayTransactionHashBuilder::new(fbb);
         let tx_hash = tx_hash.into();
         builder.add_tx_hash(&tx_hash);
+        builder.add_fee_rate(fee_rate);
+        builder.finish()
+    }
+}
+
+impl<'a> FbsRelayTransactionResult<'a> {
+    pub fn build<'b>(
+        fbb: &mut FlatBufferBuilder<'b>,
+        tx_hash: &H256,
+        result: u8,
+    ) -> WIPOffset<FbsRelayTransactionResult<'b>> {
+        let mut builder = RelayTransactionResultBuilder::new(fbb);
+        let tx_hash = tx_hash.into();
+        builder.add_tx_hash(&tx_hash);
+        builder.add_result(result);
+        builder.finish()
+    }
+}
+
+impl<'a> FbsRelayTransactionHashes<'a> {
+    pub fn build<'b>(
+        fbb: &mut FlatBufferBuilder<'b>,
+        tx_hashes: &[H256],
+        fee_rates: &[u64],
+    ) -> WIPOffset<FbsRelayTransactionHashes<'b>> {
+        let vec = tx_hashes.iter().map(Into::into).collect::<Vec<FbsH256>>();
+        let tx_hashes = fbb.create_vector(&vec);
+        let fee_rates = fbb.create_vector(fee_rates);
+        let mut builder = RelayTransactionHashesBuilder::new(fbb);
+        builder.add_tx_hashes(tx_hashes);
+        builder.add_fee_rates(fee_rates);
+        builder.finish()
+    }
+}
+
+impl<'a> FbsRelayTransactions<'a> {
+    pub fn build<'b>(
+        fbb: &mut FlatBufferBuilder<'b>,
+        transactions: &[(Transaction, Cycle)],
+    ) -> WIPOffset<FbsRelayTransactions<'b>> {
+        let vec = transactions
+            .iter()
+            .map(|(transaction, cycles)| FbsRelayTransaction::build(fbb, transaction, *cycles))
+            .collect::<Vec<_>>();
+        let transactions = fbb.create_vector(&vec);
+        let mut builder = RelayTransactionsBuilder::new(fbb);
+        builder.add_transactions(transactions);
         builder.finish()
     }
 }
@@ -177,6 +230,19 @@ impl<'a> FbsGetRelayTransaction<'a> {
     }
 }
 
+impl<'a> FbsGetRelayTransactions<'a> {
+    pub fn build<'b>(
+        fbb: &mut FlatBufferBuilder<'b>,
+        tx_hashes: &[H256],
+    ) -> WIPOffset<FbsGetRelayTransactions<'b>> {
+        let vec = tx_hashes.iter().map(Into::into).collect::<Vec<FbsH256>>();
+        let tx_hashes = fbb.create_vector(&vec);
+        let mut builder = GetRelayTransactionsBuilder::new(fbb);
+        builder.add_tx_hashes(tx_hashes);
+        builder.finish()
+    }
+}
+
 impl<'a> FbsCellInput<'a> {
     pub fn build<'b>(
         fbb: &mut FlatBufferBuilder<'b>,
@@ -381,6 +447,22 @@ impl<'a> FbsGetBlocks<'a> {
     }
 }
 
+impl<'a> FbsBlocksNotFound<'a> {
+    pub fn build<'b>(
+        fbb: &mut FlatBufferBuilder<'b>,
+        block_hashes: &[H256],
+    ) -> WIPOffset<FbsBlocksNotFound<'b>> {
+        let vec = block_hashes
+            .iter()
+            .map(Into::into)
+            .collect::<Vec<FbsH256>>();
+        let block_hashes = fbb.create_vector(&vec);
+        let mut builder = BlocksNotFoundBuilder::new(fbb);
+        builder.add_block_hashes(block_hashes);
+        builder.finish()
+    }
+}
+
 impl<'a> FbsTime<'a> {
     pub fn build<'b>(fbb: &mut FlatBufferBuilder<'b>, timestamp: u64) -> WIPOffset<FbsTime<'b>> {
         let mut builder = TimeBuilder::new(fbb);
@@ -423,6 +505,17 @@ impl<'a> SyncMessage<'a> {
         builder.finish()
     }
 
+    pub fn build_blocks_not_found<'b>(
+        fbb: &mut FlatBufferBuilder<'b>,
+        block_hashes: &[H256],
+    ) -> WIPOffset<SyncMessage<'b>> {
+        let fbs_blocks_not_found = FbsBlocksNotFound::build(fbb, block_hashes);
+        let mut builder = SyncMessageBuilder::new(fbb);
+        builder.add_payload_type(SyncPayload::BlocksNotFound);
+        builder.add_payload(fbs_blocks_not_found.as_union_value());
+        builder.finish()
+    }
+
     pub fn build_block<'b>(
         fbb: &mut FlatBufferBuilder<'b>,
         block: &Block,
@@ -587,17 +680,53 @@ impl<'a> RelayMessage<'a> {
         builder.finish()
     }
 
+    pub fn build_transactions<'b>(
+        fbb: &mut FlatBufferBuilder<'b>,
+        transactions: &[(Transaction, Cycle)],
+    ) -> WIPOffset<RelayMessage<'b>> {
+        let fbs_transactions = FbsRelayTransactions::build(fbb, transactions);
+        let mut builder = RelayMessageBuilder::new(fbb);
+        builder.add_payload_type(RelayPayload::RelayTransactions);
+        builder.add_payload(fbs_transactions.as_union_value());
+        builder.finish()
+    }
+
+    pub fn build_transaction_result<'b>(
+        fbb: &mut FlatBufferBuilder<'b>,
+        tx_hash: &H256,
+        result: u8,
+    ) -> WIPOffset<RelayMessage<'b>> {
+        let fbs_result = FbsRelayTransactionResult::build(fbb, tx_hash, result);
+        let mut builder = RelayMessageBuilder::new(fbb);
+        builder.add_payload_type(RelayPayload::RelayTransactionResult);
+        builder.add_payload(fbs_result.as_union_value());
+        builder.finish()
+    }
+
     pub fn build_transaction_hash<'b>(
         fbb: &mut FlatBufferBuilder<'b>,
         tx_hash: &H256,
+        fee_rate: u64,
     ) -> WIPOffset<RelayMessage<'b>> {
-        let fbs_tx_hash = FbsRelayTransactionHash::build(fbb, tx_hash);
+        let fbs_tx_hash = FbsRelayTransactionHash::build(fbb, tx_hash, fee_rate);
         let mut builder = RelayMessageBuilder::new(fbb);
         builder.add_payload_type(RelayPayload::RelayTransactionHash);
         builder.add_payload(fbs_tx_hash.as_union_value());
         builder.finish()
     }
 
+    pub fn build_transaction_hashes<'b>(
+        fbb: &mut FlatBufferBuilder<'b>,
+        tx_hashes: &[H256],
+        fee_rates: &[u64],
+    ) -> WIPOffset<RelayMessage<'b>> {
+        let fbs_tx_hashes = FbsRelayTransactionHashes::build(fbb, tx_hashes, fee_rates);
+        let mut builder = RelayMessageBuilder::new(fbb);
+        builder.add_payload_type(RelayPayload::RelayTransactionHashes);
+        builder.add_payload(fbs_tx_hashes.as_union_value());
+        builder.finish()
+    }
+
     pub fn build_get_transaction<'b>(
         fbb: &mut FlatBufferBuilder<'b>,
         tx_hash: &H256,
@@ -609,6 +738,17 @@ impl<'a> RelayMessage<'a> {
         builder.finish()
     }
 
+    pub fn build_get_transactions<'b>(
+        fbb: &mut FlatBufferBuilder<'b>,
+        tx_hashes: &[H256],
+    ) -> WIPOffset<RelayMessage<'b>> {
+        let fbs_get_txs = FbsGetRelayTransactions::build(fbb, tx_hashes);
+        let mut builder = RelayMessageBuilder::new(fbb);
+        builder.add_payload_type(RelayPayload::GetRelayTransactions);
+        builder.add_payload(fbs_get_txs.as_union_value());
+        builder.finish()
+    }
+
     pub fn build_get_block_transactions<'b>(
         fbb: &mut FlatBufferBuilder<'b>,
         block_hash: &H256,
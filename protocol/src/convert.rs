@@ -219,12 +219,21 @@ impl<'a> TryFrom<ckb_protocol::RelayTransaction<'a>>
     }
 }
 
-impl<'a> TryFrom<ckb_protocol::RelayTransactionHash<'a>> for H256 {
+impl<'a> TryFrom<ckb_protocol::RelayTransactionHash<'a>> for (H256, u64) {
     type Error = FailureError;
 
     fn try_from(message: ckb_protocol::RelayTransactionHash<'a>) -> Result<Self, Self::Error> {
         let tx_hash = cast!(message.tx_hash())?;
-        Ok(TryInto::try_into(tx_hash)?)
+        Ok((TryInto::try_into(tx_hash)?, message.fee_rate()))
+    }
+}
+
+impl<'a> TryFrom<ckb_protocol::RelayTransactionResult<'a>> for (H256, u8) {
+    type Error = FailureError;
+
+    fn try_from(message: ckb_protocol::RelayTransactionResult<'a>) -> Result<Self, Self::Error> {
+        let tx_hash = cast!(message.tx_hash())?;
+        Ok((TryInto::try_into(tx_hash)?, message.result()))
     }
 }
 
@@ -89,8 +89,9 @@ impl<CS: ChainStore + 'static> ChainRpc for ChainRpcImpl<CS> {
 
         Ok(tx.or_else(|| {
             self.shared
-                .get_transaction(&hash)
-                .map(|(tx, block_hash)| TransactionWithStatus::with_committed(tx, block_hash))
+                .store()
+                .get_transaction_with_info(&hash)
+                .map(|(tx, info)| TransactionWithStatus::with_committed(tx, info.block_hash))
         }))
     }
 
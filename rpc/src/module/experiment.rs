@@ -116,15 +116,15 @@ impl<'a, CS: ChainStore> DaoWithdrawCalculator<'a, CS> {
         withdraw_hash: H256,
     ) -> ::std::result::Result<Capacity, DaoWithdrawError> {
         let cell_out_point = out_point.cell.ok_or(DaoWithdrawError::MissingCell)?;
-        let (tx, block_hash) = self
+        let (tx, info) = self
             .chain_state
             .store()
-            .get_transaction(&cell_out_point.tx_hash)
+            .get_transaction_with_info(&cell_out_point.tx_hash)
             .ok_or(DaoWithdrawError::MissingCell)?;
         let deposit_ext = self
             .chain_state
             .store()
-            .get_block_ext(&block_hash)
+            .get_block_ext(&info.block_hash)
             .ok_or(DaoWithdrawError::MissingHeader)?;
         let withdraw_ext = self
             .chain_state
@@ -183,13 +183,9 @@ impl<'a, CS: ChainStore> DryRunner<'a, CS> {
     pub(crate) fn run(&self, tx: CoreTransaction) -> Result<DryRunResult> {
         match resolve_transaction(&tx, &mut Default::default(), self, self) {
             Ok(resolved) => {
-                let consensus = self.chain_state.consensus();
-                let max_cycles = consensus.max_block_cycles;
                 let script_config = self.chain_state.script_config();
                 let store = self.chain_state.store();
-                match ScriptVerifier::new(&resolved, Arc::clone(store), script_config)
-                    .verify(max_cycles)
-                {
+                match ScriptVerifier::new(&resolved, Arc::clone(store), script_config).estimate() {
                     Ok(cycles) => Ok(DryRunResult {
                         cycles: Cycle(cycles),
                     }),
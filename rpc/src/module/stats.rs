@@ -1,10 +1,14 @@
+use crate::error::RPCError;
+use ckb_script::ScriptKind as CoreScriptKind;
 use ckb_shared::shared::Shared;
 use ckb_store::ChainStore;
 use ckb_sync::Synchronizer;
-use ckb_traits::BlockMedianTimeContext;
+use ckb_traits::{BlockMedianTimeContext, ChainProvider};
 use jsonrpc_core::Result;
 use jsonrpc_derive::rpc;
-use jsonrpc_types::{ChainInfo, EpochNumber, PeerState, Timestamp};
+use jsonrpc_types::{
+    ChainInfo, Cycle, EpochNumber, PeerState, ScriptKind, ScriptStats, Timestamp, Unsigned,
+};
 
 #[rpc]
 pub trait StatsRpc {
@@ -13,6 +17,9 @@ pub trait StatsRpc {
 
     #[rpc(name = "get_peers_state")]
     fn get_peers_state(&self) -> Result<Vec<PeerState>>;
+
+    #[rpc(name = "get_script_stats")]
+    fn get_script_stats(&self) -> Result<Vec<ScriptStats>>;
 }
 
 pub(crate) struct StatsRpcImpl<CS>
@@ -60,4 +67,30 @@ impl<CS: ChainStore + 'static> StatsRpc for StatsRpcImpl<CS> {
             })
             .collect())
     }
+
+    fn get_script_stats(&self) -> Result<Vec<ScriptStats>> {
+        let profiler = match &self.shared.script_config().profiler {
+            Some(profiler) => profiler,
+            None => {
+                return Err(RPCError::custom(
+                    RPCError::Invalid,
+                    "script profiling is off".to_owned(),
+                ))
+            }
+        };
+        Ok(profiler
+            .stats()
+            .into_iter()
+            .map(|stats| ScriptStats {
+                script_hash: stats.script_hash,
+                kind: match stats.kind {
+                    CoreScriptKind::Lock => ScriptKind::Lock,
+                    CoreScriptKind::Type => ScriptKind::Type,
+                },
+                runs: Unsigned(stats.runs),
+                cycles: Cycle(stats.cycles),
+                wall_time: Unsigned(stats.wall.as_micros() as u64),
+            })
+            .collect())
+    }
 }
@@ -4,7 +4,7 @@ use crate::module::{
 };
 use crate::RpcServer;
 use ckb_chain::chain::{ChainController, ChainService};
-use ckb_chain_spec::consensus::Consensus;
+use ckb_chain_spec::consensus::{Consensus, MaturityMetric};
 use ckb_core::block::BlockBuilder;
 use ckb_core::header::HeaderBuilder;
 use ckb_core::script::Script;
@@ -82,7 +82,7 @@ fn setup_node(
             .build();
         Consensus::default()
             .set_genesis_block(genesis)
-            .set_cellbase_maturity(0)
+            .set_cellbase_maturity(MaturityMetric::Blocks(0))
     };
     let shared = SharedBuilder::<MemoryKeyValueDB>::new()
         .consensus(consensus)
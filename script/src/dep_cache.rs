@@ -0,0 +1,62 @@
+use ckb_core::block::Block;
+use ckb_core::transaction::CellOutPoint;
+use ckb_core::Bytes;
+use ckb_util::Mutex;
+use lru_cache::LruCache;
+use std::sync::Arc;
+
+/// Data of the dep cells loaded by the script verifiers, shared by all the transactions of a
+/// block and kept across blocks.
+///
+/// Entries are keyed by the out point (tx hash and index) of the dep cell, the outputs of the
+/// blocks detached in a reorg must be evicted through `invalidate_block`.
+pub struct DepCellDataCache {
+    inner: Mutex<LruCache<CellOutPoint, Arc<Bytes>>>,
+}
+
+impl DepCellDataCache {
+    pub fn new(capacity: usize) -> Self {
+        DepCellDataCache {
+            inner: Mutex::new(LruCache::new(capacity)),
+        }
+    }
+
+    /// Returns the cached data of the cell, calls `load` and caches its result on miss.
+    pub fn get_or_load<F>(&self, out_point: &CellOutPoint, load: F) -> Arc<Bytes>
+    where
+        F: FnOnce() -> Bytes,
+    {
+        if let Some(data) = self.inner.lock().get(out_point) {
+            return Arc::clone(data);
+        }
+        // loaded without the lock, so the verifiers running in parallel don't wait for each
+        // other's store reads, at the cost of loading the same cell twice on a race
+        let data = Arc::new(load());
+        self.inner
+            .lock()
+            .insert(out_point.to_owned(), Arc::clone(&data));
+        data
+    }
+
+    /// Evicts the outputs of all the transactions in `block`
+    pub fn invalidate_block(&self, block: &Block) {
+        let mut inner = self.inner.lock();
+        for tx in block.transactions() {
+            let tx_hash = tx.hash();
+            for index in 0..tx.outputs().len() {
+                inner.remove(&CellOutPoint {
+                    tx_hash: tx_hash.to_owned(),
+                    index: index as u32,
+                });
+            }
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.inner.lock().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.inner.lock().is_empty()
+    }
+}
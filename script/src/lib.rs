@@ -1,10 +1,18 @@
 mod cost_model;
+mod dep_cache;
+mod profiler;
 mod syscalls;
 mod verify;
 
+use ckb_core::Cycle;
 use ckb_vm::Error as VMInternalError;
+use numext_fixed_hash::H256;
 use serde_derive::{Deserialize, Serialize};
 
+pub use crate::dep_cache::DepCellDataCache;
+pub use crate::profiler::{
+    ScriptKind, ScriptProfiler, ScriptProfilerRef, ScriptStats, ScriptStatsAggregator,
+};
 pub use crate::verify::TransactionScriptsVerifier;
 
 #[derive(Clone, Serialize, Deserialize, Eq, PartialEq, Hash, Debug)]
@@ -22,6 +30,62 @@ impl Default for Runner {
 #[derive(Clone, Serialize, Deserialize, Eq, PartialEq, Hash, Debug, Default)]
 pub struct ScriptConfig {
     pub runner: Runner,
+    /// Keep the running totals of every script, the node installs a `ScriptStatsAggregator`
+    /// as the profiler
+    #[serde(default)]
+    pub profile: bool,
+    /// Receives the cycles and the time of every script group run
+    #[serde(skip)]
+    pub profiler: Option<ScriptProfilerRef>,
+}
+
+/// The script being run, which is either the lock script of an input or the
+/// type script of an output
+#[derive(Debug, PartialEq, Clone, Copy, Eq, Hash)]
+pub enum ScriptGroup {
+    Lock(usize),
+    Type(usize),
+}
+
+/// A message printed by a script through the debug syscall
+#[derive(Debug, PartialEq, Clone, Eq)]
+pub struct ScriptDebugEntry {
+    pub group: ScriptGroup,
+    pub message: String,
+}
+
+/// The progress of a script verification which has been suspended between two script groups
+#[derive(Debug, PartialEq, Clone, Eq, Default)]
+pub struct VerificationSnapshot {
+    /// The completed groups with the cycles they consumed, in the order they have been run
+    pub completed: Vec<(ScriptGroup, Cycle)>,
+    /// The group to run when the verification is resumed
+    pub pending: Option<ScriptGroup>,
+}
+
+#[derive(Debug, PartialEq, Clone, Eq)]
+pub enum VerifyOutcome {
+    /// All the script groups passed, with the total cycles consumed
+    Completed(Cycle),
+    Suspended(VerificationSnapshot),
+}
+
+/// The cycles consumed by a single script group
+#[derive(Debug, PartialEq, Clone, Eq)]
+pub struct GroupCycles {
+    /// The lock or type script run, with the index of the input or output it covers
+    pub group: ScriptGroup,
+    /// The hash of the script, `None` for the issuing dao input which has no lock script
+    pub script_hash: Option<H256>,
+    pub cycles: Cycle,
+}
+
+/// The cycles consumed by a transaction, broken down by script group
+#[derive(Debug, PartialEq, Clone, Eq, Default)]
+pub struct CycleBreakdown {
+    pub total: Cycle,
+    /// The script groups in the order they have been run
+    pub groups: Vec<GroupCycles>,
 }
 
 #[derive(Debug, PartialEq, Clone, Copy, Eq)]
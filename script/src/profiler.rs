@@ -0,0 +1,164 @@
+use ckb_core::Cycle;
+use ckb_util::Mutex;
+use fnv::FnvHashMap;
+use numext_fixed_hash::H256;
+use serde_derive::{Deserialize, Serialize};
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::ops::Deref;
+use std::sync::Arc;
+use std::time::Duration;
+
+#[derive(Debug, PartialEq, Clone, Copy, Eq, Hash, Serialize, Deserialize)]
+pub enum ScriptKind {
+    Lock,
+    Type,
+}
+
+/// Receives the cost of every script group run by the verifiers, see `ScriptConfig::profiler`
+pub trait ScriptProfiler: Send + Sync {
+    /// Called once a group has passed, with the hash of its script
+    fn record(&self, script_hash: &H256, kind: ScriptKind, cycles: Cycle, wall: Duration);
+
+    /// The running totals of the scripts, the most expensive in cycles first. Empty if the
+    /// profiler keeps none.
+    fn stats(&self) -> Vec<ScriptStats> {
+        Vec::new()
+    }
+}
+
+/// A profiler installed in `ScriptConfig`, two handles are equal when they share the profiler
+#[derive(Clone)]
+pub struct ScriptProfilerRef(Arc<dyn ScriptProfiler>);
+
+impl ScriptProfilerRef {
+    pub fn new(profiler: Arc<dyn ScriptProfiler>) -> Self {
+        ScriptProfilerRef(profiler)
+    }
+
+    fn address(&self) -> usize {
+        &*self.0 as *const dyn ScriptProfiler as *const () as usize
+    }
+}
+
+impl Deref for ScriptProfilerRef {
+    type Target = dyn ScriptProfiler;
+
+    fn deref(&self) -> &Self::Target {
+        &*self.0
+    }
+}
+
+impl PartialEq for ScriptProfilerRef {
+    fn eq(&self, other: &Self) -> bool {
+        self.address() == other.address()
+    }
+}
+
+impl Eq for ScriptProfilerRef {}
+
+impl Hash for ScriptProfilerRef {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.address().hash(state)
+    }
+}
+
+impl fmt::Debug for ScriptProfilerRef {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "ScriptProfilerRef({:#x})", self.address())
+    }
+}
+
+/// The running totals of a script
+#[derive(Debug, PartialEq, Clone, Eq)]
+pub struct ScriptStats {
+    pub script_hash: H256,
+    pub kind: ScriptKind,
+    /// The number of groups run
+    pub runs: u64,
+    pub cycles: Cycle,
+    pub wall: Duration,
+}
+
+/// A profiler keeping the running totals of each script
+#[derive(Default)]
+pub struct ScriptStatsAggregator {
+    inner: Mutex<FnvHashMap<(H256, ScriptKind), ScriptStats>>,
+}
+
+impl ScriptStatsAggregator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn total_cycles(&self) -> Cycle {
+        self.inner.lock().values().map(|stats| stats.cycles).sum()
+    }
+
+    pub fn reset(&self) {
+        self.inner.lock().clear();
+    }
+}
+
+impl ScriptProfiler for ScriptStatsAggregator {
+    fn record(&self, script_hash: &H256, kind: ScriptKind, cycles: Cycle, wall: Duration) {
+        let mut inner = self.inner.lock();
+        let stats = inner
+            .entry((script_hash.to_owned(), kind))
+            .or_insert_with(|| ScriptStats {
+                script_hash: script_hash.to_owned(),
+                kind,
+                runs: 0,
+                cycles: 0,
+                wall: Duration::default(),
+            });
+        stats.runs += 1;
+        stats.cycles = stats.cycles.saturating_add(cycles);
+        stats.wall += wall;
+    }
+
+    fn stats(&self) -> Vec<ScriptStats> {
+        let mut stats: Vec<_> = self.inner.lock().values().cloned().collect();
+        stats.sort_by(|a, b| b.cycles.cmp(&a.cycles));
+        stats
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use numext_fixed_hash::h256;
+
+    #[test]
+    fn aggregate_by_script() {
+        let aggregator = ScriptStatsAggregator::new();
+        let (lock, type_) = (h256!("0x1"), h256!("0x2"));
+        aggregator.record(&lock, ScriptKind::Lock, 10, Duration::from_millis(1));
+        aggregator.record(&type_, ScriptKind::Type, 30, Duration::from_millis(2));
+        aggregator.record(&lock, ScriptKind::Lock, 5, Duration::from_millis(1));
+
+        let stats = aggregator.stats();
+        assert_eq!(
+            stats
+                .iter()
+                .map(|stats| (stats.script_hash.clone(), stats.runs, stats.cycles))
+                .collect::<Vec<_>>(),
+            vec![(type_, 1, 30), (lock, 2, 15)]
+        );
+        assert_eq!(stats[1].wall, Duration::from_millis(2));
+        assert_eq!(aggregator.total_cycles(), 45);
+        aggregator.reset();
+        assert!(aggregator.stats().is_empty());
+    }
+
+    #[test]
+    fn profiler_ref_identity() {
+        let aggregator: Arc<dyn ScriptProfiler> = Arc::new(ScriptStatsAggregator::new());
+        let profiler = ScriptProfilerRef::new(Arc::clone(&aggregator));
+        assert_eq!(profiler, ScriptProfilerRef::new(aggregator));
+        assert_ne!(
+            profiler,
+            ScriptProfilerRef::new(Arc::new(ScriptStatsAggregator::new()))
+        );
+    }
+}
@@ -1,17 +1,27 @@
 use crate::syscalls::DEBUG_PRINT_SYSCALL_NUMBER;
+use crate::{ScriptDebugEntry, ScriptGroup};
 use ckb_vm::{
     registers::{A0, A7},
     Error as VMError, Memory, Register, SupportMachine, Syscalls,
 };
 use log::debug;
+use std::cell::RefCell;
+
+/// Collects the debug messages of a script group
+#[derive(Clone, Copy)]
+pub struct DebugCapture<'a> {
+    pub group: ScriptGroup,
+    pub entries: &'a RefCell<Vec<ScriptDebugEntry>>,
+}
 
 pub struct Debugger<'a> {
     prefix: &'a str,
+    capture: Option<DebugCapture<'a>>,
 }
 
 impl<'a> Debugger<'a> {
-    pub fn new(prefix: &'a str) -> Debugger<'a> {
-        Debugger { prefix }
+    pub fn new(prefix: &'a str, capture: Option<DebugCapture<'a>>) -> Debugger<'a> {
+        Debugger { prefix, capture }
     }
 }
 
@@ -44,6 +54,12 @@ impl<'a, Mac: SupportMachine> Syscalls<Mac> for Debugger<'a> {
         machine.add_cycles((buffer.len() as u64 + 1) * 10)?;
         let s = String::from_utf8(buffer).map_err(|_| VMError::ParseError)?;
         debug!(target: "script", "{} DEBUG OUTPUT: {}", self.prefix, s);
+        if let Some(capture) = self.capture {
+            capture.entries.borrow_mut().push(ScriptDebugEntry {
+                group: capture.group,
+                message: s,
+            });
+        }
         Ok(true)
     }
 }
@@ -6,7 +6,7 @@ mod load_script_hash;
 mod load_tx_hash;
 mod utils;
 
-pub use self::debugger::Debugger;
+pub use self::debugger::{DebugCapture, Debugger};
 pub use self::load_cell::LoadCell;
 pub use self::load_header::LoadHeader;
 pub use self::load_input::LoadInput;
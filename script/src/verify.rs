@@ -1,7 +1,10 @@
 use crate::{
     cost_model::instruction_cycles,
-    syscalls::{Debugger, LoadCell, LoadHeader, LoadInput, LoadScriptHash, LoadTxHash},
-    Runner, ScriptConfig, ScriptError,
+    syscalls::{
+        DebugCapture, Debugger, LoadCell, LoadHeader, LoadInput, LoadScriptHash, LoadTxHash,
+    },
+    CycleBreakdown, DepCellDataCache, GroupCycles, Runner, ScriptConfig, ScriptDebugEntry,
+    ScriptError, ScriptGroup, ScriptKind, VerificationSnapshot, VerifyOutcome,
 };
 use ckb_core::cell::{CellMeta, ResolvedOutPoint, ResolvedTransaction};
 use ckb_core::extras::BlockExt;
@@ -13,15 +16,18 @@ use ckb_resource::bundled;
 use ckb_store::{ChainStore, LazyLoadCellOutput};
 use ckb_vm::{
     machine::asm::{AsmCoreMachine, AsmMachine},
-    DefaultCoreMachine, DefaultMachineBuilder, SparseMemory, SupportMachine, TraceMachine,
+    DefaultCoreMachine, DefaultMachineBuilder, Error as VMInternalError, SparseMemory,
+    SupportMachine, TraceMachine,
 };
 use dao::calculate_maximum_withdraw;
 use fnv::FnvHashMap;
 use log::info;
 use numext_fixed_hash::H256;
+use std::cell::RefCell;
 use std::cmp::min;
 use std::path::PathBuf;
 use std::sync::Arc;
+use std::time::Instant;
 
 pub const SYSTEM_DAO_CYCLES: u64 = 5000;
 
@@ -43,6 +49,7 @@ pub struct TransactionScriptsVerifier<'a, CS> {
     witnesses: FnvHashMap<u32, &'a [Bytes]>,
     hash: H256,
     config: &'a ScriptConfig,
+    dep_cell_data_cache: Option<Arc<DepCellDataCache>>,
 }
 
 impl<'a, CS: ChainStore> TransactionScriptsVerifier<'a, CS> {
@@ -82,12 +89,38 @@ impl<'a, CS: ChainStore> TransactionScriptsVerifier<'a, CS> {
             .map(|(idx, wit)| (idx as u32, &wit[..]))
             .collect();
 
+        // The data hashes of the dep cells resolved without their outputs nor data hashes are read
+        // from their cell metas in the store, in one batch
+        let unhashed_deps = resolved_deps
+            .iter()
+            .filter_map(|dep_cell| dep_cell.cell.cell_meta())
+            .filter(|cell_meta| cell_meta.data_hash().is_none() && cell_meta.cell_output.is_none())
+            .map(|cell_meta| cell_meta.out_point.to_owned())
+            .collect::<Vec<_>>();
+        let stored_data_hashes: FnvHashMap<CellOutPoint, H256> = if unhashed_deps.is_empty() {
+            FnvHashMap::default()
+        } else {
+            let cell_metas = store.get_cell_metas(&unhashed_deps);
+            unhashed_deps
+                .into_iter()
+                .zip(cell_metas)
+                .filter_map(|(out_point, cell_meta)| {
+                    cell_meta
+                        .and_then(|cell_meta| cell_meta.data_hash)
+                        .map(|data_hash| (out_point, data_hash))
+                })
+                .collect()
+        };
+
         let binary_index: FnvHashMap<H256, usize> = resolved_deps
             .iter()
             .enumerate()
             .map(|(i, dep_cell)| {
                 if let Some(cell_meta) = &dep_cell.cell.cell_meta() {
-                    let hash = match cell_meta.data_hash() {
+                    let hash = match cell_meta
+                        .data_hash()
+                        .or_else(|| stored_data_hashes.get(&cell_meta.out_point))
+                    {
                         Some(hash) => hash.to_owned(),
                         None => {
                             let output = store.lazy_load_cell_output(cell_meta);
@@ -129,9 +162,22 @@ impl<'a, CS: ChainStore> TransactionScriptsVerifier<'a, CS> {
             witnesses,
             config,
             hash: tx_hash.to_owned(),
+            dep_cell_data_cache: None,
         }
     }
 
+    /// Same as `new`, but loads the script binaries of the dep cells through the shared cache
+    pub fn new_with_cache(
+        rtx: &'a ResolvedTransaction,
+        store: Arc<CS>,
+        config: &'a ScriptConfig,
+        dep_cell_data_cache: Arc<DepCellDataCache>,
+    ) -> TransactionScriptsVerifier<'a, CS> {
+        let mut verifier = Self::new(rtx, store, config);
+        verifier.dep_cell_data_cache = Some(dep_cell_data_cache);
+        verifier
+    }
+
     fn build_load_tx_hash(&self) -> LoadTxHash {
         LoadTxHash::new(&self.hash.as_bytes())
     }
@@ -163,9 +209,17 @@ impl<'a, CS: ChainStore> TransactionScriptsVerifier<'a, CS> {
             self.resolved_deps[*index]
                 .cell
                 .cell_meta()
-                .map(|cell_meta| self.store.lazy_load_cell_output(&cell_meta))
+                .map(|cell_meta| match &self.dep_cell_data_cache {
+                    Some(cache) => cache
+                        .get_or_load(&cell_meta.out_point, || {
+                            self.store.lazy_load_cell_output(&cell_meta).data
+                        })
+                        .as_ref()
+                        .to_owned(),
+                    None => self.store.lazy_load_cell_output(&cell_meta).data,
+                })
         }) {
-            Some(cell_output) => Ok(cell_output.data),
+            Some(data) => Ok(data),
             None => Err(ScriptError::InvalidReferenceIndex),
         }
     }
@@ -176,13 +230,30 @@ impl<'a, CS: ChainStore> TransactionScriptsVerifier<'a, CS> {
         prefix: &str,
         appended_arguments: &[Bytes],
         max_cycles: Cycle,
+    ) -> Result<Cycle, ScriptError> {
+        self.verify_script_with_capture(script, prefix, appended_arguments, max_cycles, None)
+    }
+
+    fn verify_script_with_capture(
+        &self,
+        script: &Script,
+        prefix: &str,
+        appended_arguments: &[Bytes],
+        max_cycles: Cycle,
+        capture: Option<DebugCapture>,
     ) -> Result<Cycle, ScriptError> {
         let current_script_hash = script.hash_with_appended_arguments(&appended_arguments);
         let mut args = vec!["verify".into()];
         args.extend_from_slice(&script.args);
         args.extend_from_slice(&appended_arguments);
         if script.code_hash == DAO_CODE_HASH {
-            return self.verify_dao(&args, prefix, max_cycles, &current_script_hash.as_bytes());
+            return self.verify_dao(
+                &args,
+                prefix,
+                max_cycles,
+                &current_script_hash.as_bytes(),
+                capture,
+            );
         }
         self.extract_script(script).and_then(|script_binary| {
             self.run(
@@ -191,71 +262,208 @@ impl<'a, CS: ChainStore> TransactionScriptsVerifier<'a, CS> {
                 prefix,
                 max_cycles,
                 &current_script_hash.as_bytes(),
+                capture,
             )
         })
     }
 
     pub fn verify(&self, max_cycles: Cycle) -> Result<Cycle, ScriptError> {
-        let mut cycles = 0;
-        for (i, (input, input_cell)) in self
-            .inputs
-            .iter()
-            .zip(self.resolved_inputs.iter())
-            .enumerate()
-        {
-            if input_cell.cell.is_issuing_dao_input() {
-                if !self.valid_dao_withdraw_transaction() {
-                    return Err(ScriptError::InvalidIssuingDaoInput);
-                } else {
-                    continue;
+        self.verify_with_breakdown(max_cycles)
+            .map(|breakdown| breakdown.total)
+    }
+
+    /// Same as `verify`, but also reports the cycles consumed by each script group.
+    pub fn verify_with_breakdown(&self, max_cycles: Cycle) -> Result<CycleBreakdown, ScriptError> {
+        self.verify_with_capture(max_cycles, None)
+    }
+
+    /// Same as `verify`, but also returns the messages printed by the scripts through
+    /// the debug syscall, in the order they are printed.
+    pub fn verify_with_debug(
+        &self,
+        max_cycles: Cycle,
+    ) -> (Result<Cycle, ScriptError>, Vec<ScriptDebugEntry>) {
+        let entries = RefCell::new(Vec::new());
+        let result = self
+            .verify_with_capture(max_cycles, Some(&entries))
+            .map(|breakdown| breakdown.total);
+        (result, entries.into_inner())
+    }
+
+    /// Runs the script groups in order until the cycles used in this installment would exceed
+    /// `limit`, see `resume`.
+    pub fn verify_until(&self, limit: Cycle) -> Result<VerifyOutcome, ScriptError> {
+        self.resume(VerificationSnapshot::default(), limit)
+    }
+
+    /// Continues a suspended verification from its pending group, with a fresh budget of
+    /// `additional_limit` cycles. Groups are never split, a group which does not fit in the
+    /// budget is run again from its beginning in the next installment, so `additional_limit`
+    /// must be large enough for at least the pending group to make progress.
+    pub fn resume(
+        &self,
+        snapshot: VerificationSnapshot,
+        additional_limit: Cycle,
+    ) -> Result<VerifyOutcome, ScriptError> {
+        let VerificationSnapshot { mut completed, .. } = snapshot;
+        let mut remaining = additional_limit;
+        for group in self.script_groups().into_iter().skip(completed.len()) {
+            match self.verify_group(group, remaining, None) {
+                Ok(GroupCycles { cycles, .. }) => {
+                    remaining -= cycles;
+                    completed.push((group, cycles));
                 }
-            }
-            let input_cell_meta = input_cell.cell.cell_meta();
-            let input_cell = match &input_cell_meta {
-                Some(cell) => cell,
-                None => {
-                    return Err(ScriptError::NoScript);
+                Err(ScriptError::ExceededMaximumCycles)
+                | Err(ScriptError::VMError(VMInternalError::InvalidCycles)) => {
+                    return Ok(VerifyOutcome::Suspended(VerificationSnapshot {
+                        completed,
+                        pending: Some(group),
+                    }));
                 }
-            };
-            let output = self.store.lazy_load_cell_output(input_cell);
-
-            let prefix = format!("Transaction {:x}, input {}", self.hash, i);
-            let mut appended_arguments = vec![];
-            appended_arguments.extend_from_slice(&input.args);
-            if let Some(witness) = self.witnesses.get(&(i as u32)) {
-                appended_arguments.extend_from_slice(&witness);
+                Err(err) => return Err(err),
             }
+        }
+        completed
+            .iter()
+            .try_fold(0, |total: Cycle, (_, cycle)| total.checked_add(*cycle))
+            .map(VerifyOutcome::Completed)
+            .ok_or(ScriptError::ExceededMaximumCycles)
+    }
 
-            let cycle = self.verify_script(&output.lock, &prefix, &appended_arguments, max_cycles - cycles).map_err(|e| {
-                info!(target: "script", "Error validating input {} of transaction {:x}: {:?}", i, self.hash, e);
-                e
-            })?;
-            let current_cycles = cycles
-                .checked_add(cycle)
+    fn verify_with_capture(
+        &self,
+        max_cycles: Cycle,
+        entries: Option<&RefCell<Vec<ScriptDebugEntry>>>,
+    ) -> Result<CycleBreakdown, ScriptError> {
+        let mut breakdown = CycleBreakdown::default();
+        for group in self.script_groups() {
+            let group_cycles = self.verify_group(group, max_cycles - breakdown.total, entries)?;
+            let current_cycles = breakdown
+                .total
+                .checked_add(group_cycles.cycles)
                 .ok_or(ScriptError::ExceededMaximumCycles)?;
             if current_cycles > max_cycles {
                 return Err(ScriptError::ExceededMaximumCycles);
             }
-            cycles = current_cycles;
+            breakdown.total = current_cycles;
+            breakdown.groups.push(group_cycles);
         }
-        for (i, cell_meta) in self.outputs.iter().enumerate() {
-            let output = cell_meta.cell_output.as_ref().expect("output already set");
-            if let Some(ref type_) = output.type_ {
+        Ok(breakdown)
+    }
+
+    // Lock scripts of all inputs, followed by type scripts of the outputs which have one,
+    // this is the order in which the groups are run.
+    fn script_groups(&self) -> Vec<ScriptGroup> {
+        let locks = (0..self.inputs.len().min(self.resolved_inputs.len())).map(ScriptGroup::Lock);
+        let types = self
+            .outputs
+            .iter()
+            .enumerate()
+            .filter(|(_, cell_meta)| {
+                cell_meta
+                    .cell_output
+                    .as_ref()
+                    .expect("output already set")
+                    .type_
+                    .is_some()
+            })
+            .map(|(i, _)| ScriptGroup::Type(i));
+        locks.chain(types).collect()
+    }
+
+    fn verify_group(
+        &self,
+        group: ScriptGroup,
+        max_cycles: Cycle,
+        entries: Option<&RefCell<Vec<ScriptDebugEntry>>>,
+    ) -> Result<GroupCycles, ScriptError> {
+        let profiler = match self.config.profiler.as_ref() {
+            Some(profiler) => profiler,
+            None => return self.run_group(group, max_cycles, entries),
+        };
+        let start = Instant::now();
+        let result = self.run_group(group, max_cycles, entries);
+        if let Ok(GroupCycles {
+            script_hash: Some(script_hash),
+            cycles,
+            ..
+        }) = &result
+        {
+            let kind = match group {
+                ScriptGroup::Lock(_) => ScriptKind::Lock,
+                ScriptGroup::Type(_) => ScriptKind::Type,
+            };
+            profiler.record(script_hash, kind, *cycles, start.elapsed());
+        }
+        result
+    }
+
+    fn run_group(
+        &self,
+        group: ScriptGroup,
+        max_cycles: Cycle,
+        entries: Option<&RefCell<Vec<ScriptDebugEntry>>>,
+    ) -> Result<GroupCycles, ScriptError> {
+        let capture = entries.map(|entries| DebugCapture { group, entries });
+        match group {
+            ScriptGroup::Lock(i) => {
+                let input = self.inputs[i];
+                let input_cell = self.resolved_inputs[i];
+                if input_cell.cell.is_issuing_dao_input() {
+                    if !self.valid_dao_withdraw_transaction() {
+                        return Err(ScriptError::InvalidIssuingDaoInput);
+                    } else {
+                        return Ok(GroupCycles {
+                            group,
+                            script_hash: None,
+                            cycles: 0,
+                        });
+                    }
+                }
+                let input_cell_meta = input_cell.cell.cell_meta();
+                let input_cell = match &input_cell_meta {
+                    Some(cell) => cell,
+                    None => {
+                        return Err(ScriptError::NoScript);
+                    }
+                };
+                let output = self.store.lazy_load_cell_output(input_cell);
+
+                let prefix = format!("Transaction {:x}, input {}", self.hash, i);
+                let mut appended_arguments = vec![];
+                appended_arguments.extend_from_slice(&input.args);
+                if let Some(witness) = self.witnesses.get(&(i as u32)) {
+                    appended_arguments.extend_from_slice(&witness);
+                }
+
+                let cycles = self.verify_script_with_capture(&output.lock, &prefix, &appended_arguments, max_cycles, capture).map_err(|e| {
+                    info!(target: "script", "Error validating input {} of transaction {:x}: {:?}", i, self.hash, e);
+                    e
+                })?;
+                Ok(GroupCycles {
+                    group,
+                    script_hash: Some(output.lock.hash()),
+                    cycles,
+                })
+            }
+            ScriptGroup::Type(i) => {
+                let output = self.outputs[i]
+                    .cell_output
+                    .as_ref()
+                    .expect("output already set");
+                let type_ = output.type_.as_ref().ok_or(ScriptError::NoScript)?;
                 let prefix = format!("Transaction {:x}, output {}", self.hash, i);
-                let cycle = self.verify_script(type_, &prefix, &[], max_cycles - cycles).map_err(|e| {
+                let cycles = self.verify_script_with_capture(type_, &prefix, &[], max_cycles, capture).map_err(|e| {
                     info!(target: "script", "Error validating output {} of transaction {:x}: {:?}", i, self.hash, e);
                     e
                 })?;
-                let current_cycles = cycles
-                    .checked_add(cycle)
-                    .ok_or(ScriptError::ExceededMaximumCycles)?;
-                if current_cycles > max_cycles {
-                    return Err(ScriptError::ExceededMaximumCycles);
-                }
-                cycles = current_cycles;
+                Ok(GroupCycles {
+                    group,
+                    script_hash: Some(type_.hash()),
+                    cycles,
+                })
             }
         }
-        Ok(cycles)
     }
 
     fn verify_dao(
@@ -264,6 +472,7 @@ impl<'a, CS: ChainStore> TransactionScriptsVerifier<'a, CS> {
         prefix: &str,
         max_cycles: Cycle,
         current_script_hash: &[u8],
+        capture: Option<DebugCapture>,
     ) -> Result<Cycle, ScriptError> {
         if args.len() != 6 {
             return Err(ScriptError::ArgumentNumber);
@@ -285,7 +494,13 @@ impl<'a, CS: ChainStore> TransactionScriptsVerifier<'a, CS> {
             args[5].to_owned(),
         ];
         let cycles = self
-            .verify_default_lock(&lock_arguments, prefix, max_cycles, current_script_hash)?
+            .verify_default_lock(
+                &lock_arguments,
+                prefix,
+                max_cycles,
+                current_script_hash,
+                capture,
+            )?
             .checked_add(SYSTEM_DAO_CYCLES)
             .ok_or(ScriptError::ExceededMaximumCycles)?;;
         if cycles > max_cycles {
@@ -361,6 +576,7 @@ impl<'a, CS: ChainStore> TransactionScriptsVerifier<'a, CS> {
         prefix: &str,
         max_cycles: Cycle,
         current_script_hash: &[u8],
+        capture: Option<DebugCapture>,
     ) -> Result<Cycle, ScriptError> {
         // TODO: this is a temporary solution for now, we can change this to use
         // composable contracts when we manage to build NervosDAO as a script
@@ -376,6 +592,7 @@ impl<'a, CS: ChainStore> TransactionScriptsVerifier<'a, CS> {
             prefix,
             max_cycles,
             current_script_hash,
+            capture,
         )
     }
 
@@ -386,6 +603,7 @@ impl<'a, CS: ChainStore> TransactionScriptsVerifier<'a, CS> {
         prefix: &str,
         max_cycles: Cycle,
         current_script_hash: &[u8],
+        capture: Option<DebugCapture>,
     ) -> Result<Cycle, ScriptError> {
         let (code, cycles) = match self.config.runner {
             Runner::Assembly => {
@@ -397,7 +615,7 @@ impl<'a, CS: ChainStore> TransactionScriptsVerifier<'a, CS> {
                     .syscall(Box::new(self.build_load_cell()))
                     .syscall(Box::new(self.build_load_input()))
                     .syscall(Box::new(self.build_load_header()))
-                    .syscall(Box::new(Debugger::new(prefix)))
+                    .syscall(Box::new(Debugger::new(prefix, capture)))
                     .build();
                 let mut machine = AsmMachine::new(machine);
                 machine
@@ -419,7 +637,7 @@ impl<'a, CS: ChainStore> TransactionScriptsVerifier<'a, CS> {
                     .syscall(Box::new(self.build_load_cell()))
                     .syscall(Box::new(self.build_load_input()))
                     .syscall(Box::new(self.build_load_header()))
-                    .syscall(Box::new(Debugger::new(prefix)))
+                    .syscall(Box::new(Debugger::new(prefix, capture)))
                     .build();
                 let mut machine = TraceMachine::new(machine);
                 machine
@@ -453,15 +671,24 @@ impl<'a, CS: ChainStore> TransactionScriptsVerifier<'a, CS> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::{ScriptProfiler, ScriptProfilerRef, ScriptStatsAggregator};
     use byteorder::{LittleEndian, WriteBytesExt};
+    use ckb_chain_spec::consensus::Consensus;
+    use ckb_core::block::{Block, BlockBuilder};
     use ckb_core::cell::{BlockInfo, CellMetaBuilder};
-    use ckb_core::extras::DaoStats;
-    use ckb_core::header::HeaderBuilder;
+    use ckb_core::extras::{DaoStats, EpochExt, TransactionAddress, TransactionInfo};
+    use ckb_core::header::{Header, HeaderBuilder};
     use ckb_core::script::Script;
-    use ckb_core::transaction::{CellInput, CellOutput, OutPoint, TransactionBuilder};
-    use ckb_core::{capacity_bytes, Capacity};
-    use ckb_db::MemoryKeyValueDB;
-    use ckb_store::{ChainKVStore, StoreBatch, COLUMNS};
+    use ckb_core::transaction::{
+        CellInput, CellOutput, OutPoint, ProposalShortId, Transaction, TransactionBuilder,
+    };
+    use ckb_core::uncle::UncleBlock;
+    use ckb_core::{capacity_bytes, Capacity, EpochNumber};
+    use ckb_db::{Error as DBError, MemoryKeyValueDB};
+    use ckb_store::{
+        ChainKVStore, IndexedCell, IndexedCellsPage, Prunable, PrunedTransaction, StoreBatch,
+        COLUMNS,
+    };
     use crypto::secp::Generator;
     use faster_hex::hex_encode;
     use hash::{blake2b_256, sha3_256};
@@ -469,6 +696,7 @@ mod tests {
     use std::fs::File;
     use std::io::{Read, Write};
     use std::path::Path;
+    use std::sync::atomic::{AtomicUsize, Ordering};
     use std::sync::Arc;
     use test_chain_utils::create_always_success_cell;
 
@@ -517,12 +745,545 @@ mod tests {
             store,
             &ScriptConfig {
                 runner: Runner::Assembly,
+                ..Default::default()
             },
         );
 
         assert!(verifier.verify(100).is_ok());
     }
 
+    struct CountingStore {
+        inner: ChainKVStore<MemoryKeyValueDB>,
+        cell_output_loads: AtomicUsize,
+    }
+
+    impl ChainStore for CountingStore {
+        type Batch = <ChainKVStore<MemoryKeyValueDB> as ChainStore>::Batch;
+
+        fn new_batch(&self) -> Result<Self::Batch, DBError> {
+            self.inner.new_batch()
+        }
+
+        fn get_block(&self, block_hash: &H256) -> Option<Block> {
+            self.inner.get_block(block_hash)
+        }
+
+        fn read_block(&self, block_hash: &H256) -> Prunable<Block> {
+            self.inner.read_block(block_hash)
+        }
+
+        fn get_header(&self, block_hash: &H256) -> Option<Header> {
+            self.inner.get_header(block_hash)
+        }
+
+        fn get_block_body(&self, block_hash: &H256) -> Option<Vec<Transaction>> {
+            self.inner.get_block_body(block_hash)
+        }
+
+        fn get_block_proposal_txs_ids(&self, h: &H256) -> Option<Vec<ProposalShortId>> {
+            self.inner.get_block_proposal_txs_ids(h)
+        }
+
+        fn get_block_uncles(&self, block_hash: &H256) -> Option<Vec<UncleBlock>> {
+            self.inner.get_block_uncles(block_hash)
+        }
+
+        fn get_block_ext(&self, block_hash: &H256) -> Option<BlockExt> {
+            self.inner.get_block_ext(block_hash)
+        }
+
+        fn init(&self, consensus: &Consensus) -> Result<(), DBError> {
+            self.inner.init(consensus)
+        }
+
+        fn get_block_hash(&self, number: BlockNumber) -> Option<H256> {
+            self.inner.get_block_hash(number)
+        }
+
+        fn get_block_number(&self, hash: &H256) -> Option<BlockNumber> {
+            self.inner.get_block_number(hash)
+        }
+
+        fn get_tip_header(&self) -> Option<Header> {
+            self.inner.get_tip_header()
+        }
+
+        fn get_transaction(&self, h: &H256) -> Option<(Transaction, H256)> {
+            self.inner.get_transaction(h)
+        }
+
+        fn get_transaction_address(&self, hash: &H256) -> Option<TransactionAddress> {
+            self.inner.get_transaction_address(hash)
+        }
+
+        fn get_transaction_info(&self, hash: &H256) -> Option<TransactionInfo> {
+            self.inner.get_transaction_info(hash)
+        }
+
+        fn get_cell_meta(&self, tx_hash: &H256, index: u32) -> Option<CellMeta> {
+            self.inner.get_cell_meta(tx_hash, index)
+        }
+
+        fn get_cell_metas(&self, out_points: &[CellOutPoint]) -> Vec<Option<CellMeta>> {
+            self.inner.get_cell_metas(out_points)
+        }
+
+        fn get_cell_output(&self, tx_hash: &H256, index: u32) -> Option<CellOutput> {
+            self.cell_output_loads.fetch_add(1, Ordering::SeqCst);
+            self.inner.get_cell_output(tx_hash, index)
+        }
+
+        fn get_current_epoch_ext(&self) -> Option<EpochExt> {
+            self.inner.get_current_epoch_ext()
+        }
+
+        fn get_epoch_ext(&self, hash: &H256) -> Option<EpochExt> {
+            self.inner.get_epoch_ext(hash)
+        }
+
+        fn get_epoch_index(&self, number: EpochNumber) -> Option<H256> {
+            self.inner.get_epoch_index(number)
+        }
+
+        fn get_block_epoch_index(&self, h256: &H256) -> Option<H256> {
+            self.inner.get_block_epoch_index(h256)
+        }
+
+        fn get_cells_by_lock_hash(
+            &self,
+            lock_hash: &H256,
+            from: Option<&IndexedCell>,
+            limit: usize,
+        ) -> Option<IndexedCellsPage> {
+            self.inner.get_cells_by_lock_hash(lock_hash, from, limit)
+        }
+
+        fn get_pruned_number(&self) -> Option<BlockNumber> {
+            self.inner.get_pruned_number()
+        }
+
+        fn get_pruned_transactions(&self, number: BlockNumber) -> Option<Vec<PrunedTransaction>> {
+            self.inner.get_pruned_transactions(number)
+        }
+    }
+
+    #[test]
+    fn check_dep_cell_data_cache() {
+        let (always_success_cell, always_success_script) = create_always_success_cell();
+        let dep_tx = TransactionBuilder::default()
+            .output(always_success_cell)
+            .build();
+        let block = BlockBuilder::default().transaction(dep_tx.clone()).build();
+
+        let store = Arc::new(CountingStore {
+            inner: new_memory_store(),
+            cell_output_loads: AtomicUsize::new(0),
+        });
+        let mut batch = store.new_batch().unwrap();
+        batch.insert_block(&block).unwrap();
+        batch.attach_block(&block).unwrap();
+        batch.commit().unwrap();
+
+        // the cell meta from the store does not carry the output, so loading the script
+        // binary hits the store
+        let dep_cell_meta = store.get_cell_meta(dep_tx.hash(), 0).unwrap();
+        assert!(dep_cell_meta.cell_output.is_none());
+
+        // a block of transactions which all reference the same dep cell
+        let transactions: Vec<Transaction> = (0..10)
+            .map(|since| {
+                TransactionBuilder::default()
+                    .input(CellInput::new(OutPoint::null(), since, vec![]))
+                    .build()
+            })
+            .collect();
+        let rtxs: Vec<ResolvedTransaction> = transactions
+            .iter()
+            .map(|transaction| ResolvedTransaction {
+                transaction,
+                resolved_deps: vec![ResolvedOutPoint::cell_only(dep_cell_meta.clone())],
+                resolved_inputs: vec![ResolvedOutPoint::cell_only(
+                    CellMetaBuilder::from_cell_output(CellOutput::new(
+                        capacity_bytes!(100),
+                        Bytes::default(),
+                        always_success_script.clone(),
+                        None,
+                    ))
+                    .block_info(BlockInfo::new(1, 0))
+                    .build(),
+                )],
+            })
+            .collect();
+
+        let config = ScriptConfig {
+            runner: Runner::Assembly,
+            ..Default::default()
+        };
+        let cache = Arc::new(DepCellDataCache::new(16));
+        let verify_block = || {
+            for rtx in &rtxs {
+                let verifier = TransactionScriptsVerifier::new_with_cache(
+                    rtx,
+                    Arc::clone(&store),
+                    &config,
+                    Arc::clone(&cache),
+                );
+                assert!(verifier.verify(100).is_ok());
+            }
+        };
+
+        verify_block();
+        assert_eq!(store.cell_output_loads.load(Ordering::SeqCst), 1);
+
+        // outputs of a detached block are loaded again
+        cache.invalidate_block(&block);
+        assert!(cache.is_empty());
+        verify_block();
+        assert_eq!(store.cell_output_loads.load(Ordering::SeqCst), 2);
+    }
+
+    fn create_debugger_cell() -> (ResolvedOutPoint, Script) {
+        let mut file =
+            File::open(Path::new(env!("CARGO_MANIFEST_DIR")).join("../script/testdata/debugger"))
+                .unwrap();
+        let mut buffer = Vec::new();
+        file.read_to_end(&mut buffer).unwrap();
+        let code_hash: H256 = (&blake2b_256(&buffer)).into();
+        let debugger_cell = ResolvedOutPoint::cell_only(
+            CellMetaBuilder::from_cell_output(CellOutput::new(
+                Capacity::bytes(buffer.len()).unwrap(),
+                Bytes::from(buffer),
+                Script::default(),
+                None,
+            ))
+            .block_info(BlockInfo::new(1, 0))
+            .data_hash(code_hash.to_owned())
+            .build(),
+        );
+        (debugger_cell, Script::new(vec![], code_hash))
+    }
+
+    #[test]
+    fn check_debug_output_capture() {
+        let (debugger_cell, debugger_script) = create_debugger_cell();
+        let (always_success_cell, always_success_script) = create_always_success_cell();
+        let always_success_cell = ResolvedOutPoint::cell_only(
+            CellMetaBuilder::from_cell_output(always_success_cell)
+                .block_info(BlockInfo::new(1, 0))
+                .build(),
+        );
+
+        let transaction = TransactionBuilder::default()
+            .input(CellInput::new(OutPoint::null(), 0, vec![]))
+            .input(CellInput::new(OutPoint::null(), 0, vec![]))
+            .output(CellOutput::new(
+                capacity_bytes!(100),
+                Bytes::default(),
+                always_success_script.clone(),
+                Some(debugger_script.clone()),
+            ))
+            .build();
+
+        let input_cell = |lock| {
+            ResolvedOutPoint::cell_only(
+                CellMetaBuilder::from_cell_output(CellOutput::new(
+                    capacity_bytes!(100),
+                    Bytes::default(),
+                    lock,
+                    None,
+                ))
+                .block_info(BlockInfo::new(1, 0))
+                .build(),
+            )
+        };
+        let rtx = ResolvedTransaction {
+            transaction: &transaction,
+            resolved_deps: vec![always_success_cell, debugger_cell],
+            resolved_inputs: vec![
+                input_cell(always_success_script),
+                input_cell(debugger_script),
+            ],
+        };
+
+        let store = Arc::new(new_memory_store());
+        let verifier = TransactionScriptsVerifier::new(
+            &rtx,
+            store,
+            &ScriptConfig {
+                runner: Runner::Assembly,
+                ..Default::default()
+            },
+        );
+
+        let (result, entries) = verifier.verify_with_debug(100_000_000);
+        assert_eq!(result, verifier.verify(100_000_000));
+        assert!(result.is_ok());
+        assert_eq!(
+            entries,
+            vec![
+                ScriptDebugEntry {
+                    group: ScriptGroup::Lock(1),
+                    message: "debugger script".to_owned(),
+                },
+                ScriptDebugEntry {
+                    group: ScriptGroup::Type(0),
+                    message: "debugger script".to_owned(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn check_resume_verification() {
+        let (debugger_cell, debugger_script) = create_debugger_cell();
+        let (always_success_cell, always_success_script) = create_always_success_cell();
+        let always_success_cell = ResolvedOutPoint::cell_only(
+            CellMetaBuilder::from_cell_output(always_success_cell)
+                .block_info(BlockInfo::new(1, 0))
+                .build(),
+        );
+
+        let transaction = TransactionBuilder::default()
+            .input(CellInput::new(OutPoint::null(), 0, vec![]))
+            .input(CellInput::new(OutPoint::null(), 0, vec![]))
+            .output(CellOutput::new(
+                capacity_bytes!(100),
+                Bytes::default(),
+                always_success_script.clone(),
+                Some(debugger_script.clone()),
+            ))
+            .build();
+
+        let input_cell = |lock| {
+            ResolvedOutPoint::cell_only(
+                CellMetaBuilder::from_cell_output(CellOutput::new(
+                    capacity_bytes!(100),
+                    Bytes::default(),
+                    lock,
+                    None,
+                ))
+                .block_info(BlockInfo::new(1, 0))
+                .build(),
+            )
+        };
+        let rtx = ResolvedTransaction {
+            transaction: &transaction,
+            resolved_deps: vec![always_success_cell, debugger_cell],
+            resolved_inputs: vec![
+                input_cell(always_success_script.clone()),
+                input_cell(debugger_script.clone()),
+            ],
+        };
+
+        let store = Arc::new(new_memory_store());
+        let config = ScriptConfig {
+            runner: Runner::Assembly,
+            ..Default::default()
+        };
+        let verifier = TransactionScriptsVerifier::new(&rtx, store, &config);
+
+        let always_success_cycles = verifier
+            .verify_script(&always_success_script, "", &[], 100_000_000)
+            .unwrap();
+        let debugger_cycles = verifier
+            .verify_script(&debugger_script, "", &[], 100_000_000)
+            .unwrap();
+        let total = verifier.verify(100_000_000).unwrap();
+        assert_eq!(total, always_success_cycles + debugger_cycles * 2);
+
+        // Each installment has room for one more group, but not for two
+        let snapshot = match verifier
+            .verify_until(always_success_cycles + debugger_cycles - 1)
+            .unwrap()
+        {
+            VerifyOutcome::Suspended(snapshot) => snapshot,
+            outcome => panic!("unexpected outcome {:?}", outcome),
+        };
+        assert_eq!(
+            snapshot,
+            VerificationSnapshot {
+                completed: vec![(ScriptGroup::Lock(0), always_success_cycles)],
+                pending: Some(ScriptGroup::Lock(1)),
+            }
+        );
+
+        let snapshot = match verifier.resume(snapshot, debugger_cycles * 2 - 1).unwrap() {
+            VerifyOutcome::Suspended(snapshot) => snapshot,
+            outcome => panic!("unexpected outcome {:?}", outcome),
+        };
+        assert_eq!(
+            snapshot,
+            VerificationSnapshot {
+                completed: vec![
+                    (ScriptGroup::Lock(0), always_success_cycles),
+                    (ScriptGroup::Lock(1), debugger_cycles),
+                ],
+                pending: Some(ScriptGroup::Type(0)),
+            }
+        );
+
+        assert_eq!(
+            verifier.resume(snapshot, debugger_cycles * 2 - 1),
+            Ok(VerifyOutcome::Completed(total))
+        );
+    }
+
+    #[test]
+    fn check_cycle_breakdown() {
+        let (debugger_cell, debugger_script) = create_debugger_cell();
+        let (always_success_cell, always_success_script) = create_always_success_cell();
+        let always_success_cell = ResolvedOutPoint::cell_only(
+            CellMetaBuilder::from_cell_output(always_success_cell)
+                .block_info(BlockInfo::new(1, 0))
+                .build(),
+        );
+
+        let transaction = TransactionBuilder::default()
+            .input(CellInput::new(OutPoint::null(), 0, vec![]))
+            .input(CellInput::new(OutPoint::null(), 0, vec![]))
+            .build();
+
+        let input_cell = |lock| {
+            ResolvedOutPoint::cell_only(
+                CellMetaBuilder::from_cell_output(CellOutput::new(
+                    capacity_bytes!(100),
+                    Bytes::default(),
+                    lock,
+                    None,
+                ))
+                .block_info(BlockInfo::new(1, 0))
+                .build(),
+            )
+        };
+        let rtx = ResolvedTransaction {
+            transaction: &transaction,
+            resolved_deps: vec![always_success_cell, debugger_cell],
+            resolved_inputs: vec![
+                input_cell(always_success_script.clone()),
+                input_cell(debugger_script.clone()),
+            ],
+        };
+
+        let store = Arc::new(new_memory_store());
+        let config = ScriptConfig {
+            runner: Runner::Assembly,
+            ..Default::default()
+        };
+        let verifier = TransactionScriptsVerifier::new(&rtx, store, &config);
+
+        let always_success_cycles = verifier
+            .verify_script(&always_success_script, "", &[], 100_000_000)
+            .unwrap();
+        let debugger_cycles = verifier
+            .verify_script(&debugger_script, "", &[], 100_000_000)
+            .unwrap();
+
+        let breakdown = verifier.verify_with_breakdown(100_000_000).unwrap();
+        assert_eq!(
+            breakdown.groups,
+            vec![
+                GroupCycles {
+                    group: ScriptGroup::Lock(0),
+                    script_hash: Some(always_success_script.hash()),
+                    cycles: always_success_cycles,
+                },
+                GroupCycles {
+                    group: ScriptGroup::Lock(1),
+                    script_hash: Some(debugger_script.hash()),
+                    cycles: debugger_cycles,
+                },
+            ]
+        );
+        assert_eq!(
+            breakdown
+                .groups
+                .iter()
+                .map(|group| group.cycles)
+                .sum::<Cycle>(),
+            breakdown.total
+        );
+        assert_eq!(verifier.verify(100_000_000), Ok(breakdown.total));
+    }
+
+    #[test]
+    fn check_profiler_totals() {
+        let (_, debugger_script) = create_debugger_cell();
+        let (_, always_success_script) = create_always_success_cell();
+        let deps = || {
+            let (always_success_cell, _) = create_always_success_cell();
+            vec![
+                ResolvedOutPoint::cell_only(
+                    CellMetaBuilder::from_cell_output(always_success_cell)
+                        .block_info(BlockInfo::new(1, 0))
+                        .build(),
+                ),
+                create_debugger_cell().0,
+            ]
+        };
+        let input_cell = |lock| {
+            ResolvedOutPoint::cell_only(
+                CellMetaBuilder::from_cell_output(CellOutput::new(
+                    capacity_bytes!(100),
+                    Bytes::default(),
+                    lock,
+                    None,
+                ))
+                .block_info(BlockInfo::new(1, 0))
+                .build(),
+            )
+        };
+        let transactions: Vec<_> = (1..=3)
+            .map(|inputs| {
+                (0..inputs)
+                    .fold(TransactionBuilder::default(), |builder, _| {
+                        builder.input(CellInput::new(OutPoint::null(), 0, vec![]))
+                    })
+                    .build()
+            })
+            .collect();
+        let locks = [
+            always_success_script.clone(),
+            debugger_script.clone(),
+            always_success_script.clone(),
+        ];
+
+        let aggregator = Arc::new(ScriptStatsAggregator::new());
+        let config = ScriptConfig {
+            runner: Runner::Assembly,
+            profile: true,
+            profiler: Some(ScriptProfilerRef::new(
+                Arc::clone(&aggregator) as Arc<dyn ScriptProfiler>
+            )),
+        };
+        let store = Arc::new(new_memory_store());
+        let mut total = 0;
+        for transaction in &transactions {
+            let rtx = ResolvedTransaction {
+                transaction,
+                resolved_deps: deps(),
+                resolved_inputs: locks[..transaction.inputs().len()]
+                    .iter()
+                    .cloned()
+                    .map(input_cell)
+                    .collect(),
+            };
+            let verifier = TransactionScriptsVerifier::new(&rtx, Arc::clone(&store), &config);
+            total += verifier.verify(100_000_000).unwrap();
+        }
+
+        let stats = aggregator.stats();
+        assert_eq!(aggregator.total_cycles(), total);
+        assert_eq!(stats.iter().map(|stats| stats.cycles).sum::<Cycle>(), total);
+        let runs = |script: &Script| {
+            stats
+                .iter()
+                .find(|stats| stats.script_hash == script.hash())
+                .map(|stats| (stats.kind, stats.runs))
+        };
+        assert_eq!(runs(&always_success_script), Some((ScriptKind::Lock, 4)));
+        assert_eq!(runs(&debugger_script), Some((ScriptKind::Lock, 2)));
+    }
+
     #[test]
     fn check_signature() {
         let mut file = open_cell_verify();
@@ -596,6 +1357,7 @@ mod tests {
             store,
             &ScriptConfig {
                 runner: Runner::Assembly,
+                ..Default::default()
             },
         );
 
@@ -675,6 +1437,7 @@ mod tests {
             store,
             &ScriptConfig {
                 runner: Runner::Rust,
+                ..Default::default()
             },
         );
 
@@ -755,6 +1518,7 @@ mod tests {
             store,
             &ScriptConfig {
                 runner: Runner::Assembly,
+                ..Default::default()
             },
         );
 
@@ -835,6 +1599,7 @@ mod tests {
             store,
             &ScriptConfig {
                 runner: Runner::Assembly,
+                ..Default::default()
             },
         );
 
@@ -900,6 +1665,7 @@ mod tests {
             store,
             &ScriptConfig {
                 runner: Runner::Assembly,
+                ..Default::default()
             },
         );
 
@@ -995,6 +1761,7 @@ mod tests {
             store,
             &ScriptConfig {
                 runner: Runner::Assembly,
+                ..Default::default()
             },
         );
 
@@ -1092,6 +1859,7 @@ mod tests {
             store,
             &ScriptConfig {
                 runner: Runner::Assembly,
+                ..Default::default()
             },
         );
 
@@ -1123,6 +1891,7 @@ mod tests {
             store,
             &ScriptConfig {
                 runner: Runner::Assembly,
+                ..Default::default()
             },
         );
 
@@ -1231,6 +2000,7 @@ mod tests {
             store,
             &ScriptConfig {
                 runner: Runner::Assembly,
+                ..Default::default()
             },
         );
 
@@ -1339,6 +2109,7 @@ mod tests {
             store,
             &ScriptConfig {
                 runner: Runner::Assembly,
+                ..Default::default()
             },
         );
 
@@ -1441,6 +2212,7 @@ mod tests {
             store,
             &ScriptConfig {
                 runner: Runner::Assembly,
+                ..Default::default()
             },
         );
 
@@ -1549,6 +2321,7 @@ mod tests {
             store,
             &ScriptConfig {
                 runner: Runner::Assembly,
+                ..Default::default()
             },
         );
 
@@ -1645,6 +2418,7 @@ mod tests {
             store,
             &ScriptConfig {
                 runner: Runner::Assembly,
+                ..Default::default()
             },
         );
 
@@ -1753,6 +2527,7 @@ mod tests {
             store,
             &ScriptConfig {
                 runner: Runner::Assembly,
+                ..Default::default()
             },
         );
 
@@ -1861,6 +2636,7 @@ mod tests {
             store,
             &ScriptConfig {
                 runner: Runner::Assembly,
+                ..Default::default()
             },
         );
 
@@ -11,7 +11,7 @@ use ckb_core::cell::{
 };
 use ckb_core::extras::EpochExt;
 use ckb_core::header::{BlockNumber, Header};
-use ckb_core::transaction::CellOutput;
+use ckb_core::transaction::{CellOutPoint, CellOutput};
 use ckb_core::transaction::{OutPoint, ProposalShortId, Transaction};
 use ckb_core::Cycle;
 use ckb_script::ScriptConfig;
@@ -19,7 +19,7 @@ use ckb_store::ChainStore;
 use ckb_traits::BlockMedianTimeContext;
 use ckb_util::LinkedFnvHashSet;
 use ckb_util::{FnvHashMap, FnvHashSet};
-use ckb_verification::{ContextualTransactionVerifier, TransactionVerifier};
+use ckb_verification::{ContextualTransactionVerifier, MedianTimeCache, TransactionVerifier};
 use dao_utils::calculate_transaction_fee;
 use log::{debug, trace};
 use lru_cache::LruCache;
@@ -28,6 +28,9 @@ use numext_fixed_uint::U256;
 use std::cell::{Ref, RefCell};
 use std::sync::Arc;
 
+// The main chain blocks whose median times are cached for the transactions in the pool
+const MEDIAN_TIME_CACHE_SIZE: usize = 1024;
+
 #[derive(Debug, Clone)]
 pub struct ChainState<CS> {
     store: Arc<CS>,
@@ -40,6 +43,10 @@ pub struct ChainState<CS> {
     consensus: Arc<Consensus>,
     current_epoch_ext: EpochExt,
     script_config: ScriptConfig,
+    // median time of the blocks before the tip, recomputed on tip change
+    tip_median_time: Option<u64>,
+    // median times of the main chain blocks, invalidated on reorg
+    median_time_cache: Arc<MedianTimeCache>,
 }
 
 impl<CS: ChainStore> ChainState<CS> {
@@ -97,7 +104,7 @@ impl<CS: ChainStore> ChainState<CS> {
             .get_block_ext(&tip_header.hash())
             .ok_or_else(|| SharedError::InvalidData("failed to get block_ext".to_owned()))?
             .total_difficulty;
-        Ok(ChainState {
+        let mut chain_state = ChainState {
             store: Arc::clone(store),
             tip_header,
             total_difficulty,
@@ -107,7 +114,11 @@ impl<CS: ChainStore> ChainState<CS> {
             consensus,
             current_epoch_ext: epoch_ext,
             script_config,
-        })
+            tip_median_time: None,
+            median_time_cache: Arc::new(MedianTimeCache::new(MEDIAN_TIME_CACHE_SIZE)),
+        };
+        chain_state.tip_median_time = chain_state.compute_tip_median_time();
+        Ok(chain_state)
     }
 
     pub fn store(&self) -> &Arc<CS> {
@@ -144,7 +155,30 @@ impl<CS: ChainStore> ChainState<CS> {
     fn init_cell_set(store: &CS, number: u64) -> CellSet {
         let mut cell_set = CellSet::new();
 
-        for n in 0..=number {
+        // the pruned blocks keep only the transactions with live outputs, which are restored
+        // along with their dead outputs
+        let unpruned = store.get_pruned_number().map_or(0, |pruned| pruned + 1);
+        for n in 0..unpruned {
+            let hash = store.get_block_hash(n).unwrap();
+            let epoch_hash = store.get_block_epoch_index(&hash).unwrap();
+            let epoch_ext = store.get_epoch_ext(&epoch_hash).unwrap();
+            for tx in store.get_pruned_transactions(n).unwrap_or_default() {
+                cell_set.insert(
+                    tx.hash.to_owned(),
+                    n,
+                    epoch_ext.number(),
+                    tx.cellbase,
+                    tx.outputs.len(),
+                );
+                for (index, output) in tx.outputs.iter().enumerate() {
+                    if output.is_none() {
+                        cell_set.mark_dead(&OutPoint::new_cell(tx.hash.to_owned(), index as u32));
+                    }
+                }
+            }
+        }
+
+        for n in unpruned..=number {
             let hash = store.get_block_hash(n).unwrap();
             let epoch_hash = store.get_block_epoch_index(&hash).unwrap();
             let epoch_ext = store.get_epoch_ext(&epoch_hash).unwrap();
@@ -177,6 +211,19 @@ impl<CS: ChainStore> ChainState<CS> {
         self.tip_header.hash()
     }
 
+    /// Median time of the blocks before the tip
+    pub fn tip_median_time(&self) -> Option<u64> {
+        self.tip_median_time
+    }
+
+    fn compute_tip_median_time(&self) -> Option<u64> {
+        self.block_median_time(self.tip_number().saturating_sub(1))
+    }
+
+    pub fn median_time_cache(&self) -> &Arc<MedianTimeCache> {
+        &self.median_time_cache
+    }
+
     pub fn current_epoch_ext(&self) -> &EpochExt {
         &self.current_epoch_ext
     }
@@ -230,6 +277,7 @@ impl<CS: ChainStore> ChainState<CS> {
         self.tip_header = header;
         self.total_difficulty = total_difficulty;
         self.cell_set.update(txo_diff);
+        self.tip_median_time = self.compute_tip_median_time();
     }
 
     pub fn get_tx_with_cycles_from_pool(
@@ -291,12 +339,14 @@ impl<CS: ChainStore> ChainState<CS> {
     ) -> Result<Cycle, PoolError> {
         match cycles {
             Some(cycles) => {
-                ContextualTransactionVerifier::new(
+                ContextualTransactionVerifier::new_with_cache(
                     &rtx,
                     &self,
                     self.tip_number(),
                     self.current_epoch_ext().number(),
-                    self.consensus().cellbase_maturity,
+                    self.tip_median_time(),
+                    self.consensus().cellbase_maturity(),
+                    Arc::clone(&self.median_time_cache),
                 )
                 .verify()
                 .map_err(PoolError::InvalidTx)?;
@@ -304,14 +354,18 @@ impl<CS: ChainStore> ChainState<CS> {
             }
             None => {
                 let max_cycles = self.consensus.max_block_cycles();
-                let cycles = TransactionVerifier::new(
+                let cycles = TransactionVerifier::new_with_cache(
                     &rtx,
                     Arc::clone(self.store()),
                     &self,
                     self.tip_number(),
                     self.current_epoch_ext().number(),
-                    self.consensus().cellbase_maturity,
+                    self.tip_median_time(),
+                    self.consensus().cellbase_maturity(),
+                    self.consensus().max_extra_witnesses(),
                     &self.script_config,
+                    Arc::clone(&self.median_time_cache),
+                    None,
                 )
                 .verify(max_cycles)
                 .map_err(PoolError::InvalidTx)?;
@@ -406,13 +460,24 @@ impl<CS: ChainStore> ChainState<CS> {
         let mut detached = LinkedFnvHashSet::default();
         let mut attached = LinkedFnvHashSet::default();
 
+        // the first block number whose block has changed, the median times from it on are stale
+        let mut first_changed = None;
         for blk in detached_blocks {
+            let number = blk.header().number();
+            first_changed =
+                Some(first_changed.map_or(number, |first: BlockNumber| first.min(number)));
             detached.extend(blk.transactions().iter().skip(1).cloned())
         }
 
         for blk in attached_blocks {
+            let number = blk.header().number();
+            first_changed =
+                Some(first_changed.map_or(number, |first: BlockNumber| first.min(number)));
             attached.extend(blk.transactions().iter().skip(1).cloned())
         }
+        if let Some(number) = first_changed {
+            self.median_time_cache.invalidate_from(number);
+        }
 
         let retain: Vec<Transaction> = detached.difference(&attached).cloned().collect();
 
@@ -543,6 +608,47 @@ impl<CS: ChainStore> CellProvider for ChainState<CS> {
             CellStatus::Unspecified
         }
     }
+
+    fn cells(&self, out_points: &[&OutPoint]) -> Vec<CellStatus> {
+        let mut live_cells = Vec::new();
+        let mut statuses = out_points
+            .iter()
+            .enumerate()
+            .map(|(i, out_point)| match &out_point.cell {
+                Some(cell_out_point) => match self.cell_set().get(&cell_out_point.tx_hash) {
+                    Some(tx_meta) => match tx_meta.is_dead(cell_out_point.index as usize) {
+                        Some(false) => {
+                            live_cells.push((i, cell_out_point.to_owned()));
+                            CellStatus::Unknown
+                        }
+                        Some(true) => CellStatus::Dead,
+                        None => CellStatus::Unknown,
+                    },
+                    None => CellStatus::Unknown,
+                },
+                None => CellStatus::Unspecified,
+            })
+            .collect::<Vec<_>>();
+        load_live_cells(self.store.as_ref(), &mut statuses, live_cells);
+        statuses
+    }
+}
+
+// Fills the statuses of the live cells at their indexes with their cell metas, looked up in the
+// store in one batch
+fn load_live_cells<CS: ChainStore>(
+    store: &CS,
+    statuses: &mut [CellStatus],
+    live_cells: Vec<(usize, CellOutPoint)>,
+) {
+    if live_cells.is_empty() {
+        return;
+    }
+    let (indexes, out_points): (Vec<_>, Vec<_>) = live_cells.into_iter().unzip();
+    for (i, cell_meta) in indexes.into_iter().zip(store.get_cell_metas(&out_points)) {
+        let cell_meta = cell_meta.expect("store should be consistent with cell_set");
+        statuses[i] = CellStatus::live_cell(cell_meta);
+    }
 }
 
 impl<CS: ChainStore> HeaderProvider for ChainState<CS> {
@@ -553,8 +659,19 @@ impl<CS: ChainStore> HeaderProvider for ChainState<CS> {
                     if let Some(cell_out_point) = &out_point.cell {
                         self.store
                             .get_transaction_address(&cell_out_point.tx_hash)
-                            .map_or(HeaderStatus::InclusionFaliure, |address| {
-                                if address.block_hash == *block_hash {
+                            .map(|address| address.block_hash)
+                            // the addresses of the pruned transactions are removed, while their
+                            // cell metas still tell their blocks
+                            .or_else(|| {
+                                self.store
+                                    .get_cell_meta(&cell_out_point.tx_hash, cell_out_point.index)
+                                    .and_then(|cell_meta| cell_meta.block_info)
+                                    .and_then(|block_info| {
+                                        self.store.get_block_hash(block_info.number)
+                                    })
+                            })
+                            .map_or(HeaderStatus::InclusionFaliure, |hash| {
+                                if hash == *block_hash {
                                     HeaderStatus::live_header(header)
                                 } else {
                                     HeaderStatus::InclusionFaliure
@@ -609,6 +726,45 @@ impl<'a, CS: ChainStore> CellProvider for ChainCellSetOverlay<'a, CS> {
             CellStatus::Unspecified
         }
     }
+
+    fn cells(&self, out_points: &[&OutPoint]) -> Vec<CellStatus> {
+        let mut stored_cells = Vec::new();
+        let mut statuses = out_points
+            .iter()
+            .enumerate()
+            .map(|(i, out_point)| match &out_point.cell {
+                Some(cell_out_point) => match self.overlay.get(&cell_out_point.tx_hash) {
+                    Some(tx_meta) => match tx_meta.is_dead(cell_out_point.index as usize) {
+                        Some(false) => match self.outputs.get(&cell_out_point.tx_hash) {
+                            Some(outputs) => {
+                                let output = &outputs[cell_out_point.index as usize];
+                                let cell_meta =
+                                    CellMetaBuilder::from_cell_output(output.to_owned())
+                                        .out_point(cell_out_point.to_owned())
+                                        .block_info(BlockInfo::new(
+                                            tx_meta.block_number(),
+                                            tx_meta.epoch_number(),
+                                        ))
+                                        .cellbase(tx_meta.is_cellbase())
+                                        .build();
+                                CellStatus::live_cell(cell_meta)
+                            }
+                            None => {
+                                stored_cells.push((i, cell_out_point.to_owned()));
+                                CellStatus::Unknown
+                            }
+                        },
+                        Some(true) => CellStatus::Dead,
+                        None => CellStatus::Unknown,
+                    },
+                    None => CellStatus::Unknown,
+                },
+                None => CellStatus::Unspecified,
+            })
+            .collect::<Vec<_>>();
+        load_live_cells(self.store.as_ref(), &mut statuses, stored_cells);
+        statuses
+    }
 }
 
 impl<CS: ChainStore> BlockMedianTimeContext for &ChainState<CS> {
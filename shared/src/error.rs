@@ -1,6 +1,7 @@
 use ckb_core::cell::UnresolvableError;
 use ckb_db::Error as DBError;
 use failure::Fail;
+use numext_fixed_hash::H256;
 
 #[derive(Debug, PartialEq, Clone, Eq, Fail)]
 pub enum SharedError {
@@ -10,6 +11,8 @@ pub enum SharedError {
     InvalidTransaction(String),
     #[fail(display = "InvalidParentBlock")]
     InvalidParentBlock,
+    #[fail(display = "PrunedBlock: {:#x}", _0)]
+    PrunedBlock(H256),
     #[fail(display = "InvalidData error: {}", _0)]
     InvalidData(String),
     #[fail(display = "DB error: {}", _0)]
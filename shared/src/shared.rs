@@ -8,9 +8,9 @@ use ckb_core::header::{BlockNumber, Header};
 use ckb_core::transaction::{ProposalShortId, Transaction};
 use ckb_core::uncle::UncleBlock;
 use ckb_core::Cycle;
-use ckb_db::{CacheDB, DBConfig, KeyValueDB, MemoryKeyValueDB, RocksDB};
-use ckb_script::ScriptConfig;
-use ckb_store::{ChainKVStore, ChainStore, COLUMNS, COLUMN_BLOCK_HEADER};
+use ckb_db::{CacheDB, DBConfig, IterableKeyValueDB, MemoryKeyValueDB, PersistentDB};
+use ckb_script::{DepCellDataCache, ScriptConfig, ScriptProfilerRef, ScriptStatsAggregator};
+use ckb_store::{default_cache_policies, ChainKVStore, ChainStore, StoreConfig, COLUMNS};
 use ckb_traits::ChainProvider;
 use ckb_util::{lock_or_panic, Mutex, MutexGuard};
 use lru_cache::LruCache;
@@ -18,12 +18,14 @@ use numext_fixed_hash::H256;
 use std::sync::Arc;
 
 const TXS_VERIFY_CACHE_SIZE: usize = 10_000;
+const DEP_CELL_DATA_CACHE_SIZE: usize = 32;
 
 #[derive(Debug)]
 pub struct Shared<CS> {
     store: Arc<CS>,
     chain_state: Arc<Mutex<ChainState<CS>>>,
     txs_verify_cache: Arc<Mutex<LruCache<H256, Cycle>>>,
+    dep_cell_data_cache: Arc<DepCellDataCache>,
     consensus: Arc<Consensus>,
     script_config: ScriptConfig,
 }
@@ -37,6 +39,7 @@ impl<CS: ChainStore> ::std::clone::Clone for Shared<CS> {
             consensus: Arc::clone(&self.consensus),
             script_config: self.script_config.clone(),
             txs_verify_cache: Arc::clone(&self.txs_verify_cache),
+            dep_cell_data_cache: Arc::clone(&self.dep_cell_data_cache),
         }
     }
 }
@@ -51,6 +54,7 @@ impl<CS: ChainStore> Shared<CS> {
         let store = Arc::new(store);
         let consensus = Arc::new(consensus);
         let txs_verify_cache = Arc::new(Mutex::new(LruCache::new(TXS_VERIFY_CACHE_SIZE)));
+        let dep_cell_data_cache = Arc::new(DepCellDataCache::new(DEP_CELL_DATA_CACHE_SIZE));
         let chain_state = Arc::new(Mutex::new(ChainState::init(
             &store,
             Arc::clone(&consensus),
@@ -64,6 +68,7 @@ impl<CS: ChainStore> Shared<CS> {
             consensus,
             script_config,
             txs_verify_cache,
+            dep_cell_data_cache,
         })
     }
 
@@ -74,6 +79,10 @@ impl<CS: ChainStore> Shared<CS> {
     pub fn lock_txs_verify_cache(&self) -> MutexGuard<LruCache<H256, Cycle>> {
         lock_or_panic(&self.txs_verify_cache)
     }
+
+    pub fn dep_cell_data_cache(&self) -> &Arc<DepCellDataCache> {
+        &self.dep_cell_data_cache
+    }
 }
 
 impl<CS: ChainStore> ChainProvider for Shared<CS> {
@@ -180,20 +189,22 @@ impl<CS: ChainStore> ChainProvider for Shared<CS> {
     }
 }
 
-pub struct SharedBuilder<DB: KeyValueDB> {
+pub struct SharedBuilder<DB: IterableKeyValueDB> {
     db: Option<DB>,
     consensus: Option<Consensus>,
     tx_pool_config: Option<TxPoolConfig>,
     script_config: Option<ScriptConfig>,
+    store_config: Option<StoreConfig>,
 }
 
-impl<DB: KeyValueDB> Default for SharedBuilder<DB> {
+impl<DB: IterableKeyValueDB> Default for SharedBuilder<DB> {
     fn default() -> Self {
         SharedBuilder {
             db: None,
             consensus: None,
             tx_pool_config: None,
             script_config: None,
+            store_config: None,
         }
     }
 }
@@ -205,27 +216,27 @@ impl SharedBuilder<MemoryKeyValueDB> {
             consensus: None,
             tx_pool_config: None,
             script_config: None,
+            store_config: None,
         }
     }
 }
 
-impl SharedBuilder<CacheDB<RocksDB>> {
+impl SharedBuilder<CacheDB<PersistentDB>> {
     pub fn new() -> Self {
         Default::default()
     }
 
     pub fn db(mut self, config: &DBConfig) -> Self {
-        self.db = Some(CacheDB::new(
-            RocksDB::open(config, COLUMNS),
-            &[(COLUMN_BLOCK_HEADER, 4096)],
-        ));
+        let mut policies = default_cache_policies();
+        policies.extend(config.cache.iter().map(|(col, policy)| (*col, *policy)));
+        self.db = Some(CacheDB::new(PersistentDB::open(config, COLUMNS), &policies));
         self
     }
 }
 
 pub const MIN_TXS_VERIFY_CACHE_SIZE: Option<usize> = Some(100);
 
-impl<DB: KeyValueDB> SharedBuilder<DB> {
+impl<DB: IterableKeyValueDB> SharedBuilder<DB> {
     pub fn consensus(mut self, value: Consensus) -> Self {
         self.consensus = Some(value);
         self
@@ -241,11 +252,23 @@ impl<DB: KeyValueDB> SharedBuilder<DB> {
         self
     }
 
+    pub fn store_config(mut self, config: StoreConfig) -> Self {
+        self.store_config = Some(config);
+        self
+    }
+
     pub fn build(self) -> Result<Shared<ChainKVStore<DB>>, SharedError> {
-        let store = ChainKVStore::new(self.db.unwrap());
+        let store_config = self.store_config.unwrap_or_else(Default::default);
+        let store = ChainKVStore::new_with_config(self.db.unwrap(), &store_config);
         let consensus = self.consensus.unwrap_or_else(Consensus::default);
         let tx_pool_config = self.tx_pool_config.unwrap_or_else(Default::default);
-        let script_config = self.script_config.unwrap_or_else(Default::default);
+        let mut script_config = self.script_config.unwrap_or_else(Default::default);
+        if script_config.profile && script_config.profiler.is_none() {
+            script_config.profiler = Some(ScriptProfilerRef::new(Arc::new(
+                ScriptStatsAggregator::new(),
+            )));
+        }
+        store.init_cell_index().map_err(SharedError::DB)?;
         Shared::init(store, consensus, tx_pool_config, script_config)
     }
 }
@@ -1,6 +1,6 @@
 use crate::shared::{Shared, SharedBuilder};
 use ckb_core::{block::BlockBuilder, header::HeaderBuilder};
-use ckb_db::{KeyValueDB, MemoryKeyValueDB};
+use ckb_db::{IterableKeyValueDB, MemoryKeyValueDB};
 use ckb_store::{ChainKVStore, ChainStore, StoreBatch};
 use ckb_traits::{BlockMedianTimeContext, ChainProvider};
 
@@ -10,7 +10,7 @@ fn new_shared() -> Shared<ChainKVStore<MemoryKeyValueDB>> {
 
 fn insert_block_timestamps<T>(store: &ChainKVStore<T>, timestamps: &[u64])
 where
-    T: KeyValueDB,
+    T: IterableKeyValueDB,
 {
     let mut blocks = Vec::with_capacity(timestamps.len());
     let tip_header = store.get_tip_header().expect("tip");
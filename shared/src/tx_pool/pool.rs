@@ -8,6 +8,7 @@ use ckb_core::{Capacity, Cycle};
 use faketime::unix_time_as_millis;
 use log::trace;
 use lru_cache::LruCache;
+use numext_fixed_hash::H256;
 
 #[derive(Debug, Clone)]
 pub struct TxPool {
@@ -114,6 +115,17 @@ impl TxPool {
             })
     }
 
+    /// The hash of the transaction of the short id, without cloning the transaction. The short
+    /// ids may collide, the hash tells whether it is the transaction looked for.
+    pub fn get_tx_hash(&self, id: &ProposalShortId) -> Option<&H256> {
+        self.pending
+            .get_tx(id)
+            .or_else(|| self.proposed.get_tx(id))
+            .or_else(|| self.orphan.get_tx(id))
+            .or_else(|| self.conflict.get(id).map(|e| &e.transaction))
+            .map(Transaction::hash)
+    }
+
     pub fn get_tx(&self, id: &ProposalShortId) -> Option<Transaction> {
         self.pending
             .get_tx(id)
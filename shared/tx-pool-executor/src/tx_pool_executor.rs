@@ -8,6 +8,7 @@ use ckb_verification::TransactionVerifier;
 use fnv::FnvHashMap;
 use numext_fixed_hash::H256;
 use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
+use std::cmp;
 use std::sync::Arc;
 
 struct StoreBlockMedianTimeContext<CS> {
@@ -45,35 +46,67 @@ impl<CS: ChainStore> TxPoolExecutor<CS> {
             .map(|cycles_vec| *cycles_vec.get(0).expect("tx verified cycles"))
     }
 
+    /// Verify the transaction with at most `max_cycles` cycles, which fails if the scripts
+    /// consume more, then add it to tx_pool
+    pub fn verify_and_add_tx_to_pool_with_max_cycles(
+        &self,
+        tx: Transaction,
+        max_cycles: Cycle,
+    ) -> Result<Cycle, PoolError> {
+        self.verify_and_add_txs_to_pool_inner(vec![tx], Some(max_cycles))
+            .map(|cycles_vec| *cycles_vec.get(0).expect("tx verified cycles"))
+    }
+
     pub fn verify_and_add_txs_to_pool(
         &self,
         txs: Vec<Transaction>,
+    ) -> Result<Vec<Cycle>, PoolError> {
+        self.verify_and_add_txs_to_pool_inner(txs, None)
+    }
+
+    fn verify_and_add_txs_to_pool_inner(
+        &self,
+        txs: Vec<Transaction>,
+        max_cycles: Option<Cycle>,
     ) -> Result<Vec<Cycle>, PoolError> {
         if txs.is_empty() {
             return Ok(Vec::new());
         }
         // resolve txs
         // early release the chain_state lock because tx verification is slow
-        let (resolved_txs, cached_txs, unresolvable_txs, consensus, tip_number, epoch_number) = {
+        let (
+            resolved_txs,
+            cached_txs,
+            unresolvable_txs,
+            consensus,
+            tip_number,
+            epoch_number,
+            tip_median_time,
+        ) = {
             let chain_state = self.shared.lock_chain_state();
             let txs_verify_cache = self.shared.lock_txs_verify_cache();
             let consensus = chain_state.consensus();
             let tip_number = chain_state.tip_number();
             let epoch_number = chain_state.current_epoch_ext().number();
+            let tip_median_time = chain_state.tip_median_time();
             let mut resolved_txs = Vec::with_capacity(txs.len());
             let mut unresolvable_txs = Vec::with_capacity(txs.len());
             let mut cached_txs = Vec::with_capacity(txs.len());
             for tx in &txs {
-                if let Some(cycles) = txs_verify_cache.get(tx.hash()) {
-                    cached_txs.push((tx.hash().to_owned(), Ok(*cycles)));
-                } else {
-                    match chain_state.resolve_tx_from_pending_and_proposed(tx) {
+                // the cached transactions exceeding `max_cycles` are verified again to fail
+                // the same way as the uncached ones
+                match txs_verify_cache
+                    .get(tx.hash())
+                    .filter(|cycles| max_cycles.map_or(true, |max_cycles| **cycles <= max_cycles))
+                {
+                    Some(cycles) => cached_txs.push((tx.hash().to_owned(), Ok(*cycles))),
+                    None => match chain_state.resolve_tx_from_pending_and_proposed(tx) {
                         Ok(resolved_tx) => resolved_txs.push((tx.hash().to_owned(), resolved_tx)),
                         Err(err) => unresolvable_txs.push((
                             tx.hash().to_owned(),
                             PoolError::UnresolvableTransaction(err),
                         )),
-                    }
+                    },
                 }
             }
             (
@@ -83,6 +116,7 @@ impl<CS: ChainStore> TxPoolExecutor<CS> {
                 consensus,
                 tip_number,
                 epoch_number,
+                tip_median_time,
             )
         };
 
@@ -94,6 +128,9 @@ impl<CS: ChainStore> TxPoolExecutor<CS> {
 
         let store = Arc::clone(&self.shared.store());
         let max_block_cycles = consensus.max_block_cycles();
+        let max_cycles = max_cycles.map_or(max_block_cycles, |max_cycles| {
+            cmp::min(max_cycles, max_block_cycles)
+        });
         let block_median_time_context = StoreBlockMedianTimeContext {
             store: Arc::clone(&store),
             median_time_block_count: consensus.median_time_block_count() as u64,
@@ -108,10 +145,12 @@ impl<CS: ChainStore> TxPoolExecutor<CS> {
                     &block_median_time_context,
                     tip_number,
                     epoch_number,
+                    tip_median_time,
                     consensus.cellbase_maturity(),
+                    consensus.max_extra_witnesses(),
                     self.shared.script_config(),
                 )
-                .verify(max_block_cycles)
+                .verify(max_cycles)
                 .map(|cycles| (tx, cycles))
                 .map_err(PoolError::InvalidTx);
                 (tx_hash.to_owned(), verified_result)
@@ -170,7 +209,7 @@ impl<CS: ChainStore> TxPoolExecutor<CS> {
 mod tests {
     use super::*;
     use ckb_chain::chain::ChainService;
-    use ckb_chain_spec::consensus::Consensus;
+    use ckb_chain_spec::consensus::{Consensus, MaturityMetric};
     use ckb_core::block::BlockBuilder;
     use ckb_core::cell::UnresolvableError;
     use ckb_core::header::HeaderBuilder;
@@ -204,7 +243,7 @@ mod tests {
             .build();
         let consensus = Consensus::default()
             .set_genesis_block(block.clone())
-            .set_cellbase_maturity(0);
+            .set_cellbase_maturity(MaturityMetric::Blocks(0));
 
         let shared = SharedBuilder::<MemoryKeyValueDB>::new()
             .consensus(consensus)
@@ -255,6 +294,7 @@ mod tests {
                             None,
                         ))
                         .dep(always_success_out_point.to_owned())
+                        .witness(vec![])
                         .build()
                 })
                 .collect();
@@ -302,6 +342,7 @@ mod tests {
                         None,
                     ))
                     .dep(always_success_out_point.to_owned())
+                    .witness(vec![])
                     .build()
             })
             .collect::<Vec<_>>();
@@ -2,7 +2,7 @@ use ckb_core::block::{Block, BlockBuilder};
 use ckb_core::extras::EpochExt;
 use ckb_core::header::Header;
 use ckb_core::header::HeaderBuilder;
-use ckb_core::{capacity_bytes, BlockNumber, Capacity, Cycle, Version};
+use ckb_core::{capacity_bytes, BlockNumber, Capacity, Cycle, EpochNumber, Version};
 use ckb_pow::{Pow, PowEngine};
 use numext_fixed_hash::H256;
 use numext_fixed_uint::U256;
@@ -14,9 +14,10 @@ pub(crate) const DEFAULT_SECONDARY_EPOCH_REWARD: Capacity = capacity_bytes!(5);
 pub(crate) const MAX_UNCLE_NUM: usize = 2;
 pub(crate) const MAX_UNCLE_AGE: usize = 6;
 pub(crate) const TX_PROPOSAL_WINDOW: ProposalWindow = ProposalWindow(2, 10);
-pub(crate) const CELLBASE_MATURITY: BlockNumber = 100;
+pub(crate) const CELLBASE_MATURITY: MaturityMetric = MaturityMetric::Blocks(100);
 // TODO: should adjust this value based on CKB average block time
 pub(crate) const MEDIAN_TIME_BLOCK_COUNT: usize = 11;
+pub(crate) const MAX_EXTRA_WITNESSES: usize = 8;
 
 //TODO：find best ORPHAN_RATE_TARGET
 pub(crate) const ORPHAN_RATE_TARGET_RECIP: u64 = 20;
@@ -44,6 +45,15 @@ impl ProposalWindow {
     }
 }
 
+/// How the cellbase maturity is measured
+#[derive(Clone, PartialEq, Debug, Eq, Copy)]
+pub enum MaturityMetric {
+    /// Cellbase outputs can be spent after this many blocks since they are committed
+    Blocks(BlockNumber),
+    /// Cellbase outputs can be spent after this many epochs since they are committed
+    Epochs(EpochNumber),
+}
+
 #[derive(Clone, PartialEq, Debug)]
 pub struct Consensus {
     pub id: String,
@@ -58,9 +68,9 @@ pub struct Consensus {
     pub tx_proposal_window: ProposalWindow,
     pub pow: Pow,
     // For each input, if the referenced output transaction is cellbase,
-    // it must have at least `cellbase_maturity` confirmations;
-    // else reject this transaction.
-    pub cellbase_maturity: BlockNumber,
+    // it must have at least `cellbase_maturity` confirmations, measured in
+    // blocks or epochs; else reject this transaction.
+    pub cellbase_maturity: MaturityMetric,
     // This parameter indicates the count of past blocks used in the median time calculation
     pub median_time_block_count: usize,
     // Maximum cycles that all the scripts in all the commit transactions can take
@@ -72,6 +82,9 @@ pub struct Consensus {
     // block version number supported
     pub max_block_proposals_limit: u64,
     pub genesis_epoch_ext: EpochExt,
+    // The number of witnesses a transaction may carry beyond one per input, for the scripts
+    // which read extra witnesses
+    pub max_extra_witnesses: usize,
 }
 
 // genesis difficulty should not be zero
@@ -110,6 +123,7 @@ impl Default for Consensus {
             genesis_epoch_ext,
             block_version: BLOCK_VERSION,
             max_block_proposals_limit: MAX_BLOCK_PROPOSALS_LIMIT,
+            max_extra_witnesses: MAX_EXTRA_WITNESSES,
         }
     }
 }
@@ -152,11 +166,17 @@ impl Consensus {
     }
 
     #[must_use]
-    pub fn set_cellbase_maturity(mut self, cellbase_maturity: BlockNumber) -> Self {
+    pub fn set_cellbase_maturity(mut self, cellbase_maturity: MaturityMetric) -> Self {
         self.cellbase_maturity = cellbase_maturity;
         self
     }
 
+    #[must_use]
+    pub fn set_max_extra_witnesses(mut self, max_extra_witnesses: usize) -> Self {
+        self.max_extra_witnesses = max_extra_witnesses;
+        self
+    }
+
     pub fn set_tx_proposal_window(mut self, proposal_window: ProposalWindow) -> Self {
         self.tx_proposal_window = proposal_window;
         self
@@ -219,7 +239,7 @@ impl Consensus {
         self.pow.engine()
     }
 
-    pub fn cellbase_maturity(&self) -> BlockNumber {
+    pub fn cellbase_maturity(&self) -> MaturityMetric {
         self.cellbase_maturity
     }
 
@@ -239,6 +259,10 @@ impl Consensus {
         self.max_block_proposals_limit
     }
 
+    pub fn max_extra_witnesses(&self) -> usize {
+        self.max_extra_witnesses
+    }
+
     pub fn block_version(&self) -> Version {
         self.block_version
     }
@@ -9,14 +9,14 @@
 //! we must put nested config struct in the tail to make it serializable,
 //! details https://docs.rs/toml/0.5.0/toml/ser/index.html
 
-use crate::consensus::{Consensus, GENESIS_EPOCH_LENGTH};
+use crate::consensus::{Consensus, MaturityMetric, GENESIS_EPOCH_LENGTH, MAX_EXTRA_WITNESSES};
 use ckb_core::block::Block;
 use ckb_core::block::BlockBuilder;
 use ckb_core::extras::EpochExt;
 use ckb_core::header::HeaderBuilder;
 use ckb_core::script::Script;
 use ckb_core::transaction::{CellInput, CellOutput, Transaction, TransactionBuilder};
-use ckb_core::{BlockNumber, Bytes, Capacity, Cycle};
+use ckb_core::{BlockNumber, Bytes, Capacity, Cycle, EpochNumber};
 use ckb_pow::{Pow, PowEngine};
 use ckb_resource::{Resource, ResourceLocator};
 use numext_fixed_hash::H256;
@@ -44,6 +44,12 @@ pub struct Params {
     pub secondary_epoch_reward: Capacity,
     pub max_block_cycles: Cycle,
     pub cellbase_maturity: BlockNumber,
+    /// Measure the cellbase maturity in epochs, overrides `cellbase_maturity` if present
+    #[serde(default)]
+    pub cellbase_maturity_epochs: Option<EpochNumber>,
+    /// The number of witnesses a transaction may carry beyond one per input, 8 if absent
+    #[serde(default)]
+    pub max_extra_witnesses: Option<usize>,
 }
 
 #[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
@@ -198,14 +204,24 @@ impl ChainSpec {
             genesis_block.header().difficulty().clone() // difficulty,
         );
 
+        let cellbase_maturity = match self.params.cellbase_maturity_epochs {
+            Some(epochs) => MaturityMetric::Epochs(epochs),
+            None => MaturityMetric::Blocks(self.params.cellbase_maturity),
+        };
+
         let consensus = Consensus::default()
             .set_id(self.name.clone())
             .set_genesis_epoch_ext(genesis_epoch_ext)
             .set_genesis_block(genesis_block)
-            .set_cellbase_maturity(self.params.cellbase_maturity)
+            .set_cellbase_maturity(cellbase_maturity)
             .set_epoch_reward(self.params.epoch_reward)
             .set_secondary_epoch_reward(self.params.secondary_epoch_reward)
             .set_max_block_cycles(self.params.max_block_cycles)
+            .set_max_extra_witnesses(
+                self.params
+                    .max_extra_witnesses
+                    .unwrap_or(MAX_EXTRA_WITNESSES),
+            )
             .set_pow(self.pow.clone());
 
         Ok(consensus)
@@ -1,12 +1,12 @@
 use ckb_app_config::{ExitCode, ImportArgs};
 use ckb_chain::chain::ChainService;
-use ckb_db::{CacheDB, RocksDB};
+use ckb_db::{CacheDB, PersistentDB};
 use ckb_instrument::Import;
 use ckb_notify::NotifyService;
 use ckb_shared::shared::SharedBuilder;
 
 pub fn import(args: ImportArgs) -> Result<(), ExitCode> {
-    let shared = SharedBuilder::<CacheDB<RocksDB>>::default()
+    let shared = SharedBuilder::<CacheDB<PersistentDB>>::default()
         .consensus(args.consensus)
         .db(&args.config.db)
         .build()
@@ -2,7 +2,8 @@ use crate::helper::{deadlock_detection, wait_for_exit};
 use build_info::Version;
 use ckb_app_config::{ExitCode, RunArgs};
 use ckb_chain::chain::ChainService;
-use ckb_db::{CacheDB, RocksDB};
+use ckb_chain::prune::PruneService;
+use ckb_db::{CacheDB, PersistentDB};
 use ckb_miner::BlockAssembler;
 use ckb_network::{CKBProtocol, NetworkService, NetworkState};
 use ckb_notify::NotifyService;
@@ -18,11 +19,12 @@ use std::sync::Arc;
 pub fn run(args: RunArgs, version: Version) -> Result<(), ExitCode> {
     deadlock_detection();
 
-    let shared = SharedBuilder::<CacheDB<RocksDB>>::new()
+    let shared = SharedBuilder::<CacheDB<PersistentDB>>::new()
         .consensus(args.consensus)
         .db(&args.config.db)
         .tx_pool_config(args.config.tx_pool)
         .script_config(args.config.script)
+        .store_config(args.config.store)
         .build()
         .map_err(|err| {
             eprintln!("Run error: {:?}", err);
@@ -36,6 +38,8 @@ pub fn run(args: RunArgs, version: Version) -> Result<(), ExitCode> {
     let chain_service = ChainService::new(shared.clone(), notify.clone());
     let chain_controller = chain_service.start(Some("ChainService"));
     info!(target: "main", "chain genesis hash: {:#x}", shared.genesis_hash());
+    let _prune_controller = PruneService::new(shared.clone(), &args.config.prune)
+        .map(|prune_service| prune_service.start(Some("PruneService")));
 
     let block_assembler = BlockAssembler::new(shared.clone(), args.config.block_assembler);
     let block_assembler_controller = block_assembler.start(Some("MinerAgent"), &notify);
@@ -47,13 +51,14 @@ pub fn run(args: RunArgs, version: Version) -> Result<(), ExitCode> {
     let synchronizer = Synchronizer::new(
         chain_controller.clone(),
         Arc::clone(&sync_shared_state),
-        args.config.sync,
+        args.config.sync.clone(),
     );
 
     let relayer = Relayer::new(
         chain_controller.clone(),
         sync_shared_state,
         synchronizer.peers(),
+        args.config.sync,
     );
     let net_timer = NetTimeProtocol::default();
 
@@ -0,0 +1,365 @@
+//! The optional index of the live cells by their lock script hashes. The outputs are keyed by
+//! `lock_hash || block number || tx index || output index` so that the cells of a lock hash are
+//! iterated in the order of the chain. The index keys are also kept by the out points, so that a
+//! spent cell is removed from the index and indexed again when the block spending it is detached.
+
+use crate::{
+    ChainKVStore, ChainStore, StoreBatch, COLUMN_CELL_INDEX, COLUMN_CELL_INDEX_KEY, COLUMN_META,
+};
+use ckb_core::header::BlockNumber;
+use ckb_core::transaction::CellOutPoint;
+use ckb_db::{Col, Error, IterableKeyValueDB};
+use numext_fixed_hash::H256;
+
+const META_CELL_INDEX_KEY: &[u8] = b"CELL_INDEX";
+// The number of the index entries deleted or the blocks indexed in a batch by the rebuilding
+const REBUILD_BATCH_SIZE: usize = 10_000;
+
+pub(crate) fn cell_index_key(
+    lock_hash: &H256,
+    number: BlockNumber,
+    tx_index: u32,
+    index: u32,
+) -> Vec<u8> {
+    let mut key: [u8; 48] = [0; 48];
+    key[..32].copy_from_slice(lock_hash.as_bytes());
+    key[32..40].copy_from_slice(&number.to_be_bytes());
+    key[40..44].copy_from_slice(&tx_index.to_be_bytes());
+    key[44..48].copy_from_slice(&index.to_be_bytes());
+    key.to_vec()
+}
+
+/// A live cell found by its lock script hash
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IndexedCell {
+    pub out_point: CellOutPoint,
+    pub block_number: BlockNumber,
+    /// The position of the transaction in its block
+    pub tx_index: u32,
+}
+
+impl IndexedCell {
+    pub(crate) fn index_key(&self, lock_hash: &H256) -> Vec<u8> {
+        cell_index_key(
+            lock_hash,
+            self.block_number,
+            self.tx_index,
+            self.out_point.index,
+        )
+    }
+
+    pub(crate) fn from_index_entry(key: &[u8], value: &[u8]) -> Self {
+        let mut number = [0; 8];
+        number.copy_from_slice(&key[32..40]);
+        let mut tx_index = [0; 4];
+        tx_index.copy_from_slice(&key[40..44]);
+        let mut index = [0; 4];
+        index.copy_from_slice(&key[44..48]);
+        IndexedCell {
+            out_point: CellOutPoint {
+                tx_hash: H256::from_slice(value).expect("db safe access"),
+                index: u32::from_be_bytes(index),
+            },
+            block_number: BlockNumber::from_be_bytes(number),
+            tx_index: u32::from_be_bytes(tx_index),
+        }
+    }
+}
+
+/// A page of the live cells found by their lock script hash
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct IndexedCellsPage {
+    pub cells: Vec<IndexedCell>,
+    /// Whether more cells follow the page, the next page starts after its last cell
+    pub has_more: bool,
+}
+
+impl<T: IterableKeyValueDB> ChainKVStore<T> {
+    /// Builds the cell index when it is turned on but not built yet, and drops the built mark
+    /// when it is turned off, for the index isn't maintained meanwhile
+    pub fn init_cell_index(&self) -> Result<(), Error> {
+        let built = self.get(COLUMN_META, META_CELL_INDEX_KEY).is_some();
+        if self.cell_index() && !built {
+            self.rebuild_cell_index()?;
+        } else if !self.cell_index() && built {
+            let mut batch = self.new_batch()?;
+            batch.delete(COLUMN_META, META_CELL_INDEX_KEY)?;
+            batch.commit()?;
+        }
+        Ok(())
+    }
+
+    /// Rebuilds the cell index from the main chain. The outputs of the pruned blocks are indexed
+    /// from their pruned transactions.
+    pub fn rebuild_cell_index(&self) -> Result<(), Error> {
+        let mut batch = self.new_batch()?;
+        batch.delete(COLUMN_META, META_CELL_INDEX_KEY)?;
+        batch.commit()?;
+        self.clear_column(COLUMN_CELL_INDEX)?;
+        self.clear_column(COLUMN_CELL_INDEX_KEY)?;
+
+        // nothing to index in an empty store, the blocks attached later maintain the index
+        let tip_number = self.get_tip_header().map(|header| header.number());
+        let pruned_number = self.get_pruned_number();
+        let mut batch = self.new_batch()?;
+        for number in tip_number.map_or(1..=0, |tip_number| 0..=tip_number) {
+            let hash = self.get_block_hash(number).ok_or_else(|| {
+                Error::DBError(format!("block {} is not in the main chain", number))
+            })?;
+            if pruned_number.map_or(false, |pruned| number <= pruned) {
+                let transactions = self.get_pruned_transactions(number).unwrap_or_default();
+                batch.index_pruned_cells(number, &transactions)?;
+            } else {
+                let transactions = self.get_block_body(&hash).ok_or_else(|| {
+                    Error::DBError(format!("the body of block {} is not found", number))
+                })?;
+                batch.index_cells(number, &transactions)?;
+            }
+            if (number + 1) % REBUILD_BATCH_SIZE as BlockNumber == 0 {
+                batch.commit()?;
+                batch = self.new_batch()?;
+            }
+        }
+        batch.insert_raw(COLUMN_META, META_CELL_INDEX_KEY, &[])?;
+        batch.commit()
+    }
+
+    fn clear_column(&self, col: Col) -> Result<(), Error> {
+        loop {
+            let keys = self
+                .db()
+                .iter(col)?
+                .take(REBUILD_BATCH_SIZE)
+                .map(|item| item.map(|(key, _)| key))
+                .collect::<Result<Vec<_>, Error>>()?;
+            if keys.is_empty() {
+                return Ok(());
+            }
+            let mut batch = self.new_batch()?;
+            for key in keys {
+                batch.delete(col, &key)?;
+            }
+            batch.commit()?;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{prune_blocks, StoreConfig, COLUMNS};
+    use ckb_chain_spec::consensus::Consensus;
+    use ckb_core::block::{Block, BlockBuilder};
+    use ckb_core::header::{Header, HeaderBuilder};
+    use ckb_core::script::Script;
+    use ckb_core::transaction::{CellInput, CellOutput, OutPoint, TransactionBuilder};
+    use ckb_core::Capacity;
+    use ckb_db::MemoryKeyValueDB;
+
+    fn lock(n: u8) -> Script {
+        Script::new(vec![vec![n].into()], H256::zero())
+    }
+
+    fn new_store() -> ChainKVStore<MemoryKeyValueDB> {
+        let config = StoreConfig {
+            cell_index: true,
+            ..Default::default()
+        };
+        let store =
+            ChainKVStore::new_with_config(MemoryKeyValueDB::open(COLUMNS as usize), &config);
+        store.init(&Consensus::default()).unwrap();
+        store
+    }
+
+    // A block with a transaction of two outputs locked by `lock(1)` and `lock(2)`, spending
+    // `spent` if any
+    fn gen_block(parent: &Header, nonce: u64, spent: Option<OutPoint>) -> Block {
+        let outputs = vec![1, 2]
+            .into_iter()
+            .map(|n| CellOutput {
+                capacity: Capacity::shannons(nonce),
+                lock: lock(n),
+                ..Default::default()
+            })
+            .collect();
+        let mut builder = TransactionBuilder::default().outputs(outputs);
+        if let Some(out_point) = spent {
+            builder = builder.input(CellInput::new(out_point, 0, vec![]));
+        }
+        BlockBuilder::default()
+            .header_builder(
+                HeaderBuilder::default()
+                    .parent_hash(parent.hash().to_owned())
+                    .number(parent.number() + 1)
+                    .nonce(nonce),
+            )
+            .transaction(builder.build())
+            .build()
+    }
+
+    fn attach(store: &ChainKVStore<MemoryKeyValueDB>, block: &Block) {
+        let mut batch = store.new_batch().unwrap();
+        batch.insert_block(block).unwrap();
+        batch.attach_block(block).unwrap();
+        batch.insert_tip_header(block.header()).unwrap();
+        batch.commit().unwrap();
+    }
+
+    fn detach(store: &ChainKVStore<MemoryKeyValueDB>, block: &Block, parent: &Header) {
+        let mut batch = store.new_batch().unwrap();
+        batch.detach_block(block).unwrap();
+        batch.insert_tip_header(parent).unwrap();
+        batch.commit().unwrap();
+    }
+
+    fn out_point(block: &Block, index: u32) -> OutPoint {
+        OutPoint::new_cell(block.transactions()[0].hash().to_owned(), index)
+    }
+
+    fn cells(store: &ChainKVStore<MemoryKeyValueDB>, n: u8) -> Vec<CellOutPoint> {
+        store
+            .get_cells_by_lock_hash(&lock(n).hash(), None, usize::max_value())
+            .unwrap()
+            .cells
+            .into_iter()
+            .map(|cell| cell.out_point)
+            .collect()
+    }
+
+    fn cell_out_point(block: &Block, index: u32) -> CellOutPoint {
+        out_point(block, index).cell.unwrap()
+    }
+
+    #[test]
+    fn attach_and_detach() {
+        let store = new_store();
+        let genesis = Consensus::default().genesis_block().header().to_owned();
+        let block1 = gen_block(&genesis, 1, None);
+        let block2 = gen_block(block1.header(), 2, Some(out_point(&block1, 0)));
+        attach(&store, &block1);
+        attach(&store, &block2);
+        assert_eq!(
+            cells(&store, 1),
+            vec![cell_out_point(&block2, 0)],
+            "the spent cell is removed"
+        );
+        assert_eq!(store.db().iter(COLUMN_CELL_INDEX).unwrap().count(), 3);
+        assert_eq!(
+            cells(&store, 2),
+            vec![cell_out_point(&block1, 1), cell_out_point(&block2, 1)]
+        );
+
+        // detaching block 2 revives the cell it spent
+        detach(&store, &block2, block1.header());
+        assert_eq!(cells(&store, 1), vec![cell_out_point(&block1, 0)]);
+        assert_eq!(cells(&store, 2), vec![cell_out_point(&block1, 1)]);
+
+        // re-attaching a different block 2
+        let block2 = gen_block(block1.header(), 3, Some(out_point(&block1, 1)));
+        attach(&store, &block2);
+        assert_eq!(
+            cells(&store, 1),
+            vec![cell_out_point(&block1, 0), cell_out_point(&block2, 0)]
+        );
+        assert_eq!(cells(&store, 2), vec![cell_out_point(&block2, 1)]);
+    }
+
+    #[test]
+    fn reorg_in_one_batch() {
+        let store = new_store();
+        let genesis = Consensus::default().genesis_block().header().to_owned();
+        let block1 = gen_block(&genesis, 1, None);
+        let block2 = gen_block(block1.header(), 2, Some(out_point(&block1, 0)));
+        let block3 = gen_block(block2.header(), 3, Some(out_point(&block2, 0)));
+        attach(&store, &block1);
+        attach(&store, &block2);
+        attach(&store, &block3);
+
+        // the blocks are detached in any order, then the fork spends a cell created by itself
+        let fork2 = gen_block(block1.header(), 4, Some(out_point(&block1, 1)));
+        let fork3 = gen_block(fork2.header(), 5, Some(out_point(&fork2, 0)));
+        let mut batch = store.new_batch().unwrap();
+        batch.detach_block(&block2).unwrap();
+        batch.detach_block(&block3).unwrap();
+        batch.insert_block(&fork2).unwrap();
+        batch.attach_block(&fork2).unwrap();
+        batch.insert_block(&fork3).unwrap();
+        batch.attach_block(&fork3).unwrap();
+        batch.insert_tip_header(fork3.header()).unwrap();
+        batch.commit().unwrap();
+        assert_eq!(
+            cells(&store, 1),
+            vec![cell_out_point(&block1, 0), cell_out_point(&fork3, 0)]
+        );
+        assert_eq!(
+            cells(&store, 2),
+            vec![cell_out_point(&fork2, 1), cell_out_point(&fork3, 1)]
+        );
+        assert_eq!(store.db().iter(COLUMN_CELL_INDEX).unwrap().count(), 4);
+    }
+
+    #[test]
+    fn paging() {
+        let store = new_store();
+        let mut parent = Consensus::default().genesis_block().header().to_owned();
+        let mut blocks = Vec::new();
+        for nonce in 1..=5 {
+            let block = gen_block(&parent, nonce, None);
+            attach(&store, &block);
+            parent = block.header().to_owned();
+            blocks.push(block);
+        }
+        let lock_hash = lock(1).hash();
+        let page = store.get_cells_by_lock_hash(&lock_hash, None, 2).unwrap();
+        assert_eq!(page.cells.len(), 2);
+        assert_eq!(page.cells[1].out_point, cell_out_point(&blocks[1], 0));
+        assert_eq!(page.cells[1].block_number, 2);
+        assert!(page.has_more);
+        let page = store
+            .get_cells_by_lock_hash(&lock_hash, page.cells.last(), 2)
+            .unwrap();
+        assert!(page.has_more);
+        assert_eq!(
+            page.cells
+                .iter()
+                .map(|cell| cell.out_point.to_owned())
+                .collect::<Vec<_>>(),
+            vec![cell_out_point(&blocks[2], 0), cell_out_point(&blocks[3], 0)]
+        );
+        // the last page stops at the last cell of the lock hash
+        let page = store
+            .get_cells_by_lock_hash(&lock_hash, page.cells.last(), 2)
+            .unwrap();
+        assert_eq!(page.cells.len(), 1);
+        assert!(!page.has_more);
+        // a full page of the last cells has no more either
+        let page = store.get_cells_by_lock_hash(&lock_hash, None, 5).unwrap();
+        assert_eq!(page.cells.len(), 5);
+        assert!(!page.has_more);
+        assert_eq!(
+            store.get_cells_by_lock_hash(&lock(3).hash(), None, 2),
+            Some(IndexedCellsPage::default())
+        );
+    }
+
+    #[test]
+    fn rebuild() {
+        let store = new_store();
+        let mut parent = Consensus::default().genesis_block().header().to_owned();
+        let mut spent = None;
+        for nonce in 1..=5 {
+            let block = gen_block(&parent, nonce, spent);
+            attach(&store, &block);
+            spent = Some(out_point(&block, 0));
+            parent = block.header().to_owned();
+        }
+        prune_blocks(&store, 2).unwrap();
+        let expected = (cells(&store, 1), cells(&store, 2));
+
+        // the index maintained by the attached blocks isn't marked built
+        assert!(store.get(COLUMN_META, META_CELL_INDEX_KEY).is_none());
+        store.init_cell_index().unwrap();
+        assert_eq!((cells(&store, 1), cells(&store, 2)), expected);
+        assert!(store.get(COLUMN_META, META_CELL_INDEX_KEY).is_some());
+    }
+}
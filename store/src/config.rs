@@ -0,0 +1,119 @@
+use crate::{
+    COLUMN_BLOCK_BODY, COLUMN_BLOCK_EPOCH, COLUMN_BLOCK_HEADER, COLUMN_BLOCK_PROPOSAL_IDS,
+    COLUMN_BLOCK_TRANSACTION_ADDRESSES, COLUMN_BLOCK_UNCLE, COLUMN_CELL_INDEX,
+    COLUMN_CELL_INDEX_KEY, COLUMN_CELL_META, COLUMN_EPOCH, COLUMN_EXT, COLUMN_INDEX, COLUMN_META,
+    COLUMN_PRUNED_CELL_OUTPUT, COLUMN_PRUNED_TRANSACTIONS, COLUMN_TRANSACTION_ADDR,
+    COLUMN_TRANSACTION_INFO,
+};
+use ckb_db::{CachePolicy, Col};
+use serde_derive::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+// The names of the columns in the cache config
+const COLUMN_NAMES: &[(&str, Col)] = &[
+    ("index", COLUMN_INDEX),
+    ("block_header", COLUMN_BLOCK_HEADER),
+    ("block_body", COLUMN_BLOCK_BODY),
+    ("block_uncle", COLUMN_BLOCK_UNCLE),
+    ("meta", COLUMN_META),
+    ("transaction_addr", COLUMN_TRANSACTION_ADDR),
+    ("ext", COLUMN_EXT),
+    (
+        "block_transaction_addresses",
+        COLUMN_BLOCK_TRANSACTION_ADDRESSES,
+    ),
+    ("block_proposal_ids", COLUMN_BLOCK_PROPOSAL_IDS),
+    ("cell_meta", COLUMN_CELL_META),
+    ("block_epoch", COLUMN_BLOCK_EPOCH),
+    ("epoch", COLUMN_EPOCH),
+    ("pruned_transactions", COLUMN_PRUNED_TRANSACTIONS),
+    ("cell_index", COLUMN_CELL_INDEX),
+    ("cell_index_key", COLUMN_CELL_INDEX_KEY),
+    ("transaction_info", COLUMN_TRANSACTION_INFO),
+    ("pruned_cell_output", COLUMN_PRUNED_CELL_OUTPUT),
+];
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct StoreConfig {
+    /// Index the cells by their lock script hashes, the index is built on start when turned on
+    #[serde(default)]
+    pub cell_index: bool,
+    /// The cache policies by column names, overriding the defaults
+    #[serde(default)]
+    pub cache: HashMap<String, CachePolicy>,
+}
+
+impl StoreConfig {
+    /// The cache policies overridden by the config, by columns
+    pub fn cache_policies(&self) -> Result<HashMap<Col, CachePolicy>, String> {
+        self.cache
+            .iter()
+            .map(|(name, policy)| {
+                let col = COLUMN_NAMES
+                    .iter()
+                    .find(|(column_name, _)| column_name == name)
+                    .map(|(_, col)| *col)
+                    .ok_or_else(|| format!("unknown column {} in the store cache config", name))?;
+                if policy.enabled && policy.capacity == 0 {
+                    return Err(format!(
+                        "the cache capacity of column {} must be positive while it is enabled",
+                        name
+                    ));
+                }
+                Ok((col, *policy))
+            })
+            .collect()
+    }
+}
+
+/// The headers, the cell metas and the epochs are small and read again and again, they are
+/// cached. The block bodies are large and mostly read once, caching them would evict the others.
+pub fn default_cache_policies() -> HashMap<Col, CachePolicy> {
+    let mut policies = HashMap::new();
+    policies.insert(COLUMN_BLOCK_HEADER, CachePolicy::enabled(4096));
+    policies.insert(COLUMN_CELL_META, CachePolicy::enabled(4096));
+    policies.insert(COLUMN_BLOCK_EPOCH, CachePolicy::enabled(4096));
+    policies.insert(COLUMN_EPOCH, CachePolicy::enabled(64));
+    policies.insert(COLUMN_BLOCK_BODY, CachePolicy::disabled());
+    policies
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cache_policies_by_names() {
+        let mut config = StoreConfig::default();
+        config
+            .cache
+            .insert("block_body".to_owned(), CachePolicy::enabled(16));
+        config
+            .cache
+            .insert("block_header".to_owned(), CachePolicy::disabled());
+        let policies = config.cache_policies().unwrap();
+        assert_eq!(policies.len(), 2);
+        assert_eq!(policies[&COLUMN_BLOCK_BODY], CachePolicy::enabled(16));
+        assert_eq!(policies[&COLUMN_BLOCK_HEADER], CachePolicy::disabled());
+
+        config
+            .cache
+            .insert("unknown".to_owned(), CachePolicy::disabled());
+        assert!(config.cache_policies().is_err());
+    }
+
+    #[test]
+    fn reject_empty_enabled_cache() {
+        let mut config = StoreConfig::default();
+        config
+            .cache
+            .insert("block_header".to_owned(), CachePolicy::enabled(0));
+        assert_eq!(
+            config.cache_policies(),
+            Err(
+                "the cache capacity of column block_header must be positive while it is enabled"
+                    .to_owned()
+            )
+        );
+    }
+}
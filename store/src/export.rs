@@ -0,0 +1,416 @@
+//! Streams the columns of the store to a portable file and back, to bootstrap a node from a
+//! trusted snapshot without copying the database directory.
+//!
+//! The file starts with a header: the magic bytes, the format version and the genesis hash of
+//! the chain. Then each column is a run of records, a record being the column, the key and the
+//! value, the lengths prefixed as 32-bit little endian integers. A column ends with its record
+//! count, the stream ends with a terminating column.
+//!
+//! The columns are read from a snapshot of the database, a store written to meanwhile is
+//! exported as it is when the export starts.
+
+use crate::{ChainKVStore, ChainStore, COLUMN_META};
+use bincode::{deserialize, serialize};
+use ckb_db::{Col, DbBatch, Error, IterableKeyValueDB, KeyValueDB};
+use numext_fixed_hash::H256;
+use serde_derive::{Deserialize, Serialize};
+use std::io::{self, Read, Write};
+
+const MAGIC: &[u8] = b"CKBX";
+pub const EXPORT_FORMAT_VERSION: u32 = 1;
+// The imported records are committed by batches of this size
+pub const IMPORT_BATCH_SIZE: u64 = 10_000;
+// Ends a column in place of a key length, followed by the record count of the column
+const END_OF_COLUMN: u32 = u32::max_value();
+// Ends the stream in place of a column
+const END_OF_STREAM: Col = u32::max_value();
+// The longest key or value, a longer length read back means the stream is corrupted
+const MAX_RECORD_LEN: usize = 64 * 1024 * 1024;
+// The progress of the import, kept in the meta column until the import completes
+const META_IMPORT_STATE_KEY: &[u8] = b"IMPORT_STATE";
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ExportStats {
+    pub columns: usize,
+    pub records: u64,
+    /// The size of the exported stream, including the header
+    pub bytes: u64,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ImportStats {
+    pub columns: usize,
+    pub records: u64,
+    /// The records of the columns completed by a previous attempt, not imported again
+    pub skipped: u64,
+}
+
+// A column is complete only once all its records are committed, the column being imported when
+// the import fails is imported again by the next attempt
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct ImportState {
+    genesis_hash: H256,
+    completed: Vec<Col>,
+    in_progress: Option<Col>,
+}
+
+fn io_error(err: io::Error) -> Error {
+    if err.kind() == io::ErrorKind::UnexpectedEof {
+        Error::DBError("the export stream is truncated".to_owned())
+    } else {
+        Error::DBError(format!("failed to access the export stream: {}", err))
+    }
+}
+
+struct Encoder<W> {
+    writer: W,
+    bytes: u64,
+}
+
+impl<W: Write> Encoder<W> {
+    fn write(&mut self, data: &[u8]) -> Result<(), Error> {
+        self.writer.write_all(data).map_err(io_error)?;
+        self.bytes += data.len() as u64;
+        Ok(())
+    }
+
+    fn write_u32(&mut self, n: u32) -> Result<(), Error> {
+        self.write(&n.to_le_bytes())
+    }
+
+    fn write_u64(&mut self, n: u64) -> Result<(), Error> {
+        self.write(&n.to_le_bytes())
+    }
+
+    fn write_prefixed(&mut self, data: &[u8]) -> Result<(), Error> {
+        if data.len() > MAX_RECORD_LEN {
+            return Err(Error::DBError(format!(
+                "can not export a key or value of {} bytes",
+                data.len()
+            )));
+        }
+        self.write_u32(data.len() as u32)?;
+        self.write(data)
+    }
+}
+
+struct Decoder<R> {
+    reader: R,
+}
+
+impl<R: Read> Decoder<R> {
+    // The buffer grows with the data actually read, a corrupted length does not allocate it all
+    fn read(&mut self, len: usize) -> Result<Vec<u8>, Error> {
+        if len > MAX_RECORD_LEN {
+            return Err(Error::DBError(format!(
+                "a key or value of {} bytes in the export stream, at most {} allowed",
+                len, MAX_RECORD_LEN
+            )));
+        }
+        let mut data = Vec::new();
+        self.reader
+            .by_ref()
+            .take(len as u64)
+            .read_to_end(&mut data)
+            .map_err(io_error)?;
+        if data.len() != len {
+            return Err(io_error(io::ErrorKind::UnexpectedEof.into()));
+        }
+        Ok(data)
+    }
+
+    fn read_u32(&mut self) -> Result<u32, Error> {
+        let mut data = [0; 4];
+        self.reader.read_exact(&mut data).map_err(io_error)?;
+        Ok(u32::from_le_bytes(data))
+    }
+
+    fn read_u64(&mut self) -> Result<u64, Error> {
+        let mut data = [0; 8];
+        self.reader.read_exact(&mut data).map_err(io_error)?;
+        Ok(u64::from_le_bytes(data))
+    }
+}
+
+impl<T: IterableKeyValueDB> ChainKVStore<T> {
+    /// Writes the columns to `writer`, in the given order, as of the time the export starts
+    pub fn export<W: Write>(&self, writer: W, columns: &[Col]) -> Result<ExportStats, Error> {
+        if self.get(COLUMN_META, META_IMPORT_STATE_KEY).is_some() {
+            return Err(Error::DBError(
+                "the store is not completely imported, can not export it".to_owned(),
+            ));
+        }
+        let genesis_hash = self
+            .get_block_hash(0)
+            .ok_or_else(|| Error::DBError("the genesis block is not found".to_owned()))?;
+
+        let mut encoder = Encoder { writer, bytes: 0 };
+        encoder.write(MAGIC)?;
+        encoder.write_u32(EXPORT_FORMAT_VERSION)?;
+        encoder.write(genesis_hash.as_bytes())?;
+
+        let snapshot = self.db().snapshot()?;
+        let mut stats = ExportStats::default();
+        for col in columns {
+            let mut records = 0;
+            encoder.write_u32(*col)?;
+            for item in snapshot.iter(*col)? {
+                let (key, value) = item?;
+                encoder.write_prefixed(&key)?;
+                encoder.write_prefixed(&value)?;
+                records += 1;
+            }
+            encoder.write_u32(END_OF_COLUMN)?;
+            encoder.write_u64(records)?;
+            stats.columns += 1;
+            stats.records += records;
+        }
+        encoder.write_u32(END_OF_STREAM)?;
+        encoder.writer.flush().map_err(io_error)?;
+        stats.bytes = encoder.bytes;
+        Ok(stats)
+    }
+}
+
+fn save_import_state<B: DbBatch>(batch: &mut B, state: &ImportState) -> Result<(), Error> {
+    batch.insert(
+        COLUMN_META,
+        META_IMPORT_STATE_KEY,
+        &serialize(state).expect("serializing should be ok"),
+    )
+}
+
+/// Reads the columns exported from the chain of `genesis_hash` into `db`, committed by batches
+/// of `IMPORT_BATCH_SIZE` records. An import failing midway is resumed by importing the same
+/// stream again, the columns completed already are skipped.
+pub fn import<T: KeyValueDB, R: Read>(
+    db: &T,
+    reader: R,
+    genesis_hash: &H256,
+) -> Result<ImportStats, Error> {
+    let mut decoder = Decoder { reader };
+    if decoder.read(MAGIC.len())? != MAGIC {
+        return Err(Error::DBError("not an export stream".to_owned()));
+    }
+    let version = decoder.read_u32()?;
+    if version != EXPORT_FORMAT_VERSION {
+        return Err(Error::DBError(format!(
+            "unsupported export format version {}, require {}",
+            version, EXPORT_FORMAT_VERSION
+        )));
+    }
+    let exported_genesis_hash = H256::from_slice(&decoder.read(32)?).expect("db safe access");
+    if exported_genesis_hash != *genesis_hash {
+        return Err(Error::DBError(format!(
+            "the export stream is of the chain of genesis {:#x}, require {:#x}",
+            exported_genesis_hash, genesis_hash
+        )));
+    }
+
+    let mut state = match db.read(COLUMN_META, META_IMPORT_STATE_KEY)? {
+        Some(raw) => {
+            let state: ImportState =
+                deserialize(&raw[..]).expect("deserialize import state should be ok");
+            if state.genesis_hash != *genesis_hash {
+                return Err(Error::DBError(
+                    "an import of another chain is in progress".to_owned(),
+                ));
+            }
+            state
+        }
+        None => ImportState {
+            genesis_hash: genesis_hash.to_owned(),
+            ..Default::default()
+        },
+    };
+
+    let mut stats = ImportStats::default();
+    loop {
+        let col = decoder.read_u32()?;
+        if col == END_OF_STREAM {
+            break;
+        }
+        let skipped = state.completed.contains(&col);
+        let mut batch = db.batch()?;
+        if !skipped {
+            state.in_progress = Some(col);
+            save_import_state(&mut batch, &state)?;
+        }
+
+        let (mut records, mut pending) = (0, 0);
+        loop {
+            let key_len = decoder.read_u32()?;
+            if key_len == END_OF_COLUMN {
+                let count = decoder.read_u64()?;
+                if count != records {
+                    return Err(Error::DBError(format!(
+                        "column {} has {} records, but {} exported",
+                        col, records, count
+                    )));
+                }
+                break;
+            }
+            let key = decoder.read(key_len as usize)?;
+            let value_len = decoder.read_u32()?;
+            let value = decoder.read(value_len as usize)?;
+            records += 1;
+            if skipped {
+                continue;
+            }
+            batch.insert(col, &key, &value)?;
+            pending += 1;
+            if pending == IMPORT_BATCH_SIZE {
+                batch.commit()?;
+                batch = db.batch()?;
+                pending = 0;
+            }
+        }
+
+        if skipped {
+            stats.skipped += records;
+        } else {
+            // the column is marked as complete along with its last records
+            state.completed.push(col);
+            state.in_progress = None;
+            save_import_state(&mut batch, &state)?;
+            batch.commit()?;
+            stats.columns += 1;
+            stats.records += records;
+        }
+    }
+
+    let mut batch = db.batch()?;
+    batch.delete(COLUMN_META, META_IMPORT_STATE_KEY)?;
+    batch.commit()?;
+    Ok(stats)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{StoreBatch, COLUMNS};
+    use ckb_chain_spec::consensus::Consensus;
+    use ckb_core::block::BlockBuilder;
+    use ckb_core::transaction::{CellOutput, TransactionBuilder};
+    use ckb_db::{DBConfig, MemoryKeyValueDB, RocksDB};
+    use numext_fixed_hash::h256;
+    use tempfile;
+
+    fn setup_store(prefix: &str) -> (ChainKVStore<RocksDB>, H256) {
+        let tmp_dir = tempfile::Builder::new().prefix(prefix).tempdir().unwrap();
+        let config = DBConfig {
+            path: tmp_dir.as_ref().to_path_buf(),
+            ..Default::default()
+        };
+        let store = ChainKVStore::new(RocksDB::open(&config, COLUMNS));
+        let consensus = Consensus::default();
+        store.init(&consensus).unwrap();
+
+        let block = BlockBuilder::default()
+            .transaction(
+                TransactionBuilder::default()
+                    .outputs(vec![CellOutput::default(); 3])
+                    .build(),
+            )
+            .build();
+        let mut batch = store.new_batch().unwrap();
+        batch.insert_block(&block).unwrap();
+        batch.attach_block(&block).unwrap();
+        batch.commit().unwrap();
+        (store, consensus.genesis_block().header().hash().to_owned())
+    }
+
+    fn contents<T: IterableKeyValueDB>(db: &T) -> Vec<Vec<(Vec<u8>, Vec<u8>)>> {
+        (0..COLUMNS)
+            .map(|col| db.iter(col).unwrap().collect::<Result<_, _>>().unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn export_and_import() {
+        let (store, genesis_hash) = setup_store("export_and_import");
+        let columns = (0..COLUMNS).collect::<Vec<_>>();
+        let mut exported = Vec::new();
+        let export_stats = store.export(&mut exported, &columns).unwrap();
+        assert_eq!(export_stats.columns, COLUMNS as usize);
+        assert_eq!(export_stats.bytes, exported.len() as u64);
+
+        let db = MemoryKeyValueDB::open(COLUMNS as usize);
+        let import_stats = import(&db, &exported[..], &genesis_hash).unwrap();
+        assert_eq!(import_stats.columns, export_stats.columns);
+        assert_eq!(import_stats.records, export_stats.records);
+        assert_eq!(contents(&db), contents(store.db()));
+    }
+
+    #[test]
+    fn import_other_chain() {
+        let (store, _) = setup_store("import_other_chain");
+        let mut exported = Vec::new();
+        store.export(&mut exported, &[COLUMN_META]).unwrap();
+
+        let db = MemoryKeyValueDB::open(COLUMNS as usize);
+        assert!(import(&db, &exported[..], &h256!("0x1")).is_err());
+        assert!(contents(&db).iter().all(Vec::is_empty));
+    }
+
+    #[test]
+    fn import_oversized_record() {
+        let (_, genesis_hash) = setup_store("import_oversized_record");
+        let mut stream = MAGIC.to_vec();
+        stream.extend_from_slice(&EXPORT_FORMAT_VERSION.to_le_bytes());
+        stream.extend_from_slice(genesis_hash.as_bytes());
+        stream.extend_from_slice(&COLUMN_META.to_le_bytes());
+        // a corrupted key length, far beyond the data left
+        stream.extend_from_slice(&(u32::max_value() - 1).to_le_bytes());
+        stream.extend_from_slice(&[0; 16]);
+
+        let db = MemoryKeyValueDB::open(COLUMNS as usize);
+        match import(&db, &stream[..], &genesis_hash) {
+            Err(Error::DBError(message)) => assert!(message.contains("at most")),
+            result => panic!("unexpected result {:?}", result),
+        }
+        // within the bound, but longer than the stream
+        let len = stream.len();
+        stream[len - 20..len - 16].copy_from_slice(&32u32.to_le_bytes());
+        assert_eq!(
+            import(&db, &stream[..], &genesis_hash),
+            Err(Error::DBError("the export stream is truncated".to_owned()))
+        );
+    }
+
+    #[test]
+    fn resume_truncated_import() {
+        let (store, genesis_hash) = setup_store("resume_truncated_import");
+        let columns = (0..COLUMNS).collect::<Vec<_>>();
+        let mut exported = Vec::new();
+        let export_stats = store.export(&mut exported, &columns).unwrap();
+
+        let db = MemoryKeyValueDB::open(COLUMNS as usize);
+        let truncated = &exported[..exported.len() / 2];
+        assert!(import(&db, truncated, &genesis_hash).is_err());
+        // the column being imported is not complete
+        let state: ImportState = deserialize(
+            &db.read(COLUMN_META, META_IMPORT_STATE_KEY)
+                .unwrap()
+                .unwrap(),
+        )
+        .unwrap();
+        let in_progress = state.in_progress.expect("a column in progress");
+        assert!(!state.completed.contains(&in_progress));
+        // and the store can not be exported as it is
+        let imported = ChainKVStore::new(db);
+        assert!(imported.export(&mut Vec::new(), &columns).is_err());
+
+        let db = imported.db();
+        let import_stats = import(db, &exported[..], &genesis_hash).unwrap();
+        assert_eq!(
+            import_stats.columns,
+            COLUMNS as usize - state.completed.len()
+        );
+        assert_eq!(
+            import_stats.records + import_stats.skipped,
+            export_stats.records
+        );
+        assert_eq!(contents(db), contents(store.db()));
+    }
+}
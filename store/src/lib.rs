@@ -1,13 +1,23 @@
+mod cell_index;
+mod config;
+mod export;
 mod flat_block_body;
 mod lazy_load_cell_output;
+mod prune;
+mod read_only;
 mod store;
 
+pub use cell_index::{IndexedCell, IndexedCellsPage};
+pub use config::{default_cache_policies, StoreConfig};
+pub use export::{import, ExportStats, ImportStats, EXPORT_FORMAT_VERSION, IMPORT_BATCH_SIZE};
 pub use lazy_load_cell_output::LazyLoadCellOutput;
+pub use prune::{prune_blocks, Prunable, PrunedTransaction};
+pub use read_only::ReadOnlyChainStore;
 pub use store::{ChainKVStore, ChainStore, DefaultStoreBatch, StoreBatch};
 
 use ckb_db::Col;
 
-pub const COLUMNS: u32 = 12;
+pub const COLUMNS: u32 = 17;
 pub const COLUMN_INDEX: Col = 0;
 pub const COLUMN_BLOCK_HEADER: Col = 1;
 pub const COLUMN_BLOCK_BODY: Col = 2;
@@ -20,3 +30,10 @@ pub const COLUMN_BLOCK_PROPOSAL_IDS: Col = 8;
 pub const COLUMN_CELL_META: Col = 9;
 pub const COLUMN_BLOCK_EPOCH: Col = 10;
 pub const COLUMN_EPOCH: Col = 11;
+pub const COLUMN_PRUNED_TRANSACTIONS: Col = 12;
+pub const COLUMN_CELL_INDEX: Col = 13;
+// The cell index keys by the out points, to find the index entries of the spent cells
+pub const COLUMN_CELL_INDEX_KEY: Col = 14;
+pub const COLUMN_TRANSACTION_INFO: Col = 15;
+// The live outputs of the pruned transactions by the out points
+pub const COLUMN_PRUNED_CELL_OUTPUT: Col = 16;
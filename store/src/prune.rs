@@ -0,0 +1,247 @@
+//! Pruning removes the bodies, the uncles and the transaction addresses of the main chain blocks
+//! buried deep enough. The headers, the block exts, the transaction infos and the cell metas are
+//! kept, and so are the outputs of the pruned transactions which are still live, to rebuild the
+//! cell set and verify the new blocks spending them.
+
+use crate::{ChainStore, StoreBatch};
+use ckb_core::header::BlockNumber;
+use ckb_core::transaction::CellOutput;
+use ckb_db::Error;
+use numext_fixed_hash::H256;
+use serde_derive::{Deserialize, Serialize};
+use std::collections::hash_map::{Entry, HashMap};
+
+/// The data of a block read from a store which may have pruned it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Prunable<T> {
+    Found(T),
+    /// The header is kept, but the data was pruned
+    Pruned,
+    NotFound,
+}
+
+impl<T> Prunable<T> {
+    pub fn found(self) -> Option<T> {
+        match self {
+            Prunable::Found(data) => Some(data),
+            _ => None,
+        }
+    }
+
+    pub fn is_pruned(&self) -> bool {
+        match self {
+            Prunable::Pruned => true,
+            _ => false,
+        }
+    }
+}
+
+/// A transaction of a pruned block, the outputs dead by the pruned number are `None`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PrunedTransaction {
+    pub hash: H256,
+    /// The position of the transaction in its block
+    pub index: u32,
+    pub cellbase: bool,
+    pub outputs: Vec<Option<CellOutput>>,
+}
+
+impl PrunedTransaction {
+    pub fn is_dead(&self) -> bool {
+        self.outputs.iter().all(Option::is_none)
+    }
+
+    fn mark_dead(&mut self, index: u32) {
+        if let Some(output) = self.outputs.get_mut(index as usize) {
+            *output = None;
+        }
+    }
+}
+
+/// Prunes the main chain blocks up to `number`, one batch per block so that an interrupted
+/// pruning resumes where it stopped. Returns the number of blocks pruned.
+pub fn prune_blocks<CS: ChainStore>(store: &CS, number: BlockNumber) -> Result<u64, Error> {
+    let start = store.get_pruned_number().map_or(0, |pruned| pruned + 1);
+    for n in start..=number {
+        prune_block(store, n)?;
+    }
+    Ok((number + 1).saturating_sub(start))
+}
+
+fn prune_block<CS: ChainStore>(store: &CS, number: BlockNumber) -> Result<(), Error> {
+    let hash = store
+        .get_block_hash(number)
+        .ok_or_else(|| Error::DBError(format!("block {} is not in the main chain", number)))?;
+    let transactions = store
+        .get_block_body(&hash)
+        .ok_or_else(|| Error::DBError(format!("the body of block {} is not found", number)))?;
+
+    // the earlier pruned transactions the block spends, by their block numbers
+    let mut spent: HashMap<BlockNumber, Vec<PrunedTransaction>> = HashMap::default();
+    let mut pruned: Vec<PrunedTransaction> = Vec::with_capacity(transactions.len());
+    for (index, tx) in transactions.iter().enumerate() {
+        for cell in tx.input_pts_iter().filter_map(|input| input.cell.as_ref()) {
+            if let Some(pruned_tx) = pruned
+                .iter_mut()
+                .find(|pruned_tx| pruned_tx.hash == cell.tx_hash)
+            {
+                pruned_tx.mark_dead(cell.index);
+                continue;
+            }
+            let block_number = match store
+                .get_cell_meta(&cell.tx_hash, cell.index)
+                .and_then(|cell_meta| cell_meta.block_info)
+            {
+                Some(block_info) => block_info.number,
+                None => continue,
+            };
+            let pruned_txs = match spent.entry(block_number) {
+                Entry::Occupied(entry) => entry.into_mut(),
+                Entry::Vacant(entry) => entry.insert(
+                    store
+                        .get_pruned_transactions(block_number)
+                        .unwrap_or_default(),
+                ),
+            };
+            if let Some(pruned_tx) = pruned_txs
+                .iter_mut()
+                .find(|pruned_tx| pruned_tx.hash == cell.tx_hash)
+            {
+                pruned_tx.mark_dead(cell.index);
+            }
+        }
+        pruned.push(PrunedTransaction {
+            hash: tx.hash().to_owned(),
+            index: index as u32,
+            cellbase: tx.is_cellbase(),
+            outputs: tx.outputs().iter().cloned().map(Some).collect(),
+        });
+    }
+
+    let mut batch = store.new_batch()?;
+    for (block_number, pruned_txs) in spent {
+        batch.insert_pruned_transactions(block_number, &pruned_txs)?;
+    }
+    batch.insert_pruned_transactions(number, &pruned)?;
+    batch.prune_block(&hash, &transactions)?;
+    batch.insert_pruned_number(number)?;
+    batch.commit()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store::cell_store_key;
+    use crate::{ChainKVStore, COLUMNS, COLUMN_PRUNED_CELL_OUTPUT};
+    use ckb_chain_spec::consensus::Consensus;
+    use ckb_core::block::{Block, BlockBuilder};
+    use ckb_core::header::HeaderBuilder;
+    use ckb_core::transaction::{CellInput, OutPoint, TransactionBuilder};
+    use ckb_core::Capacity;
+    use ckb_db::MemoryKeyValueDB;
+
+    // Builds a chain on the genesis, the transaction of each block spending the first output of
+    // the transaction of the previous block
+    fn setup_chain(len: u64) -> (ChainKVStore<MemoryKeyValueDB>, Vec<Block>) {
+        let store = ChainKVStore::new(MemoryKeyValueDB::open(COLUMNS as usize));
+        let consensus = Consensus::default();
+        store.init(&consensus).unwrap();
+
+        let mut parent = consensus.genesis_block().header().to_owned();
+        let mut blocks: Vec<Block> = Vec::new();
+        for number in 1..=len {
+            let output = CellOutput {
+                capacity: Capacity::shannons(number),
+                ..Default::default()
+            };
+            let mut builder = TransactionBuilder::default().outputs(vec![output; 2]);
+            if let Some(block) = blocks.last() {
+                builder = builder.input(CellInput::new(
+                    OutPoint::new_cell(block.transactions()[0].hash().to_owned(), 0),
+                    0,
+                    vec![],
+                ));
+            }
+            let block = BlockBuilder::default()
+                .header_builder(
+                    HeaderBuilder::default()
+                        .parent_hash(parent.hash().to_owned())
+                        .number(number),
+                )
+                .transaction(builder.build())
+                .build();
+            let mut batch = store.new_batch().unwrap();
+            batch.insert_block(&block).unwrap();
+            batch.attach_block(&block).unwrap();
+            batch.insert_tip_header(block.header()).unwrap();
+            batch.commit().unwrap();
+            parent = block.header().to_owned();
+            blocks.push(block);
+        }
+        (store, blocks)
+    }
+
+    #[test]
+    fn prune_and_read() {
+        let (store, blocks) = setup_chain(5);
+        assert_eq!(prune_blocks(&store, 3).unwrap(), 4);
+        assert_eq!(store.get_pruned_number(), Some(3));
+
+        let pruned = &blocks[2];
+        let pruned_hash = pruned.header().hash();
+        assert!(store.read_block(&pruned_hash).is_pruned());
+        assert_eq!(store.get_block(&pruned_hash), None);
+        assert_eq!(
+            store.get_header(&pruned_hash),
+            Some(pruned.header().to_owned())
+        );
+        let kept_hash = blocks[3].header().hash();
+        assert_eq!(
+            store.read_block(&kept_hash),
+            Prunable::Found(blocks[3].to_owned())
+        );
+        assert_eq!(store.read_block(&H256::zero()), Prunable::NotFound);
+
+        // resumes from the pruned number
+        assert_eq!(prune_blocks(&store, 3).unwrap(), 0);
+        assert_eq!(prune_blocks(&store, 4).unwrap(), 1);
+    }
+
+    #[test]
+    fn keep_live_outputs() {
+        let (store, blocks) = setup_chain(5);
+        prune_blocks(&store, 3).unwrap();
+
+        // the first output of block 2 is spent by block 3, the second is live
+        let tx = &blocks[1].transactions()[0];
+        assert_eq!(store.get_transaction(tx.hash()), None);
+        assert_eq!(store.get_cell_output(tx.hash(), 0), None);
+        assert_eq!(
+            store.get_cell_output(tx.hash(), 1),
+            Some(tx.outputs()[1].to_owned())
+        );
+        let pruned_txs = store.get_pruned_transactions(2).unwrap();
+        assert_eq!(pruned_txs.len(), 1);
+        assert_eq!(
+            pruned_txs[0].outputs,
+            vec![None, Some(tx.outputs()[1].to_owned())]
+        );
+
+        // the first output of block 3 is dead once block 4 spending it is pruned
+        let tx = &blocks[2].transactions()[0];
+        assert_eq!(
+            store.get_cell_output(tx.hash(), 0),
+            Some(tx.outputs()[0].to_owned())
+        );
+        prune_blocks(&store, 4).unwrap();
+        assert_eq!(store.get_cell_output(tx.hash(), 0), None);
+        assert_eq!(
+            store.get(COLUMN_PRUNED_CELL_OUTPUT, &cell_store_key(tx.hash(), 0)),
+            None
+        );
+        assert_eq!(
+            store.get_cell_output(tx.hash(), 1),
+            Some(tx.outputs()[1].to_owned())
+        );
+    }
+}
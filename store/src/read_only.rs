@@ -0,0 +1,180 @@
+use crate::{
+    ChainKVStore, ChainStore, IndexedCell, IndexedCellsPage, Prunable, PrunedTransaction,
+    StoreConfig,
+};
+use ckb_core::block::Block;
+use ckb_core::cell::CellMeta;
+use ckb_core::extras::{BlockExt, EpochExt, TransactionAddress, TransactionInfo};
+use ckb_core::header::{BlockNumber, Header};
+use ckb_core::transaction::{CellOutPoint, CellOutput, ProposalShortId, Transaction};
+use ckb_core::uncle::UncleBlock;
+use ckb_core::EpochNumber;
+use ckb_db::{Col, DBSnapshot, DbBatch, Error, IterableKeyValueDB, KeyValueDB, ReadOnlyDB, Result};
+use numext_fixed_hash::H256;
+use std::ops::{Bound, Range};
+use std::sync::Arc;
+
+// Lets `ChainKVStore` read through a `ReadOnlyDB`, the batch of it can't be made
+struct ReadOnlyBackend<T>(ReadOnlyDB<T>);
+
+enum NoBatch {}
+
+impl DbBatch for NoBatch {
+    fn insert(&mut self, _col: Col, _key: &[u8], _value: &[u8]) -> Result<()> {
+        match *self {}
+    }
+
+    fn delete(&mut self, _col: Col, _key: &[u8]) -> Result<()> {
+        match *self {}
+    }
+
+    fn get(&self, _col: Col, _key: &[u8]) -> Result<Option<Vec<u8>>> {
+        match *self {}
+    }
+
+    fn commit(self) -> Result<()> {
+        match self {}
+    }
+}
+
+impl<T: IterableKeyValueDB> KeyValueDB for ReadOnlyBackend<T> {
+    type Batch = NoBatch;
+
+    fn read(&self, col: Col, key: &[u8]) -> Result<Option<Vec<u8>>> {
+        self.0.read(col, key)
+    }
+
+    fn partial_read(&self, col: Col, key: &[u8], range: &Range<usize>) -> Result<Option<Vec<u8>>> {
+        self.0.partial_read(col, key, range)
+    }
+
+    fn read_multi(&self, col: Col, keys: &[&[u8]]) -> Result<Vec<Option<Vec<u8>>>> {
+        self.0.read_multi(col, keys)
+    }
+
+    fn batch(&self) -> Result<Self::Batch> {
+        Err(Error::DBError("the database is read-only".to_owned()))
+    }
+}
+
+impl<T: IterableKeyValueDB> IterableKeyValueDB for ReadOnlyBackend<T> {
+    fn seek<'a>(
+        &'a self,
+        col: Col,
+        from: Bound<&[u8]>,
+    ) -> Result<Box<Iterator<Item = Result<(Vec<u8>, Vec<u8>)>> + 'a>> {
+        self.0.seek(col, from)
+    }
+
+    fn snapshot<'a>(&'a self) -> Result<Box<DBSnapshot + 'a>> {
+        self.0.snapshot()
+    }
+}
+
+/// The getters of `ChainStore` over a `ReadOnlyDB`, without any way to write the chain
+pub struct ReadOnlyChainStore<T> {
+    inner: ChainKVStore<ReadOnlyBackend<T>>,
+}
+
+macro_rules! forward {
+    ($(fn $name:ident(&self $(, $arg:ident: $ty:ty)*) -> $ret:ty;)*) => {
+        $(
+            pub fn $name(&self $(, $arg: $ty)*) -> $ret {
+                self.inner.$name($($arg),*)
+            }
+        )*
+    };
+}
+
+impl<T: IterableKeyValueDB> ReadOnlyChainStore<T> {
+    pub fn new(db: ReadOnlyDB<T>) -> Self {
+        ReadOnlyChainStore {
+            inner: ChainKVStore::new(ReadOnlyBackend(db)),
+        }
+    }
+
+    pub fn new_with_config(db: ReadOnlyDB<T>, config: &StoreConfig) -> Self {
+        ReadOnlyChainStore {
+            inner: ChainKVStore::new_with_config(ReadOnlyBackend(db), config),
+        }
+    }
+
+    pub fn get(&self, col: Col, key: &[u8]) -> Option<Vec<u8>> {
+        self.inner.get(col, key)
+    }
+
+    pub fn partial_get(&self, col: Col, key: &[u8], range: &Range<usize>) -> Option<Vec<u8>> {
+        self.inner.partial_get(col, key, range)
+    }
+
+    forward! {
+        fn get_block(&self, block_hash: &H256) -> Option<Block>;
+        fn read_block(&self, block_hash: &H256) -> Prunable<Block>;
+        fn get_header(&self, block_hash: &H256) -> Option<Header>;
+        fn get_block_body(&self, block_hash: &H256) -> Option<Vec<Transaction>>;
+        fn get_block_proposal_txs_ids(&self, h: &H256) -> Option<Vec<ProposalShortId>>;
+        fn get_block_uncles(&self, block_hash: &H256) -> Option<Vec<UncleBlock>>;
+        fn get_block_ext(&self, block_hash: &H256) -> Option<BlockExt>;
+        fn get_block_hash(&self, number: BlockNumber) -> Option<H256>;
+        fn get_block_number(&self, hash: &H256) -> Option<BlockNumber>;
+        fn get_tip_header(&self) -> Option<Header>;
+        fn get_transaction(&self, h: &H256) -> Option<(Transaction, H256)>;
+        fn get_transaction_address(&self, hash: &H256) -> Option<TransactionAddress>;
+        fn get_transaction_info(&self, hash: &H256) -> Option<TransactionInfo>;
+        fn get_transaction_with_info(&self, hash: &H256) -> Option<(Transaction, TransactionInfo)>;
+        fn get_cell_meta(&self, tx_hash: &H256, index: u32) -> Option<CellMeta>;
+        fn get_cell_metas(&self, out_points: &[CellOutPoint]) -> Vec<Option<CellMeta>>;
+        fn get_cell_output(&self, tx_hash: &H256, index: u32) -> Option<CellOutput>;
+        fn get_current_epoch_ext(&self) -> Option<EpochExt>;
+        fn get_epoch_ext(&self, hash: &H256) -> Option<EpochExt>;
+        fn get_epoch_index(&self, number: EpochNumber) -> Option<H256>;
+        fn get_block_epoch_index(&self, h256: &H256) -> Option<H256>;
+        fn get_cells_by_lock_hash(
+            &self,
+            lock_hash: &H256,
+            from: Option<&IndexedCell>,
+            limit: usize
+        ) -> Option<IndexedCellsPage>;
+        fn get_pruned_number(&self) -> Option<BlockNumber>;
+        fn get_pruned_transactions(&self, number: BlockNumber) -> Option<Vec<PrunedTransaction>>;
+    }
+}
+
+impl<T: IterableKeyValueDB> ChainKVStore<Arc<T>> {
+    /// A read-only view of the store, sharing its database
+    pub fn read_only(&self) -> ReadOnlyChainStore<T> {
+        let config = StoreConfig {
+            cell_index: self.cell_index(),
+            ..Default::default()
+        };
+        ReadOnlyChainStore::new_with_config(ReadOnlyDB::new(Arc::clone(self.db())), &config)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::COLUMNS;
+    use ckb_chain_spec::consensus::Consensus;
+    use ckb_db::MemoryKeyValueDB;
+
+    #[test]
+    fn read_chain() {
+        let db = Arc::new(MemoryKeyValueDB::open(COLUMNS as usize));
+        let consensus = Consensus::default();
+        let genesis = consensus.genesis_block().clone();
+        let store = ChainKVStore::new(Arc::clone(&db));
+        store.init(&consensus).unwrap();
+
+        let read_only = ReadOnlyChainStore::new(ReadOnlyDB::new(db));
+        let hash = genesis.header().hash();
+        assert_eq!(read_only.get_tip_header(), store.get_tip_header());
+        assert_eq!(read_only.get_block(&hash), Some(genesis));
+        assert_eq!(read_only.get_block_hash(0), Some(hash.to_owned()));
+        assert_eq!(
+            read_only.get_current_epoch_ext(),
+            store.get_current_epoch_ext()
+        );
+        assert_eq!(store.read_only().get_tip_header(), store.get_tip_header());
+    }
+}
@@ -1,32 +1,46 @@
+use crate::cell_index::cell_index_key;
 use crate::flat_block_body::{
     deserialize_block_body, deserialize_transaction, serialize_block_body,
     serialize_block_body_size, TransactionAddressInner, TransactionAddressStored,
 };
 use crate::{
-    COLUMN_BLOCK_BODY, COLUMN_BLOCK_EPOCH, COLUMN_BLOCK_HEADER, COLUMN_BLOCK_PROPOSAL_IDS,
-    COLUMN_BLOCK_TRANSACTION_ADDRESSES, COLUMN_BLOCK_UNCLE, COLUMN_CELL_META, COLUMN_EPOCH,
-    COLUMN_EXT, COLUMN_INDEX, COLUMN_META, COLUMN_TRANSACTION_ADDR,
+    IndexedCell, IndexedCellsPage, Prunable, PrunedTransaction, StoreConfig, COLUMN_BLOCK_BODY,
+    COLUMN_BLOCK_EPOCH, COLUMN_BLOCK_HEADER, COLUMN_BLOCK_PROPOSAL_IDS,
+    COLUMN_BLOCK_TRANSACTION_ADDRESSES, COLUMN_BLOCK_UNCLE, COLUMN_CELL_INDEX,
+    COLUMN_CELL_INDEX_KEY, COLUMN_CELL_META, COLUMN_EPOCH, COLUMN_EXT, COLUMN_INDEX, COLUMN_META,
+    COLUMN_PRUNED_CELL_OUTPUT, COLUMN_PRUNED_TRANSACTIONS, COLUMN_TRANSACTION_ADDR,
+    COLUMN_TRANSACTION_INFO,
 };
 use bincode::{deserialize, serialize};
 use ckb_chain_spec::consensus::Consensus;
 use ckb_core::block::{Block, BlockBuilder};
 use ckb_core::cell::{BlockInfo, CellMeta};
 use ckb_core::extras::{
-    BlockExt, DaoStats, EpochExt, TransactionAddress, DEFAULT_ACCUMULATED_RATE,
+    BlockExt, DaoStats, EpochExt, TransactionAddress, TransactionInfo, DEFAULT_ACCUMULATED_RATE,
 };
 use ckb_core::header::{BlockNumber, Header};
 use ckb_core::transaction::{CellOutPoint, CellOutput, ProposalShortId, Transaction};
 use ckb_core::uncle::UncleBlock;
 use ckb_core::{Capacity, EpochNumber};
-use ckb_db::{Col, DbBatch, Error, KeyValueDB};
+use ckb_db::{Col, DbBatch, Error, IterableKeyValueDB};
 use numext_fixed_hash::H256;
 use serde::Serialize;
-use std::ops::Range;
+use serde_derive::{Deserialize, Serialize};
+use std::ops::{Bound, Range};
 
 const META_TIP_HEADER_KEY: &[u8] = b"TIP_HEADER";
 const META_CURRENT_EPOCH_KEY: &[u8] = b"CURRENT_EPOCH";
+const META_PRUNED_NUMBER_KEY: &[u8] = b"PRUNED_NUMBER";
+
+// The block hash of a transaction info is read from the transaction address, or from the main
+// chain index once the block is pruned
+#[derive(Serialize, Deserialize)]
+struct TransactionInfoStored {
+    block_number: BlockNumber,
+    index: usize,
+}
 
-fn cell_store_key(tx_hash: &H256, index: u32) -> Vec<u8> {
+pub(crate) fn cell_store_key(tx_hash: &H256, index: u32) -> Vec<u8> {
     let mut key: [u8; 36] = [0; 36];
     key[..32].copy_from_slice(tx_hash.as_bytes());
     key[32..36].copy_from_slice(&index.to_be_bytes());
@@ -35,11 +49,30 @@ fn cell_store_key(tx_hash: &H256, index: u32) -> Vec<u8> {
 
 pub struct ChainKVStore<T> {
     db: T,
+    cell_index: bool,
 }
 
-impl<T: KeyValueDB> ChainKVStore<T> {
+impl<T: IterableKeyValueDB> ChainKVStore<T> {
     pub fn new(db: T) -> Self {
-        ChainKVStore { db }
+        ChainKVStore {
+            db,
+            cell_index: false,
+        }
+    }
+
+    pub fn new_with_config(db: T, config: &StoreConfig) -> Self {
+        ChainKVStore {
+            db,
+            cell_index: config.cell_index,
+        }
+    }
+
+    pub(crate) fn db(&self) -> &T {
+        &self.db
+    }
+
+    pub(crate) fn cell_index(&self) -> bool {
+        self.cell_index
     }
 
     pub fn get(&self, col: Col, key: &[u8]) -> Option<Vec<u8>> {
@@ -51,6 +84,11 @@ impl<T: KeyValueDB> ChainKVStore<T> {
             .partial_read(col, key, range)
             .expect("db operation should be ok")
     }
+
+    fn get_pruned_cell_output(&self, tx_hash: &H256, index: u32) -> Option<CellOutput> {
+        self.get(COLUMN_PRUNED_CELL_OUTPUT, &cell_store_key(tx_hash, index))
+            .map(|raw| deserialize(&raw[..]).expect("deserialize cell output should be ok"))
+    }
 }
 
 /// Store interface by chain
@@ -62,6 +100,8 @@ pub trait ChainStore: Sync + Send {
 
     /// Get block by block header hash
     fn get_block(&self, block_hash: &H256) -> Option<Block>;
+    /// Get block by block header hash, telling apart the blocks pruned
+    fn read_block(&self, block_hash: &H256) -> Prunable<Block>;
     /// Get header by block header hash
     fn get_header(&self, block_hash: &H256) -> Option<Header>;
     /// Get block body by block header hash
@@ -83,7 +123,16 @@ pub trait ChainStore: Sync + Send {
     /// Get commit transaction and block hash by it's hash
     fn get_transaction(&self, h: &H256) -> Option<(Transaction, H256)>;
     fn get_transaction_address(&self, hash: &H256) -> Option<TransactionAddress>;
+    /// Get the block and the index of a transaction committed in the main chain
+    fn get_transaction_info(&self, hash: &H256) -> Option<TransactionInfo>;
+    /// Get a transaction committed in the main chain along with where it is
+    fn get_transaction_with_info(&self, hash: &H256) -> Option<(Transaction, TransactionInfo)> {
+        let info = self.get_transaction_info(hash)?;
+        self.get_transaction(hash).map(|(tx, _)| (tx, info))
+    }
     fn get_cell_meta(&self, tx_hash: &H256, index: u32) -> Option<CellMeta>;
+    /// Get the cell metas of the out points in one batch, in the order of `out_points`
+    fn get_cell_metas(&self, out_points: &[CellOutPoint]) -> Vec<Option<CellMeta>>;
     fn get_cell_output(&self, tx_hash: &H256, index: u32) -> Option<CellOutput>;
     // Get current epoch ext
     fn get_current_epoch_ext(&self) -> Option<EpochExt>;
@@ -93,6 +142,18 @@ pub trait ChainStore: Sync + Send {
     fn get_epoch_index(&self, number: EpochNumber) -> Option<H256>;
     // Get epoch index by block hash
     fn get_block_epoch_index(&self, h256: &H256) -> Option<H256>;
+    /// Get the live cells locked by the lock script hash in the order of the chain, after the
+    /// cell `from` and up to `limit` of them. Returns `None` when the cell index is off.
+    fn get_cells_by_lock_hash(
+        &self,
+        lock_hash: &H256,
+        from: Option<&IndexedCell>,
+        limit: usize,
+    ) -> Option<IndexedCellsPage>;
+    /// Get the number of the last pruned block, the main chain blocks up to it are pruned
+    fn get_pruned_number(&self) -> Option<BlockNumber>;
+    /// Get the transactions with live outputs of a pruned block by its number
+    fn get_pruned_transactions(&self, number: BlockNumber) -> Option<Vec<PrunedTransaction>>;
 }
 
 pub trait StoreBatch {
@@ -110,36 +171,64 @@ pub trait StoreBatch {
     fn attach_block(&mut self, block: &Block) -> Result<(), Error>;
     fn detach_block(&mut self, block: &Block) -> Result<(), Error>;
 
+    /// Remove the body, the uncles and the transaction addresses of a block
+    fn prune_block(&mut self, block_hash: &H256, transactions: &[Transaction])
+        -> Result<(), Error>;
+    /// Store the pruned transactions of a block and their outputs by the out points, the dead
+    /// outputs are removed and so are the transactions without live outputs
+    fn insert_pruned_transactions(
+        &mut self,
+        number: BlockNumber,
+        transactions: &[PrunedTransaction],
+    ) -> Result<(), Error>;
+    fn insert_pruned_number(&mut self, number: BlockNumber) -> Result<(), Error>;
+
     fn commit(self) -> Result<(), Error>;
 }
 
-impl<T: KeyValueDB> ChainStore for ChainKVStore<T> {
+impl<T: IterableKeyValueDB> ChainStore for ChainKVStore<T> {
     type Batch = DefaultStoreBatch<T::Batch>;
 
     fn new_batch(&self) -> Result<Self::Batch, Error> {
         Ok(DefaultStoreBatch {
             inner: self.db.batch()?,
+            cell_index: self.cell_index,
         })
     }
 
     fn get_block(&self, h: &H256) -> Option<Block> {
-        self.get_header(h).map(|header| {
-            let transactions = self
-                .get_block_body(h)
-                .expect("block transactions must be stored");
-            let uncles = self
-                .get_block_uncles(h)
-                .expect("block uncles must be stored");
-            let proposals = self
-                .get_block_proposal_txs_ids(h)
-                .expect("block proposal_ids must be stored");
+        self.read_block(h).found()
+    }
+
+    fn read_block(&self, h: &H256) -> Prunable<Block> {
+        let header = match self.get_header(h) {
+            Some(header) => header,
+            None => return Prunable::NotFound,
+        };
+        let transactions = match self.get_block_body(h) {
+            Some(transactions) => transactions,
+            None if self
+                .get_pruned_number()
+                .map_or(false, |pruned| header.number() <= pruned) =>
+            {
+                return Prunable::Pruned;
+            }
+            None => panic!("block transactions must be stored"),
+        };
+        let uncles = self
+            .get_block_uncles(h)
+            .expect("block uncles must be stored");
+        let proposals = self
+            .get_block_proposal_txs_ids(h)
+            .expect("block proposal_ids must be stored");
+        Prunable::Found(
             BlockBuilder::default()
                 .header(header)
                 .uncles(uncles)
                 .transactions(transactions)
                 .proposals(proposals)
-                .build()
-        })
+                .build(),
+        )
     }
 
     fn get_header(&self, h: &H256) -> Option<Header> {
@@ -263,6 +352,45 @@ impl<T: KeyValueDB> ChainStore for ChainKVStore<T> {
             .map(|raw| H256::from_slice(&raw[..]).expect("db safe access"))
     }
 
+    fn get_cells_by_lock_hash(
+        &self,
+        lock_hash: &H256,
+        from: Option<&IndexedCell>,
+        limit: usize,
+    ) -> Option<IndexedCellsPage> {
+        if !self.cell_index {
+            return None;
+        }
+        // the cell `from` is excluded
+        let from_key = from.map(|cell| cell.index_key(lock_hash));
+        let start = match from_key {
+            Some(ref key) => Bound::Excluded(&key[..]),
+            None => Bound::Included(lock_hash.as_bytes()),
+        };
+        let mut iter = self
+            .db
+            .seek(COLUMN_CELL_INDEX, start)
+            .expect("db operation should be ok")
+            .map(|item| item.expect("db operation should be ok"))
+            .take_while(|(key, _)| key.starts_with(lock_hash.as_bytes()))
+            .map(|(key, value)| IndexedCell::from_index_entry(&key, &value));
+        let cells = iter.by_ref().take(limit).collect();
+        Some(IndexedCellsPage {
+            cells,
+            has_more: iter.next().is_some(),
+        })
+    }
+
+    fn get_pruned_number(&self) -> Option<BlockNumber> {
+        self.get(COLUMN_META, META_PRUNED_NUMBER_KEY)
+            .map(|raw| deserialize(&raw[..]).expect("db safe access"))
+    }
+
+    fn get_pruned_transactions(&self, number: BlockNumber) -> Option<Vec<PrunedTransaction>> {
+        self.get(COLUMN_PRUNED_TRANSACTIONS, &number.to_le_bytes())
+            .map(|raw| deserialize(&raw[..]).expect("deserialize pruned transactions should be ok"))
+    }
+
     fn get_transaction(&self, h: &H256) -> Option<(Transaction, H256)> {
         self.get(COLUMN_TRANSACTION_ADDR, h.as_bytes())
             .map(|raw| deserialize(&raw[..]).expect("deserialize tx address should be ok"))
@@ -295,11 +423,49 @@ impl<T: KeyValueDB> ChainStore for ChainKVStore<T> {
             })
     }
 
+    fn get_transaction_info(&self, hash: &H256) -> Option<TransactionInfo> {
+        let address: Option<TransactionAddressStored> = self
+            .get(COLUMN_TRANSACTION_ADDR, hash.as_bytes())
+            .map(|raw| deserialize(&raw[..]).expect("deserialize tx address should be ok"));
+        match address {
+            // the transactions committed before the infos were stored have their addresses only
+            Some(address) => Some(TransactionInfo {
+                block_number: self.get_block_number(&address.block_hash)?,
+                block_hash: address.block_hash,
+                index: address.inner.index,
+            }),
+            None => {
+                let stored: TransactionInfoStored = self
+                    .get(COLUMN_TRANSACTION_INFO, hash.as_bytes())
+                    .map(|raw| deserialize(&raw[..]).expect("deserialize tx info should be ok"))?;
+                Some(TransactionInfo {
+                    block_hash: self.get_block_hash(stored.block_number)?,
+                    block_number: stored.block_number,
+                    index: stored.index,
+                })
+            }
+        }
+    }
+
     fn get_cell_meta(&self, tx_hash: &H256, index: u32) -> Option<CellMeta> {
         self.get(COLUMN_CELL_META, &cell_store_key(tx_hash, index))
             .map(|raw| deserialize(&raw[..]).unwrap())
     }
 
+    fn get_cell_metas(&self, out_points: &[CellOutPoint]) -> Vec<Option<CellMeta>> {
+        let keys = out_points
+            .iter()
+            .map(|out_point| cell_store_key(&out_point.tx_hash, out_point.index))
+            .collect::<Vec<_>>();
+        let keys = keys.iter().map(|key| &key[..]).collect::<Vec<_>>();
+        self.db
+            .read_multi(COLUMN_CELL_META, &keys)
+            .expect("db operation should be ok")
+            .into_iter()
+            .map(|raw| raw.map(|raw| deserialize(&raw[..]).unwrap()))
+            .collect()
+    }
+
     fn get_cell_output(&self, tx_hash: &H256, index: u32) -> Option<CellOutput> {
         self.get(COLUMN_TRANSACTION_ADDR, tx_hash.as_bytes())
             .map(|raw| deserialize(&raw[..]).expect("deserialize tx address should be ok"))
@@ -322,16 +488,18 @@ impl<T: KeyValueDB> ChainStore for ChainKVStore<T> {
                         })
                     })
             })
+            .or_else(|| self.get_pruned_cell_output(tx_hash, index))
     }
 }
 
 pub struct DefaultStoreBatch<B> {
     inner: B,
+    cell_index: bool,
 }
 
 /// helper methods
 impl<B: DbBatch> DefaultStoreBatch<B> {
-    fn insert_raw(&mut self, col: Col, key: &[u8], value: &[u8]) -> Result<(), Error> {
+    pub(crate) fn insert_raw(&mut self, col: Col, key: &[u8], value: &[u8]) -> Result<(), Error> {
         self.inner.insert(col, key, value)
     }
 
@@ -348,9 +516,102 @@ impl<B: DbBatch> DefaultStoreBatch<B> {
         )
     }
 
-    fn delete(&mut self, col: Col, key: &[u8]) -> Result<(), Error> {
+    pub(crate) fn delete(&mut self, col: Col, key: &[u8]) -> Result<(), Error> {
         self.inner.delete(col, key)
     }
+
+    // The index key of a cell, `None` if it isn't indexed, as of the writes of this batch
+    fn cell_index_key_of(&self, store_key: &[u8]) -> Result<Option<Vec<u8>>, Error> {
+        self.inner.get(COLUMN_CELL_INDEX_KEY, store_key)
+    }
+
+    fn index_cell(
+        &mut self,
+        tx_hash: &H256,
+        index: u32,
+        lock_hash: &H256,
+        number: BlockNumber,
+        tx_index: u32,
+    ) -> Result<(), Error> {
+        let store_key = cell_store_key(tx_hash, index);
+        let index_key = cell_index_key(lock_hash, number, tx_index, index);
+        self.insert_raw(COLUMN_CELL_INDEX, &index_key, tx_hash.as_bytes())?;
+        self.insert_raw(COLUMN_CELL_INDEX_KEY, &store_key, &index_key)?;
+        Ok(())
+    }
+
+    // The outputs of the transactions are indexed by their lock hashes, and the cells they spend
+    // are removed from the index. The index keys of the spent cells are kept for the detaching.
+    pub(crate) fn index_cells(
+        &mut self,
+        number: BlockNumber,
+        transactions: &[Transaction],
+    ) -> Result<(), Error> {
+        for (tx_index, tx) in transactions.iter().enumerate() {
+            for cell in tx.input_pts_iter().filter_map(|input| input.cell.as_ref()) {
+                let store_key = cell_store_key(&cell.tx_hash, cell.index);
+                if let Some(index_key) = self.cell_index_key_of(&store_key)? {
+                    self.delete(COLUMN_CELL_INDEX, &index_key)?;
+                }
+            }
+            let tx_hash = tx.hash();
+            for (index, output) in tx.outputs().iter().enumerate() {
+                self.index_cell(
+                    &tx_hash,
+                    index as u32,
+                    &output.lock.hash(),
+                    number,
+                    tx_index as u32,
+                )?;
+            }
+        }
+        Ok(())
+    }
+
+    // The outputs of the transactions are removed from the index and the cells they spent are
+    // indexed again, in the reverse order of the indexing. A spent cell whose block is detached
+    // by this batch too is left out.
+    fn unindex_cells(&mut self, transactions: &[Transaction]) -> Result<(), Error> {
+        for tx in transactions.iter().rev() {
+            let tx_hash = tx.hash();
+            for index in 0..tx.outputs().len() {
+                let store_key = cell_store_key(&tx_hash, index as u32);
+                if let Some(index_key) = self.cell_index_key_of(&store_key)? {
+                    self.delete(COLUMN_CELL_INDEX, &index_key)?;
+                }
+                self.delete(COLUMN_CELL_INDEX_KEY, &store_key)?;
+            }
+            for cell in tx.input_pts_iter().filter_map(|input| input.cell.as_ref()) {
+                let store_key = cell_store_key(&cell.tx_hash, cell.index);
+                if let Some(index_key) = self.cell_index_key_of(&store_key)? {
+                    self.insert_raw(COLUMN_CELL_INDEX, &index_key, cell.tx_hash.as_bytes())?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // Only the live outputs of the pruned transactions are known, they are indexed alone
+    pub(crate) fn index_pruned_cells(
+        &mut self,
+        number: BlockNumber,
+        transactions: &[PrunedTransaction],
+    ) -> Result<(), Error> {
+        for tx in transactions {
+            for (index, output) in tx.outputs.iter().enumerate() {
+                if let Some(output) = output {
+                    self.index_cell(
+                        &tx.hash,
+                        index as u32,
+                        &output.lock.hash(),
+                        number,
+                        tx.index,
+                    )?;
+                }
+            }
+        }
+        Ok(())
+    }
 }
 
 impl<B: DbBatch> StoreBatch for DefaultStoreBatch<B> {
@@ -393,6 +654,11 @@ impl<B: DbBatch> StoreBatch for DefaultStoreBatch<B> {
                 tx_hash.as_bytes(),
                 &addr.into_stored(hash.to_owned()),
             )?;
+            let info = TransactionInfoStored {
+                block_number: block.header().number(),
+                index: id,
+            };
+            self.insert_serialize(COLUMN_TRANSACTION_INFO, tx_hash.as_bytes(), &info)?;
             let cellbase = id == 0;
             for (index, output) in tx.outputs().iter().enumerate() {
                 let out_point = CellOutPoint {
@@ -414,6 +680,9 @@ impl<B: DbBatch> StoreBatch for DefaultStoreBatch<B> {
                 self.insert_serialize(COLUMN_CELL_META, &store_key, &cell_meta)?;
             }
         }
+        if self.cell_index {
+            self.index_cells(block.header().number(), block.transactions())?;
+        }
 
         let number = block.header().number().to_le_bytes();
         self.insert_raw(COLUMN_INDEX, &number, hash.as_bytes())?;
@@ -424,15 +693,64 @@ impl<B: DbBatch> StoreBatch for DefaultStoreBatch<B> {
         for tx in block.transactions() {
             let tx_hash = tx.hash();
             self.delete(COLUMN_TRANSACTION_ADDR, tx_hash.as_bytes())?;
+            self.delete(COLUMN_TRANSACTION_INFO, tx_hash.as_bytes())?;
             for index in 0..tx.outputs().len() {
                 let store_key = cell_store_key(&tx_hash, index as u32);
                 self.delete(COLUMN_CELL_META, &store_key)?;
             }
         }
+        if self.cell_index {
+            self.unindex_cells(block.transactions())?;
+        }
         self.delete(COLUMN_INDEX, &block.header().number().to_le_bytes())?;
         self.delete(COLUMN_INDEX, block.header().hash().as_bytes())
     }
 
+    fn prune_block(
+        &mut self,
+        block_hash: &H256,
+        transactions: &[Transaction],
+    ) -> Result<(), Error> {
+        for tx in transactions {
+            self.delete(COLUMN_TRANSACTION_ADDR, tx.hash().as_bytes())?;
+        }
+        self.delete(COLUMN_BLOCK_BODY, block_hash.as_bytes())?;
+        self.delete(COLUMN_BLOCK_TRANSACTION_ADDRESSES, block_hash.as_bytes())?;
+        self.delete(COLUMN_BLOCK_UNCLE, block_hash.as_bytes())
+    }
+
+    fn insert_pruned_transactions(
+        &mut self,
+        number: BlockNumber,
+        transactions: &[PrunedTransaction],
+    ) -> Result<(), Error> {
+        for tx in transactions {
+            for (index, output) in tx.outputs.iter().enumerate() {
+                let store_key = cell_store_key(&tx.hash, index as u32);
+                match output {
+                    Some(output) => {
+                        self.insert_serialize(COLUMN_PRUNED_CELL_OUTPUT, &store_key, output)?
+                    }
+                    None => self.delete(COLUMN_PRUNED_CELL_OUTPUT, &store_key)?,
+                }
+            }
+        }
+        let key = number.to_le_bytes();
+        let live = transactions
+            .iter()
+            .filter(|tx| !tx.is_dead())
+            .collect::<Vec<_>>();
+        if live.is_empty() {
+            self.delete(COLUMN_PRUNED_TRANSACTIONS, &key)
+        } else {
+            self.insert_serialize(COLUMN_PRUNED_TRANSACTIONS, &key, &live)
+        }
+    }
+
+    fn insert_pruned_number(&mut self, number: BlockNumber) -> Result<(), Error> {
+        self.insert_serialize(COLUMN_META, META_PRUNED_NUMBER_KEY, &number)
+    }
+
     fn insert_tip_header(&mut self, h: &Header) -> Result<(), Error> {
         self.insert_raw(COLUMN_META, META_TIP_HEADER_KEY, h.hash().as_bytes())
     }
@@ -471,6 +789,7 @@ mod tests {
     use super::*;
     use crate::store::StoreBatch;
     use ckb_chain_spec::consensus::Consensus;
+    use ckb_core::header::HeaderBuilder;
     use ckb_core::transaction::TransactionBuilder;
     use ckb_db::{DBConfig, RocksDB};
     use tempfile;
@@ -571,4 +890,122 @@ mod tests {
 
         assert_eq!(block.header(), &store.get_tip_header().unwrap());
     }
+
+    #[test]
+    fn get_cell_metas_in_order() {
+        let db = setup_db("get_cell_metas_in_order", COLUMNS);
+        let store = ChainKVStore::new(db);
+        let tx = TransactionBuilder::default()
+            .outputs(vec![CellOutput::default(); 3])
+            .build();
+        let block = BlockBuilder::default().transaction(tx.clone()).build();
+        let mut batch = store.new_batch().unwrap();
+        batch.insert_block(&block).unwrap();
+        batch.attach_block(&block).unwrap();
+        batch.commit().unwrap();
+
+        let out_point = |index| CellOutPoint {
+            tx_hash: tx.hash().to_owned(),
+            index,
+        };
+        // duplicated and missing out points included
+        let out_points = vec![out_point(2), out_point(0), out_point(3), out_point(2)];
+        let expected = out_points
+            .iter()
+            .map(|out_point| store.get_cell_meta(&out_point.tx_hash, out_point.index))
+            .collect::<Vec<_>>();
+        assert!(expected[2].is_none());
+        assert_eq!(store.get_cell_metas(&out_points), expected);
+        assert!(store.get_cell_metas(&[]).is_empty());
+    }
+
+    #[test]
+    fn transaction_info_follows_main_chain() {
+        let db = setup_db("transaction_info_follows_main_chain", COLUMNS);
+        let store = ChainKVStore::new(db);
+        let consensus = Consensus::default();
+        store.init(&consensus).unwrap();
+        let genesis = consensus.genesis_block().header();
+
+        let tx = TransactionBuilder::default()
+            .output(CellOutput::default())
+            .build();
+        let only_b = TransactionBuilder::default()
+            .outputs(vec![CellOutput::default(); 2])
+            .build();
+        let gen_block = |nonce, transactions: Vec<Transaction>| {
+            BlockBuilder::default()
+                .header_builder(
+                    HeaderBuilder::default()
+                        .parent_hash(genesis.hash().to_owned())
+                        .number(1)
+                        .nonce(nonce),
+                )
+                .transactions(transactions)
+                .build()
+        };
+        let block_a = gen_block(1, vec![tx.clone()]);
+        let block_b = gen_block(2, vec![only_b.clone(), tx.clone()]);
+        let info = |block: &Block, index| TransactionInfo {
+            block_hash: block.header().hash().to_owned(),
+            block_number: 1,
+            index,
+        };
+
+        let mut batch = store.new_batch().unwrap();
+        batch.insert_block(&block_a).unwrap();
+        batch.insert_block(&block_b).unwrap();
+        batch.attach_block(&block_a).unwrap();
+        batch.commit().unwrap();
+        assert_eq!(
+            store.get_transaction_info(tx.hash()),
+            Some(info(&block_a, 0))
+        );
+        assert_eq!(store.get_transaction_info(only_b.hash()), None);
+
+        // the transaction moves to the competing block
+        let mut batch = store.new_batch().unwrap();
+        batch.detach_block(&block_a).unwrap();
+        batch.attach_block(&block_b).unwrap();
+        batch.commit().unwrap();
+        assert_eq!(
+            store.get_transaction_with_info(tx.hash()),
+            Some((tx.clone(), info(&block_b, 1)))
+        );
+        assert_eq!(
+            store.get_transaction_info(only_b.hash()),
+            Some(info(&block_b, 0))
+        );
+
+        // and back
+        let mut batch = store.new_batch().unwrap();
+        batch.detach_block(&block_b).unwrap();
+        batch.attach_block(&block_a).unwrap();
+        batch.commit().unwrap();
+        assert_eq!(
+            store.get_transaction_info(tx.hash()),
+            Some(info(&block_a, 0))
+        );
+        assert_eq!(store.get_transaction_with_info(only_b.hash()), None);
+
+        let expected = Some(info(&block_a, 0));
+        // the transactions committed before the infos were stored
+        let mut batch = store.new_batch().unwrap();
+        batch
+            .delete(COLUMN_TRANSACTION_INFO, tx.hash().as_bytes())
+            .unwrap();
+        batch.commit().unwrap();
+        assert_eq!(store.get_transaction_info(tx.hash()), expected);
+
+        // the pruned transactions
+        let mut batch = store.new_batch().unwrap();
+        batch.detach_block(&block_a).unwrap();
+        batch.attach_block(&block_a).unwrap();
+        batch
+            .prune_block(&block_a.header().hash(), block_a.transactions())
+            .unwrap();
+        batch.commit().unwrap();
+        assert_eq!(store.get_transaction(tx.hash()), None);
+        assert_eq!(store.get_transaction_info(tx.hash()), expected);
+    }
 }
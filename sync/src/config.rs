@@ -1,14 +1,126 @@
+use crate::relayer::{
+    MAX_CYCLES_PER_ACCEPTED_TX, MAX_REJECTED_TX_RATIO, MAX_TX_ASKS_IN_FLIGHT, PEER_KNOWN_TXS_SIZE,
+    TX_ASKED_SIZE, TX_ASK_PRIORITY_THRESHOLD, TX_FILTER_SIZE, TX_REJECT_BURST, TX_REJECT_RATE,
+};
+use ckb_core::Cycle;
 use serde_derive::{Deserialize, Serialize};
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Config {
     pub orphan_block_limit: usize,
+    /// Transaction hash announcements accepted from a peer per second
+    #[serde(default = "default_tx_announce_rate")]
+    pub tx_announce_rate: u32,
+    /// Transaction hash announcements a peer may send at once, such as right after a new tip
+    #[serde(default = "default_tx_announce_burst")]
+    pub tx_announce_burst: u32,
+    /// Seconds the relayed transactions failing verification are remembered, during which their
+    /// announcements are ignored
+    #[serde(default = "default_rejected_tx_expiry")]
+    pub rejected_tx_expiry: u64,
+    /// The number of transactions known by the relayer, the least recently known are forgotten
+    #[serde(default = "default_tx_filter_size")]
+    pub tx_filter_size: usize,
+    /// The number of transactions being asked for, the oldest asks are forgotten
+    #[serde(default = "default_tx_asked_size")]
+    pub tx_asked_size: usize,
+    /// The number of transactions remembered as known by each peer, which are not relayed to it
+    #[serde(default = "default_peer_known_txs_size")]
+    pub peer_known_txs_size: usize,
+    /// The number of transactions being asked from a peer at once, the others are asked later
+    #[serde(default = "default_max_tx_asks_in_flight")]
+    pub max_tx_asks_in_flight: usize,
+    /// The number of transactions being asked for or waiting to be asked, beyond which the
+    /// waiting ones are asked by their announced fee rates, as the asks in flight complete
+    #[serde(default = "default_tx_ask_priority_threshold")]
+    pub tx_ask_priority_threshold: usize,
+    /// Rejections of relayed transactions sent back to a peer per second
+    #[serde(default = "default_tx_reject_rate")]
+    pub tx_reject_rate: u32,
+    /// Rejections of relayed transactions sent back to a peer at once
+    #[serde(default = "default_tx_reject_burst")]
+    pub tx_reject_burst: u32,
+    /// Seconds the transaction relay traffic of each peer is accounted over
+    #[serde(default = "default_tx_relay_window")]
+    pub tx_relay_window: u64,
+    /// A peer relaying more than this many invalid transactions per accepted one is reported
+    #[serde(default = "default_max_rejected_tx_ratio")]
+    pub max_rejected_tx_ratio: u64,
+    /// A peer whose transactions take more than this many cycles to verify per accepted one is
+    /// reported
+    #[serde(default = "default_max_cycles_per_accepted_tx")]
+    pub max_cycles_per_accepted_tx: Cycle,
+}
+
+fn default_tx_announce_rate() -> u32 {
+    100
+}
+
+fn default_tx_announce_burst() -> u32 {
+    1000
+}
+
+fn default_rejected_tx_expiry() -> u64 {
+    600
+}
+
+fn default_tx_filter_size() -> usize {
+    TX_FILTER_SIZE
+}
+
+fn default_tx_asked_size() -> usize {
+    TX_ASKED_SIZE
+}
+
+fn default_peer_known_txs_size() -> usize {
+    PEER_KNOWN_TXS_SIZE
+}
+
+fn default_max_tx_asks_in_flight() -> usize {
+    MAX_TX_ASKS_IN_FLIGHT
+}
+
+fn default_tx_ask_priority_threshold() -> usize {
+    TX_ASK_PRIORITY_THRESHOLD
+}
+
+fn default_tx_reject_rate() -> u32 {
+    TX_REJECT_RATE
+}
+
+fn default_tx_reject_burst() -> u32 {
+    TX_REJECT_BURST
+}
+
+fn default_tx_relay_window() -> u64 {
+    600
+}
+
+fn default_max_rejected_tx_ratio() -> u64 {
+    MAX_REJECTED_TX_RATIO
+}
+
+fn default_max_cycles_per_accepted_tx() -> Cycle {
+    MAX_CYCLES_PER_ACCEPTED_TX
 }
 
 impl Default for Config {
     fn default() -> Self {
         Config {
             orphan_block_limit: 1024,
+            tx_announce_rate: default_tx_announce_rate(),
+            tx_announce_burst: default_tx_announce_burst(),
+            rejected_tx_expiry: default_rejected_tx_expiry(),
+            tx_filter_size: default_tx_filter_size(),
+            tx_asked_size: default_tx_asked_size(),
+            peer_known_txs_size: default_peer_known_txs_size(),
+            max_tx_asks_in_flight: default_max_tx_asks_in_flight(),
+            tx_ask_priority_threshold: default_tx_ask_priority_threshold(),
+            tx_reject_rate: default_tx_reject_rate(),
+            tx_reject_burst: default_tx_reject_burst(),
+            tx_relay_window: default_tx_relay_window(),
+            max_rejected_tx_ratio: default_max_rejected_tx_ratio(),
+            max_cycles_per_accepted_tx: default_max_cycles_per_accepted_tx(),
         }
     }
 }
@@ -14,9 +14,9 @@ mod tests;
 
 pub use crate::config::Config;
 pub use crate::net_time_checker::NetTimeProtocol;
-pub use crate::relayer::Relayer;
+pub use crate::relayer::{RelayStats, Relayer};
 pub use crate::synchronizer::Synchronizer;
-pub use crate::types::SyncSharedState;
+pub use crate::types::{SyncSharedState, TxRelayStats};
 use std::time::Duration;
 
 pub const MAX_HEADERS_LEN: usize = 2_000;
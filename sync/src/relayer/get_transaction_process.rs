@@ -1,5 +1,4 @@
 use crate::relayer::Relayer;
-use ckb_core::transaction::ProposalShortId;
 use ckb_network::{CKBProtocolContext, PeerIndex};
 use ckb_protocol::{GetRelayTransaction as FbsGetRelayTransaction, RelayMessage};
 use ckb_store::ChainStore;
@@ -16,7 +15,7 @@ pub struct GetTransactionProcess<'a, CS> {
     peer: PeerIndex,
 }
 
-impl<'a, CS: ChainStore> GetTransactionProcess<'a, CS> {
+impl<'a, CS: ChainStore + 'static> GetTransactionProcess<'a, CS> {
     pub fn new(
         message: &'a FbsGetRelayTransaction,
         relayer: &'a Relayer<CS>,
@@ -34,15 +33,7 @@ impl<'a, CS: ChainStore> GetTransactionProcess<'a, CS> {
     pub fn execute(self) -> Result<(), FailureError> {
         let tx_hash = (*self.message).try_into()?;
         trace!(target: "relay", "{} request transaction({:#x})", self.peer, tx_hash);
-        let entry_opt = {
-            let short_id = ProposalShortId::from_tx_hash(&tx_hash);
-            self.relayer
-                .shared
-                .lock_chain_state()
-                .get_tx_with_cycles_from_pool(&short_id)
-                .and_then(|(tx, cycles)| cycles.map(|cycles| (tx, cycles)))
-        };
-        if let Some((tx, cycles)) = entry_opt {
+        if let Some((tx, cycles)) = self.relayer.get_relay_tx(&tx_hash) {
             let fbb = &mut FlatBufferBuilder::new();
             let message = RelayMessage::build_transaction(fbb, &tx, cycles);
             fbb.finish(message, None);
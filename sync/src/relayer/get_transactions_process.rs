@@ -0,0 +1,77 @@
+use crate::relayer::{Relayer, MAX_GET_RELAY_TXS};
+use ckb_network::{CKBProtocolContext, PeerIndex};
+use ckb_protocol::{cast, GetRelayTransactions as FbsGetRelayTransactions, RelayMessage};
+use ckb_store::ChainStore;
+use failure::Error as FailureError;
+use flatbuffers::FlatBufferBuilder;
+use log::{debug, info, trace};
+use numext_fixed_hash::H256;
+use std::convert::TryInto;
+use std::sync::Arc;
+
+pub struct GetTransactionsProcess<'a, CS> {
+    message: &'a FbsGetRelayTransactions<'a>,
+    relayer: &'a Relayer<CS>,
+    nc: Arc<dyn CKBProtocolContext>,
+    peer: PeerIndex,
+}
+
+impl<'a, CS: ChainStore + 'static> GetTransactionsProcess<'a, CS> {
+    pub fn new(
+        message: &'a FbsGetRelayTransactions,
+        relayer: &'a Relayer<CS>,
+        nc: Arc<dyn CKBProtocolContext>,
+        peer: PeerIndex,
+    ) -> Self {
+        GetTransactionsProcess {
+            message,
+            relayer,
+            nc,
+            peer,
+        }
+    }
+
+    pub fn execute(self) -> Result<(), FailureError> {
+        let fbs_tx_hashes = cast!(self.message.tx_hashes())?;
+        if fbs_tx_hashes.len() > MAX_GET_RELAY_TXS {
+            info!(
+                target: "relay",
+                "{} request {} transactions in one message, exceeds the limit {}, ignore it",
+                self.peer,
+                fbs_tx_hashes.len(),
+                MAX_GET_RELAY_TXS,
+            );
+            return Ok(());
+        }
+        let tx_hashes = fbs_tx_hashes
+            .iter()
+            .map(TryInto::try_into)
+            .collect::<Result<Vec<H256>, _>>()?;
+        trace!(target: "relay", "{} request {} transactions", self.peer, tx_hashes.len());
+
+        // The transactions not found are skipped, the peer asks them again if needed
+        let transactions = tx_hashes
+            .iter()
+            .filter_map(|tx_hash| {
+                let entry = self.relayer.get_relay_tx(tx_hash);
+                if entry.is_none() {
+                    debug!(
+                        target: "relay",
+                        "{} request transaction({:#x}), but not found or without cycles",
+                        self.peer,
+                        tx_hash,
+                    );
+                }
+                entry
+            })
+            .collect::<Vec<_>>();
+        if !transactions.is_empty() {
+            let fbb = &mut FlatBufferBuilder::new();
+            let message = RelayMessage::build_transactions(fbb, &transactions);
+            fbb.finish(message, None);
+            let data = fbb.finished_data().into();
+            self.nc.send_message_to(self.peer, data);
+        }
+        Ok(())
+    }
+}
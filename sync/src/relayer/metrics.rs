@@ -0,0 +1,75 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// The counters of the transaction relay pipeline, from the announced hashes to the verified
+/// transactions. They are bumped on the hot path without taking any relayer lock.
+#[derive(Default)]
+pub struct RelayMetrics {
+    pub announced: AtomicUsize,
+    pub already_known: AtomicUsize,
+    pub already_pooled: AtomicUsize,
+    pub asked: AtomicUsize,
+    pub shed: AtomicUsize,
+    pub received: AtomicUsize,
+    pub accepted: AtomicUsize,
+    pub rejected_script: AtomicUsize,
+    pub rejected_since: AtomicUsize,
+    pub rejected_malformed: AtomicUsize,
+    pub rejected_cycles: AtomicUsize,
+    pub orphaned: AtomicUsize,
+    pub tx_not_found: AtomicUsize,
+}
+
+/// A snapshot of the relay pipeline counters
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RelayStats {
+    /// The transaction hashes announced by the peers within their rate limit
+    pub announced: usize,
+    /// The announced transactions already known, held as orphans or rejected recently
+    pub already_known: usize,
+    /// The announced transactions already in the transaction pool
+    pub already_pooled: usize,
+    /// The announced transactions scheduled to be asked for
+    pub asked: usize,
+    /// The announced transactions dropped from the asks queue under backpressure, with the
+    /// lowest fee rate hints
+    pub shed: usize,
+    /// The transactions relayed to us
+    pub received: usize,
+    /// The transactions added into the transaction pool, including the unblocked orphans
+    pub accepted: usize,
+    pub rejected_script: usize,
+    /// The transactions whose since or cellbase maturity is not satisfied yet
+    pub rejected_since: usize,
+    pub rejected_malformed: usize,
+    /// The transactions consuming more cycles than claimed by the relaying peer
+    pub rejected_cycles: usize,
+    /// The transactions held until their missing parents are accepted
+    pub orphaned: usize,
+    /// The transactions asked by the peers but found neither in the pool, nor among the
+    /// transactions relayed recently, nor in the committed blocks
+    pub tx_not_found: usize,
+}
+
+impl RelayMetrics {
+    pub fn incr(counter: &AtomicUsize, n: usize) {
+        counter.fetch_add(n, Ordering::Relaxed);
+    }
+
+    pub fn snapshot(&self) -> RelayStats {
+        RelayStats {
+            announced: self.announced.load(Ordering::Relaxed),
+            already_known: self.already_known.load(Ordering::Relaxed),
+            already_pooled: self.already_pooled.load(Ordering::Relaxed),
+            asked: self.asked.load(Ordering::Relaxed),
+            shed: self.shed.load(Ordering::Relaxed),
+            received: self.received.load(Ordering::Relaxed),
+            accepted: self.accepted.load(Ordering::Relaxed),
+            rejected_script: self.rejected_script.load(Ordering::Relaxed),
+            rejected_since: self.rejected_since.load(Ordering::Relaxed),
+            rejected_malformed: self.rejected_malformed.load(Ordering::Relaxed),
+            rejected_cycles: self.rejected_cycles.load(Ordering::Relaxed),
+            orphaned: self.orphaned.load(Ordering::Relaxed),
+            tx_not_found: self.tx_not_found.load(Ordering::Relaxed),
+        }
+    }
+}
@@ -7,10 +7,17 @@ mod error;
 mod get_block_proposal_process;
 mod get_block_transactions_process;
 mod get_transaction_process;
+mod get_transactions_process;
+mod metrics;
+mod orphan_tx_pool;
+mod seen_tx_cache;
 #[cfg(test)]
 mod tests;
 mod transaction_hash_process;
+mod transaction_hashes_process;
 mod transaction_process;
+mod transaction_result_process;
+mod transactions_process;
 
 use self::block_proposal_process::BlockProposalProcess;
 use self::block_transactions_process::BlockTransactionsProcess;
@@ -20,23 +27,37 @@ pub use self::error::Error;
 use self::get_block_proposal_process::GetBlockProposalProcess;
 use self::get_block_transactions_process::GetBlockTransactionsProcess;
 use self::get_transaction_process::GetTransactionProcess;
+use self::get_transactions_process::GetTransactionsProcess;
+use self::metrics::RelayMetrics;
+pub use self::metrics::RelayStats;
+use self::orphan_tx_pool::OrphanTxPool;
+use self::seen_tx_cache::SeenTxCache;
 use self::transaction_hash_process::TransactionHashProcess;
+use self::transaction_hashes_process::TransactionHashesProcess;
 use self::transaction_process::TransactionProcess;
+use self::transaction_result_process::TransactionResultProcess;
+use self::transactions_process::TransactionsProcess;
+use crate::config::Config;
 use crate::relayer::compact_block::ShortTransactionID;
-use crate::types::{Peers, SyncSharedState};
+use crate::types::{Peers, SyncSharedState, TxRelayStats};
 use crate::BAD_MESSAGE_BAN_TIME;
 use ckb_chain::chain::ChainController;
 use ckb_core::block::{Block, BlockBuilder};
+use ckb_core::cell::UnresolvableError;
 use ckb_core::transaction::{ProposalShortId, Transaction};
 use ckb_core::uncle::UncleBlock;
-use ckb_network::{CKBProtocolContext, CKBProtocolHandler, PeerIndex, TargetSession};
+use ckb_core::Cycle;
+use ckb_network::{Behaviour, CKBProtocolContext, CKBProtocolHandler, PeerIndex, TargetSession};
 use ckb_protocol::{
     cast, get_root, short_transaction_id, short_transaction_id_keys, RelayMessage, RelayPayload,
 };
 use ckb_shared::chain_state::ChainState;
+use ckb_shared::tx_pool::PoolError;
 use ckb_store::ChainStore;
+use ckb_traits::ChainProvider;
 use ckb_tx_pool_executor::TxPoolExecutor;
-use ckb_util::Mutex;
+use ckb_util::{LinkedFnvHashMap, Mutex};
+use dao_utils::calculate_transaction_fee;
 use failure::Error as FailureError;
 use faketime::unix_time_as_millis;
 use flatbuffers::FlatBufferBuilder;
@@ -44,7 +65,9 @@ use fnv::{FnvHashMap, FnvHashSet};
 use log::{debug, info, trace};
 use lru_cache::LruCache;
 use numext_fixed_hash::H256;
-use std::collections::HashSet;
+use std::cmp::{self, Reverse};
+use std::collections::{HashSet, VecDeque};
+use std::mem;
 use std::sync::Arc;
 use std::time::{Duration, Instant};
 
@@ -54,7 +77,46 @@ pub const ASK_FOR_TXS_TOKEN: u64 = 1;
 pub const MAX_RELAY_PEERS: usize = 128;
 pub const TX_FILTER_SIZE: usize = 50000;
 pub const TX_ASKED_SIZE: usize = TX_FILTER_SIZE;
+pub const PEER_KNOWN_TXS_SIZE: usize = 10000;
+// The asked transactions are forgotten this long after their ask timeout if not received
+pub const TX_ASKED_EXPIRY: Duration = Duration::from_secs(10 * 60);
+pub const REJECTED_TXS_SIZE: usize = 10000;
+pub const RECENT_RELAYED_TXS_SIZE: usize = 1000;
 pub const COMPACT_BLOCK_FILTER_SIZE: usize = 8192;
+pub const MAX_RELAY_TX_HASHES: usize = 1000;
+// The maximum number of transactions asked, or relayed, in one message
+pub const MAX_GET_RELAY_TXS: usize = 32;
+pub const TX_ANNOUNCE_ABUSE_SCORE: u32 = 50;
+// The time to wait for an asked transaction before asking another announcer, doubled on each retry
+pub const TX_ASK_TIMEOUT: Duration = Duration::from_secs(30);
+pub const MAX_TX_ASK_RETRIES: u32 = 3;
+// The announcers are recorded for at most this many times `tx_asked_size` transactions, the
+// transactions announced beyond are dropped until they are announced again
+pub const MAX_TX_ANNOUNCERS_RATIO: usize = 2;
+pub const MAX_TX_ASKS_IN_FLIGHT: usize = 1000;
+pub const TX_ASK_PRIORITY_THRESHOLD: usize = 10000;
+// A fee rate hint more than this many times the real fee rate of the transaction is inflated
+pub const MAX_FEE_RATE_HINT_RATIO: u64 = 2;
+pub const INFLATED_FEE_RATE_HINT_SCORE: u32 = 10;
+pub const MAX_UNSOLICITED_TXS: u32 = 10;
+// The rejections of relayed transactions sent back to a peer per second, and at once
+pub const TX_REJECT_RATE: u32 = 10;
+pub const TX_REJECT_BURST: u32 = 100;
+// A peer is checked for the cost of its relayed transactions once this many of them are verified
+// within the accounting window
+pub const MIN_TX_RELAY_VERIFIED: u64 = 20;
+pub const MAX_REJECTED_TX_RATIO: u64 = 4;
+pub const MAX_CYCLES_PER_ACCEPTED_TX: Cycle = 100_000_000;
+pub const MAX_ORPHAN_TXS: usize = 1000;
+pub const MAX_ORPHAN_TXS_SIZE: usize = 10_000_000;
+pub const MAX_ORPHAN_TXS_PER_PEER: usize = 100;
+pub const MAX_PENDING_TX_ANNOUNCEMENTS: usize = 4096;
+pub const MAX_SEEN_TXS: usize = 10000;
+pub const MAX_SEEN_TXS_SIZE: usize = 20_000_000;
+// The relayed transactions are kept this long to reconstruct the compact blocks committing them
+pub const SEEN_TX_EXPIRY: Duration = Duration::from_secs(10 * 60);
+
+const DEFAULT_BAN_TIME: Duration = Duration::from_secs(3600 * 24 * 3);
 
 pub struct Relayer<CS> {
     chain: ChainController,
@@ -63,6 +125,7 @@ pub struct Relayer<CS> {
     // TODO refactor shared Peers struct with Synchronizer
     peers: Arc<Peers>,
     pub(crate) tx_pool_executor: Arc<TxPoolExecutor<CS>>,
+    config: Config,
 }
 
 impl<CS: ChainStore> Clone for Relayer<CS> {
@@ -73,6 +136,7 @@ impl<CS: ChainStore> Clone for Relayer<CS> {
             state: Arc::clone(&self.state),
             peers: Arc::clone(&self.peers),
             tx_pool_executor: Arc::clone(&self.tx_pool_executor),
+            config: self.config.clone(),
         }
     }
 }
@@ -82,14 +146,16 @@ impl<CS: ChainStore + 'static> Relayer<CS> {
         chain: ChainController,
         shared: Arc<SyncSharedState<CS>>,
         peers: Arc<Peers>,
+        config: Config,
     ) -> Self {
         let tx_pool_executor = Arc::new(TxPoolExecutor::new(shared.shared().clone()));
         Relayer {
             chain,
             shared,
-            state: Arc::new(RelayState::default()),
+            state: Arc::new(RelayState::new(config.tx_filter_size, config.tx_asked_size)),
             peers,
             tx_pool_executor,
+            config,
         }
     }
 
@@ -167,6 +233,41 @@ impl<CS: ChainStore + 'static> Relayer<CS> {
                 BlockProposalProcess::new(&cast!(message.payload_as_block_proposal())?, self, nc)
                     .execute()?;
             }
+            RelayPayload::RelayTransactionHashes => {
+                TransactionHashesProcess::new(
+                    &cast!(message.payload_as_relay_transaction_hashes())?,
+                    self,
+                    nc,
+                    peer,
+                )
+                .execute()?;
+            }
+            RelayPayload::GetRelayTransactions => {
+                GetTransactionsProcess::new(
+                    &cast!(message.payload_as_get_relay_transactions())?,
+                    self,
+                    nc,
+                    peer,
+                )
+                .execute()?;
+            }
+            RelayPayload::RelayTransactions => {
+                TransactionsProcess::new(
+                    &cast!(message.payload_as_relay_transactions())?,
+                    self,
+                    nc,
+                    peer,
+                )
+                .execute()?;
+            }
+            RelayPayload::RelayTransactionResult => {
+                TransactionResultProcess::new(
+                    &cast!(message.payload_as_relay_transaction_result())?,
+                    self,
+                    peer,
+                )
+                .execute()?;
+            }
             RelayPayload::NONE => {
                 cast!(None)?;
             }
@@ -239,6 +340,9 @@ impl<CS: ChainStore + 'static> Relayer<CS> {
         }
     }
 
+    // Reconstructs the block from the given transactions, then the proposed transactions in the
+    // pool, then the transactions seen recently. Returns the indexes of the transactions to get
+    // from the peer otherwise, which are the ones filled locally if the short ids collide.
     pub fn reconstruct_block(
         &self,
         chain_state: &ChainState<CS>,
@@ -261,8 +365,9 @@ impl<CS: ChainStore + 'static> Relayer<CS> {
                 }
             })
             .collect();
+        let given_short_ids: HashSet<ShortTransactionID> = txs_map.keys().cloned().collect();
 
-        if short_ids_set.is_empty() {
+        if !short_ids_set.is_empty() {
             let tx_pool = chain_state.tx_pool();
             for entry in tx_pool.proposed_txs_iter() {
                 let short_id = short_transaction_id(key0, key1, &entry.transaction.witness_hash());
@@ -277,8 +382,26 @@ impl<CS: ChainStore + 'static> Relayer<CS> {
             }
         }
 
+        if !short_ids_set.is_empty() {
+            self.state.seen_txs.scan(unix_time_as_millis(), |tx| {
+                let short_id = short_transaction_id(key0, key1, &tx.witness_hash());
+                if short_ids_set.remove(&short_id) {
+                    txs_map.insert(short_id, tx.clone());
+                }
+                !short_ids_set.is_empty()
+            });
+        }
+
         let txs_len = compact_block.prefilled_transactions.len() + compact_block.short_ids.len();
         let mut block_transactions: Vec<Option<Transaction>> = Vec::with_capacity(txs_len);
+        // The indexes of the transactions filled from the pool or the seen transactions
+        let mut local_indexes = Vec::new();
+        let mut fill = |short_id: &ShortTransactionID, block_transactions: &mut Vec<_>| {
+            if !given_short_ids.contains(short_id) && txs_map.contains_key(short_id) {
+                local_indexes.push(block_transactions.len());
+            }
+            block_transactions.push(txs_map.remove(short_id));
+        };
 
         let short_ids_iter = &mut compact_block.short_ids.iter();
         // fill transactions gap
@@ -287,13 +410,13 @@ impl<CS: ChainStore + 'static> Relayer<CS> {
             if gap > 0 {
                 short_ids_iter
                     .take(gap)
-                    .for_each(|short_id| block_transactions.push(txs_map.remove(short_id)));
+                    .for_each(|short_id| fill(short_id, &mut block_transactions));
             }
             block_transactions.push(Some(pt.transaction.clone()));
         });
 
         // append remain transactions
-        short_ids_iter.for_each(|short_id| block_transactions.push(txs_map.remove(short_id)));
+        short_ids_iter.for_each(|short_id| fill(short_id, &mut block_transactions));
 
         let missing = block_transactions.iter().any(Option::is_none);
 
@@ -309,6 +432,16 @@ impl<CS: ChainStore + 'static> Relayer<CS> {
                 .proposals(compact_block.proposals.clone())
                 .build();
 
+            // The short ids only identify the transactions up to collisions, the roots
+            // committing their full hashes tell whether the local ones are the right ones
+            let mismatched = block.header().transactions_root()
+                != compact_block.header.transactions_root()
+                || block.header().witnesses_root() != compact_block.header.witnesses_root();
+            if mismatched && !local_indexes.is_empty() {
+                debug!(target: "relay", "compact block {:x} reconstructed with mismatched transactions, get {} local ones from the peer", compact_block.header.hash(), local_indexes.len());
+                return Err(local_indexes);
+            }
+
             Ok(block)
         } else {
             let missing_indexes = block_transactions
@@ -351,6 +484,480 @@ impl<CS: ChainStore + 'static> Relayer<CS> {
         }
     }
 
+    // Returns how many of the `count` transaction hashes announced by the peer are within its
+    // rate limit, the peer is punished when it keeps exceeding the limit
+    pub(crate) fn accept_tx_announcements(
+        &self,
+        nc: &CKBProtocolContext,
+        peer: PeerIndex,
+        count: usize,
+    ) -> usize {
+        let now = unix_time_as_millis();
+        let (accepted, abused) = match self.peers.state.write().get_mut(&peer) {
+            Some(peer_state) => {
+                let (accepted, abused) = peer_state.throttle_tx_announcements(
+                    count,
+                    self.config.tx_announce_rate,
+                    self.config.tx_announce_burst,
+                    now,
+                );
+                peer_state.account_tx_relay(self.tx_relay_window(), now, |stats| {
+                    stats.announced += accepted as u64;
+                });
+                (accepted, abused)
+            }
+            None => return count,
+        };
+        if accepted < count {
+            debug!(target: "relay", "peer {} exceeds the transaction announcement rate, drop {} hashes", peer, count - accepted);
+        }
+        if abused {
+            info!(target: "relay", "peer {} keeps exceeding the transaction announcement rate, ban it", peer);
+            self.peers.misbehavior(peer, TX_ANNOUNCE_ABUSE_SCORE);
+            nc.ban_peer(peer, BAD_MESSAGE_BAN_TIME);
+        }
+        accepted
+    }
+
+    // Register the transactions announced by the peer, which are neither known nor in the
+    // transaction pool, to be asked for in `ask_for_txs`
+    pub(crate) fn add_ask_for_txs(&self, peer: PeerIndex, tx_hashes: Vec<H256>) {
+        let unknown_tx_hashes = {
+            let mut seen = FnvHashSet::default();
+            let mut tx_filter = self.state.tx_filter.lock();
+            let mut rejected_by_peer = self.peers.rejected_txs.lock();
+            tx_hashes
+                .into_iter()
+                .filter(|tx_hash| {
+                    // the transactions being verified are known too, but their announcers are
+                    // asked for them if the received ones turn out to be garbage
+                    if self.state.add_verifying_tx_announcer(tx_hash, peer) {
+                        debug!(target: "relay", "transaction({:#x}) from {} being verified, ignore it", tx_hash, peer);
+                        RelayMetrics::incr(&self.state.metrics.already_known, 1);
+                        false
+                    } else if tx_filter.contains_key(tx_hash)
+                        // the transactions held as orphans are known too
+                        || self.state.orphan_txs.contains(tx_hash)
+                        || rejected_by_peer.contains(peer, tx_hash)
+                    {
+                        debug!(target: "relay", "transaction({:#x}) from {} already known, ignore it", tx_hash, peer);
+                        RelayMetrics::incr(&self.state.metrics.already_known, 1);
+                        false
+                    } else {
+                        seen.insert(tx_hash.clone())
+                    }
+                })
+                .collect::<Vec<_>>()
+        };
+
+        // the transactions rejected recently are not fetched and verified again
+        let unknown_tx_hashes = {
+            let tip_hash = self.shared.lock_chain_state().tip_hash().to_owned();
+            let now = unix_time_as_millis();
+            let expiry = self.config.rejected_tx_expiry * 1000;
+            let mut rejected_txs = self.state.rejected_txs.lock();
+            unknown_tx_hashes
+                .into_iter()
+                .filter(|tx_hash| {
+                    let expired = rejected_txs
+                        .get(tx_hash)
+                        .map(|rejected| rejected.is_expired(now, expiry, &tip_hash));
+                    match expired {
+                        Some(false) => {
+                            debug!(target: "relay", "transaction({:#x}) from {} rejected recently, ignore it", tx_hash, peer);
+                            RelayMetrics::incr(&self.state.metrics.already_known, 1);
+                            false
+                        }
+                        Some(true) => {
+                            rejected_txs.remove(tx_hash);
+                            true
+                        }
+                        None => true,
+                    }
+                })
+                .collect::<Vec<_>>()
+        };
+
+        let (pooled_tx_hashes, new_tx_hashes): (Vec<_>, Vec<_>) = {
+            let chain_state = self.shared.lock_chain_state();
+            let tx_pool = chain_state.tx_pool();
+            // a pooled transaction of the same short id but another hash is not the announced
+            // one, which is asked for
+            unknown_tx_hashes.into_iter().partition(|tx_hash| {
+                tx_pool.get_tx_hash(&ProposalShortId::from_tx_hash(tx_hash)) == Some(tx_hash)
+            })
+        };
+        if !pooled_tx_hashes.is_empty() {
+            RelayMetrics::incr(&self.state.metrics.already_pooled, pooled_tx_hashes.len());
+            trace!(target: "relay", "{} transactions from {} already in transaction pool, ignore them", pooled_tx_hashes.len(), peer);
+            self.state.mark_as_known_txs(pooled_tx_hashes);
+        }
+        if new_tx_hashes.is_empty() {
+            return;
+        }
+
+        // Every announcer is recorded, but only the transactions not being asked yet are scheduled,
+        // the others are asked from the recorded announcers by `retry_asked_txs` if the ask times out
+        let fee_rates = new_tx_hashes
+            .iter()
+            .map(|tx_hash| self.state.fee_rate_hint(tx_hash))
+            .collect::<Vec<_>>();
+        let max_tx_announcers = self.state.tx_asked_size * MAX_TX_ANNOUNCERS_RATIO;
+        let (new_tx_hashes, saturated) = {
+            let mut tx_announcers = self.state.tx_announcers.lock();
+            let new_tx_hashes = new_tx_hashes
+                .into_iter()
+                .zip(fee_rates)
+                .filter_map(
+                    |(tx_hash, fee_rate)| match tx_announcers.get_mut(&tx_hash) {
+                        Some(announcers) => {
+                            if !announcers.peers.contains(&peer) {
+                                announcers.peers.push(peer);
+                            }
+                            announcers.fee_rate = cmp::max(announcers.fee_rate, fee_rate);
+                            None
+                        }
+                        None if tx_announcers.len() >= max_tx_announcers => {
+                            trace!(target: "relay", "too many transactions being asked for, drop tx({:#x}) from {}", tx_hash, peer);
+                            None
+                        }
+                        None => {
+                            tx_announcers
+                                .insert(tx_hash.clone(), TxAnnouncers::new(peer, fee_rate));
+                            Some(tx_hash)
+                        }
+                    },
+                )
+                .collect::<Vec<_>>();
+            let saturated = tx_announcers.len() > self.config.tx_ask_priority_threshold;
+            (new_tx_hashes, saturated)
+        };
+        if new_tx_hashes.is_empty() {
+            return;
+        }
+        // Under backpressure the new transactions wait, `retry_asked_txs` asks the waiting ones
+        // with the highest fee rate hints first as the asks in flight complete
+        if saturated {
+            trace!(target: "relay", "too many transactions being asked for, queue {} transactions from {}", new_tx_hashes.len(), peer);
+            self.shed_tx_asks();
+            return;
+        }
+
+        debug!(target: "relay", "{} transactions from {} not known, get them from the peer", new_tx_hashes.len(), peer);
+        let last_ask_timeouts = {
+            let tx_already_asked = self.state.tx_already_asked.lock();
+            new_tx_hashes
+                .iter()
+                .map(|tx_hash| tx_already_asked.get(tx_hash).cloned())
+                .collect::<Vec<_>>()
+        };
+        // The asks beyond the in-flight limit of the peer are deferred, `retry_asked_txs` asks
+        // them from the announcers once they have room
+        let max_tx_asks_in_flight = self.config.max_tx_asks_in_flight;
+        let next_ask_timeouts = match self.peers.state.write().get_mut(&peer) {
+            Some(peer_state) => new_tx_hashes
+                .into_iter()
+                .zip(last_ask_timeouts)
+                .filter_map(|(tx_hash, last_ask_timeout)| {
+                    if peer_state.tx_asks_in_flight() >= max_tx_asks_in_flight {
+                        trace!(target: "relay", "peer {} has too many asks in flight, defer asking tx({:#x})", peer, tx_hash);
+                        return None;
+                    }
+                    peer_state
+                        .add_ask_for_tx(tx_hash.clone(), last_ask_timeout)
+                        .map(|next_ask_timeout| (tx_hash, next_ask_timeout))
+                })
+                .collect::<Vec<_>>(),
+            None => return,
+        };
+        RelayMetrics::incr(&self.state.metrics.asked, next_ask_timeouts.len());
+        let mut tx_announcers = self.state.tx_announcers.lock();
+        let mut tx_already_asked = self.state.tx_already_asked.lock();
+        for (tx_hash, next_ask_timeout) in next_ask_timeouts {
+            if let Some(announcers) = tx_announcers.get_mut(&tx_hash) {
+                announcers.asked = Some((peer, next_ask_timeout + TX_ASK_TIMEOUT));
+            }
+            tx_already_asked.insert(tx_hash, next_ask_timeout);
+        }
+        // the oldest asks are forgotten, worst case the transactions are asked again earlier
+        let mut evicted = 0;
+        while tx_already_asked.len() > self.state.tx_asked_size {
+            tx_already_asked.pop_front();
+            evicted += 1;
+        }
+        if evicted > 0 {
+            debug!(target: "relay", "tx_already_asked is full, evict {} transactions", evicted);
+        }
+    }
+
+    // Drop the queued asks with the lowest fee rate hints while more than `tx_asked_size`
+    // transactions are being asked for, they are asked for again if announced again
+    fn shed_tx_asks(&self) {
+        let shed = {
+            let mut tx_announcers = self.state.tx_announcers.lock();
+            let excess = tx_announcers.len().saturating_sub(self.state.tx_asked_size);
+            if excess == 0 {
+                return;
+            }
+            let mut queued = tx_announcers
+                .iter()
+                .filter(|(_, announcers)| announcers.asked.is_none())
+                .map(|(tx_hash, announcers)| (announcers.fee_rate, tx_hash.clone()))
+                .collect::<Vec<_>>();
+            queued.sort_by_key(|(fee_rate, _)| *fee_rate);
+            queued
+                .into_iter()
+                .take(excess)
+                .map(|(_, tx_hash)| {
+                    tx_announcers.remove(&tx_hash);
+                    tx_hash
+                })
+                .collect::<Vec<_>>()
+        };
+        if shed.is_empty() {
+            return;
+        }
+
+        debug!(target: "relay", "too many transactions being asked for, shed {} with the lowest fee rate hints", shed.len());
+        RelayMetrics::incr(&self.state.metrics.shed, shed.len());
+        {
+            let mut tx_already_asked = self.state.tx_already_asked.lock();
+            let mut fee_rate_hints = self.state.fee_rate_hints.lock();
+            for tx_hash in &shed {
+                tx_already_asked.remove(tx_hash);
+                fee_rate_hints.remove(tx_hash);
+            }
+        }
+        for peer_state in self.peers.state.write().values_mut() {
+            for tx_hash in &shed {
+                peer_state.remove_ask_for_tx(tx_hash);
+            }
+        }
+    }
+
+    // Remember the fee rates the peer hints for the transactions it announces, they only order
+    // the asks under backpressure, 0 means no hint
+    pub(crate) fn add_fee_rate_hints(&self, peer: PeerIndex, hints: &[(H256, u64)]) {
+        let mut fee_rate_hints = self.state.fee_rate_hints.lock();
+        for (tx_hash, fee_rate) in hints.iter().filter(|(_, fee_rate)| *fee_rate > 0) {
+            if let Some(peer_hints) = fee_rate_hints.get_refresh(tx_hash) {
+                peer_hints.insert(peer, *fee_rate);
+                continue;
+            }
+            let mut peer_hints = FnvHashMap::default();
+            peer_hints.insert(peer, *fee_rate);
+            fee_rate_hints.insert(tx_hash.clone(), peer_hints);
+        }
+    }
+
+    // Compare the fee rates hinted by the announcers of the accepted transaction with its real
+    // fee rate, the peers inflating their hints are punished
+    fn check_fee_rate_hints(&self, tx_hash: &H256, fee_rate: u64) {
+        let hints = match self.state.fee_rate_hints.lock().remove(tx_hash) {
+            Some(hints) => hints,
+            None => return,
+        };
+        let max_fee_rate = fee_rate.saturating_mul(MAX_FEE_RATE_HINT_RATIO);
+        for (peer, hint) in hints {
+            if hint > max_fee_rate {
+                debug!(target: "relay", "peer {} hints fee rate {} for tx: {:x}, real fee rate {}", peer, hint, tx_hash, fee_rate);
+                self.peers.misbehavior(peer, INFLATED_FEE_RATE_HINT_SCORE);
+            }
+        }
+    }
+
+    // The fee of the transaction in the pool in shannons per 1000 bytes, `None` if it can not be
+    // resolved
+    fn pooled_fee_rate(&self, tx: &Transaction) -> Option<u64> {
+        let chain_state = self.shared.lock_chain_state();
+        let rtx = chain_state.resolve_tx_from_pending_and_proposed(tx).ok()?;
+        let fee = calculate_transaction_fee(Arc::clone(chain_state.store()), &rtx)?;
+        Some(fee.as_u64().saturating_mul(1000) / tx.serialized_size() as u64)
+    }
+
+    // Buffer the transactions announced during initial block download, the oldest announcements
+    // are dropped when the buffer is full. They are asked for by `drain_pending_tx_announcements`
+    // once the chain is synced.
+    pub(crate) fn add_pending_tx_announcements(&self, peer: PeerIndex, tx_hashes: Vec<H256>) {
+        let mut pending = self.state.pending_tx_announcements.lock();
+        for tx_hash in tx_hashes {
+            if !pending.contains_key(&tx_hash) {
+                pending.insert(tx_hash, peer);
+            }
+        }
+        let mut evicted = 0;
+        while pending.len() > MAX_PENDING_TX_ANNOUNCEMENTS {
+            pending.pop_front();
+            evicted += 1;
+        }
+        if evicted > 0 {
+            debug!(target: "relay", "pending_tx_announcements is full, evict {} transactions", evicted);
+        }
+    }
+
+    // Ask for the transactions announced during initial block download, except the ones committed
+    // in the blocks synced since, the known and pooled ones are filtered by `add_ask_for_txs`
+    pub(crate) fn drain_pending_tx_announcements(&self) {
+        let pending = {
+            let mut pending = self.state.pending_tx_announcements.lock();
+            if pending.is_empty() {
+                return;
+            }
+            mem::replace(&mut *pending, LinkedFnvHashMap::default())
+        };
+
+        let store = self.shared.shared().store();
+        let mut committed = Vec::new();
+        let mut peer_txs: FnvHashMap<PeerIndex, Vec<H256>> = FnvHashMap::default();
+        for (tx_hash, peer) in pending {
+            if store.get_transaction_info(&tx_hash).is_some() {
+                committed.push(tx_hash);
+            } else {
+                peer_txs.entry(peer).or_insert_with(Vec::new).push(tx_hash);
+            }
+        }
+        if !committed.is_empty() {
+            debug!(target: "relay", "{} transactions announced during IBD already committed, ignore them", committed.len());
+            self.state.mark_as_known_txs(committed);
+        }
+        for (peer, tx_hashes) in peer_txs {
+            self.add_ask_for_txs(peer, tx_hashes);
+        }
+    }
+
+    // Ask the transactions again from another announcer when the asked peer does not respond in
+    // time or disconnects, gives up once it failed `MAX_TX_ASK_RETRIES` times or no announcer is left.
+    // The deferred asks are sent too, to the first announcer with room for more asks in flight.
+    pub(crate) fn retry_asked_txs(&self, nc: &CKBProtocolContext, now: Instant) {
+        // The number of asks each connected peer has room for
+        let mut capacities = self
+            .peers
+            .state
+            .read()
+            .iter()
+            .map(|(peer, peer_state)| {
+                let in_flight = peer_state.tx_asks_in_flight();
+                (
+                    *peer,
+                    self.config.max_tx_asks_in_flight.saturating_sub(in_flight),
+                )
+            })
+            .collect::<FnvHashMap<_, _>>();
+        let mut retries = Vec::new();
+        let mut exhausted = Vec::new();
+        // The asks taken away from the peers they timed out on
+        let mut timed_out = Vec::new();
+        {
+            let mut tx_announcers = self.state.tx_announcers.lock();
+            // Under backpressure the transactions with the highest fee rate hints are asked for
+            // first, and only while fewer than the threshold are being asked for
+            let threshold = self.config.tx_ask_priority_threshold;
+            let saturated = tx_announcers.len() > threshold;
+            let mut in_flight = tx_announcers
+                .values()
+                .filter(|announcers| announcers.asked.is_some())
+                .count();
+            let mut tx_hashes = tx_announcers.keys().cloned().collect::<Vec<_>>();
+            if saturated {
+                tx_hashes.sort_by_key(|tx_hash| Reverse(tx_announcers[tx_hash].fee_rate));
+            }
+            for tx_hash in tx_hashes {
+                let announcers = match tx_announcers.get_mut(&tx_hash) {
+                    Some(announcers) => announcers,
+                    None => continue,
+                };
+                // `None` when the ask is deferred
+                let asked_peer = match announcers.asked {
+                    Some((peer, timeout)) if timeout > now && capacities.contains_key(&peer) => {
+                        continue;
+                    }
+                    Some((peer, _)) => Some(peer),
+                    None => None,
+                };
+                if let Some(peer) = asked_peer {
+                    announcers.retries += 1;
+                    announcers.asked = None;
+                    in_flight -= 1;
+                    // the ask no longer counts against the peer it timed out on
+                    if let Some(capacity) = capacities.get_mut(&peer) {
+                        *capacity += 1;
+                    }
+                    timed_out.push((peer, tx_hash.clone()));
+                }
+                announcers
+                    .peers
+                    .retain(|peer| Some(*peer) != asked_peer && capacities.contains_key(peer));
+                if announcers.retries >= MAX_TX_ASK_RETRIES || announcers.peers.is_empty() {
+                    exhausted.push(tx_hash);
+                    continue;
+                }
+                if saturated && in_flight >= threshold {
+                    continue;
+                }
+                // the first announcer with room, or ask later
+                let peer = match announcers
+                    .peers
+                    .iter()
+                    .find(|peer| capacities.get(peer).map_or(false, |capacity| *capacity > 0))
+                {
+                    Some(peer) => *peer,
+                    None => continue,
+                };
+                if let Some(capacity) = capacities.get_mut(&peer) {
+                    *capacity -= 1;
+                }
+                announcers.asked =
+                    Some((peer, now + TX_ASK_TIMEOUT * 2u32.pow(announcers.retries)));
+                in_flight += 1;
+                retries.push((peer, tx_hash));
+            }
+            for tx_hash in &exhausted {
+                tx_announcers.remove(tx_hash);
+            }
+        }
+
+        if !exhausted.is_empty() {
+            debug!(target: "relay", "give up asking for {} transactions", exhausted.len());
+            {
+                let mut tx_already_asked = self.state.tx_already_asked.lock();
+                let mut fee_rate_hints = self.state.fee_rate_hints.lock();
+                for tx_hash in &exhausted {
+                    tx_already_asked.remove(tx_hash);
+                    fee_rate_hints.remove(tx_hash);
+                }
+            }
+            for peer_state in self.peers.state.write().values_mut() {
+                for tx_hash in &exhausted {
+                    peer_state.remove_ask_for_tx(tx_hash);
+                }
+            }
+        }
+
+        let mut retries_by_peer: FnvHashMap<PeerIndex, Vec<H256>> = FnvHashMap::default();
+        for (peer, tx_hash) in retries {
+            retries_by_peer.entry(peer).or_default().push(tx_hash);
+        }
+        {
+            let mut peers_state = self.peers.state.write();
+            for (peer, tx_hash) in &timed_out {
+                if let Some(peer_state) = peers_state.get_mut(peer) {
+                    peer_state.remove_ask_for_tx(tx_hash);
+                }
+            }
+            for (peer, tx_hashes) in &retries_by_peer {
+                if let Some(peer_state) = peers_state.get_mut(peer) {
+                    for tx_hash in tx_hashes {
+                        peer_state.add_asked_tx(tx_hash.clone());
+                    }
+                }
+            }
+        }
+        for (peer, tx_hashes) in retries_by_peer {
+            debug!(target: "relay", "ask for {} transactions again from {}", tx_hashes.len(), peer);
+            send_get_transactions(nc, peer, &tx_hashes);
+        }
+    }
+
     // Ask for relay transaction by hash from all peers
     pub fn ask_for_txs(&self, nc: &CKBProtocolContext) {
         for (peer, peer_state) in self.peers.state.write().iter_mut() {
@@ -366,6 +973,7 @@ impl<CS: ChainStore + 'static> Relayer<CS> {
                     !already_known
                 })
                 .collect::<Vec<_>>();
+            peer_state.mark_asks_sent(&tx_hashes);
             if !tx_hashes.is_empty() {
                 debug!(
                     target: "relay",
@@ -374,19 +982,376 @@ impl<CS: ChainStore + 'static> Relayer<CS> {
                     peer,
                 );
             }
-            for tx_hash in tx_hashes {
-                let fbb = &mut FlatBufferBuilder::new();
-                let message = RelayMessage::build_get_transaction(fbb, &tx_hash);
-                fbb.finish(message, None);
-                let data = fbb.finished_data().into();
-                nc.send_message_to(*peer, data);
+            send_get_transactions(nc, *peer, &tx_hashes);
+        }
+    }
+
+    // The peer knows the transactions it announces or relays to us, they are never relayed back
+    pub(crate) fn mark_as_known_by_peer(&self, peer: PeerIndex, tx_hashes: &[H256]) {
+        let mut known_txs = self.peers.known_txs.lock();
+        for tx_hash in tx_hashes {
+            known_txs.insert(peer, tx_hash.to_owned());
+        }
+    }
+
+    // Relays the hash of the accepted transaction to the connected peers not knowing it yet, with
+    // its fee rate as a hint, 0 if not known
+    pub(crate) fn broadcast_tx_hash(
+        &self,
+        nc: &CKBProtocolContext,
+        peer: PeerIndex,
+        tx_hash: &H256,
+        fee_rate: u64,
+    ) {
+        let selected_peers: Vec<PeerIndex> = {
+            let mut known_txs = self.peers.known_txs.lock();
+            nc.connected_peers()
+                .into_iter()
+                .filter(|target_peer| {
+                    known_txs.insert(*target_peer, tx_hash.clone()) && (peer != *target_peer)
+                })
+                .take(MAX_RELAY_PEERS)
+                .collect()
+        };
+        if selected_peers.is_empty() {
+            return;
+        }
+
+        let fbb = &mut FlatBufferBuilder::new();
+        let message = RelayMessage::build_transaction_hash(fbb, tx_hash, fee_rate);
+        fbb.finish(message, None);
+        let data = fbb.finished_data().into();
+        nc.filter_broadcast(TargetSession::Multi(selected_peers), data);
+    }
+
+    // Looks up the transaction asked for by a peer in the pool, then among the transactions relayed
+    // recently, then in the committed blocks, served with the cycles cached when it was verified.
+    // The scripts are never run again for a peer, a committed transaction whose cycles are no
+    // longer cached is not served.
+    pub(crate) fn get_relay_tx(&self, tx_hash: &H256) -> Option<(Transaction, Cycle)> {
+        let short_id = ProposalShortId::from_tx_hash(tx_hash);
+        let entry = self
+            .shared
+            .lock_chain_state()
+            .get_tx_with_cycles_from_pool(&short_id)
+            .filter(|(tx, _)| tx.hash() == tx_hash)
+            .and_then(|(tx, cycles)| cycles.map(|cycles| (tx, cycles)));
+        let entry = entry
+            .or_else(|| self.state.recent_relayed_txs.lock().get(tx_hash).cloned())
+            .or_else(|| {
+                let (tx, _) = self.shared.shared().store().get_transaction(tx_hash)?;
+                let cycles = self.shared.lock_txs_verify_cache().get(tx_hash).cloned()?;
+                Some((tx, cycles))
+            });
+        if entry.is_none() {
+            RelayMetrics::incr(&self.state.metrics.tx_not_found, 1);
+        }
+        entry
+    }
+
+    // Adds the relayed transaction into the pool and relays its hash to the other peers. The
+    // transaction is held as an orphan if it spends outputs of unknown transactions, and the
+    // orphans it unblocks are retried once it is accepted, recursively.
+    //
+    // Returns true if the transaction may be valid as relayed by another peer, that is it
+    // exceeds the claimed cycles or fails its scripts, whose witnesses the hash does not commit.
+    pub(crate) fn accept_relayed_tx(
+        &self,
+        nc: &CKBProtocolContext,
+        peer: PeerIndex,
+        tx: Transaction,
+        relay_cycles: Cycle,
+    ) -> bool {
+        let relayed_tx_hash = tx.hash().to_owned();
+        let mut garbage = false;
+        RelayMetrics::incr(&self.state.metrics.received, 1);
+        self.mark_as_known_by_peer(peer, &[tx.hash().to_owned()]);
+        self.state
+            .seen_txs
+            .insert(tx.clone(), unix_time_as_millis());
+        let mut queue = VecDeque::new();
+        queue.push_back((tx, relay_cycles, peer));
+        while let Some((tx, relay_cycles, peer)) = queue.pop_front() {
+            let tx_hash = tx.hash().to_owned();
+            // The claimed cycles bound the verification, the transaction is accepted with the
+            // measured cycles, which are what we relay onward, but the peer is reported if the
+            // claim is not exact
+            match self
+                .tx_pool_executor
+                .verify_and_add_tx_to_pool_with_max_cycles(tx.clone(), relay_cycles)
+            {
+                Ok(cycles) => {
+                    if cycles != relay_cycles {
+                        debug!(
+                            target: "relay",
+                            "peer {} relay wrong cycles tx: {:x} real cycles {} wrong cycles {}",
+                            peer, tx_hash, cycles, relay_cycles,
+                        );
+                        nc.report_peer(peer, Behaviour::MismatchedTxCycles);
+                    }
+                    RelayMetrics::incr(&self.state.metrics.accepted, 1);
+                    self.send_tx_result(nc, peer, &tx_hash, RelayTxResult::Accepted);
+                    self.account_verified_tx(nc, peer, true, cycles);
+                    // the hints are advisory, the pool orders the transaction by its real fee
+                    let fee_rate = self.pooled_fee_rate(&tx);
+                    if let Some(fee_rate) = fee_rate {
+                        self.check_fee_rate_hints(&tx_hash, fee_rate);
+                    }
+                    // kept to answer the peers asking for it after it leaves the pool
+                    self.state
+                        .recent_relayed_txs
+                        .lock()
+                        .insert(tx_hash.clone(), (tx, cycles));
+                    self.broadcast_tx_hash(nc, peer, &tx_hash, fee_rate.unwrap_or(0));
+
+                    let orphans = self.state.orphan_txs.remove_orphans_by_parent(&tx_hash);
+                    if !orphans.is_empty() {
+                        debug!(target: "relay", "transaction {:x} unblocks {} orphan transactions", tx_hash, orphans.len());
+                    }
+                    queue.extend(
+                        orphans
+                            .into_iter()
+                            .map(|orphan| (orphan.transaction, orphan.cycles, orphan.peer)),
+                    );
+                }
+                Err(PoolError::InvalidTx(err)) if err.is_exceeded_maximum_cycles() => {
+                    debug!(
+                        target: "relay",
+                        "peer {} relay tx: {:x} consuming more cycles than the claimed {}",
+                        peer, tx_hash, relay_cycles,
+                    );
+                    RelayMetrics::incr(&self.state.metrics.rejected_cycles, 1);
+                    nc.report_peer(peer, Behaviour::MismatchedTxCycles);
+                    self.send_tx_result(nc, peer, &tx_hash, RelayTxResult::Invalid);
+                    self.account_verified_tx(nc, peer, false, relay_cycles);
+                    garbage = tx_hash == relayed_tx_hash;
+                }
+                Err(PoolError::UnresolvableTransaction(UnresolvableError::Unknown(out_points))) => {
+                    let missing_parents = out_points
+                        .iter()
+                        .filter_map(|out_point| out_point.cell.as_ref())
+                        .map(|cell| cell.tx_hash.to_owned())
+                        .collect::<FnvHashSet<_>>()
+                        .into_iter()
+                        .collect();
+                    debug!(target: "relay", "peer {} relay an orphan tx: {:x}", peer, tx_hash);
+                    RelayMetrics::incr(&self.state.metrics.orphaned, 1);
+                    self.send_tx_result(nc, peer, &tx_hash, RelayTxResult::Temporary);
+                    let evicted =
+                        self.state
+                            .orphan_txs
+                            .insert(tx, relay_cycles, peer, missing_parents);
+                    // the evicted orphans can be asked again when they are announced
+                    let mut tx_filter = self.state.tx_filter.lock();
+                    for tx_hash in evicted {
+                        tx_filter.remove(&tx_hash);
+                    }
+                }
+                Err(err) => {
+                    let reason = RejectReason::from_pool_error(&err);
+                    // an immature transaction may become valid as the chain grows
+                    let result = match reason {
+                        Some(RejectReason::Script) | Some(RejectReason::Malformed) => {
+                            RelayTxResult::Invalid
+                        }
+                        Some(RejectReason::Immature) | None => RelayTxResult::Temporary,
+                    };
+                    self.send_tx_result(nc, peer, &tx_hash, result);
+                    if result == RelayTxResult::Invalid {
+                        // the scripts are run within the claimed cycles, the malformed
+                        // transactions fail before running them
+                        let cycles = if reason == Some(RejectReason::Script) {
+                            relay_cycles
+                        } else {
+                            0
+                        };
+                        self.account_verified_tx(nc, peer, false, cycles);
+                    }
+                    if let Some(reason) = reason {
+                        let counter = match reason {
+                            RejectReason::Script => &self.state.metrics.rejected_script,
+                            RejectReason::Immature => &self.state.metrics.rejected_since,
+                            RejectReason::Malformed => &self.state.metrics.rejected_malformed,
+                        };
+                        RelayMetrics::incr(counter, 1);
+                        self.reject_tx(tx_hash.clone(), reason);
+                        garbage = tx_hash == relayed_tx_hash && reason == RejectReason::Script;
+                    }
+                    if err.is_bad_tx() {
+                        debug!(target: "relay", "peer {} relay a invalid tx: {:x}, error: {:?}", peer, tx_hash, err);
+                        sentry::capture_message(
+                            &format!(
+                                "ban peer {} {:?}, reason: relay invalid tx: {:?}, error: {:?}",
+                                peer, DEFAULT_BAN_TIME, tx, err
+                            ),
+                            sentry::Level::Info,
+                        );
+                        nc.ban_peer(peer, DEFAULT_BAN_TIME);
+                    } else {
+                        debug!(target: "relay", "peer {} relay a conflict or missing input tx: {:x}, error: {:?}", peer, tx_hash, err);
+                    }
+                }
+            }
+        }
+        garbage
+    }
+
+    // Ask for the transaction from the peers announcing it while it was verified, once the copy
+    // received turns out to be garbage
+    pub(crate) fn ask_fallback_announcers(&self, tx_hash: &H256, peers: Vec<PeerIndex>) {
+        if peers.is_empty() {
+            return;
+        }
+        debug!(target: "relay", "ask for tx({:#x}) again from {} fallback announcers", tx_hash, peers.len());
+        self.state.tx_filter.lock().remove(tx_hash);
+        self.state.rejected_txs.lock().remove(tx_hash);
+        for peer in peers {
+            self.add_ask_for_txs(peer, vec![tx_hash.to_owned()]);
+        }
+    }
+
+    // Tells the peer relaying the transaction whether it is accepted, the rejections are rate
+    // limited and dropped beyond the limit
+    fn send_tx_result(
+        &self,
+        nc: &CKBProtocolContext,
+        peer: PeerIndex,
+        tx_hash: &H256,
+        result: RelayTxResult,
+    ) {
+        if result != RelayTxResult::Accepted {
+            let allowed = self
+                .peers
+                .state
+                .write()
+                .get_mut(&peer)
+                .map_or(true, |peer_state| {
+                    peer_state.throttle_tx_rejects(
+                        self.config.tx_reject_rate,
+                        self.config.tx_reject_burst,
+                        unix_time_as_millis(),
+                    )
+                });
+            if !allowed {
+                trace!(target: "relay", "too many rejections sent to peer {}, drop the one of tx({:#x})", peer, tx_hash);
+                return;
+            }
+        }
+        let fbb = &mut FlatBufferBuilder::new();
+        let message = RelayMessage::build_transaction_result(fbb, tx_hash, result as u8);
+        fbb.finish(message, None);
+        nc.send_message_to(peer, fbb.finished_data().into());
+    }
+
+    // The transaction relay accounting window in ms
+    fn tx_relay_window(&self) -> u64 {
+        self.config.tx_relay_window * 1000
+    }
+
+    // Accounts the verification of a transaction relayed by the peer. The peer is reported once
+    // enough of its transactions are verified within the window, and they are mostly invalid, or
+    // cost too many cycles per accepted one, then its accounting starts over.
+    fn account_verified_tx(
+        &self,
+        nc: &CKBProtocolContext,
+        peer: PeerIndex,
+        accepted: bool,
+        cycles: Cycle,
+    ) {
+        let window = self.tx_relay_window();
+        let now = unix_time_as_millis();
+        let costly = match self.peers.state.write().get_mut(&peer) {
+            Some(peer_state) => {
+                peer_state.account_tx_relay(window, now, |stats| {
+                    if accepted {
+                        stats.accepted += 1;
+                    } else {
+                        stats.rejected += 1;
+                    }
+                    stats.cycles = stats.cycles.saturating_add(cycles);
+                });
+                let stats = peer_state.tx_relay_stats(window, now);
+                let costly = stats.accepted + stats.rejected >= MIN_TX_RELAY_VERIFIED
+                    && (stats.rejected
+                        > stats
+                            .accepted
+                            .saturating_mul(self.config.max_rejected_tx_ratio)
+                        || stats.cycles / cmp::max(stats.accepted, 1)
+                            > self.config.max_cycles_per_accepted_tx);
+                if costly {
+                    peer_state.clear_tx_relay_stats();
+                    Some(stats)
+                } else {
+                    None
+                }
+            }
+            None => None,
+        };
+        if let Some(stats) = costly {
+            info!(
+                target: "relay",
+                "peer {} relays {} accepted and {} invalid transactions costing {} cycles recently, report it",
+                peer, stats.accepted, stats.rejected, stats.cycles,
+            );
+            nc.report_peer(peer, Behaviour::CostlyTxRelay);
+        }
+    }
+
+    // The peer tells us the transaction is invalid, it is neither asked from the peer nor relayed
+    // to it any more. The other announcers are still asked, for a peer's word is not enough to
+    // reject the transaction.
+    pub(crate) fn on_tx_rejected_by_peer(&self, peer: PeerIndex, tx_hash: H256) {
+        self.mark_as_known_by_peer(peer, &[tx_hash.clone()]);
+        self.peers.rejected_txs.lock().insert(peer, tx_hash.clone());
+        if let Some(peer_state) = self.peers.state.write().get_mut(&peer) {
+            peer_state.remove_ask_for_tx(&tx_hash);
+        }
+        // the ask from the peer times out at once, `retry_asked_txs` asks another announcer
+        if let Some(announcers) = self.state.tx_announcers.lock().get_mut(&tx_hash) {
+            announcers.peers.retain(|announcer| *announcer != peer);
+            if let Some((asked_peer, timeout)) = announcers.asked.as_mut() {
+                if *asked_peer == peer {
+                    *timeout = Instant::now();
+                }
             }
         }
     }
 
+    // Remember the transaction failing verification, and stop asking for it
+    fn reject_tx(&self, tx_hash: H256, reason: RejectReason) {
+        let tip_hash = if reason == RejectReason::Immature {
+            Some(self.shared.lock_chain_state().tip_hash().to_owned())
+        } else {
+            None
+        };
+        self.state.tx_announcers.lock().remove(&tx_hash);
+        self.state.tx_already_asked.lock().remove(&tx_hash);
+        self.state.rejected_txs.lock().insert(
+            tx_hash,
+            RejectedTx {
+                reason,
+                timestamp: unix_time_as_millis(),
+                tip_hash,
+            },
+        );
+    }
+
     pub fn peers(&self) -> Arc<Peers> {
         Arc::clone(&self.peers)
     }
+
+    /// The counters of the transaction relay pipeline
+    pub fn metrics(&self) -> RelayStats {
+        self.state.metrics.snapshot()
+    }
+
+    /// The transaction relay traffic of the peer over the accounting window, `None` if the peer
+    /// is not connected
+    pub fn tx_relay_stats(&self, peer: PeerIndex) -> Option<TxRelayStats> {
+        self.peers.state.read().get(&peer).map(|peer_state| {
+            peer_state.tx_relay_stats(self.tx_relay_window(), unix_time_as_millis())
+        })
+    }
 }
 
 impl<CS: ChainStore + 'static> CKBProtocolHandler for Relayer<CS> {
@@ -401,11 +1366,6 @@ impl<CS: ChainStore + 'static> CKBProtocolHandler for Relayer<CS> {
         peer_index: PeerIndex,
         data: bytes::Bytes,
     ) {
-        // If self is in the IBD state, don't process any relayer message.
-        if self.shared.is_initial_block_download() {
-            return;
-        }
-
         let msg = match get_root::<RelayMessage>(&data) {
             Ok(msg) => msg,
             _ => {
@@ -415,6 +1375,15 @@ impl<CS: ChainStore + 'static> CKBProtocolHandler for Relayer<CS> {
             }
         };
 
+        // If self is in the IBD state, only the transaction announcements are processed, they
+        // are buffered until the chain is synced, see `add_pending_tx_announcements`.
+        if self.shared.is_initial_block_download() {
+            match msg.payload_type() {
+                RelayPayload::RelayTransactionHash | RelayPayload::RelayTransactionHashes => {}
+                _ => return,
+            }
+        }
+
         debug!(target: "relay", "received msg {:?} from {}", msg.payload_type(), peer_index);
         let start_time = Instant::now();
         self.process(nc, peer_index, msg);
@@ -439,6 +1408,8 @@ impl<CS: ChainStore + 'static> CKBProtocolHandler for Relayer<CS> {
 
     fn disconnected(&mut self, _nc: Arc<dyn CKBProtocolContext + Sync>, peer_index: PeerIndex) {
         info!(target: "relay", "RelayProtocol.disconnected peer={}", peer_index);
+        self.peers.known_txs.lock().remove(peer_index);
+        self.peers.rejected_txs.lock().remove(peer_index);
         // TODO
     }
 
@@ -452,43 +1423,251 @@ impl<CS: ChainStore + 'static> CKBProtocolHandler for Relayer<CS> {
         trace!(target: "relay", "start notify token={}", token);
         match token {
             TX_PROPOSAL_TOKEN => self.prune_tx_proposal_request(nc.as_ref()),
-            ASK_FOR_TXS_TOKEN => self.ask_for_txs(nc.as_ref()),
+            ASK_FOR_TXS_TOKEN => {
+                self.drain_pending_tx_announcements();
+                self.ask_for_txs(nc.as_ref());
+                self.retry_asked_txs(nc.as_ref(), Instant::now());
+                self.state.clear_expired_asked_txs(Instant::now());
+            }
             _ => unreachable!(),
         }
         trace!(target: "relay", "finished notify token={} cost={:?}", token, start_time.elapsed());
     }
 }
 
+/// The coarse result of the verification of a relayed transaction, sent back to the peer
+/// relaying it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RelayTxResult {
+    Accepted = 0,
+    /// The transaction never becomes valid
+    Invalid = 1,
+    /// The transaction may become valid later, such as an orphan or when the pool is full
+    Temporary = 2,
+}
+
+impl RelayTxResult {
+    pub fn from_u8(result: u8) -> Option<Self> {
+        match result {
+            0 => Some(RelayTxResult::Accepted),
+            1 => Some(RelayTxResult::Invalid),
+            2 => Some(RelayTxResult::Temporary),
+            _ => None,
+        }
+    }
+}
+
+/// The category of the deterministic failure a relayed transaction is rejected for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RejectReason {
+    /// The scripts fail or are invalid
+    Script,
+    /// The structure or the capacities of the transaction are invalid
+    Malformed,
+    /// The since or the cellbase maturity is not satisfied at the current tip
+    Immature,
+}
+
+impl RejectReason {
+    // `None` for the failures caused by transient conditions, such as missing inputs or a full
+    // pool, the transaction may be valid later
+    fn from_pool_error(err: &PoolError) -> Option<Self> {
+        use ckb_verification::TransactionError::*;
+        match err {
+            PoolError::InvalidTx(err) => match err {
+                InvalidScript | ScriptFailure(_) | InvalidSignature => Some(RejectReason::Script),
+                Immature | CellbaseImmaturity => Some(RejectReason::Immature),
+                CapacityOverflow
+                | InsufficientCellCapacity { .. }
+                | DuplicateDeps
+                | Empty
+                | NullInput { .. }
+                | InsufficientWitnesses { .. }
+                | TooManyWitnesses { .. }
+                | OutputsSumOverflow { .. }
+                | CapacitySumOverflow { .. }
+                | Version
+                | InvalidSince => Some(RejectReason::Malformed),
+            },
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RejectedTx {
+    pub reason: RejectReason,
+    /// Unix time in milliseconds
+    pub timestamp: u64,
+    /// The tip the transaction is rejected at, only for `RejectReason::Immature`
+    pub tip_hash: Option<H256>,
+}
+
+impl RejectedTx {
+    fn is_expired(&self, now: u64, expiry: u64, tip_hash: &H256) -> bool {
+        now.saturating_sub(self.timestamp) >= expiry
+            || self
+                .tip_hash
+                .as_ref()
+                .map_or(false, |hash| hash != tip_hash)
+    }
+}
+
+// Ask for the transactions by batches of at most `MAX_GET_RELAY_TXS` hashes
+fn send_get_transactions(nc: &CKBProtocolContext, peer: PeerIndex, tx_hashes: &[H256]) {
+    for chunk in tx_hashes.chunks(MAX_GET_RELAY_TXS) {
+        let fbb = &mut FlatBufferBuilder::new();
+        let message = RelayMessage::build_get_transactions(fbb, chunk);
+        fbb.finish(message, None);
+        let data = fbb.finished_data().into();
+        nc.send_message_to(peer, data);
+    }
+}
+
+/// The peers which announced a transaction being asked for
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TxAnnouncers {
+    /// The announcers which may still be asked, in the order they announced the transaction
+    pub peers: Vec<PeerIndex>,
+    /// The peer being asked and the time the ask times out
+    pub asked: Option<(PeerIndex, Instant)>,
+    pub retries: u32,
+    /// The highest fee rate hinted by the announcers, in shannons per 1000 bytes
+    pub fee_rate: u64,
+}
+
+impl TxAnnouncers {
+    fn new(peer: PeerIndex, fee_rate: u64) -> Self {
+        TxAnnouncers {
+            peers: vec![peer],
+            asked: None,
+            retries: 0,
+            fee_rate,
+        }
+    }
+}
+
 pub struct RelayState {
     pub pending_compact_blocks: Mutex<FnvHashMap<H256, CompactBlock>>,
     pub inflight_proposals: Mutex<FnvHashSet<ProposalShortId>>,
     pub pending_proposals_request: Mutex<FnvHashMap<ProposalShortId, FnvHashSet<PeerIndex>>>,
     pub tx_filter: Mutex<LruCache<H256, ()>>,
-    pub tx_already_asked: Mutex<LruCache<H256, Instant>>,
+    /// The next ask timeout of each asked transaction, in insertion order
+    pub tx_already_asked: Mutex<LinkedFnvHashMap<H256, Instant>>,
+    pub tx_announcers: Mutex<FnvHashMap<H256, TxAnnouncers>>,
+    /// The fee rates hinted by the announcers of each transaction, checked once it is accepted
+    pub fee_rate_hints: Mutex<LruCache<H256, FnvHashMap<PeerIndex, u64>>>,
+    pub orphan_txs: OrphanTxPool,
+    /// The transactions received and waiting for their verification, and the peers announcing
+    /// them meanwhile, asked for them instead if the received ones turn out to be garbage
+    pub verifying_txs: Mutex<FnvHashMap<H256, Vec<PeerIndex>>>,
+    /// The transactions announced during initial block download and their first announcers, in
+    /// announcement order
+    pub pending_tx_announcements: Mutex<LinkedFnvHashMap<H256, PeerIndex>>,
+    /// The transactions relayed to us recently, including the ones rejected or evicted since
+    pub seen_txs: SeenTxCache,
+    pub metrics: RelayMetrics,
+    pub rejected_txs: Mutex<LruCache<H256, RejectedTx>>,
+    /// The transactions accepted recently and their cycles
+    pub recent_relayed_txs: Mutex<LruCache<H256, (Transaction, Cycle)>>,
     pub compact_block_filter: Mutex<LruCache<H256, ()>>,
+    tx_filter_size: usize,
+    tx_asked_size: usize,
 }
 
-impl Default for RelayState {
-    fn default() -> Self {
+impl RelayState {
+    pub fn new(tx_filter_size: usize, tx_asked_size: usize) -> Self {
         RelayState {
             pending_compact_blocks: Mutex::new(FnvHashMap::default()),
             inflight_proposals: Mutex::new(FnvHashSet::default()),
             pending_proposals_request: Mutex::new(FnvHashMap::default()),
-            tx_filter: Mutex::new(LruCache::new(TX_FILTER_SIZE)),
-            tx_already_asked: Mutex::new(LruCache::new(TX_ASKED_SIZE)),
+            tx_filter: Mutex::new(LruCache::new(tx_filter_size)),
+            tx_already_asked: Mutex::new(LinkedFnvHashMap::default()),
+            tx_announcers: Mutex::new(FnvHashMap::default()),
+            fee_rate_hints: Mutex::new(LruCache::new(tx_asked_size)),
+            orphan_txs: OrphanTxPool::new(
+                MAX_ORPHAN_TXS,
+                MAX_ORPHAN_TXS_SIZE,
+                MAX_ORPHAN_TXS_PER_PEER,
+            ),
+            verifying_txs: Mutex::new(FnvHashMap::default()),
+            pending_tx_announcements: Mutex::new(LinkedFnvHashMap::default()),
+            seen_txs: SeenTxCache::new(MAX_SEEN_TXS, MAX_SEEN_TXS_SIZE, SEEN_TX_EXPIRY),
+            metrics: RelayMetrics::default(),
+            rejected_txs: Mutex::new(LruCache::new(REJECTED_TXS_SIZE)),
+            recent_relayed_txs: Mutex::new(LruCache::new(RECENT_RELAYED_TXS_SIZE)),
             compact_block_filter: Mutex::new(LruCache::new(COMPACT_BLOCK_FILTER_SIZE)),
+            tx_filter_size,
+            tx_asked_size,
         }
     }
-}
 
-impl RelayState {
     fn mark_as_known_tx(&self, hash: H256) {
-        self.tx_already_asked.lock().remove(&hash);
-        self.tx_filter.lock().insert(hash, ());
+        self.mark_as_known_txs(vec![hash]);
+    }
+
+    fn mark_as_known_txs(&self, hashes: Vec<H256>) {
+        let mut tx_announcers = self.tx_announcers.lock();
+        let mut tx_already_asked = self.tx_already_asked.lock();
+        let mut tx_filter = self.tx_filter.lock();
+        let mut evicted = 0;
+        for hash in hashes {
+            tx_announcers.remove(&hash);
+            tx_already_asked.remove(&hash);
+            // the least recently known transactions are forgotten, worst case they are asked
+            // again and found in the transaction pool
+            if tx_filter.len() >= self.tx_filter_size && !tx_filter.contains_key(&hash) {
+                evicted += 1;
+            }
+            tx_filter.insert(hash, ());
+        }
+        if evicted > 0 {
+            debug!(target: "relay", "tx_filter is full, evict {} transactions", evicted);
+        }
+    }
+
+    // Purge the asked transactions neither received nor asked again long after their ask
+    // timeout. The asks are roughly ordered by timeout, so only the oldest ones are checked.
+    fn clear_expired_asked_txs(&self, now: Instant) {
+        let mut tx_already_asked = self.tx_already_asked.lock();
+        let mut purged = 0;
+        while tx_already_asked
+            .front()
+            .map_or(false, |(_, timeout)| *timeout + TX_ASKED_EXPIRY < now)
+        {
+            tx_already_asked.pop_front();
+            purged += 1;
+        }
+        if purged > 0 {
+            debug!(target: "relay", "purge {} expired asked transactions", purged);
+        }
+    }
+
+    // The highest fee rate hinted for the transaction, 0 if not hinted
+    fn fee_rate_hint(&self, hash: &H256) -> u64 {
+        self.fee_rate_hints
+            .lock()
+            .get(hash)
+            .and_then(|hints| hints.values().max().cloned())
+            .unwrap_or(0)
+    }
+
+    // Record the peer announcing the transaction being verified as a fallback source, returns
+    // false if the transaction is not being verified
+    fn add_verifying_tx_announcer(&self, hash: &H256, peer: PeerIndex) -> bool {
+        match self.verifying_txs.lock().get_mut(hash) {
+            Some(announcers) => {
+                if !announcers.contains(&peer) {
+                    announcers.push(peer);
+                }
+                true
+            }
+            None => false,
+        }
     }
 
     fn already_known_tx(&self, hash: &H256) -> bool {
-        self.tx_filter.lock().contains_key(hash)
+        self.tx_filter.lock().contains_key(hash) || self.orphan_txs.contains(hash)
     }
 
     fn already_known_compact_block(&self, hash: &H256) -> bool {
@@ -499,3 +1678,36 @@ impl RelayState {
         self.compact_block_filter.lock().insert(hash, ());
     }
 }
+
+/// Marks a transaction as being verified until dropped
+pub(crate) struct VerifyingTxGuard {
+    state: Arc<RelayState>,
+    hash: H256,
+}
+
+impl VerifyingTxGuard {
+    /// Registers the transaction handed to the verifier, it is no longer verifying once the
+    /// guard is dropped, even if the verification panics
+    pub(crate) fn new(state: &Arc<RelayState>, hash: H256) -> Self {
+        state.verifying_txs.lock().insert(hash.clone(), Vec::new());
+        VerifyingTxGuard {
+            state: Arc::clone(state),
+            hash,
+        }
+    }
+
+    /// Ends the verification, returns the peers announcing the transaction meanwhile
+    pub(crate) fn finish(self) -> Vec<PeerIndex> {
+        self.state
+            .verifying_txs
+            .lock()
+            .remove(&self.hash)
+            .unwrap_or_default()
+    }
+}
+
+impl Drop for VerifyingTxGuard {
+    fn drop(&mut self) {
+        self.state.verifying_txs.lock().remove(&self.hash);
+    }
+}
@@ -0,0 +1,272 @@
+use ckb_core::transaction::Transaction;
+use ckb_core::Cycle;
+use ckb_network::PeerIndex;
+use ckb_util::Mutex;
+use fnv::{FnvHashMap, FnvHashSet};
+use numext_fixed_hash::H256;
+use std::collections::BTreeMap;
+
+/// A relayed transaction spending outputs of transactions not known yet
+#[derive(Debug, Clone, PartialEq)]
+pub struct OrphanTx {
+    pub transaction: Transaction,
+    /// The cycles claimed by the relaying peer
+    pub cycles: Cycle,
+    pub peer: PeerIndex,
+    /// The hashes of the transactions whose outputs are missing
+    pub missing_parents: Vec<H256>,
+    size: usize,
+    sequence: u64,
+}
+
+#[derive(Default)]
+struct Inner {
+    txs: FnvHashMap<H256, OrphanTx>,
+    // The orphans waiting for each missing parent
+    by_parent: FnvHashMap<H256, FnvHashSet<H256>>,
+    // The orphans by insertion sequence, the oldest first
+    by_sequence: BTreeMap<u64, H256>,
+    peer_counts: FnvHashMap<PeerIndex, usize>,
+    total_size: usize,
+    next_sequence: u64,
+}
+
+impl Inner {
+    fn remove(&mut self, tx_hash: &H256) -> Option<OrphanTx> {
+        let orphan = self.txs.remove(tx_hash)?;
+        for parent in &orphan.missing_parents {
+            if let Some(children) = self.by_parent.get_mut(parent) {
+                children.remove(tx_hash);
+                if children.is_empty() {
+                    self.by_parent.remove(parent);
+                }
+            }
+        }
+        self.by_sequence.remove(&orphan.sequence);
+        if let Some(count) = self.peer_counts.get_mut(&orphan.peer) {
+            *count -= 1;
+            if *count == 0 {
+                self.peer_counts.remove(&orphan.peer);
+            }
+        }
+        self.total_size -= orphan.size;
+        Some(orphan)
+    }
+
+    fn oldest(&self, peer: Option<PeerIndex>) -> Option<H256> {
+        self.by_sequence
+            .values()
+            .find(|tx_hash| peer.is_none() || peer == self.txs.get(tx_hash).map(|tx| tx.peer))
+            .cloned()
+    }
+}
+
+/// The relayed transactions which cannot be resolved because their parents are still in flight,
+/// bounded by count and total serialized size, and by count per relaying peer. The oldest
+/// orphans are evicted first.
+pub struct OrphanTxPool {
+    inner: Mutex<Inner>,
+    max_count: usize,
+    max_size: usize,
+    max_per_peer: usize,
+}
+
+impl OrphanTxPool {
+    pub fn new(max_count: usize, max_size: usize, max_per_peer: usize) -> Self {
+        OrphanTxPool {
+            inner: Mutex::new(Inner::default()),
+            max_count,
+            max_size,
+            max_per_peer,
+        }
+    }
+
+    /// Holds the transaction until one of its missing parents is accepted, evicts the oldest
+    /// orphans of the same peer if it exceeds its quota, then the oldest orphans of all peers
+    /// if the pool is full. Returns the hashes of the evicted orphans, the transaction itself
+    /// is evicted if it does not fit in the pool alone.
+    pub fn insert(
+        &self,
+        transaction: Transaction,
+        cycles: Cycle,
+        peer: PeerIndex,
+        missing_parents: Vec<H256>,
+    ) -> Vec<H256> {
+        let tx_hash = transaction.hash().to_owned();
+        let mut inner = self.inner.lock();
+        if inner.txs.contains_key(&tx_hash) {
+            return Vec::new();
+        }
+
+        let size = transaction.serialized_size();
+        let sequence = inner.next_sequence;
+        inner.next_sequence += 1;
+        for parent in &missing_parents {
+            inner
+                .by_parent
+                .entry(parent.clone())
+                .or_insert_with(FnvHashSet::default)
+                .insert(tx_hash.clone());
+        }
+        inner.by_sequence.insert(sequence, tx_hash.clone());
+        *inner.peer_counts.entry(peer).or_default() += 1;
+        inner.total_size += size;
+        inner.txs.insert(
+            tx_hash,
+            OrphanTx {
+                transaction,
+                cycles,
+                peer,
+                missing_parents,
+                size,
+                sequence,
+            },
+        );
+
+        let mut evicted = Vec::new();
+        while inner.peer_counts.get(&peer).cloned().unwrap_or(0) > self.max_per_peer {
+            match inner.oldest(Some(peer)) {
+                Some(oldest) => {
+                    inner.remove(&oldest);
+                    evicted.push(oldest);
+                }
+                None => break,
+            }
+        }
+        while inner.txs.len() > self.max_count || inner.total_size > self.max_size {
+            match inner.oldest(None) {
+                Some(oldest) => {
+                    inner.remove(&oldest);
+                    evicted.push(oldest);
+                }
+                None => break,
+            }
+        }
+        evicted
+    }
+
+    pub fn remove(&self, tx_hash: &H256) -> Option<OrphanTx> {
+        self.inner.lock().remove(tx_hash)
+    }
+
+    /// Removes the orphans waiting for the transaction, which is accepted now
+    pub fn remove_orphans_by_parent(&self, tx_hash: &H256) -> Vec<OrphanTx> {
+        let mut inner = self.inner.lock();
+        let children = inner.by_parent.remove(tx_hash).unwrap_or_default();
+        let mut orphans = children
+            .iter()
+            .filter_map(|child| inner.remove(child))
+            .collect::<Vec<_>>();
+        orphans.sort_by_key(|orphan| orphan.sequence);
+        orphans
+    }
+
+    pub fn contains(&self, tx_hash: &H256) -> bool {
+        self.inner.lock().txs.contains_key(tx_hash)
+    }
+
+    pub fn len(&self) -> usize {
+        self.inner.lock().txs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn peer_count(&self, peer: PeerIndex) -> usize {
+        self.inner
+            .lock()
+            .peer_counts
+            .get(&peer)
+            .cloned()
+            .unwrap_or(0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ckb_core::transaction::{CellInput, CellOutput, OutPoint, TransactionBuilder};
+    use ckb_core::{Bytes, Capacity};
+    use numext_fixed_hash::h256;
+
+    fn gen_orphan(parent: &H256, index: usize) -> Transaction {
+        TransactionBuilder::default()
+            .input(CellInput::new(
+                OutPoint::new_cell(parent.to_owned(), 0),
+                0,
+                Default::default(),
+            ))
+            .output(CellOutput::new(
+                Capacity::bytes(500 + index).unwrap(),
+                Bytes::default(),
+                Default::default(),
+                None,
+            ))
+            .build()
+    }
+
+    fn insert(pool: &OrphanTxPool, tx: &Transaction, peer: usize) -> Vec<H256> {
+        let parents = tx
+            .inputs()
+            .iter()
+            .filter_map(|input| input.previous_output.cell.as_ref())
+            .map(|cell| cell.tx_hash.to_owned())
+            .collect();
+        pool.insert(tx.to_owned(), 0, peer.into(), parents)
+    }
+
+    #[test]
+    fn test_remove_orphans_by_parent() {
+        let pool = OrphanTxPool::new(10, usize::max_value(), 10);
+        let parent = h256!("0x1");
+        let txs = (0..3).map(|i| gen_orphan(&parent, i)).collect::<Vec<_>>();
+        for tx in &txs {
+            assert!(insert(&pool, tx, 1).is_empty());
+        }
+        let other = gen_orphan(&h256!("0x2"), 0);
+        insert(&pool, &other, 1);
+
+        let orphans = pool.remove_orphans_by_parent(&parent);
+        assert_eq!(
+            orphans
+                .into_iter()
+                .map(|orphan| orphan.transaction)
+                .collect::<Vec<_>>(),
+            txs
+        );
+        assert_eq!(pool.len(), 1);
+        assert!(pool.contains(other.hash()));
+        assert_eq!(pool.peer_count(1.into()), 1);
+    }
+
+    #[test]
+    fn test_evict_oldest() {
+        let pool = OrphanTxPool::new(3, usize::max_value(), 2);
+        let parent = h256!("0x1");
+        let txs = (0..5).map(|i| gen_orphan(&parent, i)).collect::<Vec<_>>();
+
+        // the peer quota evicts the oldest orphan of the same peer
+        insert(&pool, &txs[0], 1);
+        insert(&pool, &txs[1], 2);
+        insert(&pool, &txs[2], 1);
+        assert_eq!(insert(&pool, &txs[3], 1), vec![txs[0].hash().to_owned()]);
+        assert_eq!(pool.peer_count(1.into()), 2);
+        assert!(pool.contains(txs[1].hash()));
+
+        // the pool size evicts the oldest orphan of all peers
+        assert_eq!(insert(&pool, &txs[4], 3), vec![txs[1].hash().to_owned()]);
+        assert_eq!(pool.len(), 3);
+        assert_eq!(pool.peer_count(2.into()), 0);
+
+        // the total size bound
+        let size = txs[0].serialized_size();
+        let pool = OrphanTxPool::new(10, size * 2, 10);
+        insert(&pool, &txs[0], 1);
+        insert(&pool, &txs[1], 2);
+        assert_eq!(insert(&pool, &txs[2], 3), vec![txs[0].hash().to_owned()]);
+        assert_eq!(pool.len(), 2);
+        assert_eq!(pool.remove_orphans_by_parent(&parent).len(), 2);
+        assert!(pool.is_empty());
+    }
+}
@@ -0,0 +1,187 @@
+use ckb_core::transaction::Transaction;
+use ckb_util::{LinkedFnvHashMap, Mutex};
+use numext_fixed_hash::H256;
+use std::time::Duration;
+
+struct SeenTx {
+    transaction: Transaction,
+    size: usize,
+    /// Unix time in milliseconds
+    timestamp: u64,
+}
+
+#[derive(Default)]
+struct Inner {
+    // The transactions by hash, in the order they are seen, the oldest first
+    txs: LinkedFnvHashMap<H256, SeenTx>,
+    total_size: usize,
+}
+
+impl Inner {
+    fn pop_oldest(&mut self) -> Option<H256> {
+        let (tx_hash, seen) = self.txs.pop_front()?;
+        self.total_size -= seen.size;
+        Some(tx_hash)
+    }
+}
+
+/// The transactions relayed to us recently, whatever became of them since, kept to reconstruct
+/// the compact blocks committing transactions no longer in the pool. Bounded by count, total
+/// serialized size and age, the oldest transactions are evicted first.
+pub struct SeenTxCache {
+    inner: Mutex<Inner>,
+    max_count: usize,
+    max_size: usize,
+    max_age: u64,
+}
+
+impl SeenTxCache {
+    pub fn new(max_count: usize, max_size: usize, max_age: Duration) -> Self {
+        SeenTxCache {
+            inner: Mutex::new(Inner::default()),
+            max_count,
+            max_size,
+            max_age: max_age.as_millis() as u64,
+        }
+    }
+
+    /// Remembers the transaction seen at `now`, then evicts the expired transactions and the
+    /// oldest ones while the cache is full
+    pub fn insert(&self, transaction: Transaction, now: u64) {
+        let tx_hash = transaction.hash().to_owned();
+        let size = transaction.serialized_size();
+        let mut inner = self.inner.lock();
+        if let Some(seen) = inner.txs.remove(&tx_hash) {
+            inner.total_size -= seen.size;
+        }
+        inner.total_size += size;
+        inner.txs.insert(
+            tx_hash,
+            SeenTx {
+                transaction,
+                size,
+                timestamp: now,
+            },
+        );
+
+        while inner
+            .txs
+            .front()
+            .map_or(false, |(_, seen)| self.is_expired(seen, now))
+            || inner.txs.len() > self.max_count
+            || inner.total_size > self.max_size
+        {
+            if inner.pop_oldest().is_none() {
+                break;
+            }
+        }
+    }
+
+    pub fn get(&self, tx_hash: &H256, now: u64) -> Option<Transaction> {
+        self.inner
+            .lock()
+            .txs
+            .get(tx_hash)
+            .filter(|seen| !self.is_expired(seen, now))
+            .map(|seen| seen.transaction.to_owned())
+    }
+
+    /// Calls `f` with the transactions not expired at `now`, the newest first, until it returns
+    /// false
+    pub fn scan<F>(&self, now: u64, mut f: F)
+    where
+        F: FnMut(&Transaction) -> bool,
+    {
+        let inner = self.inner.lock();
+        for (_, seen) in inner.txs.iter().rev() {
+            if self.is_expired(seen, now) || !f(&seen.transaction) {
+                break;
+            }
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.inner.lock().txs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn is_expired(&self, seen: &SeenTx, now: u64) -> bool {
+        now.saturating_sub(seen.timestamp) >= self.max_age
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ckb_core::transaction::{CellInput, CellOutput, OutPoint, TransactionBuilder};
+    use ckb_core::{Bytes, Capacity};
+    use numext_fixed_hash::h256;
+
+    fn gen_tx(index: usize) -> Transaction {
+        TransactionBuilder::default()
+            .input(CellInput::new(
+                OutPoint::new_cell(h256!("0x1"), 0),
+                0,
+                Default::default(),
+            ))
+            .output(CellOutput::new(
+                Capacity::bytes(500 + index).unwrap(),
+                Bytes::default(),
+                Default::default(),
+                None,
+            ))
+            .build()
+    }
+
+    fn scanned(cache: &SeenTxCache, now: u64) -> Vec<Transaction> {
+        let mut txs = Vec::new();
+        cache.scan(now, |tx| {
+            txs.push(tx.to_owned());
+            true
+        });
+        txs
+    }
+
+    #[test]
+    fn test_evict_by_count_and_size() {
+        let txs = (0..4).map(gen_tx).collect::<Vec<_>>();
+        let cache = SeenTxCache::new(2, usize::max_value(), Duration::from_secs(60));
+        for tx in &txs[..3] {
+            cache.insert(tx.to_owned(), 0);
+        }
+        assert_eq!(scanned(&cache, 0), vec![txs[2].clone(), txs[1].clone()]);
+
+        // seen again, it becomes the newest
+        cache.insert(txs[1].to_owned(), 0);
+        cache.insert(txs[3].to_owned(), 0);
+        assert_eq!(scanned(&cache, 0), vec![txs[3].clone(), txs[1].clone()]);
+
+        let size = txs[0].serialized_size();
+        let cache = SeenTxCache::new(10, size * 2, Duration::from_secs(60));
+        for tx in &txs[..3] {
+            cache.insert(tx.to_owned(), 0);
+        }
+        assert_eq!(cache.len(), 2);
+        assert!(cache.get(txs[0].hash(), 0).is_none());
+        assert_eq!(cache.get(txs[2].hash(), 0), Some(txs[2].clone()));
+    }
+
+    #[test]
+    fn test_evict_by_age() {
+        let txs = (0..3).map(gen_tx).collect::<Vec<_>>();
+        let cache = SeenTxCache::new(10, usize::max_value(), Duration::from_secs(1));
+        cache.insert(txs[0].to_owned(), 0);
+        cache.insert(txs[1].to_owned(), 500);
+        assert!(cache.get(txs[0].hash(), 1000).is_none());
+        assert_eq!(scanned(&cache, 1000), vec![txs[1].clone()]);
+
+        cache.insert(txs[2].to_owned(), 1200);
+        assert_eq!(cache.len(), 2);
+        cache.insert(txs[2].to_owned(), 1600);
+        assert_eq!(scanned(&cache, 1600), vec![txs[2].clone()]);
+        assert!(scanned(&cache, 2600).is_empty());
+    }
+}
@@ -1,7 +1,10 @@
+use super::get_transactions_process::process_block;
+use super::transaction_hashes_process::DummyNetworkContext;
+use super::transaction_process::new_parent_and_child;
 use crate::relayer::compact_block::{CompactBlock, ShortTransactionID};
-use crate::{Relayer, SyncSharedState};
+use crate::{Config, Relayer, SyncSharedState};
 use ckb_chain::chain::ChainService;
-use ckb_chain_spec::consensus::Consensus;
+use ckb_chain_spec::consensus::{Consensus, MaturityMetric};
 use ckb_core::block::{Block, BlockBuilder};
 use ckb_core::header::HeaderBuilder;
 use ckb_core::transaction::{
@@ -19,7 +22,7 @@ use numext_fixed_uint::U256;
 use std::sync::Arc;
 use test_chain_utils::create_always_success_cell;
 
-fn new_header_builder(
+pub(super) fn new_header_builder(
     shared: &Shared<ChainKVStore<MemoryKeyValueDB>>,
     parent: &Block,
 ) -> HeaderBuilder {
@@ -36,7 +39,7 @@ fn new_header_builder(
         .difficulty(epoch.difficulty().to_owned())
 }
 
-fn new_transaction(
+pub(super) fn new_transaction(
     relayer: &Relayer<ChainKVStore<MemoryKeyValueDB>>,
     index: usize,
     always_success_out_point: &OutPoint,
@@ -65,10 +68,11 @@ fn new_transaction(
             None,
         ))
         .dep(always_success_out_point.to_owned())
+        .witness(Default::default())
         .build()
 }
 
-fn build_chain(tip: BlockNumber) -> (Relayer<ChainKVStore<MemoryKeyValueDB>>, OutPoint) {
+pub(super) fn build_chain(tip: BlockNumber) -> (Relayer<ChainKVStore<MemoryKeyValueDB>>, OutPoint) {
     let (always_success_cell, always_success_script) = create_always_success_cell();
     let always_success_tx = TransactionBuilder::default()
         .input(CellInput::new(OutPoint::null(), 0, Default::default()))
@@ -86,7 +90,7 @@ fn build_chain(tip: BlockNumber) -> (Relayer<ChainKVStore<MemoryKeyValueDB>>, Ou
         .build();
         let consensus = Consensus::default()
             .set_genesis_block(genesis)
-            .set_cellbase_maturity(0);
+            .set_cellbase_maturity(MaturityMetric::Blocks(0));
         SharedBuilder::<MemoryKeyValueDB>::new()
             .consensus(consensus)
             .build()
@@ -127,6 +131,7 @@ fn build_chain(tip: BlockNumber) -> (Relayer<ChainKVStore<MemoryKeyValueDB>>, Ou
             chain_controller,
             sync_shared_state,
             Arc::new(Default::default()),
+            Config::default(),
         ),
         always_success_out_point,
     )
@@ -232,3 +237,88 @@ fn test_reconstruct_block() {
         );
     }
 }
+
+#[test]
+fn test_reconstruct_block_from_seen_txs() {
+    let (relayer, always_success_out_point) = build_chain(5);
+
+    // proposed, so it is taken from the pool
+    let pooled = new_transaction(&relayer, 0, &always_success_out_point);
+    process_block(&relayer, vec![pooled.proposal_short_id()], Vec::new());
+    process_block(&relayer, Vec::new(), Vec::new());
+    process_block(&relayer, Vec::new(), Vec::new());
+    relayer
+        .tx_pool_executor
+        .verify_and_add_tx_to_pool(pooled.clone())
+        .expect("adding transaction into pool");
+
+    // the child is relayed but held as an orphan, so it is only seen, while the parent is unknown
+    let (parent, child) = new_parent_and_child(&relayer, &always_success_out_point);
+    let nc = DummyNetworkContext::default();
+    relayer.accept_relayed_tx(&nc, 1.into(), child.clone(), 0);
+    assert!(relayer.state.orphan_txs.contains(child.hash()));
+    assert!(relayer
+        .shared
+        .lock_chain_state()
+        .get_tx_with_cycles_from_pool(&child.proposal_short_id())
+        .is_none());
+
+    let new_compact_block = |transactions: Vec<Transaction>, committed: &Block| {
+        let mut compact = CompactBlock {
+            header: committed.header().to_owned(),
+            nonce: 2,
+            prefilled_transactions: vec![IndexTransaction {
+                index: 0,
+                transaction: transactions[0].clone(),
+            }],
+            ..Default::default()
+        };
+        let (key0, key1) = short_transaction_id_keys(compact.header.nonce(), compact.nonce);
+        compact.short_ids = transactions
+            .iter()
+            .skip(1)
+            .map(|tx| short_transaction_id(key0, key1, &tx.witness_hash()))
+            .collect();
+        compact
+    };
+    let cellbase = TransactionBuilder::default()
+        .input(CellInput::new_cellbase_input(9))
+        .build();
+    let block = BlockBuilder::default()
+        .transactions(vec![
+            cellbase.clone(),
+            pooled.clone(),
+            parent.clone(),
+            child.clone(),
+        ])
+        .build();
+
+    // Case: only the unknown transaction is requested from the peer
+    {
+        let compact = new_compact_block(block.transactions().to_vec(), &block);
+        let chain_state = relayer.shared.lock_chain_state();
+        assert_eq!(
+            relayer.reconstruct_block(&chain_state, &compact, Vec::new()),
+            Err(vec![2]),
+        );
+        assert_eq!(
+            relayer.reconstruct_block(&chain_state, &compact, vec![parent.clone()]),
+            Ok(block.clone()),
+        );
+    }
+
+    // Case: the short id of the seen transaction collides with the one committed in the block,
+    // the roots do not match, so the transactions filled locally are requested from the peer
+    {
+        let other = new_transaction(&relayer, 1, &always_success_out_point);
+        let committed = BlockBuilder::default()
+            .transactions(vec![cellbase, pooled, parent.clone(), other])
+            .build();
+        let compact = new_compact_block(block.transactions().to_vec(), &committed);
+        let chain_state = relayer.shared.lock_chain_state();
+        assert_eq!(
+            relayer.reconstruct_block(&chain_state, &compact, vec![parent]),
+            Err(vec![1, 3]),
+        );
+    }
+}
@@ -0,0 +1,151 @@
+use super::compact_block_process::{build_chain, new_header_builder};
+use super::transaction_hashes_process::DummyNetworkContext;
+use super::transaction_process::new_parent_and_child;
+use crate::relayer::get_transactions_process::GetTransactionsProcess;
+use crate::relayer::Relayer;
+use ckb_core::block::BlockBuilder;
+use ckb_core::transaction::{
+    CellInput, CellOutput, ProposalShortId, Transaction, TransactionBuilder,
+};
+use ckb_core::{capacity_bytes, Bytes, Capacity, Cycle};
+use ckb_db::memorydb::MemoryKeyValueDB;
+use ckb_network::{CKBProtocolContext, PeerIndex};
+use ckb_protocol::{get_root, FlatbuffersVectorIterator, RelayMessage};
+use ckb_store::ChainKVStore;
+use ckb_traits::ChainProvider;
+use flatbuffers::FlatBufferBuilder;
+use numext_fixed_hash::{h256, H256};
+use std::convert::TryInto;
+use std::sync::Arc;
+use test_chain_utils::create_always_success_cell;
+
+// Appends a block with the proposals and the transactions to the tip
+pub(super) fn process_block(
+    relayer: &Relayer<ChainKVStore<MemoryKeyValueDB>>,
+    proposals: Vec<ProposalShortId>,
+    transactions: Vec<Transaction>,
+) {
+    let (_, always_success_script) = create_always_success_cell();
+    let shared = relayer.shared.shared();
+    let parent = {
+        let tip_hash = shared.lock_chain_state().tip_hash().to_owned();
+        shared.block(&tip_hash).expect("getting tip block")
+    };
+    let cellbase = TransactionBuilder::default()
+        .input(CellInput::new_cellbase_input(parent.header().number() + 1))
+        .output(CellOutput::new(
+            capacity_bytes!(50000),
+            Bytes::default(),
+            always_success_script,
+            None,
+        ))
+        .build();
+    let block = BlockBuilder::from_header_builder(new_header_builder(shared, &parent))
+        .transaction(cellbase)
+        .transactions(transactions)
+        .proposals(proposals)
+        .build();
+    relayer
+        .chain
+        .process_block(Arc::new(block), false)
+        .expect("processing block should be ok");
+}
+
+// The transactions responded to the GetRelayTransactions message
+fn get_transactions(
+    relayer: &Relayer<ChainKVStore<MemoryKeyValueDB>>,
+    tx_hashes: &[H256],
+) -> Vec<(Transaction, Cycle)> {
+    let nc = Arc::new(DummyNetworkContext::default());
+    let fbb = &mut FlatBufferBuilder::new();
+    let message = RelayMessage::build_get_transactions(fbb, tx_hashes);
+    fbb.finish(message, None);
+    let message = get_root::<RelayMessage>(fbb.finished_data()).expect("valid message");
+    let peer: PeerIndex = 1.into();
+    GetTransactionsProcess::new(
+        &message
+            .payload_as_get_relay_transactions()
+            .expect("get relay transactions"),
+        relayer,
+        Arc::<DummyNetworkContext>::clone(&nc) as Arc<dyn CKBProtocolContext>,
+        peer,
+    )
+    .execute()
+    .expect("process get transactions");
+
+    let sent = nc.sent.lock();
+    sent.iter()
+        .flat_map(|(_, data)| {
+            let message = get_root::<RelayMessage>(data).expect("valid message");
+            let transactions = message
+                .payload_as_relay_transactions()
+                .and_then(|message| message.transactions())
+                .expect("relay transactions");
+            FlatbuffersVectorIterator::new(transactions)
+                .map(|transaction| transaction.try_into().expect("valid transaction"))
+                .collect::<Vec<(Transaction, Cycle)>>()
+        })
+        .collect()
+}
+
+#[test]
+fn test_get_transactions_after_committed() {
+    let (relayer, always_success_out_point) = build_chain(5);
+    let (tx, _) = new_parent_and_child(&relayer, &always_success_out_point);
+    let cycles = {
+        let (probe, _) = build_chain(5);
+        probe
+            .tx_pool_executor
+            .verify_and_add_tx_to_pool(tx.clone())
+            .expect("adding transaction into pool")
+    };
+
+    // proposed, so the transaction leaves the pool once committed
+    let short_id = tx.proposal_short_id();
+    process_block(&relayer, vec![short_id], Vec::new());
+    process_block(&relayer, Vec::new(), Vec::new());
+    process_block(&relayer, Vec::new(), Vec::new());
+    assert!(relayer
+        .shared
+        .lock_chain_state()
+        .contains_proposal_id(&short_id));
+
+    // accepted and announced
+    let nc = DummyNetworkContext::default();
+    relayer.accept_relayed_tx(&nc, 2.into(), tx.clone(), cycles);
+    assert_eq!(
+        get_transactions(&relayer, &[tx.hash().to_owned()]),
+        vec![(tx.clone(), cycles)]
+    );
+
+    // committed before the peer asks for it
+    process_block(&relayer, Vec::new(), vec![tx.clone()]);
+    assert!(relayer
+        .shared
+        .lock_chain_state()
+        .get_tx_with_cycles_from_pool(&short_id)
+        .is_none());
+    assert_eq!(
+        get_transactions(&relayer, &[tx.hash().to_owned()]),
+        vec![(tx.clone(), cycles)]
+    );
+    // served from the store once forgotten by the recent relay cache
+    relayer.state.recent_relayed_txs.lock().remove(tx.hash());
+    assert_eq!(
+        get_transactions(&relayer, &[tx.hash().to_owned()]),
+        vec![(tx.clone(), cycles)]
+    );
+    // the scripts are not run again once the cycles are forgotten by the verify cache
+    relayer.shared.lock_txs_verify_cache().remove(tx.hash());
+    assert!(get_transactions(&relayer, &[tx.hash().to_owned()]).is_empty());
+    assert!(relayer
+        .shared
+        .lock_txs_verify_cache()
+        .get(tx.hash())
+        .is_none());
+    assert_eq!(relayer.metrics().tx_not_found, 1);
+
+    // found nowhere, nothing is sent
+    assert!(get_transactions(&relayer, &[h256!("0x1")]).is_empty());
+    assert_eq!(relayer.metrics().tx_not_found, 2);
+}
@@ -1,2 +1,6 @@
 mod compact_block_process;
 mod compact_block_verifier;
+mod get_transactions_process;
+mod transaction_hashes_process;
+mod transaction_process;
+mod transaction_result_process;
@@ -0,0 +1,686 @@
+use super::compact_block_process::{build_chain, new_transaction};
+use crate::relayer::transaction_hashes_process::TransactionHashesProcess;
+use crate::relayer::{
+    RelayState, Relayer, VerifyingTxGuard, MAX_GET_RELAY_TXS, MAX_TX_ANNOUNCERS_RATIO,
+    TX_ANNOUNCE_ABUSE_SCORE, TX_ASKED_EXPIRY, TX_ASK_TIMEOUT,
+};
+use crate::MAX_TIP_AGE;
+use bytes::Bytes;
+use ckb_core::transaction::ProposalShortId;
+use ckb_network::{Behaviour, CKBProtocolContext, Peer, PeerIndex, ProtocolId, TargetSession};
+use ckb_protocol::{get_root, RelayMessage};
+use ckb_store::ChainStore;
+use ckb_util::Mutex;
+use flatbuffers::FlatBufferBuilder;
+use futures::future::Future;
+use hash::blake2b_256;
+use numext_fixed_hash::{h256, H256};
+use std::convert::TryInto;
+use std::mem;
+use std::ops::Range;
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+#[derive(Default)]
+pub(super) struct DummyNetworkContext {
+    pub(super) banned: Mutex<Vec<PeerIndex>>,
+    pub(super) sent: Mutex<Vec<(PeerIndex, Bytes)>>,
+    pub(super) reported: Mutex<Vec<(PeerIndex, Behaviour)>>,
+    pub(super) connected: Vec<PeerIndex>,
+    // The peers each transaction hash is relayed to
+    pub(super) broadcast: Mutex<Vec<(Vec<PeerIndex>, Bytes)>>,
+    // Holds the future tasks until `run_tasks`, instead of running them at once
+    pub(super) defer_tasks: bool,
+    pub(super) tasks: Mutex<Vec<Box<Future<Item = (), Error = ()> + 'static + Send>>>,
+}
+
+impl DummyNetworkContext {
+    pub(super) fn run_tasks(&self) {
+        let tasks = mem::replace(&mut *self.tasks.lock(), Vec::new());
+        for task in tasks {
+            task.wait().expect("run future task");
+        }
+    }
+
+    // The transactions asked by the GetRelayTransactions messages sent so far
+    pub(super) fn asked_txs(&self) -> Vec<(PeerIndex, H256)> {
+        self.sent
+            .lock()
+            .iter()
+            .flat_map(|(peer, data)| {
+                let message = get_root::<RelayMessage>(data).expect("valid message");
+                let tx_hashes = match message.payload_as_get_relay_transactions() {
+                    Some(message) => message.tx_hashes().expect("get relay transactions"),
+                    None => return Vec::new(),
+                };
+                tx_hashes
+                    .iter()
+                    .map(|tx_hash| (*peer, tx_hash.try_into().expect("valid hash")))
+                    .collect::<Vec<_>>()
+            })
+            .collect()
+    }
+
+    // The transaction results sent so far
+    pub(super) fn tx_results(&self) -> Vec<(PeerIndex, H256, u8)> {
+        self.sent
+            .lock()
+            .iter()
+            .filter_map(|(peer, data)| {
+                let message = get_root::<RelayMessage>(data).expect("valid message");
+                let (tx_hash, result): (H256, u8) = message
+                    .payload_as_relay_transaction_result()?
+                    .try_into()
+                    .expect("valid transaction result");
+                Some((*peer, tx_hash, result))
+            })
+            .collect()
+    }
+}
+
+impl CKBProtocolContext for DummyNetworkContext {
+    fn set_notify(&self, _interval: Duration, _token: u64) {
+        unimplemented!();
+    }
+    fn quick_send_message(&self, _proto_id: ProtocolId, _peer_index: PeerIndex, _data: Bytes) {}
+    fn quick_send_message_to(&self, _peer_index: PeerIndex, _data: Bytes) {}
+    fn quick_filter_broadcast(&self, _target: TargetSession, _data: Bytes) {}
+    // Runs the task at once, unless deferred
+    fn future_task(&self, task: Box<Future<Item = (), Error = ()> + 'static + Send>) {
+        if self.defer_tasks {
+            self.tasks.lock().push(task);
+        } else {
+            task.wait().expect("run future task");
+        }
+    }
+    fn send_message(&self, _proto_id: ProtocolId, _peer_index: PeerIndex, _data: Bytes) {}
+    fn send_message_to(&self, peer_index: PeerIndex, data: Bytes) {
+        self.sent.lock().push((peer_index, data));
+    }
+    fn filter_broadcast(&self, target: TargetSession, data: Bytes) {
+        if let TargetSession::Multi(peers) = target {
+            self.broadcast.lock().push((peers, data));
+        }
+    }
+    fn disconnect(&self, _peer_index: PeerIndex) {}
+    fn get_peer(&self, _peer_index: PeerIndex) -> Option<Peer> {
+        None
+    }
+    fn connected_peers(&self) -> Vec<PeerIndex> {
+        self.connected.clone()
+    }
+    fn report_peer(&self, peer_index: PeerIndex, behaviour: Behaviour) {
+        self.reported.lock().push((peer_index, behaviour));
+    }
+    fn ban_peer(&self, peer_index: PeerIndex, _timeout: Duration) {
+        self.banned.lock().push(peer_index);
+    }
+    fn protocol_id(&self) -> ProtocolId {
+        unimplemented!();
+    }
+}
+
+fn tx_hashes(range: Range<u64>) -> Vec<H256> {
+    range
+        .map(|i| (&blake2b_256(i.to_le_bytes())).into())
+        .collect()
+}
+
+pub(super) fn announce_tx_hashes<CS: ChainStore + 'static>(
+    relayer: &Relayer<CS>,
+    nc: &Arc<DummyNetworkContext>,
+    peer: PeerIndex,
+    tx_hashes: &[H256],
+) {
+    announce_tx_hashes_with_fee_rates(relayer, nc, peer, tx_hashes, &[]);
+}
+
+pub(super) fn announce_tx_hashes_with_fee_rates<CS: ChainStore + 'static>(
+    relayer: &Relayer<CS>,
+    nc: &Arc<DummyNetworkContext>,
+    peer: PeerIndex,
+    tx_hashes: &[H256],
+    fee_rates: &[u64],
+) {
+    let fbb = &mut FlatBufferBuilder::new();
+    let message = RelayMessage::build_transaction_hashes(fbb, tx_hashes, fee_rates);
+    fbb.finish(message, None);
+    let message = get_root::<RelayMessage>(fbb.finished_data()).expect("valid message");
+    let nc: Arc<dyn CKBProtocolContext> = Arc::<DummyNetworkContext>::clone(nc);
+    TransactionHashesProcess::new(
+        &message
+            .payload_as_relay_transaction_hashes()
+            .expect("relay transaction hashes"),
+        relayer,
+        nc,
+        peer,
+    )
+    .execute()
+    .expect("process transaction hashes");
+}
+
+#[test]
+fn test_add_ask_for_txs() {
+    let (relayer, always_success_out_point) = build_chain(5);
+    let peer: PeerIndex = 1.into();
+    relayer.peers().on_connected(peer, None, false, true);
+
+    let known_tx_hash = h256!("0x1");
+    relayer.state.mark_as_known_tx(known_tx_hash.clone());
+
+    let pooled_tx = new_transaction(&relayer, 0, &always_success_out_point);
+    relayer
+        .tx_pool_executor
+        .verify_and_add_tx_to_pool(pooled_tx.clone())
+        .expect("adding transaction into pool");
+
+    let new_tx_hashes = vec![h256!("0x2"), h256!("0x3")];
+    let mut tx_hashes = vec![known_tx_hash.clone(), pooled_tx.hash().to_owned()];
+    tx_hashes.extend(new_tx_hashes.iter().cloned());
+    // announced twice in the same batch
+    tx_hashes.push(new_tx_hashes[0].clone());
+    relayer.add_ask_for_txs(peer, tx_hashes);
+
+    // the pooled transaction becomes known
+    assert!(relayer.state.already_known_tx(pooled_tx.hash()));
+
+    let tx_already_asked = relayer.state.tx_already_asked.lock();
+    assert_eq!(tx_already_asked.len(), new_tx_hashes.len());
+    for tx_hash in &new_tx_hashes {
+        assert!(tx_already_asked.contains_key(tx_hash));
+    }
+    assert!(!tx_already_asked.contains_key(&known_tx_hash));
+    assert!(!tx_already_asked.contains_key(pooled_tx.hash()));
+}
+
+#[test]
+fn test_ask_for_tx_colliding_with_pooled_short_id() {
+    let (relayer, always_success_out_point) = build_chain(5);
+    let nc = Arc::new(DummyNetworkContext::default());
+    let peer: PeerIndex = 1.into();
+    relayer.peers().on_connected(peer, None, false, true);
+
+    let pooled_tx = new_transaction(&relayer, 0, &always_success_out_point);
+    relayer
+        .tx_pool_executor
+        .verify_and_add_tx_to_pool(pooled_tx.clone())
+        .expect("adding transaction into pool");
+    // another hash of the same short id as the pooled transaction
+    let colliding_tx_hash = {
+        let mut bytes = pooled_tx.hash().to_vec();
+        bytes[31] ^= 1;
+        H256::from_slice(&bytes).expect("valid hash")
+    };
+    assert_eq!(
+        ProposalShortId::from_tx_hash(&colliding_tx_hash),
+        pooled_tx.proposal_short_id()
+    );
+
+    announce_tx_hashes(
+        &relayer,
+        &nc,
+        peer,
+        &[pooled_tx.hash().to_owned(), colliding_tx_hash.clone()],
+    );
+    thread::sleep(Duration::from_millis(1));
+    relayer.ask_for_txs(nc.as_ref());
+
+    // only the pooled transaction is ignored
+    assert_eq!(nc.asked_txs(), vec![(peer, colliding_tx_hash.clone())]);
+    assert!(relayer.state.already_known_tx(pooled_tx.hash()));
+    // nor is the pooled transaction served for the colliding one
+    assert!(relayer.get_relay_tx(&colliding_tx_hash).is_none());
+}
+
+#[test]
+fn test_tx_announcements_rate_limit() {
+    let (mut relayer, _) = build_chain(1);
+    // no refill during the test, only the burst is accepted
+    relayer.config.tx_announce_rate = 0;
+    relayer.config.tx_announce_burst = 3;
+    let nc = Arc::new(DummyNetworkContext::default());
+    let peer: PeerIndex = 1.into();
+    relayer.peers().on_connected(peer, None, false, true);
+
+    let first = tx_hashes(0..5);
+    announce_tx_hashes(&relayer, &nc, peer, &first);
+    {
+        let tx_already_asked = relayer.state.tx_already_asked.lock();
+        assert_eq!(tx_already_asked.len(), 3);
+        for tx_hash in &first[..3] {
+            assert!(tx_already_asked.contains_key(tx_hash));
+        }
+        for tx_hash in &first[3..] {
+            assert!(!tx_already_asked.contains_key(tx_hash));
+        }
+    }
+    assert!(nc.banned.lock().is_empty());
+    assert_eq!(relayer.peers().misbehavior.read().get(&peer), None);
+
+    // keep exceeding the limit
+    announce_tx_hashes(&relayer, &nc, peer, &tx_hashes(5..1005));
+    assert_eq!(relayer.state.tx_already_asked.lock().len(), 3);
+    assert_eq!(*nc.banned.lock(), vec![peer]);
+    assert_eq!(
+        relayer.peers().misbehavior.read().get(&peer),
+        Some(&TX_ANNOUNCE_ABUSE_SCORE)
+    );
+}
+
+#[test]
+fn test_retry_asked_txs() {
+    let (relayer, _) = build_chain(1);
+    let nc = DummyNetworkContext::default();
+    let peers: Vec<PeerIndex> = (1..4).map(Into::into).collect();
+    for peer in &peers {
+        relayer.peers().on_connected(*peer, None, false, true);
+    }
+
+    let tx_hash = h256!("0x1");
+    for peer in &peers {
+        relayer.add_ask_for_txs(*peer, vec![tx_hash.clone()]);
+    }
+    // only the first announcer is scheduled, the others are recorded
+    {
+        let tx_announcers = relayer.state.tx_announcers.lock();
+        let announcers = tx_announcers.get(&tx_hash).expect("announcers recorded");
+        assert_eq!(announcers.peers, peers);
+        assert_eq!(announcers.asked.map(|(peer, _)| peer), Some(peers[0]));
+    }
+    assert_eq!(relayer.state.tx_already_asked.lock().len(), 1);
+
+    // nothing to retry before the ask times out
+    relayer.retry_asked_txs(&nc, Instant::now());
+    assert!(nc.asked_txs().is_empty());
+
+    // the asked peer disconnects, ask the next announcer
+    relayer.peers().state.write().remove(&peers[0]);
+    let now = Instant::now();
+    relayer.retry_asked_txs(&nc, now);
+    assert_eq!(nc.asked_txs(), vec![(peers[1], tx_hash.clone())]);
+    {
+        let tx_announcers = relayer.state.tx_announcers.lock();
+        let announcers = &tx_announcers[&tx_hash];
+        assert_eq!(announcers.retries, 1);
+        assert_eq!(announcers.asked, Some((peers[1], now + TX_ASK_TIMEOUT * 2)));
+    }
+
+    // the ask times out, ask the last announcer with a doubled timeout
+    let now = now + TX_ASK_TIMEOUT * 2;
+    relayer.retry_asked_txs(&nc, now);
+    assert_eq!(
+        nc.asked_txs(),
+        vec![(peers[1], tx_hash.clone()), (peers[2], tx_hash.clone())]
+    );
+    assert_eq!(
+        relayer.state.tx_announcers.lock()[&tx_hash].asked,
+        Some((peers[2], now + TX_ASK_TIMEOUT * 4))
+    );
+
+    // no announcer is left, give up and clean up
+    relayer.retry_asked_txs(&nc, now + TX_ASK_TIMEOUT * 4);
+    assert_eq!(nc.asked_txs().len(), 2);
+    assert!(relayer.state.tx_announcers.lock().is_empty());
+    assert!(!relayer.state.tx_already_asked.lock().contains_key(&tx_hash));
+}
+
+#[test]
+fn test_asked_tx_arrives() {
+    let (relayer, _) = build_chain(1);
+    let peer: PeerIndex = 1.into();
+    relayer.peers().on_connected(peer, None, false, true);
+
+    let tx_hash = h256!("0x1");
+    relayer.add_ask_for_txs(peer, vec![tx_hash.clone()]);
+    assert!(relayer.state.tx_announcers.lock().contains_key(&tx_hash));
+    relayer.state.mark_as_known_tx(tx_hash.clone());
+    assert!(relayer.state.tx_announcers.lock().is_empty());
+}
+
+#[test]
+fn test_tx_announcers_bounded() {
+    let (mut relayer, _) = build_chain(1);
+    relayer.state = Arc::new(RelayState::new(10, 2));
+    let peer: PeerIndex = 1.into();
+    relayer.peers().on_connected(peer, None, false, true);
+
+    let hashes = tx_hashes(0..10);
+    relayer.add_ask_for_txs(peer, hashes.clone());
+    let max_tx_announcers = 2 * MAX_TX_ANNOUNCERS_RATIO;
+    {
+        let tx_announcers = relayer.state.tx_announcers.lock();
+        assert_eq!(tx_announcers.len(), max_tx_announcers);
+        assert!(hashes[..max_tx_announcers]
+            .iter()
+            .all(|tx_hash| tx_announcers.contains_key(tx_hash)));
+    }
+    // room is made once the asked transactions arrive, the dropped ones are recorded if they are
+    // announced again
+    relayer.state.mark_as_known_tx(hashes[0].clone());
+    relayer.add_ask_for_txs(peer, vec![hashes[9].clone()]);
+    assert!(relayer.state.tx_announcers.lock().contains_key(&hashes[9]));
+}
+
+#[test]
+fn test_batch_get_transactions() {
+    let (relayer, _) = build_chain(1);
+    let nc = Arc::new(DummyNetworkContext::default());
+    let peer: PeerIndex = 1.into();
+    relayer.peers().on_connected(peer, None, false, true);
+
+    let hashes = tx_hashes(0..50);
+    announce_tx_hashes(&relayer, &nc, peer, &hashes);
+    // the asks are due once the time they are scheduled at has passed
+    thread::sleep(Duration::from_millis(1));
+    relayer.ask_for_txs(nc.as_ref());
+
+    let max_messages = (hashes.len() + MAX_GET_RELAY_TXS - 1) / MAX_GET_RELAY_TXS;
+    assert!(nc.sent.lock().len() <= max_messages);
+    let mut asked = nc
+        .asked_txs()
+        .into_iter()
+        .map(|(asked_peer, tx_hash)| {
+            assert_eq!(asked_peer, peer);
+            tx_hash
+        })
+        .collect::<Vec<_>>();
+    asked.sort();
+    let mut expected = hashes.clone();
+    expected.sort();
+    assert_eq!(asked, expected);
+    // the timeouts are still tracked per transaction
+    assert_eq!(relayer.state.tx_already_asked.lock().len(), hashes.len());
+}
+
+#[test]
+fn test_bounded_known_and_asked_txs() {
+    let (mut relayer, _) = build_chain(1);
+    relayer.state = Arc::new(RelayState::new(2, 3));
+    let peer: PeerIndex = 1.into();
+    relayer.peers().on_connected(peer, None, false, true);
+
+    let hashes = tx_hashes(0..5);
+    relayer.add_ask_for_txs(peer, hashes.clone());
+    {
+        // the oldest asks are evicted
+        let tx_already_asked = relayer.state.tx_already_asked.lock();
+        assert_eq!(tx_already_asked.len(), 3);
+        for tx_hash in &hashes[2..] {
+            assert!(tx_already_asked.contains_key(tx_hash));
+        }
+    }
+
+    relayer.state.mark_as_known_txs(hashes[..3].to_vec());
+    assert_eq!(relayer.state.tx_filter.lock().len(), 2);
+    assert!(!relayer.state.already_known_tx(&hashes[0]));
+    assert!(relayer.state.already_known_tx(&hashes[2]));
+
+    // the asks timing out long ago are purged
+    let now = Instant::now();
+    relayer.state.clear_expired_asked_txs(now);
+    assert_eq!(relayer.state.tx_already_asked.lock().len(), 2);
+    relayer
+        .state
+        .clear_expired_asked_txs(now + TX_ASKED_EXPIRY + TX_ASK_TIMEOUT * 2);
+    assert!(relayer.state.tx_already_asked.lock().is_empty());
+}
+
+#[cfg(not(disable_faketime))]
+#[test]
+fn test_drain_tx_announcements_after_ibd() {
+    let (relayer, always_success_out_point) = build_chain(5);
+    let nc = Arc::new(DummyNetworkContext::default());
+    let peer1: PeerIndex = 1.into();
+    let peer2: PeerIndex = 2.into();
+    relayer.peers().on_connected(peer1, None, false, true);
+    relayer.peers().on_connected(peer2, None, false, true);
+
+    // the tip becomes too old, we are in IBD
+    let now = faketime::unix_time_as_millis();
+    let faketime_file =
+        faketime::millis_tempfile(now + MAX_TIP_AGE * 2).expect("create faketime file");
+    faketime::enable(&faketime_file);
+    assert!(relayer.shared.is_initial_block_download());
+
+    // committed in the genesis block
+    let committed_tx_hash = always_success_out_point
+        .cell
+        .as_ref()
+        .expect("cell out point")
+        .tx_hash
+        .to_owned();
+    let pooled_tx = new_transaction(&relayer, 0, &always_success_out_point);
+    let new_tx_hashes = vec![h256!("0x2"), h256!("0x3")];
+    announce_tx_hashes(
+        &relayer,
+        &nc,
+        peer1,
+        &[
+            committed_tx_hash.clone(),
+            pooled_tx.hash().to_owned(),
+            new_tx_hashes[0].clone(),
+        ],
+    );
+    announce_tx_hashes(&relayer, &nc, peer2, &new_tx_hashes);
+    assert_eq!(relayer.state.pending_tx_announcements.lock().len(), 4);
+    assert!(relayer.state.tx_already_asked.lock().is_empty());
+
+    // the pooled transaction arrives while syncing
+    relayer
+        .tx_pool_executor
+        .verify_and_add_tx_to_pool(pooled_tx.clone())
+        .expect("adding transaction into pool");
+
+    // IBD completes
+    faketime::write_millis(&faketime_file, now).expect("write millis");
+    assert!(!relayer.shared.is_initial_block_download());
+    relayer.drain_pending_tx_announcements();
+
+    assert!(relayer.state.pending_tx_announcements.lock().is_empty());
+    assert!(relayer.state.already_known_tx(&committed_tx_hash));
+    assert!(relayer.state.already_known_tx(pooled_tx.hash()));
+    let tx_already_asked = relayer.state.tx_already_asked.lock();
+    assert_eq!(tx_already_asked.len(), new_tx_hashes.len());
+    for tx_hash in &new_tx_hashes {
+        assert!(tx_already_asked.contains_key(tx_hash));
+    }
+    // asked from the first announcer
+    assert_eq!(
+        relayer.state.tx_announcers.lock()[&new_tx_hashes[0]].peers,
+        vec![peer1]
+    );
+}
+
+#[test]
+fn test_tx_asks_in_flight_limits() {
+    let (mut relayer, _) = build_chain(1);
+    relayer.config.max_tx_asks_in_flight = 2;
+    let nc = Arc::new(DummyNetworkContext::default());
+    let peers: Vec<PeerIndex> = (1..5).map(Into::into).collect();
+    for peer in &peers {
+        relayer.peers().on_connected(*peer, None, false, true);
+    }
+
+    // announced by every peer, asked from the first one only
+    let tx_hash = h256!("0x1");
+    for peer in &peers {
+        announce_tx_hashes(&relayer, &nc, *peer, &[tx_hash.clone()]);
+    }
+    thread::sleep(Duration::from_millis(1));
+    relayer.ask_for_txs(nc.as_ref());
+    assert_eq!(nc.asked_txs(), vec![(peers[0], tx_hash.clone())]);
+    assert_eq!(relayer.state.tx_announcers.lock()[&tx_hash].peers, peers);
+
+    // the others are the fallbacks on timeout
+    relayer.retry_asked_txs(nc.as_ref(), Instant::now() + TX_ASK_TIMEOUT * 2);
+    assert_eq!(nc.asked_txs()[1..], [(peers[1], tx_hash.clone())]);
+    nc.sent.lock().clear();
+
+    // at most 2 asks in flight from the same peer, the others are deferred
+    let peer = peers[3];
+    let hashes = tx_hashes(0..4);
+    announce_tx_hashes(&relayer, &nc, peer, &hashes);
+    thread::sleep(Duration::from_millis(1));
+    relayer.ask_for_txs(nc.as_ref());
+    assert_eq!(
+        nc.asked_txs(),
+        vec![(peer, hashes[0].clone()), (peer, hashes[1].clone())]
+    );
+    for tx_hash in &hashes[2..] {
+        assert_eq!(relayer.state.tx_announcers.lock()[tx_hash].asked, None);
+    }
+    relayer.retry_asked_txs(nc.as_ref(), Instant::now());
+    assert_eq!(nc.asked_txs().len(), 2);
+
+    // a deferred ask is sent once an asked transaction arrives
+    relayer
+        .peers()
+        .state
+        .write()
+        .get_mut(&peer)
+        .expect("peer state")
+        .remove_ask_for_tx(&hashes[0]);
+    relayer.state.mark_as_known_tx(hashes[0].clone());
+    relayer.retry_asked_txs(nc.as_ref(), Instant::now());
+    let asked_txs = nc.asked_txs();
+    assert_eq!(asked_txs.len(), 3);
+    assert!(hashes[2..].contains(&asked_txs[2].1));
+}
+
+#[test]
+fn test_ask_again_after_timeout() {
+    let (mut relayer, _) = build_chain(1);
+    relayer.config.max_tx_asks_in_flight = 1;
+    let nc = Arc::new(DummyNetworkContext::default());
+    let peers: Vec<PeerIndex> = (1..3).map(Into::into).collect();
+    for peer in &peers {
+        relayer.peers().on_connected(*peer, None, false, true);
+    }
+
+    let hashes = tx_hashes(0..2);
+    for peer in &peers {
+        announce_tx_hashes(&relayer, &nc, *peer, &hashes[..1]);
+    }
+    thread::sleep(Duration::from_millis(1));
+    relayer.ask_for_txs(nc.as_ref());
+    assert_eq!(nc.asked_txs(), vec![(peers[0], hashes[0].clone())]);
+
+    // the ask times out and moves to the other announcer, freeing the slot of the first one
+    relayer.retry_asked_txs(nc.as_ref(), Instant::now() + TX_ASK_TIMEOUT * 2);
+    assert_eq!(nc.asked_txs()[1..], [(peers[1], hashes[0].clone())]);
+    assert_eq!(
+        relayer.peers().state.read()[&peers[0]].tx_asks_in_flight(),
+        0
+    );
+
+    // so the first peer is asked for the next transaction it announces
+    announce_tx_hashes(&relayer, &nc, peers[0], &hashes[1..]);
+    thread::sleep(Duration::from_millis(1));
+    relayer.ask_for_txs(nc.as_ref());
+    assert_eq!(nc.asked_txs()[2..], [(peers[0], hashes[1].clone())]);
+}
+
+#[test]
+fn test_single_ask_for_repeated_announcements() {
+    let (relayer, _) = build_chain(1);
+    let nc = Arc::new(DummyNetworkContext::default());
+    let peers: Vec<PeerIndex> = (1..3).map(Into::into).collect();
+    for peer in &peers {
+        relayer.peers().on_connected(*peer, None, false, true);
+    }
+
+    let tx_hash = h256!("0x1");
+    for peer in &peers {
+        announce_tx_hashes(&relayer, &nc, *peer, &[tx_hash.clone()]);
+        thread::sleep(Duration::from_millis(100));
+        relayer.ask_for_txs(nc.as_ref());
+        relayer.retry_asked_txs(nc.as_ref(), Instant::now());
+    }
+    assert_eq!(nc.asked_txs(), vec![(peers[0], tx_hash.clone())]);
+    // the second announcer is the fallback
+    assert_eq!(relayer.state.tx_announcers.lock()[&tx_hash].peers, peers);
+
+    // not asked while being verified
+    let verifying_tx_hash = h256!("0x2");
+    let verifying = VerifyingTxGuard::new(&relayer.state, verifying_tx_hash.clone());
+    announce_tx_hashes(&relayer, &nc, peers[1], &[verifying_tx_hash.clone()]);
+    assert!(!relayer
+        .state
+        .tx_already_asked
+        .lock()
+        .contains_key(&verifying_tx_hash));
+    assert_eq!(verifying.finish(), vec![peers[1]]);
+}
+
+#[test]
+fn test_ask_by_fee_rate_under_backpressure() {
+    let (mut relayer, _) = build_chain(1);
+    relayer.config.tx_ask_priority_threshold = 2;
+    relayer.state = Arc::new(RelayState::new(10, 5));
+    let nc = Arc::new(DummyNetworkContext::default());
+    let peer: PeerIndex = 1.into();
+    relayer.peers().on_connected(peer, None, false, true);
+    let arrive = |tx_hash: &H256| {
+        relayer
+            .peers()
+            .state
+            .write()
+            .get_mut(&peer)
+            .expect("peer state")
+            .remove_ask_for_tx(tx_hash);
+        relayer.state.mark_as_known_tx(tx_hash.clone());
+    };
+
+    let hashes = tx_hashes(0..6);
+    announce_tx_hashes_with_fee_rates(&relayer, &nc, peer, &hashes[..2], &[1, 1]);
+    thread::sleep(Duration::from_millis(1));
+    relayer.ask_for_txs(nc.as_ref());
+    assert_eq!(nc.asked_txs().len(), 2);
+
+    // saturated, the new asks are queued and the lowest fee rate hint is shed
+    announce_tx_hashes_with_fee_rates(&relayer, &nc, peer, &hashes[2..], &[10, 40, 20, 30]);
+    assert_eq!(relayer.metrics().shed, 1);
+    {
+        let tx_announcers = relayer.state.tx_announcers.lock();
+        assert_eq!(tx_announcers.len(), 5);
+        assert!(!tx_announcers.contains_key(&hashes[2]));
+        for tx_hash in &hashes[3..] {
+            assert_eq!(tx_announcers[tx_hash].asked, None);
+        }
+    }
+    thread::sleep(Duration::from_millis(1));
+    relayer.ask_for_txs(nc.as_ref());
+    relayer.retry_asked_txs(nc.as_ref(), Instant::now());
+    assert_eq!(nc.asked_txs().len(), 2);
+
+    // the highest fee rate hints are asked first as the asked transactions arrive
+    arrive(&hashes[0]);
+    relayer.retry_asked_txs(nc.as_ref(), Instant::now());
+    assert_eq!(nc.asked_txs()[2..], [(peer, hashes[3].clone())]);
+    arrive(&hashes[1]);
+    relayer.retry_asked_txs(nc.as_ref(), Instant::now());
+    assert_eq!(nc.asked_txs()[3..], [(peer, hashes[5].clone())]);
+}
+
+#[test]
+fn test_no_fee_rate_priority_below_threshold() {
+    let (relayer, _) = build_chain(1);
+    let nc = Arc::new(DummyNetworkContext::default());
+    let peer: PeerIndex = 1.into();
+    relayer.peers().on_connected(peer, None, false, true);
+
+    let hashes = tx_hashes(0..4);
+    announce_tx_hashes_with_fee_rates(&relayer, &nc, peer, &hashes, &[10, 20, 30, 40]);
+    thread::sleep(Duration::from_millis(1));
+    relayer.ask_for_txs(nc.as_ref());
+    // asked in the announcement order
+    assert_eq!(
+        nc.asked_txs(),
+        hashes
+            .iter()
+            .map(|tx_hash| (peer, tx_hash.clone()))
+            .collect::<Vec<_>>()
+    );
+    assert_eq!(relayer.metrics().shed, 0);
+}
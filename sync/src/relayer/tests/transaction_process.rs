@@ -0,0 +1,670 @@
+use super::compact_block_process::{build_chain, new_transaction};
+use super::transaction_hashes_process::{announce_tx_hashes, DummyNetworkContext};
+use crate::relayer::transaction_process::TransactionProcess;
+use crate::relayer::{
+    RejectReason, RelayStats, RelayTxResult, Relayer, INFLATED_FEE_RATE_HINT_SCORE,
+    MAX_CYCLES_PER_ACCEPTED_TX, MAX_UNSOLICITED_TXS, TX_ASK_TIMEOUT,
+};
+use crate::types::TxRelayStats;
+use ckb_core::transaction::{
+    CellInput, CellOutput, OutPoint, ProposalShortId, Transaction, TransactionBuilder,
+};
+use ckb_core::{Bytes, Capacity, Cycle};
+use ckb_db::memorydb::MemoryKeyValueDB;
+use ckb_network::{Behaviour, CKBProtocolContext, PeerIndex};
+use ckb_protocol::{get_root, RelayMessage};
+use ckb_store::ChainKVStore;
+use ckb_traits::ChainProvider;
+use flatbuffers::FlatBufferBuilder;
+use numext_fixed_hash::{h256, H256};
+use std::convert::TryInto;
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+use test_chain_utils::create_always_success_cell;
+
+// A transaction spending the tip cellbase, and a transaction spending its output
+pub(super) fn new_parent_and_child(
+    relayer: &Relayer<ChainKVStore<MemoryKeyValueDB>>,
+    always_success_out_point: &OutPoint,
+) -> (Transaction, Transaction) {
+    let (_, always_success_script) = create_always_success_cell();
+    let previous_output = {
+        let tip_hash = relayer.shared.shared().lock_chain_state().tip_hash();
+        let block = relayer
+            .shared
+            .shared()
+            .block(&tip_hash)
+            .expect("getting tip block");
+        block.transactions()[0].output_pts()[0].clone()
+    };
+    let parent = TransactionBuilder::default()
+        .input(CellInput::new(previous_output, 0, Default::default()))
+        .output(CellOutput::new(
+            Capacity::bytes(1000).unwrap(),
+            Bytes::default(),
+            always_success_script,
+            None,
+        ))
+        .dep(always_success_out_point.to_owned())
+        .witness(Default::default())
+        .build();
+    let child = TransactionBuilder::default()
+        .input(CellInput::new(
+            OutPoint::new_cell(parent.hash().to_owned(), 0),
+            0,
+            Default::default(),
+        ))
+        .output(CellOutput::new(
+            Capacity::bytes(500).unwrap(),
+            Bytes::default(),
+            Default::default(),
+            None,
+        ))
+        .dep(always_success_out_point.to_owned())
+        .witness(Default::default())
+        .build();
+    (parent, child)
+}
+
+fn relay_tx(
+    relayer: &Relayer<ChainKVStore<MemoryKeyValueDB>>,
+    nc: &Arc<DummyNetworkContext>,
+    peer: PeerIndex,
+    tx: &Transaction,
+    cycles: Cycle,
+) {
+    let fbb = &mut FlatBufferBuilder::new();
+    let message = RelayMessage::build_transaction(fbb, tx, cycles);
+    fbb.finish(message, None);
+    let message = get_root::<RelayMessage>(fbb.finished_data()).expect("valid message");
+    let nc: Arc<dyn CKBProtocolContext + Sync> = Arc::<DummyNetworkContext>::clone(nc);
+    TransactionProcess::new(
+        &message
+            .payload_as_relay_transaction()
+            .expect("relay transaction"),
+        relayer,
+        nc,
+        peer,
+    )
+    .execute()
+    .expect("process transaction");
+}
+
+fn is_pooled(relayer: &Relayer<ChainKVStore<MemoryKeyValueDB>>, tx: &Transaction) -> bool {
+    relayer
+        .shared
+        .lock_chain_state()
+        .get_tx_with_cycles_from_pool(&tx.proposal_short_id())
+        .is_some()
+}
+
+#[test]
+fn test_accept_orphan_tx_after_parent() {
+    let (relayer, always_success_out_point) = build_chain(5);
+    let (parent, child) = new_parent_and_child(&relayer, &always_success_out_point);
+    // the same chain, used to get the cycles of the transactions
+    let (probe, _) = build_chain(5);
+    let cycles = [&parent, &child]
+        .iter()
+        .map(|tx| {
+            probe
+                .tx_pool_executor
+                .verify_and_add_tx_to_pool((*tx).to_owned())
+                .expect("adding transaction into pool")
+        })
+        .collect::<Vec<_>>();
+
+    let nc = DummyNetworkContext::default();
+    let peer: PeerIndex = 1.into();
+    relayer.accept_relayed_tx(&nc, peer, child.clone(), cycles[1]);
+    assert!(relayer.state.orphan_txs.contains(child.hash()));
+    assert!(relayer.state.already_known_tx(child.hash()));
+
+    relayer.accept_relayed_tx(&nc, peer, parent.clone(), cycles[0]);
+    assert!(relayer.state.orphan_txs.is_empty());
+    assert!(nc.banned.lock().is_empty());
+    assert!(nc.reported.lock().is_empty());
+    let chain_state = relayer.shared.lock_chain_state();
+    for tx in &[parent, child] {
+        assert!(chain_state
+            .tx_pool()
+            .get_tx_with_cycles(&ProposalShortId::from_tx_hash(tx.hash()))
+            .is_some());
+    }
+}
+
+#[test]
+fn test_relayed_tx_cycles_claims() {
+    let cycles = {
+        let (probe, always_success_out_point) = build_chain(5);
+        let (parent, _) = new_parent_and_child(&probe, &always_success_out_point);
+        probe
+            .tx_pool_executor
+            .verify_and_add_tx_to_pool(parent)
+            .expect("adding transaction into pool")
+    };
+
+    // (claimed cycles, accepted, reported)
+    let cases = vec![
+        (cycles, true, false),
+        (cycles - 1, false, true),
+        (cycles + 1, true, true),
+    ];
+    for (claimed_cycles, accepted, reported) in cases {
+        let (relayer, always_success_out_point) = build_chain(5);
+        let (tx, _) = new_parent_and_child(&relayer, &always_success_out_point);
+        let nc = DummyNetworkContext::default();
+        let peer: PeerIndex = 1.into();
+        relayer.accept_relayed_tx(&nc, peer, tx.clone(), claimed_cycles);
+
+        let pooled = relayer
+            .shared
+            .lock_chain_state()
+            .tx_pool()
+            .get_tx_with_cycles(&ProposalShortId::from_tx_hash(tx.hash()));
+        assert_eq!(pooled.is_some(), accepted, "claimed {}", claimed_cycles);
+        // the measured cycles are kept, and relayed onward
+        if let Some((_, pooled_cycles)) = pooled {
+            assert_eq!(pooled_cycles, Some(cycles));
+        }
+        let expected_reports = if reported {
+            vec![(peer, Behaviour::MismatchedTxCycles)]
+        } else {
+            Vec::new()
+        };
+        assert_eq!(*nc.reported.lock(), expected_reports);
+        assert!(nc.banned.lock().is_empty());
+    }
+}
+
+#[test]
+fn test_rejected_tx_not_asked_again() {
+    let (relayer, always_success_out_point) = build_chain(5);
+    let (parent, _) = new_parent_and_child(&relayer, &always_success_out_point);
+    // without the always success dep, the lock script of the input is not found
+    let tx = TransactionBuilder::from_transaction(parent)
+        .deps_clear()
+        .build();
+    let tx_hash = tx.hash().to_owned();
+    let nc = DummyNetworkContext::default();
+    let peers: Vec<PeerIndex> = (1..4).map(Into::into).collect();
+    for peer in &peers {
+        relayer.peers().on_connected(*peer, None, false, true);
+    }
+
+    relayer.add_ask_for_txs(peers[0], vec![tx_hash.clone()]);
+    thread::sleep(Duration::from_millis(1));
+    relayer.ask_for_txs(&nc);
+    assert_eq!(nc.asked_txs().len(), 1);
+    relayer.accept_relayed_tx(&nc, peers[0], tx, 1_000_000);
+    assert_eq!(
+        relayer
+            .state
+            .rejected_txs
+            .lock()
+            .get(&tx_hash)
+            .map(|rejected| rejected.reason),
+        Some(RejectReason::Script)
+    );
+
+    for peer in &peers[1..] {
+        relayer.add_ask_for_txs(*peer, vec![tx_hash.clone()]);
+    }
+    thread::sleep(Duration::from_millis(1));
+    relayer.ask_for_txs(&nc);
+    relayer.retry_asked_txs(&nc, Instant::now() + Duration::from_secs(3600));
+    assert_eq!(nc.asked_txs().len(), 1);
+}
+
+#[test]
+fn test_relay_tx_hash_to_peers_not_knowing_it() {
+    let cycles = {
+        let (probe, always_success_out_point) = build_chain(5);
+        let (parent, _) = new_parent_and_child(&probe, &always_success_out_point);
+        probe
+            .tx_pool_executor
+            .verify_and_add_tx_to_pool(parent)
+            .expect("adding transaction into pool")
+    };
+    let (relayer, always_success_out_point) = build_chain(5);
+    let (tx, _) = new_parent_and_child(&relayer, &always_success_out_point);
+    let peers: Vec<PeerIndex> = vec![1.into(), 2.into(), 3.into()];
+    let nc = DummyNetworkContext {
+        connected: peers.clone(),
+        ..Default::default()
+    };
+
+    // relayed to the peers except the sender
+    relayer.accept_relayed_tx(&nc, peers[0], tx.clone(), cycles);
+    assert_eq!(
+        nc.broadcast
+            .lock()
+            .iter()
+            .map(|(peers, _)| peers.to_owned())
+            .collect::<Vec<_>>(),
+        vec![peers[1..].to_vec()]
+    );
+
+    // accepted again, such as after a reorg, no one is told twice
+    relayer.broadcast_tx_hash(&nc, peers[1], tx.hash(), 0);
+    assert_eq!(nc.broadcast.lock().len(), 1);
+
+    // the peer reconnecting knows nothing
+    relayer.peers().known_txs.lock().remove(peers[2]);
+    relayer.broadcast_tx_hash(&nc, peers[1], tx.hash(), 0);
+    assert_eq!(nc.broadcast.lock()[1].0, vec![peers[2]]);
+}
+
+#[test]
+fn test_inflated_fee_rate_hints() {
+    let cycles = {
+        let (probe, always_success_out_point) = build_chain(5);
+        let (parent, _) = new_parent_and_child(&probe, &always_success_out_point);
+        probe
+            .tx_pool_executor
+            .verify_and_add_tx_to_pool(parent)
+            .expect("adding transaction into pool")
+    };
+    let (relayer, always_success_out_point) = build_chain(5);
+    let (tx, _) = new_parent_and_child(&relayer, &always_success_out_point);
+    let input_capacity = {
+        let tip_hash = relayer.shared.shared().lock_chain_state().tip_hash();
+        let block = relayer
+            .shared
+            .shared()
+            .block(&tip_hash)
+            .expect("getting tip block");
+        block.transactions()[0].outputs()[0].capacity
+    };
+    let fee = input_capacity
+        .safe_sub(Capacity::bytes(1000).unwrap())
+        .expect("enough capacity");
+    let fee_rate = fee.as_u64() * 1000 / tx.serialized_size() as u64;
+
+    let peers: Vec<PeerIndex> = vec![1.into(), 2.into(), 3.into()];
+    let nc = DummyNetworkContext {
+        connected: peers.clone(),
+        ..Default::default()
+    };
+    relayer.add_fee_rate_hints(peers[0], &[(tx.hash().to_owned(), fee_rate)]);
+    relayer.add_fee_rate_hints(peers[1], &[(tx.hash().to_owned(), u64::max_value())]);
+    relayer.accept_relayed_tx(&nc, peers[0], tx.clone(), cycles);
+
+    // only the peer inflating its hint is punished
+    let misbehavior = relayer.peers().misbehavior.read().clone();
+    assert_eq!(misbehavior.get(&peers[0]), None);
+    assert_eq!(
+        misbehavior.get(&peers[1]),
+        Some(&INFLATED_FEE_RATE_HINT_SCORE)
+    );
+    assert!(!relayer.state.fee_rate_hints.lock().contains_key(tx.hash()));
+
+    // the real fee rate is relayed onward
+    let broadcast = nc.broadcast.lock();
+    let message = get_root::<RelayMessage>(&broadcast[0].1).expect("valid message");
+    let relayed: (H256, u64) = message
+        .payload_as_relay_transaction_hash()
+        .expect("relay transaction hash")
+        .try_into()
+        .expect("valid hash");
+    assert_eq!(relayed, (tx.hash().to_owned(), fee_rate));
+}
+
+#[test]
+fn test_relay_metrics() {
+    let (relayer, always_success_out_point) = build_chain(5);
+    let (parent, child) = new_parent_and_child(&relayer, &always_success_out_point);
+    let (probe, _) = build_chain(5);
+    let cycles = [&parent, &child]
+        .iter()
+        .map(|tx| {
+            probe
+                .tx_pool_executor
+                .verify_and_add_tx_to_pool((*tx).to_owned())
+                .expect("adding transaction into pool")
+        })
+        .collect::<Vec<_>>();
+    let bad_tx = TransactionBuilder::from_transaction(parent.clone())
+        .deps_clear()
+        .build();
+    let nc = Arc::new(DummyNetworkContext::default());
+    let peer: PeerIndex = 1.into();
+    relayer.peers().on_connected(peer, None, false, true);
+
+    let known_tx_hash = h256!("0x1");
+    relayer.state.mark_as_known_tx(known_tx_hash.clone());
+    let new_tx_hashes = vec![h256!("0x2"), h256!("0x3")];
+    announce_tx_hashes(
+        &relayer,
+        &nc,
+        peer,
+        &[
+            known_tx_hash,
+            new_tx_hashes[0].clone(),
+            new_tx_hashes[1].clone(),
+            new_tx_hashes[0].clone(),
+        ],
+    );
+
+    relayer.accept_relayed_tx(nc.as_ref(), peer, child.clone(), cycles[1]);
+    relayer.accept_relayed_tx(nc.as_ref(), peer, bad_tx.clone(), 1_000_000);
+    relayer.accept_relayed_tx(nc.as_ref(), peer, parent.clone(), cycles[0]);
+
+    // pooled, and rejected recently
+    announce_tx_hashes(
+        &relayer,
+        &nc,
+        peer,
+        &[
+            parent.hash().to_owned(),
+            child.hash().to_owned(),
+            bad_tx.hash().to_owned(),
+        ],
+    );
+
+    assert_eq!(
+        relayer.metrics(),
+        RelayStats {
+            announced: 7,
+            already_known: 2,
+            already_pooled: 2,
+            asked: 2,
+            received: 3,
+            accepted: 2,
+            rejected_script: 1,
+            orphaned: 1,
+            ..Default::default()
+        }
+    );
+}
+
+#[test]
+fn test_relayed_tx_solicitation() {
+    let cycles = {
+        let (probe, always_success_out_point) = build_chain(5);
+        let (parent, _) = new_parent_and_child(&probe, &always_success_out_point);
+        probe
+            .tx_pool_executor
+            .verify_and_add_tx_to_pool(parent)
+            .expect("adding transaction into pool")
+    };
+    let peer: PeerIndex = 1.into();
+
+    // asked for, accepted
+    {
+        let (relayer, always_success_out_point) = build_chain(5);
+        let (tx, _) = new_parent_and_child(&relayer, &always_success_out_point);
+        let nc = Arc::new(DummyNetworkContext::default());
+        relayer.peers().on_connected(peer, None, false, true);
+        relayer.add_ask_for_txs(peer, vec![tx.hash().to_owned()]);
+        relay_tx(&relayer, &nc, peer, &tx, cycles);
+        assert!(is_pooled(&relayer, &tx));
+        assert!(nc.reported.lock().is_empty());
+        assert!(relayer.state.tx_already_asked.lock().is_empty());
+    }
+
+    // another transaction than the asked one, dropped and the ask is kept
+    {
+        let (relayer, always_success_out_point) = build_chain(5);
+        let (tx, _) = new_parent_and_child(&relayer, &always_success_out_point);
+        let other_tx = new_transaction(&relayer, 0, &always_success_out_point);
+        let nc = Arc::new(DummyNetworkContext::default());
+        relayer.peers().on_connected(peer, None, false, true);
+        relayer.add_ask_for_txs(peer, vec![tx.hash().to_owned()]);
+        thread::sleep(Duration::from_millis(1));
+        relayer.ask_for_txs(nc.as_ref());
+        relay_tx(&relayer, &nc, peer, &other_tx, cycles);
+        assert_eq!(*nc.reported.lock(), vec![(peer, Behaviour::MismatchedTx)]);
+        assert!(!is_pooled(&relayer, &other_tx));
+        assert!(!relayer.state.already_known_tx(other_tx.hash()));
+        assert!(relayer
+            .state
+            .tx_already_asked
+            .lock()
+            .contains_key(tx.hash()));
+        assert!(relayer.peers().state.read()[&peer].contains_ask_for_tx(tx.hash()));
+    }
+
+    // another transaction than the one scheduled to be asked, but not asked yet, is not a
+    // mismatched response
+    {
+        let (relayer, always_success_out_point) = build_chain(5);
+        let (tx, _) = new_parent_and_child(&relayer, &always_success_out_point);
+        let other_tx = new_transaction(&relayer, 0, &always_success_out_point);
+        let nc = Arc::new(DummyNetworkContext::default());
+        relayer.peers().on_connected(peer, None, false, true);
+        relayer.add_ask_for_txs(peer, vec![tx.hash().to_owned()]);
+        relay_tx(&relayer, &nc, peer, &other_tx, cycles);
+        assert!(nc.reported.lock().is_empty());
+        assert!(relayer.peers().state.read()[&peer].contains_ask_for_tx(tx.hash()));
+    }
+
+    // the sent ask timed out, another transaction is not a response to it
+    {
+        let (relayer, always_success_out_point) = build_chain(5);
+        let (tx, _) = new_parent_and_child(&relayer, &always_success_out_point);
+        let other_tx = new_transaction(&relayer, 0, &always_success_out_point);
+        let nc = Arc::new(DummyNetworkContext::default());
+        relayer.peers().on_connected(peer, None, false, true);
+        relayer.add_ask_for_txs(peer, vec![tx.hash().to_owned()]);
+        thread::sleep(Duration::from_millis(1));
+        relayer.ask_for_txs(nc.as_ref());
+        relayer.retry_asked_txs(nc.as_ref(), Instant::now() + TX_ASK_TIMEOUT * 2);
+        relay_tx(&relayer, &nc, peer, &other_tx, cycles);
+        assert!(nc.reported.lock().is_empty());
+    }
+
+    // never asked for, accepted within the allowance
+    {
+        let (relayer, always_success_out_point) = build_chain(5);
+        let (tx, _) = new_parent_and_child(&relayer, &always_success_out_point);
+        let nc = Arc::new(DummyNetworkContext::default());
+        relayer.peers().on_connected(peer, None, false, true);
+        relay_tx(&relayer, &nc, peer, &tx, cycles);
+        assert!(is_pooled(&relayer, &tx));
+        assert!(nc.reported.lock().is_empty());
+
+        for _ in 1..MAX_UNSOLICITED_TXS {
+            relay_tx(&relayer, &nc, peer, &tx, cycles);
+        }
+        assert!(nc.reported.lock().is_empty());
+        relay_tx(&relayer, &nc, peer, &tx, cycles);
+        assert_eq!(*nc.reported.lock(), vec![(peer, Behaviour::UnsolicitedTx)]);
+    }
+}
+
+#[test]
+fn test_verify_relayed_tx_once() {
+    let cycles = {
+        let (probe, always_success_out_point) = build_chain(5);
+        let (parent, _) = new_parent_and_child(&probe, &always_success_out_point);
+        probe
+            .tx_pool_executor
+            .verify_and_add_tx_to_pool(parent)
+            .expect("adding transaction into pool")
+    };
+    let peers: Vec<PeerIndex> = vec![1.into(), 2.into()];
+
+    // (claimed cycles, accepted), the copy exceeding the claimed cycles is asked again
+    for (claimed_cycles, accepted) in vec![(cycles, true), (cycles - 1, false)] {
+        let (relayer, always_success_out_point) = build_chain(5);
+        let (tx, _) = new_parent_and_child(&relayer, &always_success_out_point);
+        let tx_hash = tx.hash().to_owned();
+        // a slow verifier, the verification waits until `run_tasks`
+        let nc = Arc::new(DummyNetworkContext {
+            defer_tasks: true,
+            ..Default::default()
+        });
+        for peer in &peers {
+            relayer.peers().on_connected(*peer, None, false, true);
+        }
+
+        announce_tx_hashes(&relayer, &nc, peers[0], &[tx_hash.clone()]);
+        relay_tx(&relayer, &nc, peers[0], &tx, claimed_cycles);
+        assert!(relayer.state.verifying_txs.lock().contains_key(&tx_hash));
+
+        // announced meanwhile, not asked
+        announce_tx_hashes(&relayer, &nc, peers[1], &[tx_hash.clone()]);
+        thread::sleep(Duration::from_millis(1));
+        relayer.ask_for_txs(nc.as_ref());
+        assert!(nc.asked_txs().is_empty());
+
+        nc.run_tasks();
+        assert_eq!(relayer.metrics().received, 1);
+        assert_eq!(is_pooled(&relayer, &tx), accepted);
+        assert!(relayer.state.verifying_txs.lock().is_empty());
+        thread::sleep(Duration::from_millis(1));
+        relayer.ask_for_txs(nc.as_ref());
+        let expected_asks = if accepted {
+            Vec::new()
+        } else {
+            vec![(peers[1], tx_hash.clone())]
+        };
+        assert_eq!(nc.asked_txs(), expected_asks);
+    }
+}
+
+#[test]
+fn test_verifying_tx_released_when_task_dropped() {
+    let (relayer, always_success_out_point) = build_chain(5);
+    let (tx, _) = new_parent_and_child(&relayer, &always_success_out_point);
+    let nc = Arc::new(DummyNetworkContext {
+        defer_tasks: true,
+        ..Default::default()
+    });
+    let peer: PeerIndex = 1.into();
+    relayer.peers().on_connected(peer, None, false, true);
+
+    relay_tx(&relayer, &nc, peer, &tx, 1_000_000);
+    assert!(relayer.state.verifying_txs.lock().contains_key(tx.hash()));
+    // such as unwinding from a panic of the verifier
+    nc.tasks.lock().clear();
+    assert!(relayer.state.verifying_txs.lock().is_empty());
+}
+
+#[test]
+fn test_send_relayed_tx_results() {
+    let (mut relayer, always_success_out_point) = build_chain(5);
+    // no refill during the test, only the burst of rejections is sent
+    relayer.config.tx_reject_rate = 0;
+    relayer.config.tx_reject_burst = 3;
+    let (parent, child) = new_parent_and_child(&relayer, &always_success_out_point);
+    let (probe, _) = build_chain(5);
+    let cycles = [&parent, &child]
+        .iter()
+        .map(|tx| {
+            probe
+                .tx_pool_executor
+                .verify_and_add_tx_to_pool((*tx).to_owned())
+                .expect("adding transaction into pool")
+        })
+        .collect::<Vec<_>>();
+    let bad_tx = TransactionBuilder::from_transaction(parent.clone())
+        .deps_clear()
+        .build();
+    let nc = DummyNetworkContext::default();
+    let peer: PeerIndex = 1.into();
+    relayer.peers().on_connected(peer, None, false, true);
+
+    relayer.accept_relayed_tx(&nc, peer, child.clone(), cycles[1]);
+    for _ in 0..3 {
+        relayer.accept_relayed_tx(&nc, peer, bad_tx.clone(), 1_000_000);
+    }
+    relayer.accept_relayed_tx(&nc, peer, parent.clone(), cycles[0]);
+
+    // the last rejection is beyond the limit, the acceptances are not limited
+    assert_eq!(
+        nc.tx_results(),
+        vec![
+            (
+                peer,
+                child.hash().to_owned(),
+                RelayTxResult::Temporary as u8
+            ),
+            (peer, bad_tx.hash().to_owned(), RelayTxResult::Invalid as u8),
+            (peer, bad_tx.hash().to_owned(), RelayTxResult::Invalid as u8),
+            (
+                peer,
+                parent.hash().to_owned(),
+                RelayTxResult::Accepted as u8
+            ),
+            (peer, child.hash().to_owned(), RelayTxResult::Accepted as u8),
+        ]
+    );
+}
+
+#[test]
+fn test_tx_relay_stats() {
+    let (relayer, always_success_out_point) = build_chain(5);
+    let (parent, child) = new_parent_and_child(&relayer, &always_success_out_point);
+    let (probe, _) = build_chain(5);
+    let cycles = [&parent, &child]
+        .iter()
+        .map(|tx| {
+            probe
+                .tx_pool_executor
+                .verify_and_add_tx_to_pool((*tx).to_owned())
+                .expect("adding transaction into pool")
+        })
+        .collect::<Vec<_>>();
+    let nc = Arc::new(DummyNetworkContext::default());
+    let peer: PeerIndex = 1.into();
+    relayer.peers().on_connected(peer, None, false, true);
+
+    // the parent is announced and asked for, the child is not
+    announce_tx_hashes(&relayer, &nc, peer, &[parent.hash().to_owned()]);
+    relay_tx(&relayer, &nc, peer, &parent, cycles[0]);
+    relay_tx(&relayer, &nc, peer, &child, cycles[1]);
+
+    assert_eq!(
+        relayer.tx_relay_stats(peer),
+        Some(TxRelayStats {
+            announced: 1,
+            received: 2,
+            received_bytes: (parent.serialized_size() + child.serialized_size()) as u64,
+            asked: 1,
+            unsolicited: 1,
+            accepted: 2,
+            rejected: 0,
+            cycles: cycles[0] + cycles[1],
+        })
+    );
+    assert_eq!(relayer.tx_relay_stats(2.into()), None);
+}
+
+#[test]
+fn test_costly_tx_relay_reported() {
+    let (relayer, _) = build_chain(1);
+    let nc = DummyNetworkContext::default();
+    let peers: Vec<PeerIndex> = vec![1.into(), 2.into(), 3.into()];
+    for peer in &peers {
+        relayer.peers().on_connected(*peer, None, false, true);
+    }
+
+    for i in 0..20 {
+        // 90% invalid
+        relayer.account_verified_tx(&nc, peers[0], i % 10 == 0, 1000);
+        // mostly honest
+        relayer.account_verified_tx(&nc, peers[1], i % 10 != 0, 1000);
+        // valid, but too costly to verify
+        relayer.account_verified_tx(&nc, peers[2], true, MAX_CYCLES_PER_ACCEPTED_TX + 1);
+    }
+
+    assert_eq!(
+        *nc.reported.lock(),
+        vec![
+            (peers[0], Behaviour::CostlyTxRelay),
+            (peers[2], Behaviour::CostlyTxRelay),
+        ]
+    );
+    // the accounting of the reported peers starts over
+    assert_eq!(
+        relayer.tx_relay_stats(peers[0]),
+        Some(TxRelayStats::default())
+    );
+    assert_eq!(
+        relayer.tx_relay_stats(peers[1]).map(|stats| stats.rejected),
+        Some(2)
+    );
+}
@@ -0,0 +1,72 @@
+use super::compact_block_process::build_chain;
+use super::transaction_hashes_process::{announce_tx_hashes, DummyNetworkContext};
+use crate::relayer::transaction_result_process::TransactionResultProcess;
+use crate::relayer::{RelayTxResult, Relayer, TX_ASK_TIMEOUT};
+use ckb_network::PeerIndex;
+use ckb_protocol::{get_root, RelayMessage};
+use ckb_store::ChainStore;
+use flatbuffers::FlatBufferBuilder;
+use numext_fixed_hash::{h256, H256};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+fn receive_tx_result<CS: ChainStore + 'static>(
+    relayer: &Relayer<CS>,
+    peer: PeerIndex,
+    tx_hash: &H256,
+    result: RelayTxResult,
+) {
+    let fbb = &mut FlatBufferBuilder::new();
+    let message = RelayMessage::build_transaction_result(fbb, tx_hash, result as u8);
+    fbb.finish(message, None);
+    let message = get_root::<RelayMessage>(fbb.finished_data()).expect("valid message");
+    TransactionResultProcess::new(
+        &message
+            .payload_as_relay_transaction_result()
+            .expect("relay transaction result"),
+        relayer,
+        peer,
+    )
+    .execute()
+    .expect("process transaction result");
+}
+
+#[test]
+fn test_tx_rejected_by_peer_not_asked_again() {
+    let (relayer, _) = build_chain(1);
+    let nc = Arc::new(DummyNetworkContext::default());
+    let peers: Vec<PeerIndex> = vec![1.into(), 2.into()];
+    for peer in &peers {
+        relayer.peers().on_connected(*peer, None, false, true);
+    }
+
+    let tx_hash = h256!("0x1");
+    for peer in &peers {
+        announce_tx_hashes(&relayer, &nc, *peer, &[tx_hash.clone()]);
+    }
+    thread::sleep(Duration::from_millis(1));
+    relayer.ask_for_txs(nc.as_ref());
+    assert_eq!(nc.asked_txs(), vec![(peers[0], tx_hash.clone())]);
+
+    // the asked peer finds the transaction invalid, the other announcer is asked at once
+    receive_tx_result(&relayer, peers[0], &tx_hash, RelayTxResult::Invalid);
+    assert!(!relayer.peers().state.read()[&peers[0]].contains_ask_for_tx(&tx_hash));
+    relayer.retry_asked_txs(nc.as_ref(), Instant::now());
+    assert_eq!(nc.asked_txs()[1..], [(peers[1], tx_hash.clone())]);
+
+    // announced again by the rejecting peer, never asked from it
+    relayer.retry_asked_txs(nc.as_ref(), Instant::now() + TX_ASK_TIMEOUT * 8);
+    announce_tx_hashes(&relayer, &nc, peers[0], &[tx_hash.clone()]);
+    thread::sleep(Duration::from_millis(1));
+    relayer.ask_for_txs(nc.as_ref());
+    assert_eq!(nc.asked_txs().len(), 2);
+
+    // the other results only tell the peer knows the transaction
+    let other_tx_hash = h256!("0x2");
+    receive_tx_result(&relayer, peers[1], &other_tx_hash, RelayTxResult::Temporary);
+    announce_tx_hashes(&relayer, &nc, peers[1], &[other_tx_hash.clone()]);
+    thread::sleep(Duration::from_millis(1));
+    relayer.ask_for_txs(nc.as_ref());
+    assert_eq!(nc.asked_txs()[2..], [(peers[1], other_tx_hash)]);
+}
@@ -0,0 +1,73 @@
+use crate::relayer::metrics::RelayMetrics;
+use crate::relayer::{Relayer, MAX_RELAY_TX_HASHES};
+use ckb_network::{CKBProtocolContext, PeerIndex};
+use ckb_protocol::{cast, RelayTransactionHashes as FbsRelayTransactionHashes};
+use ckb_store::ChainStore;
+use failure::Error as FailureError;
+use log::info;
+use numext_fixed_hash::H256;
+use std::convert::TryInto;
+use std::sync::Arc;
+
+pub struct TransactionHashesProcess<'a, CS> {
+    message: &'a FbsRelayTransactionHashes<'a>,
+    relayer: &'a Relayer<CS>,
+    nc: Arc<dyn CKBProtocolContext>,
+    peer: PeerIndex,
+}
+
+impl<'a, CS: ChainStore + 'static> TransactionHashesProcess<'a, CS> {
+    pub fn new(
+        message: &'a FbsRelayTransactionHashes,
+        relayer: &'a Relayer<CS>,
+        nc: Arc<dyn CKBProtocolContext>,
+        peer: PeerIndex,
+    ) -> Self {
+        TransactionHashesProcess {
+            message,
+            relayer,
+            nc,
+            peer,
+        }
+    }
+
+    pub fn execute(self) -> Result<(), FailureError> {
+        let fbs_tx_hashes = cast!(self.message.tx_hashes())?;
+        if fbs_tx_hashes.len() > MAX_RELAY_TX_HASHES {
+            info!(
+                target: "relay",
+                "peer {} announces {} transactions in one message, exceeds the limit {}, ignore it",
+                self.peer,
+                fbs_tx_hashes.len(),
+                MAX_RELAY_TX_HASHES,
+            );
+            return Ok(());
+        }
+        let accepted =
+            self.relayer
+                .accept_tx_announcements(self.nc.as_ref(), self.peer, fbs_tx_hashes.len());
+        let tx_hashes = fbs_tx_hashes
+            .iter()
+            .take(accepted)
+            .map(TryInto::try_into)
+            .collect::<Result<Vec<H256>, _>>()?;
+        RelayMetrics::incr(&self.relayer.state.metrics.announced, tx_hashes.len());
+        // the fee rate hints are optional, in the order of the hashes
+        if let Some(fee_rates) = self.message.fee_rates() {
+            let hints = tx_hashes
+                .iter()
+                .cloned()
+                .zip(fee_rates.safe_slice().iter().cloned())
+                .collect::<Vec<_>>();
+            self.relayer.add_fee_rate_hints(self.peer, &hints);
+        }
+        self.relayer.mark_as_known_by_peer(self.peer, &tx_hashes);
+        if self.relayer.shared.is_initial_block_download() {
+            self.relayer
+                .add_pending_tx_announcements(self.peer, tx_hashes);
+        } else {
+            self.relayer.add_ask_for_txs(self.peer, tx_hashes);
+        }
+        Ok(())
+    }
+}
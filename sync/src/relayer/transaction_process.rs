@@ -1,18 +1,14 @@
-use crate::relayer::Relayer;
-use crate::relayer::MAX_RELAY_PEERS;
+use crate::relayer::{Relayer, VerifyingTxGuard, MAX_UNSOLICITED_TXS};
 use ckb_core::{transaction::Transaction, Cycle};
-use ckb_network::{CKBProtocolContext, PeerIndex, TargetSession};
-use ckb_protocol::{RelayMessage, RelayTransaction as FbsRelayTransaction};
+use ckb_network::{Behaviour, CKBProtocolContext, PeerIndex};
+use ckb_protocol::RelayTransaction as FbsRelayTransaction;
 use ckb_store::ChainStore;
 use failure::Error as FailureError;
-use flatbuffers::FlatBufferBuilder;
+use faketime::unix_time_as_millis;
 use futures::{self, future::FutureResult, lazy};
 use log::debug;
 use std::convert::TryInto;
 use std::sync::Arc;
-use std::time::Duration;
-
-const DEFAULT_BAN_TIME: Duration = Duration::from_secs(3600 * 24 * 3);
 
 pub struct TransactionProcess<'a, CS> {
     message: &'a FbsRelayTransaction<'a>,
@@ -40,6 +36,45 @@ impl<'a, CS: ChainStore + Sync + 'static> TransactionProcess<'a, CS> {
         let (tx, relay_cycles): (Transaction, Cycle) = (*self.message).try_into()?;
         let tx_hash = tx.hash();
 
+        // The transactions not asked for are neither verified nor marked as known when the peer
+        // is sent asks for others, or relays too many of them, so the asks of the transactions
+        // we wait for are retried from other peers
+        let misbehaviour = match self.relayer.peers.state.write().get_mut(&self.peer) {
+            Some(peer_state) => {
+                let asked = peer_state.contains_ask_for_tx(&tx_hash);
+                peer_state.account_tx_relay(
+                    self.relayer.tx_relay_window(),
+                    unix_time_as_millis(),
+                    |stats| {
+                        stats.received += 1;
+                        stats.received_bytes += tx.serialized_size() as u64;
+                        if asked {
+                            stats.asked += 1;
+                        } else {
+                            stats.unsolicited += 1;
+                        }
+                    },
+                );
+                if asked {
+                    // Remove tx_hash from `tx_ask_for_set`
+                    peer_state.remove_ask_for_tx(&tx_hash);
+                    None
+                } else if peer_state.has_sent_asks() {
+                    Some(Behaviour::MismatchedTx)
+                } else if peer_state.add_unsolicited_tx() > MAX_UNSOLICITED_TXS {
+                    Some(Behaviour::UnsolicitedTx)
+                } else {
+                    None
+                }
+            }
+            None => None,
+        };
+        if let Some(behaviour) = misbehaviour {
+            debug!(target: "relay", "peer {} relays transaction {:#x} not asked for, {:?}", self.peer, tx_hash, behaviour);
+            self.nc.report_peer(self.peer, behaviour);
+            return Ok(());
+        }
+
         if self.relayer.state.already_known_tx(&tx_hash) {
             debug!(target: "relay", "discarding already known transaction {:#x}", tx_hash);
             return Ok(());
@@ -48,72 +83,23 @@ impl<'a, CS: ChainStore + Sync + 'static> TransactionProcess<'a, CS> {
         // Insert tx_hash into `already_known`
         // Remove tx_hash from `tx_already_asked`
         self.relayer.state.mark_as_known_tx(tx_hash.clone());
-        // Remove tx_hash from `tx_ask_for_set`
-        if let Some(peer_state) = self.relayer.peers.state.write().get_mut(&self.peer) {
-            peer_state.remove_ask_for_tx(&tx_hash);
-        }
+        // Dropped with the task, the transaction is never left verifying
+        let verifying = VerifyingTxGuard::new(&self.relayer.state, tx_hash.clone());
 
         self.nc.future_task({
-            // prepare sync data
             let nc = Arc::clone(&self.nc);
-            let self_peer = self.peer;
-            let tx_pool_executor = Arc::clone(&self.relayer.tx_pool_executor);
-            let peers = Arc::clone(&self.relayer.peers);
-            let tx_hash = tx_hash.clone();
+            let relayer = self.relayer.clone();
+            let peer = self.peer;
             let tx = tx.to_owned();
-            Box::new(
-                lazy(
-                    move|| -> FutureResult<(), ()> {
-                        let tx_pool_executor = Arc::clone(&tx_pool_executor);
-                        let tx_result = tx_pool_executor.verify_and_add_tx_to_pool(tx.to_owned());
-                        // disconnect peer if cycles mismatch
-                        match tx_result {
-                            Ok(cycles) if cycles == relay_cycles => {
-                                let selected_peers: Vec<PeerIndex> = {
-                                    let mut known_txs = peers.known_txs.lock();
-                                    nc.connected_peers()
-                                        .into_iter()
-                                        .filter(|target_peer| {
-                                            known_txs.insert(*target_peer, tx_hash.clone())
-                                                && (self_peer != *target_peer)
-                                        })
-                                    .take(MAX_RELAY_PEERS)
-                                        .collect()
-                                };
-
-                                let fbb = &mut FlatBufferBuilder::new();
-                                let message = RelayMessage::build_transaction_hash(fbb, &tx_hash);
-                                fbb.finish(message, None);
-                                let data = fbb.finished_data().into();
-                                nc.filter_broadcast(TargetSession::Multi(selected_peers), data);
-                            }
-                            Ok(cycles) => {
-                                debug!(
-                                    target: "relay",
-                                    "peer {} relay wrong cycles tx: {:?} real cycles {} wrong cycles {}",
-                                    self_peer, tx, cycles, relay_cycles,
-                                    );
-                                nc.ban_peer(self_peer, DEFAULT_BAN_TIME);
-                            }
-                            Err(err) => {
-                                if err.is_bad_tx() {
-                                    debug!(target: "relay", "peer {} relay a invalid tx: {:x}, error: {:?}", self_peer, tx_hash, err);
-                                    sentry::capture_message(
-                                        &format!(
-                                            "ban peer {} {:?}, reason: relay invalid tx: {:?}, error: {:?}",
-                                            self_peer, DEFAULT_BAN_TIME, tx, err
-                                        ),
-                                        sentry::Level::Info,
-                                        );
-                                    nc.ban_peer(self_peer, DEFAULT_BAN_TIME);
-                                } else {
-                                    debug!(target: "relay", "peer {} relay a conflict or missing input tx: {:x}, error: {:?}", self_peer, tx_hash, err);
-                                }
-                            }
-                        }
-                        futures::future::ok(())
-                    })
-            )
+            Box::new(lazy(move || -> FutureResult<(), ()> {
+                let tx_hash = tx.hash().to_owned();
+                let garbage = relayer.accept_relayed_tx(nc.as_ref(), peer, tx, relay_cycles);
+                let announcers = verifying.finish();
+                if garbage {
+                    relayer.ask_fallback_announcers(&tx_hash, announcers);
+                }
+                futures::future::ok(())
+            }))
         });
         Ok(())
     }
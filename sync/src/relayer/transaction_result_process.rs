@@ -0,0 +1,46 @@
+use crate::relayer::{RelayTxResult, Relayer};
+use ckb_network::PeerIndex;
+use ckb_protocol::RelayTransactionResult as FbsRelayTransactionResult;
+use ckb_store::ChainStore;
+use failure::Error as FailureError;
+use log::debug;
+use numext_fixed_hash::H256;
+use std::convert::TryInto;
+
+pub struct TransactionResultProcess<'a, CS> {
+    message: &'a FbsRelayTransactionResult<'a>,
+    relayer: &'a Relayer<CS>,
+    peer: PeerIndex,
+}
+
+impl<'a, CS: ChainStore + 'static> TransactionResultProcess<'a, CS> {
+    pub fn new(
+        message: &'a FbsRelayTransactionResult,
+        relayer: &'a Relayer<CS>,
+        peer: PeerIndex,
+    ) -> Self {
+        TransactionResultProcess {
+            message,
+            relayer,
+            peer,
+        }
+    }
+
+    pub fn execute(self) -> Result<(), FailureError> {
+        let (tx_hash, result): (H256, u8) = (*self.message).try_into()?;
+        match RelayTxResult::from_u8(result) {
+            Some(RelayTxResult::Invalid) => {
+                debug!(target: "relay", "peer {} rejects tx({:#x}) as invalid", self.peer, tx_hash);
+                self.relayer.on_tx_rejected_by_peer(self.peer, tx_hash);
+            }
+            // the peer knows the transaction anyway, it is not relayed back
+            Some(RelayTxResult::Accepted) | Some(RelayTxResult::Temporary) => {
+                self.relayer.mark_as_known_by_peer(self.peer, &[tx_hash]);
+            }
+            None => {
+                debug!(target: "relay", "peer {} sends an unknown result {} of tx({:#x})", self.peer, result, tx_hash);
+            }
+        }
+        Ok(())
+    }
+}
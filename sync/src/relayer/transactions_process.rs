@@ -0,0 +1,52 @@
+use crate::relayer::transaction_process::TransactionProcess;
+use crate::relayer::{Relayer, MAX_GET_RELAY_TXS};
+use ckb_network::{CKBProtocolContext, PeerIndex};
+use ckb_protocol::{cast, FlatbuffersVectorIterator, RelayTransactions as FbsRelayTransactions};
+use ckb_store::ChainStore;
+use failure::Error as FailureError;
+use log::info;
+use std::sync::Arc;
+
+pub struct TransactionsProcess<'a, CS> {
+    message: &'a FbsRelayTransactions<'a>,
+    relayer: &'a Relayer<CS>,
+    nc: Arc<dyn CKBProtocolContext + Sync>,
+    peer: PeerIndex,
+}
+
+impl<'a, CS: ChainStore + Sync + 'static> TransactionsProcess<'a, CS> {
+    pub fn new(
+        message: &'a FbsRelayTransactions,
+        relayer: &'a Relayer<CS>,
+        nc: Arc<CKBProtocolContext + Sync>,
+        peer: PeerIndex,
+    ) -> Self {
+        TransactionsProcess {
+            message,
+            relayer,
+            nc,
+            peer,
+        }
+    }
+
+    pub fn execute(self) -> Result<(), FailureError> {
+        let transactions = cast!(self.message.transactions())?;
+        if transactions.len() > MAX_GET_RELAY_TXS {
+            info!(
+                target: "relay",
+                "peer {} relays {} transactions in one message, exceeds the limit {}, ignore it",
+                self.peer,
+                transactions.len(),
+                MAX_GET_RELAY_TXS,
+            );
+            return Ok(());
+        }
+        // Each transaction is handled as if relayed alone, so the transactions missing from a
+        // partial response stay asked and are asked again once they time out
+        for transaction in FlatbuffersVectorIterator::new(transactions) {
+            TransactionProcess::new(&transaction, self.relayer, Arc::clone(&self.nc), self.peer)
+                .execute()?;
+        }
+        Ok(())
+    }
+}
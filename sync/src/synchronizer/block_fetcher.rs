@@ -174,7 +174,14 @@ where
         let window_end = fixed_last_common_header.number() + BLOCK_DOWNLOAD_WINDOW;
         let max_height = cmp::min(window_end + 1, best_known_header.number());
 
-        let mut n_height = fixed_last_common_header.number();
+        // the peer has pruned the blocks up to its pruned number
+        let mut n_height = cmp::max(
+            fixed_last_common_header.number(),
+            self.synchronizer
+                .peers
+                .pruned_number(self.peer)
+                .unwrap_or(0),
+        );
         let mut v_fetch = Vec::with_capacity(PER_FETCH_BLOCK_LIMIT);
 
         {
@@ -0,0 +1,49 @@
+use crate::synchronizer::Synchronizer;
+use crate::BLOCK_DOWNLOAD_WINDOW;
+use ckb_network::{CKBProtocolContext, PeerIndex};
+use ckb_protocol::{cast, BlocksNotFound};
+use ckb_store::ChainStore;
+use failure::Error as FailureError;
+use log::debug;
+use numext_fixed_hash::H256;
+use std::convert::TryInto;
+
+pub struct BlocksNotFoundProcess<'a, CS: ChainStore + 'a> {
+    message: &'a BlocksNotFound<'a>,
+    synchronizer: &'a Synchronizer<CS>,
+    peer: PeerIndex,
+}
+
+impl<'a, CS> BlocksNotFoundProcess<'a, CS>
+where
+    CS: ChainStore + 'a,
+{
+    pub fn new(
+        message: &'a BlocksNotFound,
+        synchronizer: &'a Synchronizer<CS>,
+        peer: PeerIndex,
+        _nc: &'a CKBProtocolContext,
+    ) -> Self {
+        BlocksNotFoundProcess {
+            message,
+            synchronizer,
+            peer,
+        }
+    }
+
+    pub fn execute(self) -> Result<(), FailureError> {
+        let block_hashes = cast!(self.message.block_hashes())?;
+
+        let mut blocks = Vec::new();
+        for fbs_h256 in block_hashes.iter().take(BLOCK_DOWNLOAD_WINDOW as usize) {
+            let block_hash: H256 = fbs_h256.try_into()?;
+            debug!(target: "sync", "block {:x} is not found by peer {:?}", block_hash, self.peer);
+            if let Some(header) = self.synchronizer.shared.block_header(&block_hash) {
+                blocks.push((block_hash, header.number()));
+            }
+        }
+        self.synchronizer.peers.blocks_not_found(self.peer, &blocks);
+
+        Ok(())
+    }
+}
@@ -2,7 +2,7 @@ use crate::synchronizer::Synchronizer;
 use crate::BLOCK_DOWNLOAD_WINDOW;
 use ckb_network::{CKBProtocolContext, PeerIndex};
 use ckb_protocol::{cast, GetBlocks, SyncMessage};
-use ckb_store::ChainStore;
+use ckb_store::{ChainStore, Prunable};
 use failure::Error as FailureError;
 use flatbuffers::FlatBufferBuilder;
 use log::{debug, warn};
@@ -39,34 +39,50 @@ where
 
         // bitcoin limits 500
         let n_limit = min(BLOCK_DOWNLOAD_WINDOW as usize, block_hashes.len());
+        let mut not_found = Vec::new();
         for fbs_h256 in block_hashes.iter().take(n_limit) {
             let block_hash = fbs_h256.try_into()?;
             debug!(target: "sync", "get_blocks {:x} from peer {:?}", block_hash, self.peer);
-            if let Some(block) = self.synchronizer.shared.get_block(&block_hash) {
-                debug!(
-                    target: "sync",
-                    "respond_block {} {:x} to peer {:?}",
-                    block.header().number(),
-                    block.header().hash(),
-                    self.peer,
-                );
-                let fbb = &mut FlatBufferBuilder::new();
-                let message = SyncMessage::build_block(fbb, &block);
-                fbb.finish(message, None);
-                self.nc
-                    .send_message_to(self.peer, fbb.finished_data().into());
-            } else {
-                // TODO response not found
-                // TODO add timeout check in synchronizer
+            match self.synchronizer.shared.read_block(&block_hash) {
+                Prunable::Found(block) => {
+                    debug!(
+                        target: "sync",
+                        "respond_block {} {:x} to peer {:?}",
+                        block.header().number(),
+                        block.header().hash(),
+                        self.peer,
+                    );
+                    let fbb = &mut FlatBufferBuilder::new();
+                    let message = SyncMessage::build_block(fbb, &block);
+                    fbb.finish(message, None);
+                    self.nc
+                        .send_message_to(self.peer, fbb.finished_data().into());
+                }
+                Prunable::Pruned => {
+                    debug!(target: "sync", "getblocks {:x} is pruned", block_hash);
+                    not_found.push(block_hash);
+                }
+                Prunable::NotFound => {
+                    // TODO add timeout check in synchronizer
 
-                // We expect that `block_hashes` is sorted descending by height.
-                // So if we cannot find the current one from local, we cannot find
-                // the next either.
-                debug!(target: "sync", "getblocks stopping since {:x} is not found", block_hash);
-                break;
+                    // We expect that `block_hashes` is sorted descending by height.
+                    // So if we cannot find the current one from local, we cannot find
+                    // the next either.
+                    debug!(target: "sync", "getblocks stopping since {:x} is not found", block_hash);
+                    break;
+                }
             }
         }
 
+        // The pruned blocks are answered explicitly, for the peer to ask them from the others
+        if !not_found.is_empty() {
+            let fbb = &mut FlatBufferBuilder::new();
+            let message = SyncMessage::build_blocks_not_found(fbb, &not_found);
+            fbb.finish(message, None);
+            self.nc
+                .send_message_to(self.peer, fbb.finished_data().into());
+        }
+
         if n_limit < block_hashes.len() {
             warn!(target: "sync", "getblocks stopping at limit {}", n_limit);
         }
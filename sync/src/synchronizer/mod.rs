@@ -1,6 +1,7 @@
 mod block_fetcher;
 mod block_pool;
 mod block_process;
+mod blocks_not_found_process;
 mod get_blocks_process;
 mod get_headers_process;
 mod headers_process;
@@ -8,6 +9,7 @@ mod headers_process;
 use self::block_fetcher::BlockFetcher;
 use self::block_pool::OrphanBlockPool;
 use self::block_process::BlockProcess;
+use self::blocks_not_found_process::BlocksNotFoundProcess;
 use self::get_blocks_process::GetBlocksProcess;
 use self::get_headers_process::GetHeadersProcess;
 use self::headers_process::HeadersProcess;
@@ -101,11 +103,12 @@ impl<CS: ChainStore> Synchronizer<CS> {
         config: Config,
     ) -> Synchronizer<CS> {
         let orphan_block_limit = config.orphan_block_limit;
+        let peer_known_txs_size = config.peer_known_txs_size;
         Synchronizer {
             config: Arc::new(config),
             chain,
             shared,
-            peers: Arc::new(Peers::default()),
+            peers: Arc::new(Peers::new(peer_known_txs_size)),
             orphan_block_pool: Arc::new(OrphanBlockPool::with_capacity(orphan_block_limit)),
             status_map: Arc::new(Mutex::new(HashMap::new())),
             n_sync: Arc::new(AtomicUsize::new(0)),
@@ -135,6 +138,15 @@ impl<CS: ChainStore> Synchronizer<CS> {
             SyncPayload::Block => {
                 BlockProcess::new(&cast!(message.payload_as_block())?, self, peer, nc).execute()?;
             }
+            SyncPayload::BlocksNotFound => {
+                BlocksNotFoundProcess::new(
+                    &cast!(message.payload_as_blocks_not_found())?,
+                    self,
+                    peer,
+                    nc,
+                )
+                .execute()?;
+            }
             SyncPayload::NONE => {
                 cast!(None)?;
             }
@@ -1132,6 +1144,17 @@ mod tests {
             &shared2.block_hash(200).unwrap()
         );
 
+        // the blocks below those peer2 has pruned are not asked from it
+        synchronizer1
+            .peers
+            .blocks_not_found(peer2, &[(shared2.block_hash(196).unwrap(), 196)]);
+        assert_eq!(synchronizer1.peers.pruned_number(peer2), Some(196));
+        let blocks_to_fetch_pruned = synchronizer1.get_blocks_to_fetch(peer2).unwrap();
+        assert_eq!(
+            blocks_to_fetch_pruned.first().unwrap(),
+            &shared2.block_hash(197).unwrap()
+        );
+
         let mut fetched_blocks = Vec::new();
         for block_hash in &blocks_to_fetch {
             fetched_blocks.push(shared2.block(block_hash).unwrap());
@@ -2,7 +2,7 @@ use crate::synchronizer::{BLOCK_FETCH_TOKEN, SEND_GET_HEADERS_TOKEN, TIMEOUT_EVI
 use crate::tests::TestNode;
 use crate::{Config, NetworkProtocol, SyncSharedState, Synchronizer};
 use ckb_chain::chain::ChainService;
-use ckb_chain_spec::consensus::Consensus;
+use ckb_chain_spec::consensus::{Consensus, MaturityMetric};
 use ckb_core::block::BlockBuilder;
 use ckb_core::header::HeaderBuilder;
 use ckb_core::transaction::{CellInput, CellOutput, OutPoint, TransactionBuilder};
@@ -88,7 +88,7 @@ fn setup_node(
 
     let consensus = Consensus::default()
         .set_genesis_block(block.clone())
-        .set_cellbase_maturity(0);
+        .set_cellbase_maturity(MaturityMetric::Blocks(0));
     let shared = SharedBuilder::<MemoryKeyValueDB>::new()
         .consensus(consensus)
         .build()
@@ -10,7 +10,7 @@ use ckb_network::{CKBProtocolContext, PeerIndex};
 use ckb_protocol::SyncMessage;
 use ckb_shared::chain_state::ChainState;
 use ckb_shared::shared::Shared;
-use ckb_store::ChainStore;
+use ckb_store::{ChainStore, Prunable};
 use ckb_traits::ChainProvider;
 use ckb_util::RwLock;
 use ckb_util::{Mutex, MutexGuard};
@@ -25,7 +25,7 @@ use std::cmp;
 use std::collections::{
     hash_map::{Entry, HashMap},
     hash_set::HashSet,
-    BTreeMap,
+    BTreeMap, VecDeque,
 };
 use std::time::{Duration, Instant};
 
@@ -33,8 +33,13 @@ const FILTER_SIZE: usize = 20000;
 const MAX_ASK_MAP_SIZE: usize = 50000;
 const MAX_ASK_SET_SIZE: usize = MAX_ASK_MAP_SIZE * 2;
 const GET_HEADERS_CACHE_SIZE: usize = 10000;
-// TODO: Need discussed
+// A peer dropping this many transaction hash announcements within the window is abusing
+const MAX_DROPPED_TX_ANNOUNCEMENTS: u32 = 500;
+const DROPPED_TX_ANNOUNCEMENTS_WINDOW: u64 = 60 * 1000; // ms
+                                                        // TODO: Need discussed
 const GET_HEADERS_TIMEOUT: Duration = Duration::from_secs(15);
+// The transaction relay accounting window of a peer is rolled over in this many slots
+const TX_RELAY_WINDOW_SLOTS: u64 = 10;
 
 // State used to enforce CHAIN_SYNC_TIMEOUT
 // Only in effect for outbound, non-manual connections, with
@@ -73,6 +78,110 @@ impl Default for ChainSyncState {
     }
 }
 
+// Token bucket of the transaction hash announcements of a peer, or of the rejections sent to
+// it, the tokens are in thousandths of a message so the refill needs no floating point.
+#[derive(Clone, Default, Debug, PartialEq)]
+struct TxAnnounceBucket {
+    tokens: u64,
+    // ms, the bucket is full before the first refill
+    last_refill: Option<u64>,
+    dropped: u32,
+    dropped_window_start: u64,
+}
+
+impl TxAnnounceBucket {
+    // Refills `rate` tokens per second up to `burst` since the last refill, returns the number of
+    // whole tokens
+    fn refill(&mut self, rate: u32, burst: u32, now: u64) -> u64 {
+        let capacity = u64::from(burst) * 1000;
+        self.tokens = match self.last_refill {
+            Some(last_refill) => {
+                let refill = now.saturating_sub(last_refill) * u64::from(rate);
+                cmp::min(capacity, self.tokens.saturating_add(refill))
+            }
+            None => capacity,
+        };
+        self.last_refill = Some(now);
+        self.tokens / 1000
+    }
+}
+
+/// The transaction relay traffic of a peer, summed over the accounting window
+#[derive(Clone, Copy, Default, Debug, PartialEq, Eq)]
+pub struct TxRelayStats {
+    /// The transaction hashes announced by the peer within its rate limit
+    pub announced: u64,
+    /// The transactions relayed by the peer
+    pub received: u64,
+    /// The serialized size of the transactions relayed by the peer
+    pub received_bytes: u64,
+    /// The received transactions asked from the peer
+    pub asked: u64,
+    /// The received transactions never asked from the peer
+    pub unsolicited: u64,
+    /// The transactions relayed by the peer and added into the transaction pool
+    pub accepted: u64,
+    /// The transactions relayed by the peer and found invalid
+    pub rejected: u64,
+    /// The cycles spent verifying the transactions relayed by the peer
+    pub cycles: Cycle,
+}
+
+impl TxRelayStats {
+    fn add(&mut self, other: &TxRelayStats) {
+        self.announced = self.announced.saturating_add(other.announced);
+        self.received = self.received.saturating_add(other.received);
+        self.received_bytes = self.received_bytes.saturating_add(other.received_bytes);
+        self.asked = self.asked.saturating_add(other.asked);
+        self.unsolicited = self.unsolicited.saturating_add(other.unsolicited);
+        self.accepted = self.accepted.saturating_add(other.accepted);
+        self.rejected = self.rejected.saturating_add(other.rejected);
+        self.cycles = self.cycles.saturating_add(other.cycles);
+    }
+}
+
+// The transaction relay traffic of a peer in `TX_RELAY_WINDOW_SLOTS` slots of the window, by
+// their start times in ms. The oldest slots expire as the new ones start, so their sum covers a
+// rolling window.
+#[derive(Clone, Default, Debug, PartialEq)]
+struct TxRelayAccounting {
+    slots: VecDeque<(u64, TxRelayStats)>,
+}
+
+impl TxRelayAccounting {
+    fn record<F: FnOnce(&mut TxRelayStats)>(&mut self, window: u64, now: u64, f: F) {
+        while self
+            .slots
+            .front()
+            .map_or(false, |(start, _)| start.saturating_add(window) <= now)
+        {
+            self.slots.pop_front();
+        }
+        let slot_len = cmp::max(window / TX_RELAY_WINDOW_SLOTS, 1);
+        let start = now - now % slot_len;
+        match self.slots.back_mut() {
+            Some((last_start, stats)) if *last_start == start => f(stats),
+            _ => {
+                let mut stats = TxRelayStats::default();
+                f(&mut stats);
+                self.slots.push_back((start, stats));
+            }
+        }
+    }
+
+    fn sum(&self, window: u64, now: u64) -> TxRelayStats {
+        let mut sum = TxRelayStats::default();
+        for (_, stats) in self
+            .slots
+            .iter()
+            .filter(|(start, _)| start.saturating_add(window) > now)
+        {
+            sum.add(stats);
+        }
+        sum
+    }
+}
+
 #[derive(Clone, Default, Debug, PartialEq)]
 pub struct PeerState {
     pub sync_started: bool,
@@ -84,6 +193,13 @@ pub struct PeerState {
     // The key is a `timeout`, means do not ask the tx before `timeout`.
     tx_ask_for_map: BTreeMap<Instant, Vec<H256>>,
     tx_ask_for_set: HashSet<H256>,
+    // The transactions of `tx_ask_for_set` a `GetRelayTransactions` is sent to the peer for
+    tx_asks_sent: HashSet<H256>,
+    tx_announce_bucket: TxAnnounceBucket,
+    tx_reject_bucket: TxAnnounceBucket,
+    // The number of transactions relayed by the peer without being asked for
+    unsolicited_txs: u32,
+    tx_relay_accounting: TxRelayAccounting,
 }
 
 impl PeerState {
@@ -101,6 +217,11 @@ impl PeerState {
             chain_sync,
             tx_ask_for_map: BTreeMap::default(),
             tx_ask_for_set: HashSet::default(),
+            tx_asks_sent: HashSet::default(),
+            tx_announce_bucket: TxAnnounceBucket::default(),
+            tx_reject_bucket: TxAnnounceBucket::default(),
+            unsolicited_txs: 0,
+            tx_relay_accounting: TxRelayAccounting::default(),
         }
     }
 
@@ -161,8 +282,107 @@ impl PeerState {
         Some(next_ask_timeout)
     }
 
+    /// Takes `count` transaction hash announcements at `now` (ms) out of a bucket refilled with
+    /// `rate` announcements per second up to `burst`. Returns the number of announcements
+    /// accepted, and whether the peer has dropped too many announcements recently, in which
+    /// case the drop counting starts over.
+    pub fn throttle_tx_announcements(
+        &mut self,
+        count: usize,
+        rate: u32,
+        burst: u32,
+        now: u64,
+    ) -> (usize, bool) {
+        let bucket = &mut self.tx_announce_bucket;
+        let accepted = cmp::min(count as u64, bucket.refill(rate, burst, now));
+        bucket.tokens -= accepted * 1000;
+        let dropped = count as u64 - accepted;
+        if dropped == 0 {
+            return (accepted as usize, false);
+        }
+
+        if now.saturating_sub(bucket.dropped_window_start) > DROPPED_TX_ANNOUNCEMENTS_WINDOW {
+            bucket.dropped_window_start = now;
+            bucket.dropped = 0;
+        }
+        bucket.dropped = bucket.dropped.saturating_add(dropped as u32);
+        let abused = bucket.dropped >= MAX_DROPPED_TX_ANNOUNCEMENTS;
+        if abused {
+            bucket.dropped = 0;
+        }
+        (accepted as usize, abused)
+    }
+
+    /// Takes a token at `now` (ms) to send the peer a rejection of a transaction it relays, out
+    /// of a bucket refilled with `rate` rejections per second up to `burst`
+    pub fn throttle_tx_rejects(&mut self, rate: u32, burst: u32, now: u64) -> bool {
+        let bucket = &mut self.tx_reject_bucket;
+        if bucket.refill(rate, burst, now) == 0 {
+            return false;
+        }
+        bucket.tokens -= 1000;
+        true
+    }
+
+    /// Forgets the ask of the transaction, once it is received, asked from another peer or
+    /// given up
     pub fn remove_ask_for_tx(&mut self, tx_hash: &H256) {
         self.tx_ask_for_set.remove(tx_hash);
+        self.tx_asks_sent.remove(tx_hash);
+    }
+
+    pub fn contains_ask_for_tx(&self, tx_hash: &H256) -> bool {
+        self.tx_ask_for_set.contains(tx_hash)
+    }
+
+    /// Counts a transaction relayed by the peer without being asked for, returns the number of
+    /// such transactions so far
+    pub fn add_unsolicited_tx(&mut self) -> u32 {
+        self.unsolicited_txs = self.unsolicited_txs.saturating_add(1);
+        self.unsolicited_txs
+    }
+
+    /// Records the transaction relay traffic of the peer at `now` (ms), into the accounting over
+    /// the last `window` ms
+    pub fn account_tx_relay<F: FnOnce(&mut TxRelayStats)>(&mut self, window: u64, now: u64, f: F) {
+        self.tx_relay_accounting.record(window, now, f);
+    }
+
+    /// The transaction relay traffic of the peer over the last `window` ms before `now` (ms)
+    pub fn tx_relay_stats(&self, window: u64, now: u64) -> TxRelayStats {
+        self.tx_relay_accounting.sum(window, now)
+    }
+
+    /// Forgets the transaction relay traffic of the peer, such as once it is punished for it
+    pub fn clear_tx_relay_stats(&mut self) {
+        self.tx_relay_accounting = TxRelayAccounting::default();
+    }
+
+    /// The number of transactions registered to be asked from the peer or asked already, and
+    /// not received yet
+    pub fn tx_asks_in_flight(&self) -> usize {
+        self.tx_ask_for_set.len()
+    }
+
+    /// Registers the transaction asked from the peer out of the `tx_ask_for_map` schedule, such
+    /// as a retry, so it counts as in flight until it is received
+    pub fn add_asked_tx(&mut self, tx_hash: H256) {
+        self.tx_ask_for_set.insert(tx_hash.clone());
+        self.tx_asks_sent.insert(tx_hash);
+    }
+
+    /// Records the scheduled asks of the transactions as sent to the peer
+    pub fn mark_asks_sent(&mut self, tx_hashes: &[H256]) {
+        for tx_hash in tx_hashes {
+            if self.tx_ask_for_set.contains(tx_hash) {
+                self.tx_asks_sent.insert(tx_hash.clone());
+            }
+        }
+    }
+
+    /// Whether the peer is sent an ask for a transaction it has not relayed yet
+    pub fn has_sent_asks(&self) -> bool {
+        !self.tx_asks_sent.is_empty()
     }
 
     pub fn pop_ask_for_txs(&mut self) -> Vec<H256> {
@@ -183,12 +403,27 @@ impl PeerState {
     }
 }
 
-#[derive(Clone, Default)]
+#[derive(Clone)]
 pub struct KnownFilter {
     inner: FnvHashMap<PeerIndex, LruCache<H256, ()>>,
+    // The number of values remembered per peer
+    size: usize,
+}
+
+impl Default for KnownFilter {
+    fn default() -> Self {
+        KnownFilter::new(FILTER_SIZE)
+    }
 }
 
 impl KnownFilter {
+    pub fn new(size: usize) -> Self {
+        KnownFilter {
+            inner: FnvHashMap::default(),
+            size,
+        }
+    }
+
     /// Adds a value to the filter.
     /// If the filter did not have this value present, `true` is returned.
     /// If the filter did have this value present, `false` is returned.
@@ -196,13 +431,24 @@ impl KnownFilter {
         match self.inner.entry(index) {
             Entry::Occupied(mut o) => o.get_mut().insert(hash, ()).is_none(),
             Entry::Vacant(v) => {
-                let mut lru = LruCache::new(FILTER_SIZE);
+                let mut lru = LruCache::new(self.size);
                 lru.insert(hash, ());
                 v.insert(lru);
                 true
             }
         }
     }
+
+    pub fn contains(&mut self, index: PeerIndex, hash: &H256) -> bool {
+        self.inner
+            .get_mut(&index)
+            .map_or(false, |lru| lru.contains_key(hash))
+    }
+
+    /// Forgets the values known by the peer, such as when it disconnects.
+    pub fn remove(&mut self, index: PeerIndex) {
+        self.inner.remove(&index);
+    }
 }
 
 #[derive(Default)]
@@ -212,10 +458,24 @@ pub struct Peers {
     pub blocks_inflight: RwLock<FnvHashMap<PeerIndex, BlocksInflight>>,
     pub best_known_headers: RwLock<FnvHashMap<PeerIndex, HeaderView>>,
     pub last_common_headers: RwLock<FnvHashMap<PeerIndex, Header>>,
+    /// The numbers up to which the peers told us their blocks are pruned
+    pub pruned_numbers: RwLock<FnvHashMap<PeerIndex, BlockNumber>>,
     pub known_txs: Mutex<KnownFilter>,
+    /// The transactions each peer tells us are invalid, never asked from it again
+    pub rejected_txs: Mutex<KnownFilter>,
     pub known_blocks: Mutex<KnownFilter>,
 }
 
+impl Peers {
+    pub fn new(known_txs_size: usize) -> Self {
+        Peers {
+            known_txs: Mutex::new(KnownFilter::new(known_txs_size)),
+            rejected_txs: Mutex::new(KnownFilter::new(known_txs_size)),
+            ..Default::default()
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct BlocksInflight {
     pub timestamp: u64,
@@ -319,6 +579,29 @@ impl Peers {
         // self.misbehavior.write().remove(peer);
         self.blocks_inflight.write().remove(&peer);
         self.last_common_headers.write().remove(&peer);
+        self.pruned_numbers.write().remove(&peer);
+    }
+
+    // The blocks the peer answered not found are asked from the other peers, and the blocks
+    // below them are never asked from this peer again
+    pub fn blocks_not_found(&self, peer: PeerIndex, blocks: &[(H256, BlockNumber)]) {
+        let mut blocks_inflight = self.blocks_inflight.write();
+        if let Some(inflight) = blocks_inflight.get_mut(&peer) {
+            for (hash, _) in blocks {
+                inflight.remove(hash);
+            }
+        }
+        if let Some(number) = blocks.iter().map(|(_, number)| *number).max() {
+            self.pruned_numbers
+                .write()
+                .entry(peer)
+                .and_modify(|pruned| *pruned = cmp::max(*pruned, number))
+                .or_insert(number);
+        }
+    }
+
+    pub fn pruned_number(&self, peer: PeerIndex) -> Option<BlockNumber> {
+        self.pruned_numbers.read().get(&peer).cloned()
     }
 
     // Return true when the block is that we have requested and received first time.
@@ -469,6 +752,9 @@ impl<CS: ChainStore> SyncSharedState<CS> {
     pub fn get_block(&self, hash: &H256) -> Option<Block> {
         self.shared.block(hash)
     }
+    pub fn read_block(&self, hash: &H256) -> Prunable<Block> {
+        self.shared.store().read_block(hash)
+    }
     pub fn tip_header(&self) -> Header {
         self.shared.lock_chain_state().tip_header().to_owned()
     }
@@ -353,6 +353,7 @@ impl Node {
                 None,
             ))
             .input(CellInput::new(OutPoint::new_cell(hash, 0), since, vec![]))
+            .witness(vec![])
             .build()
     }
 
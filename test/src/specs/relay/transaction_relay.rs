@@ -96,6 +96,7 @@ impl Spec for TransactionRelayMultiple {
                         0,
                         vec![],
                     ))
+                    .witness(vec![])
                     .build();
                 node0
                     .rpc_client()
@@ -1,10 +1,10 @@
 use crate::{assert_regex_match, Net, Spec, DEFAULT_TX_PROPOSAL_WINDOW};
+use ckb_core::since::Since;
 use ckb_core::BlockNumber;
 use log::info;
 
 pub struct ValidSince;
 
-#[allow(clippy::identity_op)]
 impl Spec for ValidSince {
     fn run(&self, net: Net) {
         info!("Running ValidSince");
@@ -16,7 +16,9 @@ impl Spec for ValidSince {
         // test relative block number since
         info!("Use tip block cellbase as tx input with a relative block number since");
         let relative_blocks: BlockNumber = 5;
-        let since = (0b1000_0000 << 56) + relative_blocks;
+        let since = Since::new_relative_block(relative_blocks)
+            .expect("valid since")
+            .as_u64();
         let tip_block = node.get_tip_block();
         let tx =
             node.new_transaction_with_since(tip_block.transactions()[0].hash().to_owned(), since);
@@ -63,7 +65,9 @@ impl Spec for ValidSince {
             tip_number
         );
         let absolute_block: BlockNumber = 10;
-        let since = (0b0000_0000 << 56) + absolute_block;
+        let since = Since::new_absolute_block(absolute_block)
+            .expect("valid since")
+            .as_u64();
         let tip_block = node.get_tip_block();
         let tx =
             node.new_transaction_with_since(tip_block.transactions()[0].hash().to_owned(), since);
@@ -9,6 +9,7 @@ use std::path::{Path, PathBuf};
 
 use serde_derive::{Deserialize, Serialize};
 
+use ckb_chain::prune::PruneConfig;
 use ckb_chain_spec::ChainSpec;
 use ckb_db::DBConfig;
 use ckb_miner::BlockAssemblerConfig;
@@ -18,6 +19,7 @@ use ckb_resource::{Resource, ResourceLocator};
 use ckb_rpc::Config as RpcConfig;
 use ckb_script::ScriptConfig;
 use ckb_shared::tx_pool::TxPoolConfig;
+use ckb_store::StoreConfig;
 use ckb_sync::Config as SyncConfig;
 use logger::Config as LogConfig;
 
@@ -43,13 +45,17 @@ pub struct CKBAppConfig {
     pub chain: ChainConfig,
 
     pub block_assembler: BlockAssemblerConfig,
-    #[serde(skip)]
+    #[serde(default)]
     pub db: DBConfig,
     pub network: NetworkConfig,
     pub rpc: RpcConfig,
     pub sync: SyncConfig,
     pub tx_pool: TxPoolConfig,
     pub script: ScriptConfig,
+    #[serde(default)]
+    pub prune: PruneConfig,
+    #[serde(default)]
+    pub store: StoreConfig,
 }
 
 // change the order of fields will break integration test, see module doc.
@@ -166,6 +172,10 @@ impl CKBAppConfig {
             )?);
         }
         self.db.path = mkdir(self.data_dir.join("db"))?;
+        self.db.cache = self.store.cache_policies().map_err(|err| {
+            eprintln!("Config Error: {}", err);
+            ExitCode::Config
+        })?;
         self.network.path = mkdir(self.data_dir.join("network"))?;
 
         Ok(self)
@@ -238,6 +248,42 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_db_options() {
+        let dir = mkdir();
+        let locator = ResourceLocator::with_root_dir(dir.path().to_path_buf()).unwrap();
+        let context = TemplateContext {
+            spec: "dev",
+            rpc_port: "7000",
+            p2p_port: "8000",
+            log_to_file: true,
+            log_to_stdout: true,
+        };
+        locator.export_ckb(&context).expect("export config files");
+        let path = locator.root_dir().join("ckb.toml");
+        let content = fs::read_to_string(&path)
+            .unwrap()
+            .replace(
+                "# options = { max_open_files = \"1024\" }",
+                "options = { max_open_files = \"1024\", block_cache = \"false\" }",
+            )
+            .replace(
+                "# raw_options = { disable_auto_compactions = \"false\" }",
+                "raw_options = { disable_auto_compactions = \"false\" }",
+            );
+        fs::write(&path, content).unwrap();
+        let app_config = AppConfig::load_for_subcommand(&locator, cli::CMD_RUN)
+            .unwrap_or_else(|err| panic!(err));
+        let ckb_config = app_config.into_ckb().unwrap_or_else(|err| panic!(err));
+        let db_options = ckb_config.db.db_options().expect("valid options");
+        assert_eq!(db_options.max_open_files, Some(1024));
+        assert_eq!(db_options.block_cache, Some(false));
+        assert_eq!(
+            ckb_config.db.raw_options().expect("valid raw options"),
+            vec![("disable_auto_compactions", "false")]
+        );
+    }
+
     #[test]
     fn test_miner_toml() {
         let dir = mkdir();
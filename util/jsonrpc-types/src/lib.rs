@@ -7,6 +7,7 @@ mod experiment;
 mod net;
 mod pool;
 mod proposal_short_id;
+mod script_stats;
 mod string;
 mod sync;
 
@@ -46,6 +47,7 @@ pub use self::experiment::DryRunResult;
 pub use self::net::{Node, NodeAddress};
 pub use self::pool::TxPoolInfo;
 pub use self::proposal_short_id::ProposalShortId;
+pub use self::script_stats::{ScriptKind, ScriptStats};
 pub use self::sync::PeerState;
 pub use jsonrpc_core::types::{error, id, params, request, response, version};
 pub use serde_derive::{Deserialize, Serialize};
@@ -0,0 +1,23 @@
+use crate::{Cycle, Unsigned};
+use numext_fixed_hash::H256;
+use serde_derive::{Deserialize, Serialize};
+
+#[derive(Clone, Serialize, Deserialize, PartialEq, Eq, Hash, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum ScriptKind {
+    Lock,
+    Type,
+}
+
+#[derive(Clone, Serialize, Deserialize, PartialEq, Eq, Hash, Debug)]
+pub struct ScriptStats {
+    pub script_hash: H256,
+    // whether the script is run as a lock script or a type script
+    pub kind: ScriptKind,
+    // the number of script groups run
+    pub runs: Unsigned,
+    // the cycles consumed by all the runs
+    pub cycles: Cycle,
+    // the time spent by all the runs, in microseconds
+    pub wall_time: Unsigned,
+}
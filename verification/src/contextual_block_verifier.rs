@@ -1,7 +1,7 @@
-use crate::error::{CellbaseError, CommitError, Error};
+use crate::error::{CellbaseError, CommitError, Error, TransactionError};
 use crate::uncles_verifier::UnclesVerifier;
-use crate::{ContextualTransactionVerifier, TransactionVerifier};
-use ckb_chain_spec::consensus::Consensus;
+use crate::{median_time_cache, ContextualTransactionVerifier, TransactionVerifier};
+use ckb_chain_spec::consensus::{Consensus, MaturityMetric};
 use ckb_core::cell::ResolvedTransaction;
 use ckb_core::extras::EpochExt;
 use ckb_core::header::Header;
@@ -9,11 +9,11 @@ use ckb_core::transaction::Capacity;
 use ckb_core::transaction::Transaction;
 use ckb_core::Cycle;
 use ckb_core::{block::Block, BlockNumber, EpochNumber};
-use ckb_script::ScriptConfig;
+use ckb_script::{DepCellDataCache, ScriptConfig};
 use ckb_store::ChainStore;
 use ckb_traits::{BlockMedianTimeContext, ChainProvider};
 use dao_utils::calculate_transaction_fee;
-use fnv::FnvHashSet;
+use fnv::{FnvHashMap, FnvHashSet};
 use log::error;
 use lru_cache::LruCache;
 use numext_fixed_hash::H256;
@@ -173,8 +173,9 @@ where
     }
 }
 
-struct BlockTxsVerifier<'a, M, CS> {
-    cellbase_maturity: BlockNumber,
+pub(crate) struct BlockTxsVerifier<'a, M, CS> {
+    cellbase_maturity: MaturityMetric,
+    max_extra_witnesses: usize,
     script_config: &'a ScriptConfig,
     max_cycles: Cycle,
     block_median_time_context: &'a M,
@@ -182,6 +183,7 @@ struct BlockTxsVerifier<'a, M, CS> {
     epoch: EpochNumber,
     store: &'a Arc<CS>,
     resolved: &'a [ResolvedTransaction<'a>],
+    dep_cell_data_cache: &'a Arc<DepCellDataCache>,
 }
 
 impl<'a, M, CS> BlockTxsVerifier<'a, M, CS>
@@ -191,7 +193,8 @@ where
 {
     #[allow(clippy::too_many_arguments)]
     pub fn new(
-        cellbase_maturity: BlockNumber,
+        cellbase_maturity: MaturityMetric,
+        max_extra_witnesses: usize,
         script_config: &'a ScriptConfig,
         max_cycles: Cycle,
         block_median_time_context: &'a M,
@@ -199,9 +202,11 @@ where
         epoch: EpochNumber,
         store: &'a Arc<CS>,
         resolved: &'a [ResolvedTransaction<'a>],
+        dep_cell_data_cache: &'a Arc<DepCellDataCache>,
     ) -> BlockTxsVerifier<'a, M, CS> {
         BlockTxsVerifier {
             cellbase_maturity,
+            max_extra_witnesses,
             script_config,
             max_cycles,
             block_median_time_context,
@@ -209,44 +214,76 @@ where
             epoch,
             store,
             resolved,
+            dep_cell_data_cache,
         }
     }
 
-    pub fn verify(&self, txs_verify_cache: &mut LruCache<H256, Cycle>) -> Result<(), Error> {
-        // make verifiers orthogonal
-        let ret_set = self
-            .resolved
-            .par_iter()
-            .enumerate()
-            .map(|(index, tx)| {
-                let tx_hash = tx.transaction.hash().to_owned();
-                if let Some(cycles) = txs_verify_cache.get(&tx_hash) {
-                    ContextualTransactionVerifier::new(
-                        &tx,
-                        self.block_median_time_context,
-                        self.number,
-                        self.epoch,
-                        self.cellbase_maturity,
-                    )
-                    .verify()
-                    .map_err(|e| Error::Transactions((index, e)))
-                    .map(|_| (tx_hash, *cycles))
-                } else {
-                    TransactionVerifier::new(
-                        &tx,
-                        Arc::clone(self.store),
-                        self.block_median_time_context,
-                        self.number,
-                        self.epoch,
-                        self.cellbase_maturity,
-                        self.script_config,
-                    )
-                    .verify(self.max_cycles)
-                    .map_err(|e| Error::Transactions((index, e)))
-                    .map(|cycles| (tx_hash, cycles))
+    // Verifies the transactions layer by layer of `dependency_layers`, the transactions of a
+    // layer in parallel. Once a transaction fails, only the transactions before it are verified
+    // further, so the error reported, and the total cycles of a valid block, are the same as
+    // verifying the transactions one by one in order. Returns the total cycles.
+    pub fn verify(&self, txs_verify_cache: &mut LruCache<H256, Cycle>) -> Result<Cycle, Error> {
+        // all transactions share the same median time cache
+        let median_time_cache = Arc::new(median_time_cache(self.resolved, self.number));
+        let mut ret_set = Vec::with_capacity(self.resolved.len());
+        let mut first_error: Option<(usize, TransactionError)> = None;
+        for layer in dependency_layers(self.resolved) {
+            let layer = layer
+                .into_iter()
+                .filter(|index| first_error.map_or(true, |(failed, _)| *index < failed))
+                .collect::<Vec<_>>();
+            // make verifiers orthogonal
+            let results = layer
+                .par_iter()
+                .map(|index| {
+                    let tx = &self.resolved[*index];
+                    let tx_hash = tx.transaction.hash().to_owned();
+                    let result = if let Some(cycles) = txs_verify_cache.get(&tx_hash) {
+                        ContextualTransactionVerifier::new_with_cache(
+                            tx,
+                            self.block_median_time_context,
+                            self.number,
+                            self.epoch,
+                            None,
+                            self.cellbase_maturity,
+                            Arc::clone(&median_time_cache),
+                        )
+                        .verify()
+                        .map(|_| (tx_hash, *cycles))
+                    } else {
+                        TransactionVerifier::new_with_cache(
+                            tx,
+                            Arc::clone(self.store),
+                            self.block_median_time_context,
+                            self.number,
+                            self.epoch,
+                            None,
+                            self.cellbase_maturity,
+                            self.max_extra_witnesses,
+                            self.script_config,
+                            Arc::clone(&median_time_cache),
+                            Some(Arc::clone(self.dep_cell_data_cache)),
+                        )
+                        .verify(self.max_cycles)
+                        .map(|cycles| (tx_hash, cycles))
+                    };
+                    (*index, result)
+                })
+                .collect::<Vec<_>>();
+            for (index, result) in results {
+                match result {
+                    Ok(ret) => ret_set.push(ret),
+                    Err(err) => {
+                        if first_error.map_or(true, |(failed, _)| index < failed) {
+                            first_error = Some((index, err));
+                        }
+                    }
                 }
-            })
-            .collect::<Result<Vec<_>, _>>()?;
+            }
+        }
+        if let Some(error) = first_error {
+            return Err(Error::Transactions(error));
+        }
 
         let sum: Cycle = ret_set.iter().map(|(_, cycles)| cycles).sum();
 
@@ -257,9 +294,39 @@ where
         if sum > self.max_cycles {
             Err(Error::ExceededMaximumCycles)
         } else {
-            Ok(())
+            Ok(sum)
+        }
+    }
+}
+
+/// Groups the indexes of the transactions of a block into layers by their dependencies within
+/// the block. A transaction spending, or depending on, an output of another transaction of the
+/// block comes in a layer after the one of that transaction, so the transactions of a layer are
+/// independent of each other.
+pub(crate) fn dependency_layers(resolved: &[ResolvedTransaction]) -> Vec<Vec<usize>> {
+    let mut indexes = FnvHashMap::default();
+    let mut depths = Vec::with_capacity(resolved.len());
+    let mut layers: Vec<Vec<usize>> = Vec::new();
+    for (index, rtx) in resolved.iter().enumerate() {
+        let transaction = rtx.transaction;
+        let depth = transaction
+            .inputs()
+            .iter()
+            .map(|input| &input.previous_output)
+            .chain(transaction.deps())
+            .filter_map(|out_point| out_point.cell.as_ref())
+            .filter_map(|cell| indexes.get(&cell.tx_hash))
+            .map(|parent: &usize| depths[*parent] + 1)
+            .max()
+            .unwrap_or(0);
+        indexes.insert(transaction.hash(), index);
+        depths.push(depth);
+        if depth == layers.len() {
+            layers.push(Vec::new());
         }
+        layers[depth].push(index);
     }
+    layers
 }
 
 fn prepare_epoch_ext<P: ChainProvider>(provider: &P, block: &Block) -> Result<EpochExt, Error> {
@@ -296,6 +363,7 @@ where
         fork_attached_blocks: &[Block],
         block: &Block,
         txs_verify_cache: &mut LruCache<H256, Cycle>,
+        dep_cell_data_cache: &Arc<DepCellDataCache>,
     ) -> Result<(), Error> {
         let consensus = self.provider.consensus();
         let store = self.provider.store();
@@ -319,6 +387,7 @@ where
 
         BlockTxsVerifier::new(
             consensus.cellbase_maturity(),
+            consensus.max_extra_witnesses(),
             self.provider.script_config(),
             consensus.max_block_cycles(),
             &block_median_time_context,
@@ -326,7 +395,9 @@ where
             block.header().epoch(),
             self.provider.store(),
             resolved,
+            dep_cell_data_cache,
         )
         .verify(txs_verify_cache)
+        .map(|_| ())
     }
 }
@@ -1,4 +1,4 @@
-use ckb_core::BlockNumber;
+use ckb_core::{BlockNumber, Capacity};
 use ckb_script::ScriptError;
 use numext_fixed_hash::H256;
 use numext_fixed_uint::U256;
@@ -142,14 +142,48 @@ pub enum EpochError {
     AncestorNotFound,
 }
 
+/// The side of a transaction whose capacity is summed
+#[derive(Debug, PartialEq, Clone, Copy, Eq)]
+pub enum InputsOrOutputs {
+    Inputs,
+    Outputs,
+}
+
 #[derive(Debug, PartialEq, Clone, Copy, Eq)]
 pub enum TransactionError {
-    /// Occur output's bytes_len exceed capacity
+    /// Overflow when computing capacity
     CapacityOverflow,
+    /// The occupied capacity of the output at `index` exceeds its capacity
+    InsufficientCellCapacity {
+        index: usize,
+        occupied: Capacity,
+        capacity: Capacity,
+    },
     DuplicateDeps,
     Empty,
+    /// The input at `index` of a non-cellbase transaction spends the null out point
+    NullInput {
+        index: usize,
+    },
+    /// Fewer witnesses than inputs
+    InsufficientWitnesses {
+        inputs: usize,
+        witnesses: usize,
+    },
+    /// More extra witnesses than allowed
+    TooManyWitnesses {
+        inputs: usize,
+        witnesses: usize,
+    },
     /// Sum of all outputs capacity exceed sum of all inputs in the transaction
-    OutputsSumOverflow,
+    OutputsSumOverflow {
+        inputs: Capacity,
+        outputs: Capacity,
+    },
+    /// Sum of the inputs or outputs capacity does not fit in a capacity
+    CapacitySumOverflow {
+        side: InputsOrOutputs,
+    },
     InvalidScript,
     ScriptFailure(ScriptError),
     InvalidSignature,
@@ -175,11 +209,27 @@ impl TransactionError {
     pub fn is_bad_tx(self) -> bool {
         use TransactionError::*;
         match self {
-            CapacityOverflow | DuplicateDeps | Empty | OutputsSumOverflow | InvalidScript
-            | ScriptFailure(_) | InvalidSignature | InvalidSince => true,
+            CapacityOverflow
+            | InsufficientCellCapacity { .. }
+            | DuplicateDeps
+            | Empty
+            | NullInput { .. }
+            | InsufficientWitnesses { .. }
+            | TooManyWitnesses { .. }
+            | OutputsSumOverflow { .. }
+            | CapacitySumOverflow { .. }
+            | InvalidScript
+            | ScriptFailure(_)
+            | InvalidSignature
+            | InvalidSince => true,
             _ => false,
         }
     }
+
+    /// Whether the scripts run out of the cycles the transaction is verified with
+    pub fn is_exceeded_maximum_cycles(self) -> bool {
+        self == TransactionError::ScriptFailure(ScriptError::ExceededMaximumCycles)
+    }
 }
 
 impl From<occupied_capacity::Error> for TransactionError {
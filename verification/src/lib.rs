@@ -10,10 +10,11 @@ mod tests;
 
 pub use crate::block_verifier::{BlockVerifier, HeaderResolverWrapper};
 pub use crate::contextual_block_verifier::ContextualBlockVerifier;
-pub use crate::error::{Error, TransactionError};
+pub use crate::error::{Error, InputsOrOutputs, TransactionError};
 pub use crate::header_verifier::{HeaderResolver, HeaderVerifier};
 pub use crate::transaction_verifier::{
-    ContextualTransactionVerifier, ScriptVerifier, TransactionVerifier,
+    median_time_cache, ContextualTransactionVerifier, MedianTimeCache, ScriptVerifier,
+    SinceRequirement, TransactionVerifier, VerifyContext, ESTIMATE_MAX_CYCLES,
 };
 
 pub const ALLOWED_FUTURE_BLOCKTIME: u64 = 15 * 1000; // 15 Second
@@ -0,0 +1,173 @@
+use crate::contextual_block_verifier::{dependency_layers, BlockTxsVerifier};
+use crate::error::{Error, TransactionError};
+use crate::transaction_verifier::TransactionVerifier;
+use ckb_chain_spec::consensus::{Consensus, MaturityMetric};
+use ckb_core::cell::{BlockInfo, CellMetaBuilder, ResolvedOutPoint, ResolvedTransaction};
+use ckb_core::script::Script;
+use ckb_core::transaction::{CellInput, CellOutput, OutPoint, Transaction, TransactionBuilder};
+use ckb_core::{capacity_bytes, BlockNumber, Bytes, Capacity, Cycle};
+use ckb_db::MemoryKeyValueDB;
+use ckb_script::{DepCellDataCache, ScriptConfig};
+use ckb_store::{ChainKVStore, COLUMNS};
+use ckb_traits::BlockMedianTimeContext;
+use lru_cache::LruCache;
+use numext_fixed_hash::h256;
+use std::sync::Arc;
+use test_chain_utils::create_always_success_cell;
+
+const MAX_CYCLES: Cycle = 100_000_000;
+
+struct FixedMedianTime;
+
+impl BlockMedianTimeContext for FixedMedianTime {
+    fn median_block_count(&self) -> u64 {
+        11
+    }
+    fn timestamp(&self, _n: BlockNumber) -> Option<u64> {
+        Some(0)
+    }
+}
+
+fn spend(previous_output: OutPoint, capacity: Capacity, lock: &Script) -> Transaction {
+    TransactionBuilder::default()
+        .input(CellInput::new(previous_output, 0, Default::default()))
+        .output(CellOutput::new(capacity, Bytes::new(), lock.clone(), None))
+        .dep(OutPoint::new_cell(h256!("0x2"), 0))
+        .witness(vec![])
+        .build()
+}
+
+// Every input is resolved to a cell of 100 bytes capacity locked by the always success script
+fn resolve<'a>(
+    transactions: &'a [Transaction],
+    always_success_cell: &CellOutput,
+    lock: &Script,
+) -> Vec<ResolvedTransaction<'a>> {
+    transactions
+        .iter()
+        .map(|transaction| ResolvedTransaction {
+            transaction,
+            resolved_deps: vec![ResolvedOutPoint::cell_only(
+                CellMetaBuilder::from_cell_output(always_success_cell.clone())
+                    .block_info(BlockInfo::new(1, 0))
+                    .build(),
+            )],
+            resolved_inputs: vec![ResolvedOutPoint::cell_only(
+                CellMetaBuilder::from_cell_output(CellOutput::new(
+                    capacity_bytes!(100),
+                    Bytes::new(),
+                    lock.clone(),
+                    None,
+                ))
+                .block_info(BlockInfo::new(1, 0))
+                .build(),
+            )],
+        })
+        .collect()
+}
+
+fn verify_block_txs(
+    store: &Arc<ChainKVStore<MemoryKeyValueDB>>,
+    resolved: &[ResolvedTransaction],
+) -> Result<Cycle, Error> {
+    let script_config = ScriptConfig::default();
+    let dep_cell_data_cache = Arc::new(DepCellDataCache::new(16));
+    BlockTxsVerifier::new(
+        MaturityMetric::Blocks(0),
+        Consensus::default().max_extra_witnesses(),
+        &script_config,
+        MAX_CYCLES,
+        &FixedMedianTime,
+        10,
+        1,
+        store,
+        resolved,
+        &dep_cell_data_cache,
+    )
+    .verify(&mut LruCache::new(16))
+}
+
+// A chain of dependent transactions a -> b -> c interleaved with the independent d and e, the
+// ones at `invalid_indexes` output more than their inputs
+fn block_txs(invalid_indexes: &[usize], lock: &Script) -> Vec<Transaction> {
+    let capacity = |index: usize| {
+        if invalid_indexes.contains(&index) {
+            capacity_bytes!(101)
+        } else {
+            capacity_bytes!(100)
+        }
+    };
+    let a = spend(OutPoint::new_cell(h256!("0x1"), 0), capacity(0), lock);
+    let d = spend(OutPoint::new_cell(h256!("0x1"), 1), capacity(1), lock);
+    let b = spend(
+        OutPoint::new_cell(a.hash().to_owned(), 0),
+        capacity(2),
+        lock,
+    );
+    let e = spend(OutPoint::new_cell(h256!("0x1"), 2), capacity(3), lock);
+    let c = spend(
+        OutPoint::new_cell(b.hash().to_owned(), 0),
+        capacity(4),
+        lock,
+    );
+    vec![a, d, b, e, c]
+}
+
+#[test]
+fn test_verify_block_txs_in_dependency_layers() {
+    let (always_success_cell, always_success_script) = create_always_success_cell();
+    let store = Arc::new(ChainKVStore::new(MemoryKeyValueDB::open(COLUMNS as usize)));
+
+    let transactions = block_txs(&[], &always_success_script);
+    let resolved = resolve(&transactions, &always_success_cell, &always_success_script);
+    assert_eq!(
+        dependency_layers(&resolved),
+        vec![vec![0, 1, 3], vec![2], vec![4]]
+    );
+
+    // the same total cycles as verifying the transactions one by one
+    let script_config = ScriptConfig::default();
+    let sequential_cycles = resolved
+        .iter()
+        .map(|rtx| {
+            TransactionVerifier::new(
+                rtx,
+                Arc::clone(&store),
+                &FixedMedianTime,
+                10,
+                1,
+                None,
+                MaturityMetric::Blocks(0),
+                Consensus::default().max_extra_witnesses(),
+                &script_config,
+            )
+            .verify(MAX_CYCLES)
+            .expect("valid transaction")
+        })
+        .sum::<Cycle>();
+    assert!(sequential_cycles > 0);
+    for _ in 0..3 {
+        assert_eq!(verify_block_txs(&store, &resolved), Ok(sequential_cycles));
+    }
+
+    // (invalid transactions, the first of them in the block order)
+    let cases = vec![(vec![2, 3], 2), (vec![3, 4], 3), (vec![1, 4], 1)];
+    for (invalid_indexes, first_invalid) in cases {
+        let transactions = block_txs(&invalid_indexes, &always_success_script);
+        let resolved = resolve(&transactions, &always_success_cell, &always_success_script);
+        for _ in 0..3 {
+            assert_eq!(
+                verify_block_txs(&store, &resolved),
+                Err(Error::Transactions((
+                    first_invalid,
+                    TransactionError::OutputsSumOverflow {
+                        inputs: capacity_bytes!(100),
+                        outputs: capacity_bytes!(101),
+                    }
+                ))),
+                "invalid transactions {:?}",
+                invalid_indexes
+            );
+        }
+    }
+}
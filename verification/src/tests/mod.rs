@@ -1,3 +1,4 @@
+mod block_txs_verifier;
 mod block_verifier;
 mod commit_verifier;
 mod dummy;
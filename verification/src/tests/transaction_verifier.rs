@@ -1,13 +1,23 @@
 use super::super::transaction_verifier::{
-    CapacityVerifier, DuplicateDepsVerifier, EmptyVerifier, MaturityVerifier, SinceVerifier,
+    CapacityVerifier, ContextualTransactionVerifier, DuplicateDepsVerifier, EmptyVerifier,
+    MaturityVerifier, MedianTimeCache, NullVerifier, ScriptVerifier, SinceRequirement,
+    SinceVerifier, TransactionVerifier, VerifyContext, WitnessesVerifier,
 };
-use crate::error::TransactionError;
+use crate::error::{InputsOrOutputs, TransactionError};
+use ckb_chain_spec::consensus::{Consensus, MaturityMetric};
 use ckb_core::cell::{BlockInfo, CellMeta, CellMetaBuilder, ResolvedOutPoint, ResolvedTransaction};
 use ckb_core::script::Script;
-use ckb_core::transaction::{CellInput, CellOutput, OutPoint, TransactionBuilder};
-use ckb_core::{capacity_bytes, Bytes, Capacity};
+use ckb_core::transaction::{CellInput, CellOutput, OutPoint, Transaction, TransactionBuilder};
+use ckb_core::{capacity_bytes, BlockNumber, Bytes, Capacity};
+use ckb_db::MemoryKeyValueDB;
+use ckb_script::ScriptConfig;
+use ckb_store::{ChainKVStore, COLUMNS};
 use ckb_traits::BlockMedianTimeContext;
+use ckb_util::Mutex;
 use numext_fixed_hash::{h256, H256};
+use std::collections::HashMap;
+use std::sync::Arc;
+use test_chain_utils::create_always_success_cell;
 
 #[test]
 pub fn test_empty() {
@@ -17,6 +27,78 @@ pub fn test_empty() {
     assert_eq!(verifier.verify().err(), Some(TransactionError::Empty));
 }
 
+#[test]
+pub fn test_null_input() {
+    let cellbase = TransactionBuilder::default()
+        .input(CellInput::new(OutPoint::null(), 0, Default::default()))
+        .build();
+    assert!(NullVerifier::new(&cellbase).verify().is_ok());
+
+    let transaction = TransactionBuilder::default()
+        .inputs(vec![
+            CellInput::new(OutPoint::new_cell(h256!("0x1"), 0), 0, Default::default()),
+            CellInput::new(OutPoint::null(), 0, Default::default()),
+            CellInput::new(OutPoint::new_cell(h256!("0x1"), 1), 0, Default::default()),
+        ])
+        .build();
+    assert_eq!(
+        NullVerifier::new(&transaction).verify().err(),
+        Some(TransactionError::NullInput { index: 1 })
+    );
+
+    // null deps are left to the dep rules
+    let transaction = TransactionBuilder::default()
+        .input(CellInput::new(
+            OutPoint::new_cell(h256!("0x1"), 0),
+            0,
+            Default::default(),
+        ))
+        .dep(OutPoint::null())
+        .build();
+    assert!(NullVerifier::new(&transaction).verify().is_ok());
+}
+
+#[test]
+pub fn test_witnesses_count() {
+    let transaction_with_witnesses = |witnesses: usize| {
+        TransactionBuilder::default()
+            .inputs(vec![
+                CellInput::new(OutPoint::new_cell(h256!("0x1"), 0), 0, Default::default()),
+                CellInput::new(OutPoint::new_cell(h256!("0x1"), 1), 0, Default::default()),
+            ])
+            .witnesses(vec![vec![]; witnesses])
+            .build()
+    };
+
+    let transaction = transaction_with_witnesses(2);
+    assert!(WitnessesVerifier::new(&transaction, 2).verify().is_ok());
+    let transaction = transaction_with_witnesses(4);
+    assert!(WitnessesVerifier::new(&transaction, 2).verify().is_ok());
+
+    let transaction = transaction_with_witnesses(1);
+    assert_eq!(
+        WitnessesVerifier::new(&transaction, 2).verify().err(),
+        Some(TransactionError::InsufficientWitnesses {
+            inputs: 2,
+            witnesses: 1,
+        })
+    );
+
+    let transaction = transaction_with_witnesses(100);
+    assert_eq!(
+        WitnessesVerifier::new(&transaction, 2).verify().err(),
+        Some(TransactionError::TooManyWitnesses {
+            inputs: 2,
+            witnesses: 100,
+        })
+    );
+
+    let cellbase = TransactionBuilder::default()
+        .input(CellInput::new_cellbase_input(1))
+        .build();
+    assert!(WitnessesVerifier::new(&cellbase, 2).verify().is_ok());
+}
+
 #[test]
 pub fn test_capacity_outofbound() {
     let transaction = TransactionBuilder::default()
@@ -37,9 +119,74 @@ pub fn test_capacity_outofbound() {
     };
     let verifier = CapacityVerifier::new(&rtx);
 
+    // 8 bytes capacity + 51 bytes data + 32 bytes lock code hash
     assert_eq!(
         verifier.verify().err(),
-        Some(TransactionError::CapacityOverflow)
+        Some(TransactionError::InsufficientCellCapacity {
+            index: 0,
+            occupied: capacity_bytes!(91),
+            capacity: capacity_bytes!(50),
+        })
+    );
+}
+
+fn verify_output_capacity(output: CellOutput) -> Result<(), TransactionError> {
+    let transaction = TransactionBuilder::default()
+        .output(CellOutput::new(
+            capacity_bytes!(100),
+            Bytes::new(),
+            Script::default(),
+            None,
+        ))
+        .output(output)
+        .build();
+    let rtx = ResolvedTransaction {
+        transaction: &transaction,
+        resolved_deps: Vec::new(),
+        resolved_inputs: vec![ResolvedOutPoint::cell_only(CellMeta::from(
+            &CellOutput::new(capacity_bytes!(1000), Bytes::new(), Script::default(), None),
+        ))],
+    };
+    CapacityVerifier::new(&rtx).verify()
+}
+
+#[test]
+pub fn test_occupied_capacity_boundary() {
+    // empty data without type script: 8 bytes capacity + 32 bytes lock code hash
+    assert!(verify_output_capacity(CellOutput::new(
+        capacity_bytes!(40),
+        Bytes::new(),
+        Script::default(),
+        None,
+    ))
+    .is_ok());
+
+    // 8 bytes capacity + 10 bytes data + 32 bytes lock code hash + 2 bytes lock args
+    // + 32 bytes type code hash
+    let lock = Script::new(vec![Bytes::from(vec![0; 2])], h256!("0x1"));
+    let type_ = Some(Script::new(vec![], h256!("0x2")));
+    assert!(verify_output_capacity(CellOutput::new(
+        capacity_bytes!(84),
+        Bytes::from(vec![1; 10]),
+        lock.clone(),
+        type_.clone(),
+    ))
+    .is_ok());
+
+    let capacity = Capacity::shannons(capacity_bytes!(84).as_u64() - 1);
+    assert_eq!(
+        verify_output_capacity(CellOutput::new(
+            capacity,
+            Bytes::from(vec![1; 10]),
+            lock,
+            type_,
+        ))
+        .err(),
+        Some(TransactionError::InsufficientCellCapacity {
+            index: 1,
+            occupied: capacity_bytes!(84),
+            capacity,
+        })
     );
 }
 
@@ -93,8 +240,8 @@ pub fn test_cellbase_maturity() {
     };
 
     let tip_number = 70;
-    let cellbase_maturity = 100;
-    let verifier = MaturityVerifier::new(&rtx, tip_number, cellbase_maturity);
+    let cellbase_maturity = MaturityMetric::Blocks(100);
+    let verifier = MaturityVerifier::new(&rtx, tip_number, 0, cellbase_maturity);
 
     assert_eq!(
         verifier.verify().err(),
@@ -102,11 +249,76 @@ pub fn test_cellbase_maturity() {
     );
 
     let tip_number = 130;
-    let verifier = MaturityVerifier::new(&rtx, tip_number, cellbase_maturity);
+    let verifier = MaturityVerifier::new(&rtx, tip_number, 0, cellbase_maturity);
 
     assert!(verifier.verify().is_ok());
 }
 
+fn cellbase_rtx(transaction: &Transaction, block_info: Option<BlockInfo>) -> ResolvedTransaction {
+    let mut cell_meta_builder = CellMetaBuilder::from_cell_output(CellOutput::new(
+        capacity_bytes!(50),
+        Bytes::new(),
+        Script::default(),
+        None,
+    ))
+    .cellbase(true);
+    if let Some(block_info) = block_info {
+        cell_meta_builder = cell_meta_builder.block_info(block_info);
+    }
+    ResolvedTransaction {
+        transaction,
+        resolved_deps: Vec::new(),
+        resolved_inputs: vec![ResolvedOutPoint::cell_only(cell_meta_builder.build())],
+    }
+}
+
+#[test]
+pub fn test_cellbase_maturity_boundary() {
+    let transaction = TransactionBuilder::default().build();
+    // committed at block 30, epoch 2
+    let rtx = cellbase_rtx(&transaction, Some(BlockInfo::new(30, 2)));
+
+    let cellbase_maturity = MaturityMetric::Blocks(100);
+    let verifier = MaturityVerifier::new(&rtx, 129, 100, cellbase_maturity);
+    assert_eq!(
+        verifier.verify().err(),
+        Some(TransactionError::CellbaseImmaturity)
+    );
+    // mature exactly at deposit number + maturity
+    let verifier = MaturityVerifier::new(&rtx, 130, 0, cellbase_maturity);
+    assert!(verifier.verify().is_ok());
+
+    let cellbase_maturity = MaturityMetric::Epochs(4);
+    let verifier = MaturityVerifier::new(&rtx, 1000, 5, cellbase_maturity);
+    assert_eq!(
+        verifier.verify().err(),
+        Some(TransactionError::CellbaseImmaturity)
+    );
+    // mature exactly at deposit epoch + maturity, no matter the block number
+    let verifier = MaturityVerifier::new(&rtx, 0, 6, cellbase_maturity);
+    assert!(verifier.verify().is_ok());
+}
+
+#[test]
+pub fn test_cellbase_maturity_without_block_info() {
+    let transaction = TransactionBuilder::default().build();
+    // a cellbase not committed yet is always immature
+    let rtx = cellbase_rtx(&transaction, None);
+
+    for cellbase_maturity in &[
+        MaturityMetric::Blocks(0),
+        MaturityMetric::Blocks(100),
+        MaturityMetric::Epochs(0),
+        MaturityMetric::Epochs(4),
+    ] {
+        let verifier = MaturityVerifier::new(&rtx, 1000, 100, *cellbase_maturity);
+        assert_eq!(
+            verifier.verify().err(),
+            Some(TransactionError::CellbaseImmaturity)
+        );
+    }
+}
+
 #[test]
 pub fn test_capacity_invalid() {
     let transaction = TransactionBuilder::default()
@@ -148,7 +360,90 @@ pub fn test_capacity_invalid() {
 
     assert_eq!(
         verifier.verify().err(),
-        Some(TransactionError::OutputsSumOverflow)
+        Some(TransactionError::OutputsSumOverflow {
+            inputs: capacity_bytes!(149),
+            outputs: capacity_bytes!(150),
+        })
+    );
+}
+
+fn capacity_only_output(capacity: Capacity) -> CellOutput {
+    CellOutput::new(capacity, Bytes::default(), Script::default(), None)
+}
+
+fn resolved_capacity_only(capacity: Capacity) -> ResolvedOutPoint {
+    ResolvedOutPoint::cell_only(CellMeta::from(&capacity_only_output(capacity)))
+}
+
+#[test]
+pub fn test_capacity_short_by_one_shannon() {
+    let transaction = TransactionBuilder::default()
+        .output(capacity_only_output(capacity_bytes!(100)))
+        .build();
+    let inputs = Capacity::shannons(capacity_bytes!(100).as_u64() - 1);
+    let rtx = ResolvedTransaction {
+        transaction: &transaction,
+        resolved_deps: Vec::new(),
+        resolved_inputs: vec![resolved_capacity_only(inputs)],
+    };
+    let verifier = CapacityVerifier::new(&rtx);
+
+    assert_eq!(
+        verifier.verify().err(),
+        Some(TransactionError::OutputsSumOverflow {
+            inputs,
+            outputs: capacity_bytes!(100),
+        })
+    );
+}
+
+#[test]
+pub fn test_capacity_outputs_sum_overflow() {
+    let transaction = TransactionBuilder::default()
+        .outputs(vec![
+            capacity_only_output(Capacity::shannons(u64::max_value())),
+            capacity_only_output(Capacity::shannons(1)),
+        ])
+        .build();
+    let rtx = ResolvedTransaction {
+        transaction: &transaction,
+        resolved_deps: Vec::new(),
+        resolved_inputs: vec![
+            resolved_capacity_only(Capacity::shannons(u64::max_value())),
+            resolved_capacity_only(Capacity::shannons(u64::max_value())),
+        ],
+    };
+    let verifier = CapacityVerifier::new(&rtx);
+
+    assert_eq!(
+        verifier.verify().err(),
+        Some(TransactionError::CapacitySumOverflow {
+            side: InputsOrOutputs::Outputs,
+        })
+    );
+}
+
+#[test]
+pub fn test_capacity_inputs_sum_overflow() {
+    let transaction = TransactionBuilder::default()
+        .output(capacity_only_output(capacity_bytes!(100)))
+        .build();
+    let rtx = ResolvedTransaction {
+        transaction: &transaction,
+        resolved_deps: Vec::new(),
+        resolved_inputs: vec![
+            resolved_capacity_only(Capacity::shannons(u64::max_value())),
+            resolved_capacity_only(Capacity::shannons(1)),
+        ],
+    };
+    let verifier = CapacityVerifier::new(&rtx);
+
+    // the absurd inputs total still covers the outputs, but does not fit in a capacity
+    assert_eq!(
+        verifier.verify().err(),
+        Some(TransactionError::CapacitySumOverflow {
+            side: InputsOrOutputs::Inputs,
+        })
     );
 }
 
@@ -169,6 +464,59 @@ pub fn test_duplicate_deps() {
     );
 }
 
+#[test]
+pub fn test_verify_all_collects_errors() {
+    // wrong version, no outputs and duplicate deps
+    let transaction = TransactionBuilder::default()
+        .version(1)
+        .input(CellInput::new(
+            OutPoint::new_cell(h256!("0x1"), 0),
+            0,
+            Default::default(),
+        ))
+        .deps(vec![
+            OutPoint::new_cell(h256!("0x2"), 0),
+            OutPoint::new_cell(h256!("0x2"), 0),
+        ])
+        .witness(vec![])
+        .build();
+
+    let rtx = ResolvedTransaction {
+        transaction: &transaction,
+        resolved_deps: Vec::new(),
+        resolved_inputs: vec![ResolvedOutPoint::cell_only(CellMeta::from(
+            &CellOutput::new(capacity_bytes!(50), Bytes::new(), Script::default(), None),
+        ))],
+    };
+
+    let store = Arc::new(ChainKVStore::new(MemoryKeyValueDB::open(COLUMNS as usize)));
+    let median_time_context = FakeMedianTime {
+        timestamps: vec![0; 11],
+    };
+    let script_config = ScriptConfig::default();
+    let verifier = TransactionVerifier::new(
+        &rtx,
+        store,
+        &median_time_context,
+        10,
+        1,
+        None,
+        MaturityMetric::Blocks(0),
+        Consensus::default().max_extra_witnesses(),
+        &script_config,
+    );
+
+    assert_eq!(verifier.verify(1000).err(), Some(TransactionError::Version));
+    assert_eq!(
+        verifier.verify_all(1000).err(),
+        Some(vec![
+            TransactionError::Version,
+            TransactionError::Empty,
+            TransactionError::DuplicateDeps,
+        ])
+    );
+}
+
 struct FakeMedianTime {
     timestamps: Vec<u64>,
 }
@@ -214,7 +562,7 @@ pub fn test_since() {
     let median_time_context = FakeMedianTime {
         timestamps: vec![0; 11],
     };
-    let verifier = SinceVerifier::new(&rtx, &median_time_context, 5, 1);
+    let verifier = SinceVerifier::new(&rtx, &median_time_context, 5, 1, None);
     assert_eq!(
         verifier.verify().err(),
         Some(TransactionError::InvalidSince)
@@ -247,10 +595,10 @@ pub fn test_since() {
     let median_time_context = FakeMedianTime {
         timestamps: vec![0; 11],
     };
-    let verifier = SinceVerifier::new(&rtx, &median_time_context, 5, 1);
+    let verifier = SinceVerifier::new(&rtx, &median_time_context, 5, 1, None);
     assert_eq!(verifier.verify().err(), Some(TransactionError::Immature));
     // spent after 10 height
-    let verifier = SinceVerifier::new(&rtx, &median_time_context, 10, 1);
+    let verifier = SinceVerifier::new(&rtx, &median_time_context, 10, 1, None);
     assert!(verifier.verify().is_ok());
 
     // relative lock
@@ -277,14 +625,14 @@ pub fn test_since() {
         )],
     };
 
-    let verifier = SinceVerifier::new(&rtx, &median_time_context, 4, 1);
+    let verifier = SinceVerifier::new(&rtx, &median_time_context, 4, 1, None);
     assert_eq!(verifier.verify().err(), Some(TransactionError::Immature));
     // spent after 1024 seconds
     // fake median time: 1124
     let median_time_context = FakeMedianTime {
         timestamps: vec![0, 100_000, 1_124_000, 2_000_000, 3_000_000],
     };
-    let verifier = SinceVerifier::new(&rtx, &median_time_context, 4, 1);
+    let verifier = SinceVerifier::new(&rtx, &median_time_context, 4, 1, None);
     assert!(verifier.verify().is_ok());
 
     // both
@@ -318,7 +666,7 @@ pub fn test_since() {
         )],
     };
 
-    let verifier = SinceVerifier::new(&rtx, &median_time_context, 4, 1);
+    let verifier = SinceVerifier::new(&rtx, &median_time_context, 4, 1, None);
     assert_eq!(verifier.verify().err(), Some(TransactionError::Immature));
     // spent after 1024 seconds and 10 blocks
     // fake median time: 1124
@@ -328,7 +676,7 @@ pub fn test_since() {
             6_000_000,
         ],
     };
-    let verifier = SinceVerifier::new(&rtx, &median_time_context, 10, 1);
+    let verifier = SinceVerifier::new(&rtx, &median_time_context, 10, 1, None);
     assert!(verifier.verify().is_ok());
     // next epoch
     let transaction = TransactionBuilder::default()
@@ -354,9 +702,9 @@ pub fn test_since() {
         )],
     };
 
-    let verifier = SinceVerifier::new(&rtx, &median_time_context, 4, 1);
+    let verifier = SinceVerifier::new(&rtx, &median_time_context, 4, 1, None);
     assert_eq!(verifier.verify().err(), Some(TransactionError::Immature));
-    let verifier = SinceVerifier::new(&rtx, &median_time_context, 4, 2);
+    let verifier = SinceVerifier::new(&rtx, &median_time_context, 4, 2, None);
     assert!(verifier.verify().is_ok());
 
     // invalid since flags
@@ -383,9 +731,456 @@ pub fn test_since() {
         )],
     };
 
-    let verifier = SinceVerifier::new(&rtx, &median_time_context, 4, 2);
+    let verifier = SinceVerifier::new(&rtx, &median_time_context, 4, 2, None);
     assert_eq!(
         verifier.verify().err(),
         Some(TransactionError::InvalidSince)
     );
 }
+
+struct CountingMedianTime {
+    calls: Mutex<HashMap<BlockNumber, usize>>,
+}
+
+impl BlockMedianTimeContext for CountingMedianTime {
+    fn median_block_count(&self) -> u64 {
+        11
+    }
+    fn timestamp(&self, _n: u64) -> Option<u64> {
+        unimplemented!();
+    }
+    fn block_median_time(&self, n: BlockNumber) -> Option<u64> {
+        *self.calls.lock().entry(n).or_insert(0) += 1;
+        // median time of block n is n seconds
+        Some(n * 1_000)
+    }
+}
+
+#[test]
+pub fn test_since_verify_batch_shares_median_time_cache() {
+    // (since, cell block number)
+    let inputs = vec![
+        // relative 2 seconds
+        (0xc000_0000_0000_0002, 1),
+        (0xc000_0000_0000_0002, 1),
+        // absolute 5 seconds
+        (0x4000_0000_0000_0005, 3),
+        // relative 8 seconds
+        (0xc000_0000_0000_0008, 3),
+    ];
+    let transactions: Vec<Transaction> = inputs
+        .iter()
+        .map(|(since, _)| {
+            TransactionBuilder::default()
+                .input(CellInput::new(
+                    OutPoint::new_cell(h256!("0x1"), 0),
+                    *since,
+                    Default::default(),
+                ))
+                .build()
+        })
+        .collect();
+    let rtxs: Vec<ResolvedTransaction> = transactions
+        .iter()
+        .zip(inputs.iter())
+        .map(|(transaction, (_, number))| ResolvedTransaction {
+            transaction,
+            resolved_deps: Vec::new(),
+            resolved_inputs: vec![ResolvedOutPoint::cell_only(
+                CellMetaBuilder::from_cell_output(CellOutput::new(
+                    capacity_bytes!(50),
+                    Bytes::new(),
+                    Script::default(),
+                    None,
+                ))
+                .block_info(BlockInfo::new(*number, 0))
+                .build(),
+            )],
+        })
+        .collect();
+
+    let median_time_context = CountingMedianTime {
+        calls: Mutex::new(HashMap::new()),
+    };
+    let results = ContextualTransactionVerifier::verify_batch(
+        &rtxs,
+        &median_time_context,
+        10,
+        1,
+        None,
+        MaturityMetric::Blocks(0),
+    );
+
+    assert_eq!(
+        results,
+        vec![
+            Ok(()),
+            Ok(()),
+            Ok(()),
+            // tip median time 9s < cell median time 2s + 8s
+            Err(TransactionError::Immature),
+        ]
+    );
+    // tip's parent, and the parents of the cells at block 1 and block 3
+    let calls = median_time_context.calls.lock();
+    assert_eq!(calls.len(), 3);
+    assert!(calls.values().all(|count| *count == 1));
+    assert_eq!(calls.get(&9), Some(&1));
+    assert_eq!(calls.get(&0), Some(&1));
+    assert_eq!(calls.get(&2), Some(&1));
+}
+
+#[test]
+pub fn test_since_shares_median_time_cache_across_verifiers() {
+    // relative 2 seconds on cells of block 5
+    let transactions: Vec<Transaction> = (0..100)
+        .map(|i| {
+            TransactionBuilder::default()
+                .input(CellInput::new(
+                    OutPoint::new_cell(h256!("0x1"), i),
+                    0xc000_0000_0000_0002,
+                    Default::default(),
+                ))
+                .build()
+        })
+        .collect();
+    let rtxs: Vec<ResolvedTransaction> = transactions
+        .iter()
+        .map(|transaction| ResolvedTransaction {
+            transaction,
+            resolved_deps: Vec::new(),
+            resolved_inputs: vec![ResolvedOutPoint::cell_only(
+                CellMetaBuilder::from_cell_output(CellOutput::new(
+                    capacity_bytes!(50),
+                    Bytes::new(),
+                    Script::default(),
+                    None,
+                ))
+                .block_info(BlockInfo::new(5, 0))
+                .build(),
+            )],
+        })
+        .collect();
+
+    let median_time_context = CountingMedianTime {
+        calls: Mutex::new(HashMap::new()),
+    };
+    let median_time_cache = Arc::new(MedianTimeCache::new(16));
+    let verify_all = || {
+        for rtx in &rtxs {
+            let verifier = ContextualTransactionVerifier::new_with_cache(
+                rtx,
+                &median_time_context,
+                10,
+                1,
+                Some(9_000),
+                MaturityMetric::Blocks(0),
+                Arc::clone(&median_time_cache),
+            );
+            assert_eq!(verifier.verify(), Ok(()));
+        }
+    };
+    verify_all();
+    // the median time of the parent of block 5 is computed once for all the verifiers
+    assert_eq!(median_time_context.calls.lock().get(&4), Some(&1));
+    assert_eq!(median_time_context.calls.lock().len(), 1);
+
+    // blocks from 5 on are detached, the median time of block 4 stays
+    median_time_cache.invalidate_from(5);
+    verify_all();
+    assert_eq!(median_time_context.calls.lock().get(&4), Some(&1));
+    median_time_cache.invalidate_from(4);
+    assert!(median_time_cache.is_empty());
+    verify_all();
+    assert_eq!(median_time_context.calls.lock().get(&4), Some(&2));
+}
+
+#[test]
+pub fn test_since_earliest_valid() {
+    // (since, cell block info)
+    let inputs = vec![
+        // absolute block 10
+        (0x0000_0000_0000_000a, Some(BlockInfo::new(1, 0))),
+        // relative 3 blocks, valid at block 12
+        (0x8000_0000_0000_0003, Some(BlockInfo::new(9, 2))),
+        // relative 1 epoch, valid at epoch 3
+        (0xa000_0000_0000_0001, Some(BlockInfo::new(9, 2))),
+        // absolute epoch 2
+        (0x2000_0000_0000_0002, Some(BlockInfo::new(1, 0))),
+        // absolute 100 seconds
+        (0x4000_0000_0000_0064, Some(BlockInfo::new(1, 0))),
+        // relative 200 seconds, valid at cell median time 4s + 200s
+        (0xc000_0000_0000_00c8, Some(BlockInfo::new(5, 1))),
+        // relative lock on a cell still in tx pool
+        (0x8000_0000_0000_0001, None),
+        // no lock
+        (0, Some(BlockInfo::new(1, 0))),
+    ];
+    let transaction = TransactionBuilder::default()
+        .inputs(
+            inputs
+                .iter()
+                .map(|(since, _)| {
+                    CellInput::new(
+                        OutPoint::new_cell(h256!("0x1"), 0),
+                        *since,
+                        Default::default(),
+                    )
+                })
+                .collect::<Vec<_>>(),
+        )
+        .build();
+    let rtx = ResolvedTransaction {
+        transaction: &transaction,
+        resolved_deps: Vec::new(),
+        resolved_inputs: inputs
+            .iter()
+            .map(|(_, block_info)| {
+                let mut builder = CellMetaBuilder::from_cell_output(CellOutput::new(
+                    capacity_bytes!(50),
+                    Bytes::new(),
+                    Script::default(),
+                    None,
+                ));
+                if let Some(block_info) = block_info {
+                    builder = builder.block_info(block_info.clone());
+                }
+                ResolvedOutPoint::cell_only(builder.build())
+            })
+            .collect(),
+    };
+
+    let median_time_context = CountingMedianTime {
+        calls: Mutex::new(HashMap::new()),
+    };
+    let verifier = SinceVerifier::new(&rtx, &median_time_context, 5, 1, None);
+    assert_eq!(
+        verifier.earliest_valid(),
+        Ok(SinceRequirement {
+            block_number: Some(12),
+            epoch_number: Some(3),
+            timestamp: Some(204_000),
+            unconfirmed_inputs: vec![6],
+        })
+    );
+
+    // invalid since flags
+    let transaction = TransactionBuilder::default()
+        .input(CellInput::new(
+            OutPoint::new_cell(h256!("0x1"), 0),
+            0x6000_0000_0000_0001,
+            Default::default(),
+        ))
+        .build();
+    let rtx = ResolvedTransaction {
+        transaction: &transaction,
+        resolved_deps: Vec::new(),
+        resolved_inputs: vec![ResolvedOutPoint::cell_only(
+            CellMetaBuilder::from_cell_output(CellOutput::new(
+                capacity_bytes!(50),
+                Bytes::new(),
+                Script::default(),
+                None,
+            ))
+            .block_info(BlockInfo::new(1, 1))
+            .build(),
+        )],
+    };
+    let verifier = SinceVerifier::new(&rtx, &median_time_context, 5, 1, None);
+    assert_eq!(
+        verifier.earliest_valid(),
+        Err(TransactionError::InvalidSince)
+    );
+
+    // the timestamp overflows in milliseconds
+    let transaction = TransactionBuilder::default()
+        .input(CellInput::new(
+            OutPoint::new_cell(h256!("0x1"), 0),
+            0x40ff_ffff_ffff_ffff,
+            Default::default(),
+        ))
+        .build();
+    let rtx = ResolvedTransaction {
+        transaction: &transaction,
+        resolved_deps: Vec::new(),
+        resolved_inputs: vec![ResolvedOutPoint::cell_only(
+            CellMetaBuilder::from_cell_output(CellOutput::new(
+                capacity_bytes!(50),
+                Bytes::new(),
+                Script::default(),
+                None,
+            ))
+            .block_info(BlockInfo::new(1, 1))
+            .build(),
+        )],
+    };
+    let verifier = SinceVerifier::new(&rtx, &median_time_context, 5, 1, None);
+    assert_eq!(verifier.verify(), Err(TransactionError::InvalidSince));
+    assert_eq!(
+        verifier.earliest_valid(),
+        Err(TransactionError::InvalidSince)
+    );
+}
+
+#[test]
+pub fn test_since_with_tip_median_time() {
+    // (since, cell block number)
+    let inputs = vec![
+        // absolute 5 seconds
+        (0x4000_0000_0000_0005, 3),
+        // relative 2 seconds
+        (0xc000_0000_0000_0002, 3),
+    ];
+    let transaction = TransactionBuilder::default()
+        .inputs(
+            inputs
+                .iter()
+                .map(|(since, _)| {
+                    CellInput::new(
+                        OutPoint::new_cell(h256!("0x1"), 0),
+                        *since,
+                        Default::default(),
+                    )
+                })
+                .collect::<Vec<_>>(),
+        )
+        .build();
+    let rtx = ResolvedTransaction {
+        transaction: &transaction,
+        resolved_deps: Vec::new(),
+        resolved_inputs: inputs
+            .iter()
+            .map(|(_, number)| {
+                ResolvedOutPoint::cell_only(
+                    CellMetaBuilder::from_cell_output(CellOutput::new(
+                        capacity_bytes!(50),
+                        Bytes::new(),
+                        Script::default(),
+                        None,
+                    ))
+                    .block_info(BlockInfo::new(*number, 0))
+                    .build(),
+                )
+            })
+            .collect(),
+    };
+
+    // without the hint, the tip median time is 9s
+    let median_time_context = CountingMedianTime {
+        calls: Mutex::new(HashMap::new()),
+    };
+    let verifier = SinceVerifier::new(&rtx, &median_time_context, 10, 1, None);
+    assert!(verifier.verify().is_ok());
+    assert_eq!(median_time_context.calls.lock().get(&9), Some(&1));
+
+    // the hint is used for the tip and the context only for the cell's block
+    let median_time_context = CountingMedianTime {
+        calls: Mutex::new(HashMap::new()),
+    };
+    let verifier = SinceVerifier::new(&rtx, &median_time_context, 10, 1, Some(9_000));
+    assert!(verifier.verify().is_ok());
+    let calls = median_time_context.calls.lock();
+    assert_eq!(calls.get(&9), None);
+    assert_eq!(calls.get(&2), Some(&1));
+    drop(calls);
+
+    // an earlier tip median time fails the absolute lock
+    let verifier = SinceVerifier::new(&rtx, &median_time_context, 10, 1, Some(4_000));
+    assert_eq!(verifier.verify().err(), Some(TransactionError::Immature));
+    assert_eq!(median_time_context.calls.lock().get(&9), None);
+}
+
+#[test]
+pub fn test_verify_with_historical_context() {
+    // relative 5 blocks
+    let transaction = TransactionBuilder::default()
+        .input(CellInput::new(
+            OutPoint::new_cell(h256!("0x1"), 0),
+            0x8000_0000_0000_0005,
+            Default::default(),
+        ))
+        .build();
+    let rtx = ResolvedTransaction {
+        transaction: &transaction,
+        resolved_deps: Vec::new(),
+        resolved_inputs: vec![ResolvedOutPoint::cell_only(
+            CellMetaBuilder::from_cell_output(CellOutput::new(
+                capacity_bytes!(50),
+                Bytes::new(),
+                Script::default(),
+                None,
+            ))
+            .block_info(BlockInfo::new(10, 0))
+            .build(),
+        )],
+    };
+    let median_time_context = FakeMedianTime {
+        timestamps: vec![0; 20],
+    };
+    let cellbase_maturity = MaturityMetric::Blocks(100);
+
+    let context = VerifyContext::new(&median_time_context, 15, 0, None);
+    let verifier =
+        ContextualTransactionVerifier::new_with_context(&rtx, context, cellbase_maturity);
+    assert!(verifier.verify().is_ok());
+
+    let context = VerifyContext::new(&median_time_context, 12, 0, None);
+    let verifier =
+        ContextualTransactionVerifier::new_with_context(&rtx, context, cellbase_maturity);
+    assert_eq!(verifier.verify().err(), Some(TransactionError::Immature));
+}
+
+#[test]
+pub fn test_estimate_matches_verify() {
+    let (always_success_cell, always_success_script) = create_always_success_cell();
+    let transaction = TransactionBuilder::default()
+        .input(CellInput::new(
+            OutPoint::new_cell(h256!("0x1"), 0),
+            0,
+            Default::default(),
+        ))
+        .input(CellInput::new(
+            OutPoint::new_cell(h256!("0x1"), 1),
+            0,
+            Default::default(),
+        ))
+        .output(CellOutput::new(
+            capacity_bytes!(100),
+            Bytes::new(),
+            always_success_script.clone(),
+            Some(always_success_script.clone()),
+        ))
+        .build();
+    let input_cell = || {
+        ResolvedOutPoint::cell_only(
+            CellMetaBuilder::from_cell_output(CellOutput::new(
+                capacity_bytes!(100),
+                Bytes::new(),
+                always_success_script.clone(),
+                None,
+            ))
+            .block_info(BlockInfo::new(1, 0))
+            .build(),
+        )
+    };
+    let rtx = ResolvedTransaction {
+        transaction: &transaction,
+        resolved_deps: vec![ResolvedOutPoint::cell_only(
+            CellMetaBuilder::from_cell_output(always_success_cell)
+                .block_info(BlockInfo::new(1, 0))
+                .build(),
+        )],
+        resolved_inputs: vec![input_cell(), input_cell()],
+    };
+
+    let store = Arc::new(ChainKVStore::new(MemoryKeyValueDB::open(COLUMNS as usize)));
+    let script_config = ScriptConfig::default();
+    let verifier = ScriptVerifier::new(&rtx, store, &script_config);
+
+    let cycles = verifier.estimate().expect("estimate");
+    assert!(cycles > 0);
+    assert_eq!(verifier.verify(cycles), Ok(cycles));
+    // the estimation is not bounded by the limit passed to verify
+    assert!(verifier.verify(cycles - 1).is_err());
+    assert_eq!(verifier.estimate(), Ok(cycles));
+}
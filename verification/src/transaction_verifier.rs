@@ -1,17 +1,151 @@
-use crate::error::TransactionError;
-use ckb_core::transaction::{Capacity, CellOutput, Transaction, TX_VERSION};
+use crate::error::{InputsOrOutputs, TransactionError};
+use ckb_chain_spec::consensus::MaturityMetric;
+use ckb_core::transaction::{Capacity, Transaction, TX_VERSION};
 use ckb_core::{
     cell::{CellMeta, ResolvedOutPoint, ResolvedTransaction},
+    since::{Since, SinceMetric},
     BlockNumber, Cycle, EpochNumber,
 };
-use ckb_script::{ScriptConfig, TransactionScriptsVerifier};
+use ckb_script::{
+    CycleBreakdown, DepCellDataCache, ScriptConfig, ScriptDebugEntry, TransactionScriptsVerifier,
+    VerificationSnapshot, VerifyOutcome,
+};
 use ckb_store::ChainStore;
 use ckb_traits::BlockMedianTimeContext;
+use ckb_util::Mutex;
 use lru_cache::LruCache;
-use std::cell::RefCell;
+use occupied_capacity::OccupiedCapacity;
+use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
 use std::collections::HashSet;
+use std::slice;
 use std::sync::Arc;
 
+/// Block median times cache shared between `SinceVerifier`s, by block numbers. The median times
+/// depend on the chain the blocks are in, the cache must only be shared by the verifiers against
+/// the same chain, and the blocks detached from it must be invalidated.
+#[derive(Debug)]
+pub struct MedianTimeCache {
+    inner: Mutex<LruCache<BlockNumber, MedianTimeSlot>>,
+}
+
+// The median time of a block once it is computed, locked while it is being computed
+type MedianTimeSlot = Arc<Mutex<Option<Option<u64>>>>;
+
+impl MedianTimeCache {
+    pub fn new(capacity: usize) -> Self {
+        MedianTimeCache {
+            inner: Mutex::new(LruCache::new(capacity)),
+        }
+    }
+
+    /// Returns the cached median time of block `number`, calls `compute` and caches its result
+    /// on miss.
+    pub fn get_or_compute<F>(&self, number: BlockNumber, compute: F) -> Option<u64>
+    where
+        F: FnOnce() -> Option<u64>,
+    {
+        let slot = {
+            let mut inner = self.inner.lock();
+            match inner.get_mut(&number) {
+                Some(slot) => Arc::clone(slot),
+                None => {
+                    let slot = MedianTimeSlot::default();
+                    inner.insert(number, Arc::clone(&slot));
+                    slot
+                }
+            }
+        };
+        // each block number is computed only once, the verifiers running in parallel only wait
+        // for each other on the same block number
+        let mut timestamp = slot.lock();
+        *timestamp.get_or_insert_with(compute)
+    }
+
+    /// Evicts the median times of block `number` and the blocks after it, whose ancestors may
+    /// have changed
+    pub fn invalidate_from(&self, number: BlockNumber) {
+        let mut inner = self.inner.lock();
+        let stale: Vec<BlockNumber> = inner
+            .iter()
+            .map(|(cached, _)| *cached)
+            .filter(|cached| *cached >= number)
+            .collect();
+        for cached in stale {
+            inner.remove(&cached);
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.inner.lock().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.inner.lock().is_empty()
+    }
+}
+
+/// Create a median time cache large enough to hold every distinct block number
+/// looked up when verifying `rtxs` against `tip_number`: the tip's parent and
+/// the parent of each input cell with a non-empty since.
+pub fn median_time_cache(rtxs: &[ResolvedTransaction], tip_number: BlockNumber) -> MedianTimeCache {
+    let mut numbers = HashSet::new();
+    numbers.insert(tip_number.saturating_sub(1));
+    for rtx in rtxs {
+        for (resolved_out_point, input) in rtx.resolved_inputs.iter().zip(rtx.transaction.inputs())
+        {
+            if input.since == 0 {
+                continue;
+            }
+            if let Some(block_info) = resolved_out_point
+                .cell()
+                .and_then(|cell_meta| cell_meta.block_info.as_ref())
+            {
+                numbers.insert(block_info.number.saturating_sub(1));
+            }
+        }
+    }
+    MedianTimeCache::new(numbers.len())
+}
+
+/// The safety ceiling of `ScriptVerifier::estimate`, 5 times the default max block cycles
+pub const ESTIMATE_MAX_CYCLES: Cycle = 100_000_000_000;
+
+/// The tip a transaction is verified against, which is not necessarily the current tip: the
+/// median time context must be anchored at the same chain, e.g. a fork, as the tip.
+pub struct VerifyContext<'a, M> {
+    pub median_time_context: &'a M,
+    pub tip_number: BlockNumber,
+    pub tip_epoch_number: EpochNumber,
+    /// The median time of the blocks before the tip, computed from `median_time_context` when
+    /// not given
+    pub tip_median_time: Option<u64>,
+}
+
+impl<'a, M> VerifyContext<'a, M> {
+    pub fn new(
+        median_time_context: &'a M,
+        tip_number: BlockNumber,
+        tip_epoch_number: EpochNumber,
+        tip_median_time: Option<u64>,
+    ) -> Self {
+        VerifyContext {
+            median_time_context,
+            tip_number,
+            tip_epoch_number,
+            tip_median_time,
+        }
+    }
+}
+
+// derive requires `M: Copy`, while only a reference to `M` is copied
+impl<'a, M> Clone for VerifyContext<'a, M> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<'a, M> Copy for VerifyContext<'a, M> {}
+
 pub struct ContextualTransactionVerifier<'a, M> {
     pub maturity: MaturityVerifier<'a>,
     pub since: SinceVerifier<'a, M>,
@@ -25,11 +159,59 @@ where
         median_time_context: &'a M,
         tip_number: BlockNumber,
         tip_epoch_number: BlockNumber,
-        cellbase_maturity: BlockNumber,
+        tip_median_time: Option<u64>,
+        cellbase_maturity: MaturityMetric,
+    ) -> Self {
+        let median_time_cache = Arc::new(median_time_cache(slice::from_ref(rtx), tip_number));
+        Self::new_with_cache(
+            rtx,
+            median_time_context,
+            tip_number,
+            tip_epoch_number,
+            tip_median_time,
+            cellbase_maturity,
+            median_time_cache,
+        )
+    }
+
+    pub fn new_with_cache(
+        rtx: &'a ResolvedTransaction,
+        median_time_context: &'a M,
+        tip_number: BlockNumber,
+        tip_epoch_number: BlockNumber,
+        tip_median_time: Option<u64>,
+        cellbase_maturity: MaturityMetric,
+        median_time_cache: Arc<MedianTimeCache>,
+    ) -> Self {
+        let context = VerifyContext::new(
+            median_time_context,
+            tip_number,
+            tip_epoch_number,
+            tip_median_time,
+        );
+        Self::with_context_and_cache(rtx, context, cellbase_maturity, median_time_cache)
+    }
+
+    /// Verify against the tip of `context` instead of the current tip
+    pub fn new_with_context(
+        rtx: &'a ResolvedTransaction,
+        context: VerifyContext<'a, M>,
+        cellbase_maturity: MaturityMetric,
+    ) -> Self {
+        let median_time_cache =
+            Arc::new(median_time_cache(slice::from_ref(rtx), context.tip_number));
+        Self::with_context_and_cache(rtx, context, cellbase_maturity, median_time_cache)
+    }
+
+    fn with_context_and_cache(
+        rtx: &'a ResolvedTransaction,
+        context: VerifyContext<'a, M>,
+        cellbase_maturity: MaturityMetric,
+        median_time_cache: Arc<MedianTimeCache>,
     ) -> Self {
         ContextualTransactionVerifier {
-            maturity: MaturityVerifier::new(&rtx, tip_number, cellbase_maturity),
-            since: SinceVerifier::new(rtx, median_time_context, tip_number, tip_epoch_number),
+            maturity: MaturityVerifier::new_with_context(&rtx, &context, cellbase_maturity),
+            since: SinceVerifier::new_with_context(rtx, context, median_time_cache),
         }
     }
 
@@ -38,11 +220,43 @@ where
         self.since.verify()?;
         Ok(())
     }
+
+    /// Verify a batch of transactions against the same tip, all `SinceVerifier`s share
+    /// one median time cache. The results are in the same order as `rtxs`.
+    pub fn verify_batch(
+        rtxs: &[ResolvedTransaction],
+        median_time_context: &M,
+        tip_number: BlockNumber,
+        tip_epoch_number: BlockNumber,
+        tip_median_time: Option<u64>,
+        cellbase_maturity: MaturityMetric,
+    ) -> Vec<Result<(), TransactionError>>
+    where
+        M: Sync,
+    {
+        let median_time_cache = Arc::new(median_time_cache(rtxs, tip_number));
+        rtxs.par_iter()
+            .map(|rtx| {
+                ContextualTransactionVerifier::new_with_cache(
+                    rtx,
+                    median_time_context,
+                    tip_number,
+                    tip_epoch_number,
+                    tip_median_time,
+                    cellbase_maturity,
+                    Arc::clone(&median_time_cache),
+                )
+                .verify()
+            })
+            .collect()
+    }
 }
 
 pub struct TransactionVerifier<'a, M, CS> {
     pub version: VersionVerifier<'a>,
     pub empty: EmptyVerifier<'a>,
+    pub null: NullVerifier<'a>,
+    pub witnesses: WitnessesVerifier<'a>,
     pub maturity: MaturityVerifier<'a>,
     pub capacity: CapacityVerifier<'a>,
     pub duplicate_deps: DuplicateDepsVerifier<'a>,
@@ -54,36 +268,168 @@ impl<'a, M, CS: ChainStore> TransactionVerifier<'a, M, CS>
 where
     M: BlockMedianTimeContext,
 {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         rtx: &'a ResolvedTransaction,
         store: Arc<CS>,
         median_time_context: &'a M,
         tip_number: BlockNumber,
         tip_epoch_number: BlockNumber,
-        cellbase_maturity: BlockNumber,
+        tip_median_time: Option<u64>,
+        cellbase_maturity: MaturityMetric,
+        max_extra_witnesses: usize,
+        script_config: &'a ScriptConfig,
+    ) -> Self {
+        let median_time_cache = Arc::new(median_time_cache(slice::from_ref(rtx), tip_number));
+        Self::new_with_cache(
+            rtx,
+            store,
+            median_time_context,
+            tip_number,
+            tip_epoch_number,
+            tip_median_time,
+            cellbase_maturity,
+            max_extra_witnesses,
+            script_config,
+            median_time_cache,
+            None,
+        )
+    }
+
+    /// Same as `new`, with the caches shared by the transactions of a block. Script binaries
+    /// are loaded through `dep_cell_data_cache` when given.
+    #[allow(clippy::too_many_arguments)]
+    pub fn new_with_cache(
+        rtx: &'a ResolvedTransaction,
+        store: Arc<CS>,
+        median_time_context: &'a M,
+        tip_number: BlockNumber,
+        tip_epoch_number: BlockNumber,
+        tip_median_time: Option<u64>,
+        cellbase_maturity: MaturityMetric,
+        max_extra_witnesses: usize,
+        script_config: &'a ScriptConfig,
+        median_time_cache: Arc<MedianTimeCache>,
+        dep_cell_data_cache: Option<Arc<DepCellDataCache>>,
+    ) -> Self {
+        let context = VerifyContext::new(
+            median_time_context,
+            tip_number,
+            tip_epoch_number,
+            tip_median_time,
+        );
+        Self::with_context_and_cache(
+            rtx,
+            store,
+            context,
+            cellbase_maturity,
+            max_extra_witnesses,
+            script_config,
+            median_time_cache,
+            dep_cell_data_cache,
+        )
+    }
+
+    /// Verify against the tip of `context` instead of the current tip
+    pub fn new_with_context(
+        rtx: &'a ResolvedTransaction,
+        store: Arc<CS>,
+        context: VerifyContext<'a, M>,
+        cellbase_maturity: MaturityMetric,
+        max_extra_witnesses: usize,
         script_config: &'a ScriptConfig,
+    ) -> Self {
+        let median_time_cache =
+            Arc::new(median_time_cache(slice::from_ref(rtx), context.tip_number));
+        Self::with_context_and_cache(
+            rtx,
+            store,
+            context,
+            cellbase_maturity,
+            max_extra_witnesses,
+            script_config,
+            median_time_cache,
+            None,
+        )
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn with_context_and_cache(
+        rtx: &'a ResolvedTransaction,
+        store: Arc<CS>,
+        context: VerifyContext<'a, M>,
+        cellbase_maturity: MaturityMetric,
+        max_extra_witnesses: usize,
+        script_config: &'a ScriptConfig,
+        median_time_cache: Arc<MedianTimeCache>,
+        dep_cell_data_cache: Option<Arc<DepCellDataCache>>,
     ) -> Self {
         TransactionVerifier {
             version: VersionVerifier::new(&rtx.transaction),
             empty: EmptyVerifier::new(&rtx.transaction),
-            maturity: MaturityVerifier::new(&rtx, tip_number, cellbase_maturity),
+            null: NullVerifier::new(&rtx.transaction),
+            witnesses: WitnessesVerifier::new(&rtx.transaction, max_extra_witnesses),
+            maturity: MaturityVerifier::new_with_context(&rtx, &context, cellbase_maturity),
             duplicate_deps: DuplicateDepsVerifier::new(&rtx.transaction),
-            script: ScriptVerifier::new(rtx, Arc::clone(&store), script_config),
+            script: match dep_cell_data_cache {
+                Some(cache) => {
+                    ScriptVerifier::new_with_cache(rtx, Arc::clone(&store), script_config, cache)
+                }
+                None => ScriptVerifier::new(rtx, Arc::clone(&store), script_config),
+            },
             capacity: CapacityVerifier::new(rtx),
-            since: SinceVerifier::new(rtx, median_time_context, tip_number, tip_epoch_number),
+            since: SinceVerifier::new_with_context(rtx, context, median_time_cache),
         }
     }
 
     pub fn verify(&self, max_cycles: Cycle) -> Result<Cycle, TransactionError> {
         self.version.verify()?;
         self.empty.verify()?;
+        self.null.verify()?;
         self.maturity.verify()?;
         self.capacity.verify()?;
         self.duplicate_deps.verify()?;
         self.since.verify()?;
+        self.witnesses.verify()?;
         let cycles = self.script.verify(max_cycles)?;
         Ok(cycles)
     }
+
+    /// Same as `verify`, but measures the script cycles with `ScriptVerifier::estimate`
+    /// instead of enforcing a limit
+    pub fn dry_run(&self) -> Result<Cycle, TransactionError> {
+        self.version.verify()?;
+        self.empty.verify()?;
+        self.null.verify()?;
+        self.maturity.verify()?;
+        self.capacity.verify()?;
+        self.duplicate_deps.verify()?;
+        self.since.verify()?;
+        self.witnesses.verify()?;
+        self.script.estimate()
+    }
+
+    /// Unlike `verify`, runs all the cheap verifiers and collects every error in a stable
+    /// order. The expensive script verification only runs when all of them passed.
+    pub fn verify_all(&self, max_cycles: Cycle) -> Result<Cycle, Vec<TransactionError>> {
+        let errors: Vec<TransactionError> = vec![
+            self.version.verify(),
+            self.empty.verify(),
+            self.null.verify(),
+            self.maturity.verify(),
+            self.capacity.verify(),
+            self.duplicate_deps.verify(),
+            self.since.verify(),
+            self.witnesses.verify(),
+        ]
+        .into_iter()
+        .filter_map(Result::err)
+        .collect();
+        if !errors.is_empty() {
+            return Err(errors);
+        }
+        self.script.verify(max_cycles).map_err(|err| vec![err])
+    }
 }
 
 pub struct VersionVerifier<'a> {
@@ -107,6 +453,7 @@ pub struct ScriptVerifier<'a, CS> {
     store: Arc<CS>,
     resolved_transaction: &'a ResolvedTransaction<'a>,
     script_config: &'a ScriptConfig,
+    dep_cell_data_cache: Option<Arc<DepCellDataCache>>,
 }
 
 impl<'a, CS: ChainStore> ScriptVerifier<'a, CS> {
@@ -119,17 +466,86 @@ impl<'a, CS: ChainStore> ScriptVerifier<'a, CS> {
             store,
             resolved_transaction,
             script_config,
+            dep_cell_data_cache: None,
+        }
+    }
+
+    pub fn new_with_cache(
+        resolved_transaction: &'a ResolvedTransaction,
+        store: Arc<CS>,
+        script_config: &'a ScriptConfig,
+        dep_cell_data_cache: Arc<DepCellDataCache>,
+    ) -> Self {
+        ScriptVerifier {
+            store,
+            resolved_transaction,
+            script_config,
+            dep_cell_data_cache: Some(dep_cell_data_cache),
+        }
+    }
+
+    fn scripts_verifier(&self) -> TransactionScriptsVerifier<CS> {
+        match &self.dep_cell_data_cache {
+            Some(cache) => TransactionScriptsVerifier::new_with_cache(
+                &self.resolved_transaction,
+                Arc::clone(&self.store),
+                &self.script_config,
+                Arc::clone(cache),
+            ),
+            None => TransactionScriptsVerifier::new(
+                &self.resolved_transaction,
+                Arc::clone(&self.store),
+                &self.script_config,
+            ),
         }
     }
 
     pub fn verify(&self, max_cycles: Cycle) -> Result<Cycle, TransactionError> {
-        TransactionScriptsVerifier::new(
-            &self.resolved_transaction,
-            Arc::clone(&self.store),
-            &self.script_config,
-        )
-        .verify(max_cycles)
-        .map_err(TransactionError::ScriptFailure)
+        self.verify_with_breakdown(max_cycles)
+            .map(|breakdown| breakdown.total)
+    }
+
+    /// Same as `verify`, but also returns the cycles consumed by each script group
+    pub fn verify_with_breakdown(
+        &self,
+        max_cycles: Cycle,
+    ) -> Result<CycleBreakdown, TransactionError> {
+        self.scripts_verifier()
+            .verify_with_breakdown(max_cycles)
+            .map_err(TransactionError::ScriptFailure)
+    }
+
+    /// Measures the cycles consumed by the scripts, which may exceed the max block cycles.
+    /// Fails on script failures, or when the scripts exceed `ESTIMATE_MAX_CYCLES`.
+    pub fn estimate(&self) -> Result<Cycle, TransactionError> {
+        self.verify(ESTIMATE_MAX_CYCLES)
+    }
+
+    /// Same as `verify`, but also returns the debug messages printed by the scripts
+    pub fn verify_with_debug(
+        &self,
+        max_cycles: Cycle,
+    ) -> (Result<Cycle, TransactionError>, Vec<ScriptDebugEntry>) {
+        let (result, entries) = self.scripts_verifier().verify_with_debug(max_cycles);
+        (result.map_err(TransactionError::ScriptFailure), entries)
+    }
+
+    /// Runs the scripts until `limit` cycles are used, returning a snapshot to `resume` from
+    /// when the remaining script groups do not fit
+    pub fn verify_until(&self, limit: Cycle) -> Result<VerifyOutcome, TransactionError> {
+        self.scripts_verifier()
+            .verify_until(limit)
+            .map_err(TransactionError::ScriptFailure)
+    }
+
+    pub fn resume(
+        &self,
+        snapshot: VerificationSnapshot,
+        additional_limit: Cycle,
+    ) -> Result<VerifyOutcome, TransactionError> {
+        self.scripts_verifier()
+            .resume(snapshot, additional_limit)
+            .map_err(TransactionError::ScriptFailure)
     }
 }
 
@@ -151,35 +567,112 @@ impl<'a> EmptyVerifier<'a> {
     }
 }
 
+pub struct NullVerifier<'a> {
+    transaction: &'a Transaction,
+}
+
+impl<'a> NullVerifier<'a> {
+    pub fn new(transaction: &'a Transaction) -> Self {
+        NullVerifier { transaction }
+    }
+
+    /// Only the cellbase may spend the null out point, dep out points are not checked here.
+    pub fn verify(&self) -> Result<(), TransactionError> {
+        if self.transaction.is_cellbase() {
+            return Ok(());
+        }
+        match self
+            .transaction
+            .inputs()
+            .iter()
+            .position(|input| input.previous_output.is_null())
+        {
+            Some(index) => Err(TransactionError::NullInput { index }),
+            None => Ok(()),
+        }
+    }
+}
+
+pub struct WitnessesVerifier<'a> {
+    transaction: &'a Transaction,
+    max_extra_witnesses: usize,
+}
+
+impl<'a> WitnessesVerifier<'a> {
+    pub fn new(transaction: &'a Transaction, max_extra_witnesses: usize) -> Self {
+        WitnessesVerifier {
+            transaction,
+            max_extra_witnesses,
+        }
+    }
+
+    /// Every input needs a witness, and at most `max_extra_witnesses` more are allowed.
+    /// The cellbase is not checked here.
+    pub fn verify(&self) -> Result<(), TransactionError> {
+        if self.transaction.is_cellbase() {
+            return Ok(());
+        }
+        let inputs = self.transaction.inputs().len();
+        let witnesses = self.transaction.witnesses().len();
+        if witnesses < inputs {
+            Err(TransactionError::InsufficientWitnesses { inputs, witnesses })
+        } else if witnesses - inputs > self.max_extra_witnesses {
+            Err(TransactionError::TooManyWitnesses { inputs, witnesses })
+        } else {
+            Ok(())
+        }
+    }
+}
+
 pub struct MaturityVerifier<'a> {
     transaction: &'a ResolvedTransaction<'a>,
     tip_number: BlockNumber,
-    cellbase_maturity: BlockNumber,
+    tip_epoch_number: EpochNumber,
+    cellbase_maturity: MaturityMetric,
 }
 
 impl<'a> MaturityVerifier<'a> {
     pub fn new(
         transaction: &'a ResolvedTransaction,
         tip_number: BlockNumber,
-        cellbase_maturity: BlockNumber,
+        tip_epoch_number: EpochNumber,
+        cellbase_maturity: MaturityMetric,
     ) -> Self {
         MaturityVerifier {
             transaction,
             tip_number,
+            tip_epoch_number,
             cellbase_maturity,
         }
     }
 
+    pub fn new_with_context<M>(
+        transaction: &'a ResolvedTransaction,
+        context: &VerifyContext<M>,
+        cellbase_maturity: MaturityMetric,
+    ) -> Self {
+        Self::new(
+            transaction,
+            context.tip_number,
+            context.tip_epoch_number,
+            cellbase_maturity,
+        )
+    }
+
     pub fn verify(&self) -> Result<(), TransactionError> {
         let cellbase_immature = |meta: &CellMeta| -> bool {
-            meta.is_cellbase()
-                && self.tip_number
-                    < meta
-                        .block_info
-                        .as_ref()
-                        .expect("cell meta should have block number when transaction verify")
-                        .number
-                        + self.cellbase_maturity
+            if !meta.is_cellbase() {
+                return false;
+            }
+            // a cellbase without block info is not committed yet
+            let block_info = match meta.block_info {
+                Some(ref block_info) => block_info,
+                None => return true,
+            };
+            match self.cellbase_maturity {
+                MaturityMetric::Blocks(blocks) => self.tip_number < block_info.number + blocks,
+                MaturityMetric::Epochs(epochs) => self.tip_epoch_number < block_info.epoch + epochs,
+            }
         };
 
         let input_immature_spend = || {
@@ -248,156 +741,175 @@ impl<'a> CapacityVerifier<'a> {
                 .transaction
                 .is_withdrawing_from_dao())
         {
-            let inputs_total = self.resolved_transaction.resolved_inputs.iter().try_fold(
-                Capacity::zero(),
-                |acc, resolved_out_point| {
-                    let capacity = resolved_out_point
+            // summed in u128 so that no sum of u64 capacities overflows, and the totals are
+            // compared exactly before being checked to fit in a capacity
+            let inputs_total: u128 = self
+                .resolved_transaction
+                .resolved_inputs
+                .iter()
+                .map(|resolved_out_point| {
+                    resolved_out_point
                         .cell()
-                        .map(|cell_meta| cell_meta.capacity)
-                        .unwrap_or_else(Capacity::zero);
-                    acc.safe_add(capacity)
-                },
-            )?;
-
-            let outputs_total = self
+                        .map_or(0, |cell_meta| u128::from(cell_meta.capacity.as_u64()))
+                })
+                .sum();
+            let outputs_total: u128 = self
                 .resolved_transaction
                 .transaction
                 .outputs()
                 .iter()
-                .try_fold(Capacity::zero(), |acc, output| {
-                    acc.safe_add(output.capacity)
-                })?;
+                .map(|output| u128::from(output.capacity.as_u64()))
+                .sum();
 
+            if outputs_total > u128::from(u64::max_value()) {
+                return Err(TransactionError::CapacitySumOverflow {
+                    side: InputsOrOutputs::Outputs,
+                });
+            }
             if inputs_total < outputs_total {
-                return Err(TransactionError::OutputsSumOverflow);
+                return Err(TransactionError::OutputsSumOverflow {
+                    inputs: Capacity::shannons(inputs_total as u64),
+                    outputs: Capacity::shannons(outputs_total as u64),
+                });
+            }
+            if inputs_total > u128::from(u64::max_value()) {
+                return Err(TransactionError::CapacitySumOverflow {
+                    side: InputsOrOutputs::Inputs,
+                });
             }
         }
 
-        if self
+        // the occupied capacity covers the whole output: the capacity field, data,
+        // lock script and type script
+        for (index, output) in self
             .resolved_transaction
             .transaction
             .outputs()
             .iter()
-            .any(CellOutput::is_occupied_capacity_overflow)
+            .enumerate()
         {
-            return Err(TransactionError::CapacityOverflow);
+            let occupied = output.occupied_capacity()?;
+            if occupied > output.capacity {
+                return Err(TransactionError::InsufficientCellCapacity {
+                    index,
+                    occupied,
+                    capacity: output.capacity,
+                });
+            }
         }
 
         Ok(())
     }
 }
 
-const LOCK_TYPE_FLAG: u64 = 1 << 63;
-const METRIC_TYPE_FLAG_MASK: u64 = 0x6000_0000_0000_0000;
-const VALUE_MASK: u64 = 0x00ff_ffff_ffff_ffff;
-const REMAIN_FLAGS_BITS: u64 = 0x1f00_0000_0000_0000;
-
-enum SinceMetric {
-    BlockNumber(u64),
-    EpochNumber(u64),
-    Timestamp(u64),
+/// The earliest tip at which a transaction passes `SinceVerifier`, in absolute values.
+#[derive(Debug, PartialEq, Clone, Eq, Default)]
+pub struct SinceRequirement {
+    pub block_number: Option<BlockNumber>,
+    pub epoch_number: Option<EpochNumber>,
+    /// The median time of the past blocks, in milliseconds
+    pub timestamp: Option<u64>,
+    /// Inputs with relative locks on cells not committed yet, whose requirements are unknown
+    /// until the cells are committed
+    pub unconfirmed_inputs: Vec<usize>,
 }
 
-/// RFC 0017
-#[derive(Copy, Clone, Debug)]
-struct Since(u64);
-
-impl Since {
-    pub fn is_absolute(self) -> bool {
-        self.0 & LOCK_TYPE_FLAG == 0
-    }
-
-    #[inline]
-    pub fn is_relative(self) -> bool {
-        !self.is_absolute()
-    }
-
-    pub fn flags_is_valid(self) -> bool {
-        (self.0 & REMAIN_FLAGS_BITS == 0)
-            && ((self.0 & METRIC_TYPE_FLAG_MASK) != (0b0110_0000 << 56))
-    }
-
-    fn extract_metric(self) -> Option<SinceMetric> {
-        let value = self.0 & VALUE_MASK;
-        match self.0 & METRIC_TYPE_FLAG_MASK {
-            //0b0000_0000
-            0x0000_0000_0000_0000 => Some(SinceMetric::BlockNumber(value)),
-            //0b0010_0000
-            0x2000_0000_0000_0000 => Some(SinceMetric::EpochNumber(value)),
-            //0b0100_0000
-            0x4000_0000_0000_0000 => Some(SinceMetric::Timestamp(value * 1000)),
-            _ => None,
-        }
-    }
+// The timestamp metric of a since is in seconds, the median times are in milliseconds
+fn since_timestamp_millis(secs: u64) -> Result<u64, TransactionError> {
+    secs.checked_mul(1000).ok_or(TransactionError::InvalidSince)
 }
 
 /// https://github.com/nervosnetwork/rfcs/blob/master/rfcs/0017-tx-valid-since/0017-tx-valid-since.md#detailed-specification
 pub struct SinceVerifier<'a, M> {
     rtx: &'a ResolvedTransaction<'a>,
-    block_median_time_context: &'a M,
-    tip_number: BlockNumber,
-    tip_epoch_number: EpochNumber,
-    median_timestamps_cache: RefCell<LruCache<BlockNumber, Option<u64>>>,
+    context: VerifyContext<'a, M>,
+    median_timestamps_cache: Arc<MedianTimeCache>,
 }
 
 impl<'a, M> SinceVerifier<'a, M>
 where
     M: BlockMedianTimeContext,
 {
+    /// `tip_median_time` is the median time of the blocks before the tip, when given it is
+    /// used instead of computing it from `block_median_time_context`
     pub fn new(
         rtx: &'a ResolvedTransaction,
         block_median_time_context: &'a M,
         tip_number: BlockNumber,
         tip_epoch_number: BlockNumber,
+        tip_median_time: Option<u64>,
     ) -> Self {
-        let median_timestamps_cache = RefCell::new(LruCache::new(rtx.resolved_inputs.len()));
-        SinceVerifier {
+        let median_timestamps_cache = Arc::new(median_time_cache(slice::from_ref(rtx), tip_number));
+        Self::new_with_cache(
             rtx,
             block_median_time_context,
             tip_number,
             tip_epoch_number,
+            tip_median_time,
+            median_timestamps_cache,
+        )
+    }
+
+    pub fn new_with_cache(
+        rtx: &'a ResolvedTransaction,
+        block_median_time_context: &'a M,
+        tip_number: BlockNumber,
+        tip_epoch_number: BlockNumber,
+        tip_median_time: Option<u64>,
+        median_timestamps_cache: Arc<MedianTimeCache>,
+    ) -> Self {
+        let context = VerifyContext::new(
+            block_median_time_context,
+            tip_number,
+            tip_epoch_number,
+            tip_median_time,
+        );
+        Self::new_with_context(rtx, context, median_timestamps_cache)
+    }
+
+    pub fn new_with_context(
+        rtx: &'a ResolvedTransaction,
+        context: VerifyContext<'a, M>,
+        median_timestamps_cache: Arc<MedianTimeCache>,
+    ) -> Self {
+        SinceVerifier {
+            rtx,
+            context,
             median_timestamps_cache,
         }
     }
 
     fn block_median_time(&self, n: BlockNumber) -> Option<u64> {
-        let result = self.median_timestamps_cache.borrow().get(&n).cloned();
-        match result {
-            Some(r) => r,
-            None => {
-                let timestamp = self.block_median_time_context.block_median_time(n);
-                self.median_timestamps_cache
-                    .borrow_mut()
-                    .insert(n, timestamp);
-                timestamp
-            }
-        }
+        self.median_timestamps_cache
+            .get_or_compute(n, || self.context.median_time_context.block_median_time(n))
+    }
+
+    fn tip_median_time(&self) -> u64 {
+        self.context
+            .tip_median_time
+            .or_else(|| self.block_median_time(self.context.tip_number.saturating_sub(1)))
+            .unwrap_or_else(|| 0)
     }
 
     fn verify_absolute_lock(&self, since: Since) -> Result<(), TransactionError> {
         if since.is_absolute() {
-            match since.extract_metric() {
-                Some(SinceMetric::BlockNumber(block_number)) => {
-                    if self.tip_number < block_number {
+            match since.metric() {
+                SinceMetric::BlockNumber(block_number) => {
+                    if self.context.tip_number < block_number {
                         return Err(TransactionError::Immature);
                     }
                 }
-                Some(SinceMetric::EpochNumber(epoch_number)) => {
-                    if self.tip_epoch_number < epoch_number {
+                SinceMetric::EpochNumber(epoch_number) => {
+                    if self.context.tip_epoch_number < epoch_number {
                         return Err(TransactionError::Immature);
                     }
                 }
-                Some(SinceMetric::Timestamp(timestamp)) => {
-                    let tip_timestamp = self
-                        .block_median_time(self.tip_number.saturating_sub(1))
-                        .unwrap_or_else(|| 0);
-                    if tip_timestamp < timestamp {
+                SinceMetric::Timestamp(secs) => {
+                    let tip_timestamp = self.tip_median_time();
+                    if tip_timestamp < since_timestamp_millis(secs)? {
                         return Err(TransactionError::Immature);
                     }
                 }
-                None => {
-                    return Err(TransactionError::InvalidSince);
-                }
             }
         }
         Ok(())
@@ -414,31 +926,29 @@ where
                 Some(ref block_info) => (block_info.number, block_info.epoch),
                 None => return Err(TransactionError::Immature),
             };
-            match since.extract_metric() {
-                Some(SinceMetric::BlockNumber(block_number)) => {
-                    if self.tip_number < cell_block_number + block_number {
+            match since.metric() {
+                SinceMetric::BlockNumber(block_number) => {
+                    if self.context.tip_number < cell_block_number + block_number {
                         return Err(TransactionError::Immature);
                     }
                 }
-                Some(SinceMetric::EpochNumber(epoch_number)) => {
-                    if self.tip_epoch_number < cell_epoch_number + epoch_number {
+                SinceMetric::EpochNumber(epoch_number) => {
+                    if self.context.tip_epoch_number < cell_epoch_number + epoch_number {
                         return Err(TransactionError::Immature);
                     }
                 }
-                Some(SinceMetric::Timestamp(timestamp)) => {
-                    let tip_timestamp = self
-                        .block_median_time(self.tip_number.saturating_sub(1))
-                        .unwrap_or_else(|| 0);
+                SinceMetric::Timestamp(secs) => {
+                    let tip_timestamp = self.tip_median_time();
                     let median_timestamp = self
                         .block_median_time(cell_block_number.saturating_sub(1))
                         .unwrap_or_else(|| 0);
-                    if tip_timestamp < median_timestamp + timestamp {
+                    let timestamp = median_timestamp
+                        .checked_add(since_timestamp_millis(secs)?)
+                        .ok_or(TransactionError::InvalidSince)?;
+                    if tip_timestamp < timestamp {
                         return Err(TransactionError::Immature);
                     }
                 }
-                None => {
-                    return Err(TransactionError::InvalidSince);
-                }
             }
         }
         Ok(())
@@ -459,11 +969,8 @@ where
             if input.since == 0 {
                 continue;
             }
-            let since = Since(input.since);
-            // check remain flags
-            if !since.flags_is_valid() {
-                return Err(TransactionError::InvalidSince);
-            }
+            // check remain flags and metric
+            let since = Since::from_raw(input.since).map_err(|_| TransactionError::InvalidSince)?;
 
             // verify time lock
             self.verify_absolute_lock(since)?;
@@ -471,4 +978,61 @@ where
         }
         Ok(())
     }
+
+    /// Returns the maximum requirement of all inputs on each metric, relative locks are
+    /// converted to absolute values from the committed block of the cells.
+    pub fn earliest_valid(&self) -> Result<SinceRequirement, TransactionError> {
+        let mut requirement = SinceRequirement::default();
+        for (index, (resolved_out_point, input)) in self
+            .rtx
+            .resolved_inputs
+            .iter()
+            .zip(self.rtx.transaction.inputs())
+            .enumerate()
+        {
+            let cell_meta = match resolved_out_point.cell() {
+                Some(cell_meta) => cell_meta,
+                None => continue,
+            };
+            if input.since == 0 {
+                continue;
+            }
+            let since = Since::from_raw(input.since).map_err(|_| TransactionError::InvalidSince)?;
+            let block_info = if since.is_relative() {
+                match cell_meta.block_info {
+                    Some(ref block_info) => Some(block_info),
+                    None => {
+                        requirement.unconfirmed_inputs.push(index);
+                        continue;
+                    }
+                }
+            } else {
+                None
+            };
+            match since.metric() {
+                SinceMetric::BlockNumber(block_number) => {
+                    let base = block_info.map_or(0, |info| info.number);
+                    requirement.block_number = requirement
+                        .block_number
+                        .max(Some(base.saturating_add(block_number)));
+                }
+                SinceMetric::EpochNumber(epoch_number) => {
+                    let base = block_info.map_or(0, |info| info.epoch);
+                    requirement.epoch_number = requirement
+                        .epoch_number
+                        .max(Some(base.saturating_add(epoch_number)));
+                }
+                SinceMetric::Timestamp(secs) => {
+                    let base = block_info.map_or(0, |info| {
+                        self.block_median_time(info.number.saturating_sub(1))
+                            .unwrap_or_else(|| 0)
+                    });
+                    requirement.timestamp = requirement
+                        .timestamp
+                        .max(Some(base.saturating_add(since_timestamp_millis(secs)?)));
+                }
+            }
+        }
+        Ok(requirement)
+    }
 }